@@ -0,0 +1,47 @@
+// Draws chunks straight from the vertex buffers written by `chunk.wgsl`. Quantized positions
+// are relative to the chunk size, which the transform of their chunk scales back.
+
+[[block]]
+struct View {
+    view_proj: mat4x4<f32>;
+};
+
+[[block]]
+struct Chunk {
+    transform: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+[[group(1), binding(0)]]
+var<uniform> chunk: Chunk;
+
+struct Vertex {
+    [[location(0)]] position: vec4<f32>;
+    [[location(1)]] normal: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = view.view_proj * chunk.transform * vec4<f32>(vertex.position.xyz, 1.0);
+    out.world_normal = (chunk.transform * vec4<f32>(vertex.normal.xyz, 0.0)).xyz;
+
+    return out;
+}
+
+// These chunks skip the PBR pipeline, so they are lit by a fixed light from above
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let light = normalize(vec3<f32>(0.3, 1.0, 0.5));
+    let diffuse = max(dot(normalize(in.world_normal), light), 0.0);
+
+    return vec4<f32>(vec3<f32>(0.0, 0.0, 1.0) * (0.2 + 0.8 * diffuse), 1.0);
+}
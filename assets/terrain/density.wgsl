@@ -0,0 +1,328 @@
+// The density pass sampling the octaves of `noise.wgsl`, a shader with a different density
+// replaces this module and writes its own samples into `density_output`
+
+// The noise repeats every 289 units so offsetting by the seed within that period picks a different volume,
+// `simplex::seed_offset` mirrors this on the CPU
+fn seed_offset(seed: u32) -> vec3<f32> {
+    return vec3<f32>(
+        f32(seed % 289u),
+        f32((seed / 289u) % 289u),
+        f32((seed / 83521u) % 289u),
+    );
+}
+
+// Written by `modifiers::to_storage_buffer`, the parameters of every kind are listed in
+// `apply_modifier`
+struct Modifier {
+    kind: u32;
+    op: u32;
+    seed: u32;
+    count: u32;
+    a: vec4<f32>;
+    b: vec4<f32>;
+};
+
+[[block]]
+struct Modifiers {
+    data: array<Modifier>;
+};
+
+// The `DensityModifier`s of `ModifiedDensity`, `input.modifier_count` of them
+[[group(0), binding(9)]]
+var<storage, read> modifiers: Modifiers;
+
+// Single sample of the noise with the index of a `NoiseBasis`, like `NoiseBasis::sample`
+fn basis_of(kind: u32, p: vec3<f32>, seed: u32) -> f32 {
+    if (kind == 1u) {
+        return opensimplex2(p, seed);
+    }
+    if (kind == 2u) {
+        return perlin(p, seed);
+    }
+    if (kind == 3u) {
+        return from_fixed(fixed_perlin(to_fixed(p, 1.0), seed));
+    }
+
+    return snoise(p + seed_offset(seed));
+}
+
+// Single sample of the noise selected by `SimplexNoise::basis`
+fn basis(p: vec3<f32>, seed: u32) -> f32 {
+    return basis_of(input.basis, p, seed);
+}
+
+// Moves the position like the warp layers of `SimplexDensity::sample`, with the seeds of
+// `DomainWarp::seed`
+fn warp(position: vec3<f32>) -> vec3<f32> {
+    var p = position;
+
+    for (var layer = 0u; layer < min(input.warp_layers, 2u); layer = layer + 1u) {
+        let q = p * input.warp_frequency;
+        let seed = input.seed + 7919u * (1u + layer * 3u);
+
+        p = p + vec3<f32>(
+            basis(q, seed),
+            basis(q, seed + 7919u),
+            basis(q, seed + 15838u),
+        ) * input.warp_strength;
+    }
+
+    return p;
+}
+
+// `density` of `NoiseBasis::FixedPoint`, summed in fixed point like
+// `SimplexDensity::sample_fixed`, so the samples come out with the same bits as on the CPU
+fn fixed_density(position: vec3<f32>) -> f32 {
+    let strength = to_fixed_scalar(input.warp_strength);
+    var p = position;
+
+    for (var layer = 0u; layer < min(input.warp_layers, 2u); layer = layer + 1u) {
+        let q = to_fixed(p, input.warp_frequency);
+        let seed = input.seed + 7919u * (1u + layer * 3u);
+
+        p = p + vec3<f32>(
+            from_fixed(fixed_mul(fixed_perlin(q, seed), strength)),
+            from_fixed(fixed_mul(fixed_perlin(q, seed + 7919u), strength)),
+            from_fixed(fixed_mul(fixed_perlin(q, seed + 15838u), strength)),
+        );
+    }
+
+    var value = 0;
+    var frequency = input.frequency;
+    var amplitude = input.amplitude;
+    var weight = FIXED_ONE;
+
+    for (var octave = 0u; octave < input.octaves; octave = octave + 1u) {
+        let seed = input.seed + octave * input.octave_seed_offset;
+        var bit = 0u;
+        if (octave < 32u) {
+            bit = 1u << octave;
+        }
+
+        var sample = fixed_perlin(to_fixed(p, frequency), seed);
+
+        if ((input.ridged_octaves & bit) != 0u) {
+            let fold = FIXED_ONE - abs(sample);
+            let ridge = fixed_mul(fixed_mul(fold, fold), weight);
+            weight = clamp(ridge * 2, 0, FIXED_ONE);
+            sample = ridge * 2 - FIXED_ONE;
+        } else {
+            if ((input.billow_octaves & bit) != 0u) {
+                sample = abs(sample) * 2 - FIXED_ONE;
+            }
+        }
+
+        value = value + fixed_mul(sample, to_fixed_scalar(amplitude));
+        frequency = frequency * input.lacunarity;
+        amplitude = amplitude * input.persistence;
+    }
+
+    return from_fixed(value);
+}
+
+// Sums the octaves like `SimplexDensity::sample`, every octave with its own seed like
+// `SimplexNoise::octave_seed` and shaped by its variant like `SimplexNoise::variant`
+fn density(position: vec3<f32>) -> f32 {
+    if (input.basis == 3u) {
+        return fixed_density(position);
+    }
+
+    let p = warp(position);
+    var value = 0.0;
+    var frequency = input.frequency;
+    var amplitude = input.amplitude;
+    var weight = 1.0;
+
+    for (var octave = 0u; octave < input.octaves; octave = octave + 1u) {
+        let seed = input.seed + octave * input.octave_seed_offset;
+        // Shifting by 32 or more is undefined, those octaves stay smooth
+        var bit = 0u;
+        if (octave < 32u) {
+            bit = 1u << octave;
+        }
+
+        var sample = basis(p * frequency, seed);
+
+        if ((input.ridged_octaves & bit) != 0u) {
+            let ridge = (1.0 - abs(sample)) * (1.0 - abs(sample)) * weight;
+            weight = min(max(ridge * 2.0, 0.0), 1.0);
+            sample = ridge * 2.0 - 1.0;
+        } else {
+            if ((input.billow_octaves & bit) != 0u) {
+                sample = abs(sample) * 2.0 - 1.0;
+            }
+        }
+
+        value = value + sample * amplitude;
+        frequency = frequency * input.lacunarity;
+        amplitude = amplitude * input.persistence;
+    }
+
+    return value;
+}
+
+// Like `modifiers::terrace`
+fn terrace(y: f32, step_height: f32, sharpness: f32) -> f32 {
+    let height = max(step_height, 0.00000011920929);
+    let steps = y / height;
+    let whole = floor(steps);
+    let t = steps - whole;
+
+    let power = 1.0 + max(sharpness, 0.0);
+    let rise = pow(t, power);
+
+    return (whole + rise / (rise + pow(1.0 - t, power))) * height;
+}
+
+// Like `sdf::smooth_union`
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    if (k <= 0.0) {
+        return min(a, b);
+    }
+
+    let h = min(max(0.5 + 0.5 * (b - a) / k, 0.0), 1.0);
+
+    return b + (a - b) * h - k * h * (1.0 - h);
+}
+
+fn smooth_max(a: f32, b: f32, k: f32) -> f32 {
+    return -smooth_min(-a, -b, k);
+}
+
+// Like `Primitive::distance`, with the kind and the parameters of `Primitive::to_gpu`
+fn primitive_distance(kind: u32, p: vec3<f32>, a: vec3<f32>, b: vec4<f32>) -> f32 {
+    // Sphere
+    if (kind == 0u) {
+        return length(p - a) - b.x;
+    }
+    // Torus
+    if (kind == 2u) {
+        let q = p - a;
+        let ring = sqrt(q.x * q.x + q.z * q.z) - b.x;
+
+        return sqrt(ring * ring + q.y * q.y) - b.y;
+    }
+    // Capsule
+    if (kind == 3u) {
+        let axis = b.xyz - a;
+        let t = min(max(dot(p - a, axis) / max(dot(axis, axis), 0.00000011920929), 0.0), 1.0);
+
+        return length(p - (a + axis * t)) - b.w;
+    }
+    // Plane
+    if (kind == 4u) {
+        return dot(p, a) - b.x;
+    }
+    // Cylinder
+    if (kind == 5u) {
+        let q = p - a;
+        let side = sqrt(q.x * q.x + q.z * q.z) - b.x;
+        let cap = abs(q.y) - b.y;
+
+        return min(max(side, cap), 0.0) + sqrt(max(side, 0.0) * max(side, 0.0) + max(cap, 0.0) * max(cap, 0.0));
+    }
+
+    // Box
+    let q = abs(p - a) - b.xyz;
+
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+// Moves the position by the warp modifiers like `ModifiedDensity::warp`
+fn warp_modifiers(position: vec3<f32>) -> vec3<f32> {
+    var p = position;
+
+    for (var i = 0u; i < input.modifier_count; i = i + 1u) {
+        let modifier = modifiers.data[i];
+
+        if (modifier.kind == 3u) {
+            let q = p * modifier.a.y;
+
+            p = p + vec3<f32>(
+                basis_of(modifier.op, q, modifier.seed),
+                basis_of(modifier.op, q, modifier.seed + 7919u),
+                basis_of(modifier.op, q, modifier.seed + 15838u)
+            ) * modifier.a.x;
+        }
+    }
+
+    return p;
+}
+
+// Applies a modifier other than a warp like `ModifiedDensity::sample`
+fn apply_modifier(modifier: Modifier, p: vec3<f32>, value: f32) -> f32 {
+    // Octaves with the frequency and amplitude of the first one in `a.xy`
+    if (modifier.kind == 0u) {
+        var sum = 0.0;
+        var frequency = modifier.a.x;
+        var amplitude = modifier.a.y;
+
+        for (var octave = 0u; octave < modifier.count; octave = octave + 1u) {
+            sum = sum + basis_of(modifier.op, p * frequency, modifier.seed) * amplitude;
+            frequency = frequency * 2.0;
+            amplitude = amplitude * 0.5;
+        }
+
+        return value + sum;
+    }
+    // The range in `a.xy`
+    if (modifier.kind == 1u) {
+        return min(max(value, modifier.a.x), modifier.a.y);
+    }
+    // The step height and the sharpness in `a.xy`
+    if (modifier.kind == 2u) {
+        return value + terrace(p.y, modifier.a.x, modifier.a.y) - p.y;
+    }
+    // The heights and the falloff in `a.xyz`
+    if (modifier.kind == 4u) {
+        return smooth_max(value, max(modifier.a.x - p.y, p.y - modifier.a.y), modifier.a.z);
+    }
+    // The primitive in `count`, its parameters in `a.xyz` and `b`, the smoothness in `a.w`
+    if (modifier.kind == 5u) {
+        let d = primitive_distance(modifier.count, p, modifier.a.xyz, modifier.b);
+
+        if (modifier.op == 0u) {
+            return smooth_min(value, d, modifier.a.w);
+        }
+        if (modifier.op == 1u) {
+            return smooth_max(value, -d, modifier.a.w);
+        }
+
+        return smooth_max(value, d, modifier.a.w);
+    }
+    // The height and the sharpness in `a.xy`
+    if (modifier.kind == 6u) {
+        return value + max(p.y - modifier.a.x, 0.0) * max(modifier.a.y, 0.0);
+    }
+
+    return value;
+}
+
+// The density with the modifiers applied, like `ModifiedDensity::sample`
+fn modified_density(position: vec3<f32>) -> f32 {
+    let p = warp_modifiers(position);
+    var value = density(p);
+
+    for (var i = 0u; i < input.modifier_count; i = i + 1u) {
+        value = apply_modifier(modifiers.data[i], p, value);
+    }
+
+    return value;
+}
+
+// Every invocation samples one point of the volume, which covers the corners of the chunk, the
+// apron shared with the next chunk and the ghost samples
+[[stage(compute), workgroup_size(8, 8, 8)]]
+fn density_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let samples = textureDimensions(density_output);
+
+    if (i32(id.x) >= samples.x || i32(id.y) >= samples.y || i32(id.z) >= samples.z) {
+        return;
+    }
+
+    // The first sample is a ghost sample one voxel before the slab origin
+    let point = vec3<f32>(f32(id.x), f32(id.y), f32(id.z + input.slab_start)) - vec3<f32>(1.0);
+    let value = modified_density((point + input.position) * input.voxel_scale);
+
+    textureStore(density_output, vec3<i32>(i32(id.x), i32(id.y), i32(id.z)), vec4<f32>(value, 0.0, 0.0, 0.0));
+}
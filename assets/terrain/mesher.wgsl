@@ -0,0 +1,551 @@
+// The marching core: the input, the bindings of both stages and the passes turning the density
+// volume into triangles. It expects the `tables` module before it, the density pass that fills
+// the volume is up to the shader including it, see `density.wgsl`.
+
+// Replaced with the number of invocations of a scan workgroup
+let SCAN_BLOCK_SIZE: u32 = 256u;
+
+struct Triangle {
+    a: vec3<f32>;
+    b: vec3<f32>;
+    c: vec3<f32>;
+    normal_a: vec3<f32>;
+    normal_b: vec3<f32>;
+    normal_c: vec3<f32>;
+};
+
+// Padded to four floats, which is the stride of `vec3<f32>` in a storage array anyway. The
+// layout matches the vertex buffer read by `chunk_draw.wgsl`.
+struct Vertex {
+    position: vec4<f32>;
+    normal: vec4<f32>;
+};
+
+// Written by `compact_quantized_main`, the four 16 bit components of the position and of the
+// normal packed into two words each
+struct QuantizedVertex {
+    position: vec2<u32>;
+    normal: vec2<u32>;
+};
+
+// Everything that differs between chunk configurations, so one pipeline serves all of them
+[[block]]
+struct Input {
+    chunk_size: u32;
+    voxel_scale: f32;
+    iso_level: f32;
+    seed: u32;
+    position: vec3<f32>;
+    frequency: f32;
+    octaves: u32;
+    amplitude: f32;
+    lacunarity: f32;
+    persistence: f32;
+    octave_seed_offset: u32;
+    ridged_octaves: u32;
+    billow_octaves: u32;
+    warp_layers: u32;
+    warp_strength: f32;
+    warp_frequency: f32;
+    // `NoiseBasis::OpenSimplex2` when 1, `NoiseBasis::Perlin` when 2, `NoiseBasis::FixedPoint`
+    // when 3, `NoiseBasis::Simplex` otherwise
+    basis: u32;
+    // Modifiers read from `modifiers` by the density pass of `density.wgsl`
+    modifier_count: u32;
+    // Permutation tables read from `perlin_tables` by `perlin` of `noise.wgsl`
+    perlin_table_count: u32;
+    // Chunks too large for a single vertex buffer are dispatched in slabs of cells along z,
+    // invocation ids are relative to the first cell of the slab
+    slab_start: u32;
+    slab_cells: u32;
+    // `VertexPlacement::Interpolated` when not 0, `VertexPlacement::Midpoint` otherwise
+    interpolate: u32;
+};
+
+// Laid out like the arguments of `draw_indirect`, the vertex count is written by the scan
+[[block]]
+struct DrawArgs {
+    vertex_count: u32;
+    instance_count: u32;
+    first_vertex: u32;
+    first_instance: u32;
+};
+
+[[block]]
+struct Vertices {
+    data: array<Vertex>;
+};
+
+[[block]]
+struct QuantizedVertices {
+    data: array<QuantizedVertex>;
+};
+
+[[block]]
+struct Counts {
+    data: array<u32>;
+};
+
+// Cells the count pass found triangles in, laid out so the first three words are the arguments
+// of `dispatch_indirect` for the compact pass
+[[block]]
+struct ActiveCells {
+    dispatch_x: u32;
+    dispatch_y: u32;
+    dispatch_z: u32;
+    count: atomic<u32>;
+    cells: array<u32>;
+};
+
+// Filled from `tables::to_storage_buffer`
+[[block]]
+struct Tables {
+    edges: array<u32, 256>;
+    triangles: array<array<i32, 16>, 256>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> input: Input;
+
+[[group(0), binding(1)]]
+var<storage, read_write> draw_args: DrawArgs;
+
+[[group(0), binding(2)]]
+var<storage, read_write> vertices: Vertices;
+
+// The same buffer, holding the vertices in the layout of the compact pass that writes them
+[[group(0), binding(2)]]
+var<storage, read_write> quantized_vertices: QuantizedVertices;
+
+// The same volume, written by the density pass and read by the marching pass. It covers the
+// slab and has a layer of ghost samples on every side like the grids of
+// `cpu::march_chunk_smooth`.
+[[group(0), binding(3)]]
+var density_output: texture_storage_3d<r32float, write>;
+
+[[group(0), binding(4)]]
+var density_input: texture_3d<f32>;
+
+// Triangle counts of the cells, turned into offsets within blocks of cells by the scan
+[[group(0), binding(5)]]
+var<storage, read_write> offsets: Counts;
+
+// Triangle counts of the blocks, turned into offsets of the blocks by the scan
+[[group(0), binding(6)]]
+var<storage, read_write> block_sums: Counts;
+
+[[group(0), binding(7)]]
+var<storage, read> tables: Tables;
+
+[[group(0), binding(8)]]
+var<storage, read_write> active: ActiveCells;
+
+// Integer mantissa of a positive float, see `cpu::mantissa_exponent`
+fn mantissa(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+
+    // Subnormals have no implicit leading bit
+    if ((bits >> 23u) == 0u) {
+        return bits & 8388607u;
+    }
+
+    return (bits & 8388607u) | 8388608u;
+}
+
+fn exponent(value: f32) -> i32 {
+    let bits = bitcast<u32>(value);
+
+    if ((bits >> 23u) == 0u) {
+        return -149;
+    }
+
+    return i32(bits >> 23u) - 150;
+}
+
+// Divides from the bits of both floats like `cpu::interpolation_step`, the division of the GPU
+// isn't correctly rounded
+fn interpolation_step(numerator: f32, denominator: f32) -> u32 {
+    if (numerator == 0.0 || (numerator < 0.0) != (denominator < 0.0)) {
+        return 0u;
+    }
+
+    let n = abs(numerator);
+    let d = abs(denominator);
+
+    if (!(n < d)) {
+        return 65536u;
+    }
+
+    let n_mantissa = mantissa(n);
+    let d_mantissa = mantissa(d);
+    let shift = exponent(n) - exponent(d) + 17;
+
+    var quotient = n_mantissa / d_mantissa;
+
+    if (shift < 0) {
+        quotient = quotient >> u32(min(-shift, 31));
+    } else {
+        var remainder = n_mantissa % d_mantissa;
+
+        for (var i = 0; i < shift; i = i + 1) {
+            remainder = remainder << 1u;
+            quotient = quotient << 1u;
+
+            if (remainder >= d_mantissa) {
+                remainder = remainder - d_mantissa;
+                quotient = quotient | 1u;
+            }
+        }
+    }
+
+    return (quotient + 1u) >> 1u;
+}
+
+// Ordered and rounded like `cpu::interpolate_vertices`, so chunks sharing a face place its
+// vertices alike on every backend
+fn interpolate_vertices(first: vec4<f32>, second: vec4<f32>, iso_level: f32) -> vec3<f32> {
+    var a = first;
+    var b = second;
+
+    if (b.z < a.z || (b.z == a.z && (b.y < a.y || (b.y == a.y && b.x < a.x)))) {
+        a = second;
+        b = first;
+    }
+
+    var t = 0.5;
+
+    if (input.interpolate != 0u && a.w != b.w) {
+        t = f32(interpolation_step(iso_level - a.w, b.w - a.w)) * 0.0000152587890625;
+    }
+
+    return a.xyz + (b.xyz - a.xyz) * t;
+}
+
+// Loads the sample at a point relative to the chunk origin
+fn load_density(point: vec3<i32>) -> f32 {
+    let slab_start = vec3<i32>(0, 0, i32(input.slab_start));
+
+    return textureLoad(density_input, point - slab_start + vec3<i32>(1), 0).x;
+}
+
+fn value_from_coord(x: u32, y: u32, z: u32) -> vec4<f32> {
+    let value = load_density(vec3<i32>(i32(x), i32(y), i32(z)));
+
+    return vec4<f32>(f32(x), f32(y), f32(z), value);
+}
+
+// Central difference of the density at a corner, the ghost samples cover the corners on the
+// faces of the chunk
+fn gradient(corner: vec4<f32>) -> vec3<f32> {
+    let point = vec3<i32>(corner.xyz);
+
+    return vec3<f32>(
+        load_density(point + vec3<i32>(1, 0, 0)) - load_density(point - vec3<i32>(1, 0, 0)),
+        load_density(point + vec3<i32>(0, 1, 0)) - load_density(point - vec3<i32>(0, 1, 0)),
+        load_density(point + vec3<i32>(0, 0, 1)) - load_density(point - vec3<i32>(0, 0, 1)),
+    ) * 0.5;
+}
+
+fn power_of_two(exponent: i32) -> f32 {
+    return bitcast<f32>(u32(exponent + 127) << 23u);
+}
+
+// Square root of an integer, rounded down
+fn isqrt(value: u32) -> u32 {
+    var remainder = value;
+    var root = 0u;
+
+    for (var bit = 1u << 30u; bit != 0u; bit = bit >> 2u) {
+        if (remainder >= root + bit) {
+            remainder = remainder - (root + bit);
+            root = (root >> 1u) + bit;
+        } else {
+            root = root >> 1u;
+        }
+    }
+
+    return root;
+}
+
+// Normalizes in integers like `cpu::normalize_exactly`, `normalize` isn't correctly rounded
+fn normalize_exactly(v: vec3<f32>) -> vec3<f32> {
+    let max_component = max(abs(v.x), max(abs(v.y), abs(v.z)));
+    let exponent = i32(bitcast<u32>(max_component) >> 23u);
+
+    if (max_component == 0.0 || exponent == 255) {
+        return vec3<f32>(0.0);
+    }
+
+    // Split in two factors, so each of them is a normal float
+    let shift = 141 - exponent;
+    let scaled = v * power_of_two(shift / 2) * power_of_two(shift - shift / 2);
+    let components = vec3<i32>(scaled);
+
+    let length = i32(isqrt(
+        u32(components.x * components.x)
+            + u32(components.y * components.y)
+            + u32(components.z * components.z)
+    ));
+
+    // Subnormal vectors may truncate to nothing
+    if (length == 0) {
+        return vec3<f32>(0.0);
+    }
+
+    return vec3<f32>(components * 32768 / vec3<i32>(length)) * 0.000030517578125;
+}
+
+// Computed like `cpu::march_chunk_smooth`, the triangles face towards the higher density
+fn interpolate_normals(a: vec4<f32>, b: vec4<f32>) -> vec3<f32> {
+    return normalize_exactly(gradient(a) + gradient(b));
+}
+
+fn cell_count() -> u32 {
+    return input.chunk_size * input.chunk_size * input.slab_cells;
+}
+
+fn cell_index(id: vec3<u32>) -> u32 {
+    return (id.z * input.chunk_size + id.y) * input.chunk_size + id.x;
+}
+
+// Inverse of `cell_index` for the given entry of the active cells
+fn active_cell(entry: u32) -> vec3<u32> {
+    let cell = active.cells[entry];
+    let layer = input.chunk_size * input.chunk_size;
+
+    return vec3<u32>(cell % input.chunk_size, (cell % layer) / input.chunk_size, cell / layer);
+}
+
+// Corners of a cell of the slab, positioned relative to the chunk origin so the slabs of a
+// chunk line up without moving their vertices
+fn corners(id: vec3<u32>) -> array<vec4<f32>, 8> {
+    let z = id.z + input.slab_start;
+
+    return array<vec4<f32>, 8>(
+        value_from_coord(id.x, id.y, z),
+        value_from_coord(id.x + 1u, id.y, z),
+        value_from_coord(id.x + 1u, id.y, z + 1u),
+        value_from_coord(id.x, id.y, z + 1u),
+        value_from_coord(id.x, id.y + 1u, z),
+        value_from_coord(id.x + 1u, id.y + 1u, z),
+        value_from_coord(id.x + 1u, id.y + 1u, z + 1u),
+        value_from_coord(id.x, id.y + 1u, z + 1u),
+    );
+}
+
+fn cube_index_from_corners(cube_corners: array<vec4<f32>, 8>) -> u32 {
+    let iso_level = input.iso_level;
+
+    var cube_index = 0u;
+
+    if (cube_corners[0].w < iso_level) { cube_index = cube_index | 1u; }
+    if (cube_corners[1].w < iso_level) { cube_index = cube_index | 2u; }
+    if (cube_corners[2].w < iso_level) { cube_index = cube_index | 4u; }
+    if (cube_corners[3].w < iso_level) { cube_index = cube_index | 8u; }
+    if (cube_corners[4].w < iso_level) { cube_index = cube_index | 16u; }
+    if (cube_corners[5].w < iso_level) { cube_index = cube_index | 32u; }
+    if (cube_corners[6].w < iso_level) { cube_index = cube_index | 64u; }
+    if (cube_corners[7].w < iso_level) { cube_index = cube_index | 128u; }
+
+    return cube_index;
+}
+
+// First pass of the marching stage, every invocation counts the triangles of one cell and
+// marks it as active when the surface crosses it
+[[stage(compute), workgroup_size(8, 8, 8)]]
+fn count_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    // Chunks meshed at a lower detail level can be smaller than a workgroup
+    if (id.x >= input.chunk_size || id.y >= input.chunk_size || id.z >= input.slab_cells) {
+        return;
+    }
+
+    let cube_index = cube_index_from_corners(corners(id));
+
+    var triangle_count = 0u;
+
+    for (var i = 0u; tables.triangles[cube_index][i] != -1; i = i + 3u) {
+        triangle_count = triangle_count + 1u;
+    }
+
+    offsets.data[cell_index(id)] = triangle_count;
+
+    // The order of the active cells doesn't matter, every cell writes at its own offset
+    if (triangle_count > 0u) {
+        active.cells[atomicAdd(&active.count, 1u)] = cell_index(id);
+    }
+}
+
+// Shared by the invocations of a scan workgroup
+var<workgroup> scan: array<u32, SCAN_BLOCK_SIZE>;
+
+// Inclusive prefix sum over the values of a scan workgroup
+fn scan_workgroup(local: u32, value: u32) -> u32 {
+    scan[local] = value;
+    workgroupBarrier();
+
+    for (var offset = 1u; offset < SCAN_BLOCK_SIZE; offset = offset * 2u) {
+        var addend = 0u;
+
+        if (local >= offset) {
+            addend = scan[local - offset];
+        }
+
+        workgroupBarrier();
+        scan[local] = scan[local] + addend;
+        workgroupBarrier();
+    }
+
+    return scan[local];
+}
+
+// Second pass, every workgroup turns the counts of a block of cells into offsets within the block
+// and stores the triangle count of the block
+[[stage(compute), workgroup_size(256)]]
+fn scan_blocks_main(
+    [[builtin(global_invocation_id)]] id: vec3<u32>,
+    [[builtin(local_invocation_index)]] local: u32,
+    [[builtin(workgroup_id)]] block: vec3<u32>,
+) {
+    var count = 0u;
+
+    if (id.x < cell_count()) {
+        count = offsets.data[id.x];
+    }
+
+    let inclusive = scan_workgroup(local, count);
+
+    if (id.x < cell_count()) {
+        offsets.data[id.x] = inclusive - count;
+    }
+
+    if (local == SCAN_BLOCK_SIZE - 1u) {
+        block_sums.data[block.x] = inclusive;
+    }
+}
+
+// Third pass, a single workgroup turns the block counts into offsets of the blocks and writes
+// the vertex count of the chunk and the workgroups of the compact pass
+[[stage(compute), workgroup_size(256)]]
+fn scan_block_sums_main([[builtin(local_invocation_index)]] local: u32) {
+    let block_count = (cell_count() + SCAN_BLOCK_SIZE - 1u) / SCAN_BLOCK_SIZE;
+
+    var total = 0u;
+
+    for (var first = 0u; first < block_count; first = first + SCAN_BLOCK_SIZE) {
+        let block = first + local;
+
+        var sum = 0u;
+
+        if (block < block_count) {
+            sum = block_sums.data[block];
+        }
+
+        let inclusive = scan_workgroup(local, sum);
+
+        if (block < block_count) {
+            block_sums.data[block] = total + inclusive - sum;
+        }
+
+        total = total + scan[SCAN_BLOCK_SIZE - 1u];
+        workgroupBarrier();
+    }
+
+    if (local == 0u) {
+        draw_args.vertex_count = total * 3u;
+        active.dispatch_x = (atomicLoad(&active.count) + SCAN_BLOCK_SIZE - 1u) / SCAN_BLOCK_SIZE;
+    }
+}
+
+// Triangle of a cell starting at the given entry of its row of the triangle table
+fn cell_triangle(cell_corners: array<vec4<f32>, 8>, cube_index: u32, i: u32) -> Triangle {
+    let iso_level = input.iso_level;
+
+    // Only variables can be indexed dynamically
+    var cube_corners = cell_corners;
+
+    var a0 = CORNER_INDEX_A_FROM_EDGE[u32(tables.triangles[cube_index][i])];
+    var b0 = CORNER_INDEX_B_FROM_EDGE[u32(tables.triangles[cube_index][i])];
+
+    var a1 = CORNER_INDEX_A_FROM_EDGE[u32(tables.triangles[cube_index][i + 1u])];
+    var b1 = CORNER_INDEX_B_FROM_EDGE[u32(tables.triangles[cube_index][i + 1u])];
+
+    var a2 = CORNER_INDEX_A_FROM_EDGE[u32(tables.triangles[cube_index][i + 2u])];
+    var b2 = CORNER_INDEX_B_FROM_EDGE[u32(tables.triangles[cube_index][i + 2u])];
+
+    return Triangle(
+        interpolate_vertices(cube_corners[a0], cube_corners[b0], iso_level),
+        interpolate_vertices(cube_corners[a1], cube_corners[b1], iso_level),
+        interpolate_vertices(cube_corners[a2], cube_corners[b2], iso_level),
+        interpolate_normals(cube_corners[a0], cube_corners[b0]),
+        interpolate_normals(cube_corners[a1], cube_corners[b1]),
+        interpolate_normals(cube_corners[a2], cube_corners[b2]),
+    );
+}
+
+// The vertices are packed in cell order, so a chunk always comes out the same
+fn first_vertex(id: vec3<u32>) -> u32 {
+    let cell = cell_index(id);
+
+    return (offsets.data[cell] + block_sums.data[cell / SCAN_BLOCK_SIZE]) * 3u;
+}
+
+// Last pass, dispatched indirectly over the active cells only. Every invocation polygonizes one
+// cell from the corners written by `density_main` and writes the triangles at the offset of the
+// cell.
+[[stage(compute), workgroup_size(256)]]
+fn compact_main([[builtin(global_invocation_id)]] index: vec3<u32>) {
+    if (index.x >= atomicLoad(&active.count)) {
+        return;
+    }
+
+    let id = active_cell(index.x);
+    let cube_corners = corners(id);
+    let cube_index = cube_index_from_corners(cube_corners);
+
+    var vertex = first_vertex(id);
+
+    for (var i = 0u; tables.triangles[cube_index][i] != -1; i = i + 3u) {
+        let triangle = cell_triangle(cube_corners, cube_index, i);
+
+        vertices.data[vertex] = Vertex(vec4<f32>(triangle.a, 1.0), vec4<f32>(triangle.normal_a, 0.0));
+        vertices.data[vertex + 1u] = Vertex(vec4<f32>(triangle.b, 1.0), vec4<f32>(triangle.normal_b, 0.0));
+        vertices.data[vertex + 2u] = Vertex(vec4<f32>(triangle.c, 1.0), vec4<f32>(triangle.normal_c, 0.0));
+
+        vertex = vertex + 3u;
+    }
+}
+
+// Positions relative to the chunk size as unorm16 and normals as snorm16, the fourth
+// components are padding
+fn quantize_vertex(position: vec3<f32>, normal: vec3<f32>) -> QuantizedVertex {
+    let relative = position / f32(input.chunk_size);
+
+    return QuantizedVertex(
+        vec2<u32>(pack2x16unorm(relative.xy), pack2x16unorm(vec2<f32>(relative.z, 1.0))),
+        vec2<u32>(pack2x16snorm(normal.xy), pack2x16snorm(vec2<f32>(normal.z, 0.0))),
+    );
+}
+
+// Same as `compact_main`, but writes quantized vertices of half the size
+[[stage(compute), workgroup_size(256)]]
+fn compact_quantized_main([[builtin(global_invocation_id)]] index: vec3<u32>) {
+    if (index.x >= atomicLoad(&active.count)) {
+        return;
+    }
+
+    let id = active_cell(index.x);
+    let cube_corners = corners(id);
+    let cube_index = cube_index_from_corners(cube_corners);
+
+    var vertex = first_vertex(id);
+
+    for (var i = 0u; tables.triangles[cube_index][i] != -1; i = i + 3u) {
+        let triangle = cell_triangle(cube_corners, cube_index, i);
+
+        quantized_vertices.data[vertex] = quantize_vertex(triangle.a, triangle.normal_a);
+        quantized_vertices.data[vertex + 1u] = quantize_vertex(triangle.b, triangle.normal_b);
+        quantized_vertices.data[vertex + 2u] = quantize_vertex(triangle.c, triangle.normal_c);
+
+        vertex = vertex + 3u;
+    }
+}
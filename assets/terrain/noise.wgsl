@@ -0,0 +1,453 @@
+// 3D simplex noise, ported to the CPU by `simplex::snoise`
+//
+// The tests of `simplex.rs` pin the CPU ports to these samples, which the shader has to keep
+// producing (to within `simplex::GPU_TOLERANCE`) when a noise here changes:
+//
+//   point                    seed        snoise        opensimplex2   perlin
+//   (0.3, 1.7, -2.2)         0           0.06471298    0.38860315     0.24206811
+//   (12.5, -3.25, 7.75)      1337        -0.21659881   0.34963715     0.28558183
+//   (-41.1, 0.05, 19.9)      0xdeadbeef  0.17174031    6.9360062e-6   0.073885836
+//   (100.25, 63.5, -7.125)   42          0.058684137   -0.0010504593  0.29722273
+
+fn mod289vec3(x: vec3<f32>) -> vec3<f32> {
+    return x - floor(x * (1. / 289.0)) * 289.0;
+}
+
+fn mod289vec4(x: vec4<f32>) -> vec4<f32> {
+    return x - floor(x * (1. / 289.0)) * 289.0;
+}
+
+fn permute(x: vec4<f32>) -> vec4<f32> {
+    return mod289vec4(((x * 34.0) + 1.0) * x);
+}
+
+fn taylor_inv_sqrt(r: vec4<f32>) -> vec4<f32> {
+  return 1.79284291400159 - 0.85373472095314 * r;
+}
+
+fn snoise(v: vec3<f32>) -> f32 {
+    let c: vec2<f32> = vec2<f32>(1.0 / 6.0, 1.0 / 3.0);
+    let d: vec4<f32> = vec4<f32>(0.0, 0.5, 1.0, 2.0);
+
+    // First corner
+    var i = floor(v + dot(v, c.yyy));
+    var x0 = v - i + dot(i, c.xxx);
+
+    // Other corners
+    var g = step(x0.yzx, x0.xyz);
+    var l = 1.0 - g;
+    var i1 = min(g.xyz, l.zxy);
+    var i2 = max(g.xyz, l.zxy);
+
+    var x1 = x0 - i1 + c.xxx;
+    var x2 = x0 - i2 + c.yyy;
+    var x3 = x0 - d.yyy;
+
+    var seed = vec2<f32>(100.0, 1.0);
+
+    // Permutations
+    i = mod289vec3(i);
+    var p = permute(permute(permute(
+        i.z + vec4<f32>(0.0, i1.z, i2.z, 1.0))
+        + i.y + vec4<f32>(0.0, i1.y, i2.y, 1.0))
+        + i.x + vec4<f32>(0.0, i1.x, i2.x, 1.0));
+
+    // p = permute(p + vec4<f32>(5.0, 5.0, 5.0, 5.0));
+
+    var n_ = 0.142857142857;
+    var ns = n_ * d.wyz - d.xzx;
+
+    var j = p - 49.0 * floor(p * ns.z * ns.z);
+
+    var x_ = floor(j * ns.z);
+    var y_ = floor(j - 7.0 * x_);
+
+    var x = x_ *ns.x + ns.yyyy;
+    var y = y_ *ns.x + ns.yyyy;
+    var h = 1.0 - abs(x) - abs(y);
+
+    var b0 = vec4<f32>(x.xy, y.xy);
+    var b1 = vec4<f32>(x.zw, y.zw);
+
+    var s0 = floor(b0) * 2.0 + 1.0;
+    var s1 = floor(b1) * 2.0 + 1.0;
+    var sh = -step(h, vec4<f32>(0.0));
+
+    var a0 = b0.xzyw + s0.xzyw * sh.xxyy;
+    var a1 = b1.xzyw + s1.xzyw * sh.zzww;
+
+    var p0 = vec3<f32>(a0.xy,h.x);
+    var p1 = vec3<f32>(a0.zw,h.y);
+    var p2 = vec3<f32>(a1.xy,h.z);
+    var p3 = vec3<f32>(a1.zw,h.w);
+
+    //Normalise gradients
+    var norm = taylor_inv_sqrt(vec4<f32>(dot(p0,p0), dot(p1,p1), dot(p2, p2), dot(p3,p3)));
+    p0 = p0 * norm.x;
+    p1 = p1 * norm.y;
+    p2 = p2 * norm.z;
+    p3 = p3 * norm.w;
+
+    // Mix final noise value
+    var m: vec4<f32> = max(vec4<f32>(0.6, 0.6, 0.6, 0.6) - vec4<f32>(dot(x0,x0), dot(x1,x1), dot(x2,x2), dot(x3,x3)), vec4<f32>(0.0, 0.0, 0.0, 0.0));
+    m = m * m;
+
+    return 42.0 * dot(m * m, vec4<f32>(dot(p0,x0), dot(p1,x1), dot(p2,x2), dot(p3,x3)));
+}
+
+// 3D OpenSimplex2 with 32 bit hashing, ported to the CPU by `simplex::opensimplex2`
+
+// Cube edge of the gradient a lattice point hashes to, one of the 12 cube edges of
+// FastNoiseLite's gradient table
+fn gradient_edge(seed: u32, primed: vec3<u32>) -> u32 {
+    var hash = (seed ^ primed.x ^ primed.y ^ primed.z) * 668265261u;
+    hash = hash ^ (hash >> 15u);
+
+    let index = (hash >> 2u) & 63u;
+    var edge = index % 12u;
+
+    if (index == 60u) {
+        edge = 8u;
+    }
+    if (index == 61u) {
+        edge = 1u;
+    }
+    if (index == 62u) {
+        edge = 9u;
+    }
+    if (index == 63u) {
+        edge = 3u;
+    }
+
+    return edge;
+}
+
+// Dot product of the offset from a lattice point with the gradient its hash picks
+fn gradient_dot(seed: u32, primed: vec3<u32>, offset: vec3<f32>) -> f32 {
+    let edge = gradient_edge(seed, primed);
+
+    var first = 1.0;
+    if ((edge & 1u) != 0u) {
+        first = -1.0;
+    }
+    var second = 1.0;
+    if ((edge & 2u) != 0u) {
+        second = -1.0;
+    }
+
+    if (edge / 4u == 0u) {
+        return offset.y * first + offset.z * second;
+    }
+    if (edge / 4u == 1u) {
+        return offset.x * first + offset.z * second;
+    }
+    return offset.x * first + offset.y * second;
+}
+
+fn opensimplex2(v: vec3<f32>, lattice_seed: u32) -> f32 {
+    let primes = vec3<u32>(501125321u, 1136930381u, 1720413743u);
+
+    // Rotation, not skew
+    let r = (v.x + v.y + v.z) * (2.0 / 3.0);
+    let p = vec3<f32>(r) - v;
+
+    let base = floor(p + vec3<f32>(0.5));
+
+    var offset = p - base;
+    // Points from the offset back towards the closest lattice point, -1 for positive offsets
+    var signs = select(vec3<f32>(1.0), vec3<f32>(-1.0), offset >= vec3<f32>(0.0));
+    var dist = abs(offset);
+    var primed = vec3<u32>(vec3<i32>(base)) * primes;
+    var seed = lattice_seed;
+
+    var value = 0.0;
+    var a = (0.6 - offset.x * offset.x) - (offset.y * offset.y + offset.z * offset.z);
+
+    for (var lattice = 0u; lattice < 2u; lattice = lattice + 1u) {
+        // Closest point of the lattice
+        if (a > 0.0) {
+            value = value + (a * a) * (a * a) * gradient_dot(seed, primed, offset);
+        }
+
+        // Second closest point, one step along the axis the offset is largest on
+        var towards = vec3<f32>(0.0, 0.0, 1.0);
+        if (dist.x >= dist.y && dist.x >= dist.z) {
+            towards = vec3<f32>(1.0, 0.0, 0.0);
+        } else {
+            if (dist.y > dist.x && dist.y >= dist.z) {
+                towards = vec3<f32>(0.0, 1.0, 0.0);
+            }
+        }
+
+        let b = a + dot(dist, towards) * 2.0;
+
+        if (b > 1.0) {
+            let falloff = b - 1.0;
+            let neighbour_step = vec3<i32>(towards * -signs);
+
+            let neighbour_primed = primed + vec3<u32>(neighbour_step) * primes;
+            let neighbour_offset = offset + towards * signs;
+
+            value = value + (falloff * falloff) * (falloff * falloff) * gradient_dot(seed, neighbour_primed, neighbour_offset);
+        }
+
+        // Moves over to the lattice offset by half a cell
+        dist = vec3<f32>(0.5) - dist;
+        offset = signs * dist;
+        primed = primed + select(vec3<u32>(0u), primes, signs < vec3<f32>(0.0));
+        signs = -signs;
+
+        a = a + ((0.75 - dist.x) - (dist.y + dist.z));
+        seed = ~seed;
+    }
+
+    return clamp(value * 32.694283, -1.0, 1.0);
+}
+
+// 3D Perlin noise of the `noise` crate, ported to the CPU by `simplex::perlin`
+
+// The permutation table of `noise::Perlin` for a seed, its 256 bytes packed into 64 words lowest
+// byte first by `GpuDensity::perlin_tables`
+struct PerlinTable {
+    seed: u32;
+    values: array<u32, 64>;
+};
+
+[[block]]
+struct PerlinTables {
+    data: array<PerlinTable>;
+};
+
+// A table for every seed the chunk samples Perlin noise with, `input.perlin_table_count` of them
+[[group(0), binding(10)]]
+var<storage, read> perlin_tables: PerlinTables;
+
+// Index of the table of a seed, seeds without one are never sampled
+fn perlin_table(seed: u32) -> u32 {
+    for (var i = 0u; i < input.perlin_table_count; i = i + 1u) {
+        if (perlin_tables.data[i].seed == seed) {
+            return i;
+        }
+    }
+
+    return 0u;
+}
+
+fn perlin_permute(table: u32, index: u32) -> u32 {
+    let word = perlin_tables.data[table].values[index >> 2u];
+
+    return (word >> ((index & 3u) * 8u)) & 255u;
+}
+
+// Hash of a lattice corner like `PermutationTable::get3` of the crate
+fn perlin_hash(table: u32, corner: vec3<i32>) -> u32 {
+    let xy = perlin_permute(table, u32(corner.x & 255)) ^ u32(corner.y & 255);
+
+    return perlin_permute(table, perlin_permute(table, xy) ^ u32(corner.z & 255));
+}
+
+// Dot product of the offset from a lattice corner with one of the 12 cube edges picked by the
+// low four bits of its hash
+fn perlin_gradient_dot(hash: u32, offset: vec3<f32>) -> f32 {
+    let h = hash & 15u;
+
+    if (h == 0u || h == 12u) {
+        return offset.x + offset.y;
+    }
+    if (h == 1u || h == 13u) {
+        return -offset.x + offset.y;
+    }
+    if (h == 2u) {
+        return offset.x - offset.y;
+    }
+    if (h == 3u) {
+        return -offset.x - offset.y;
+    }
+    if (h == 4u) {
+        return offset.x + offset.z;
+    }
+    if (h == 5u) {
+        return -offset.x + offset.z;
+    }
+    if (h == 6u) {
+        return offset.x - offset.z;
+    }
+    if (h == 7u) {
+        return -offset.x - offset.z;
+    }
+    if (h == 8u) {
+        return offset.y + offset.z;
+    }
+    if (h == 9u || h == 14u) {
+        return -offset.y + offset.z;
+    }
+    if (h == 10u) {
+        return offset.y - offset.z;
+    }
+
+    return -offset.y - offset.z;
+}
+
+// Blends the gradients of the corners of the lattice cell with quintic fades, summed in the
+// trilinear form of `perlin_3d` of the crate
+fn perlin(v: vec3<f32>, seed: u32) -> f32 {
+    let table = perlin_table(seed);
+
+    let base = floor(v);
+    let corner = vec3<i32>(base);
+    let near = v - base;
+    let far = near - vec3<f32>(1.0);
+
+    let g000 = perlin_gradient_dot(perlin_hash(table, corner), near);
+    let g100 = perlin_gradient_dot(perlin_hash(table, corner + vec3<i32>(1, 0, 0)), vec3<f32>(far.x, near.y, near.z));
+    let g010 = perlin_gradient_dot(perlin_hash(table, corner + vec3<i32>(0, 1, 0)), vec3<f32>(near.x, far.y, near.z));
+    let g110 = perlin_gradient_dot(perlin_hash(table, corner + vec3<i32>(1, 1, 0)), vec3<f32>(far.x, far.y, near.z));
+    let g001 = perlin_gradient_dot(perlin_hash(table, corner + vec3<i32>(0, 0, 1)), vec3<f32>(near.x, near.y, far.z));
+    let g101 = perlin_gradient_dot(perlin_hash(table, corner + vec3<i32>(1, 0, 1)), vec3<f32>(far.x, near.y, far.z));
+    let g011 = perlin_gradient_dot(perlin_hash(table, corner + vec3<i32>(0, 1, 1)), vec3<f32>(near.x, far.y, far.z));
+    let g111 = perlin_gradient_dot(perlin_hash(table, corner + vec3<i32>(1, 1, 1)), far);
+
+    let fade = near * near * near * (near * (near * 6.0 - 15.0) + 10.0);
+    let a = fade.x;
+    let b = fade.y;
+    let c = fade.z;
+
+    let k0 = g000;
+    let k1 = g100 - g000;
+    let k2 = g010 - g000;
+    let k3 = g001 - g000;
+    let k4 = g000 + g110 - g100 - g010;
+    let k5 = g000 + g101 - g100 - g001;
+    let k6 = g000 + g011 - g010 - g001;
+    let k7 = g100 + g010 + g001 + g111 - g000 - g110 - g101 - g011;
+
+    let value = k0 + k1 * a + k2 * b + k3 * c + k4 * a * b + k5 * a * c + k6 * b * c + k7 * a * b * c;
+
+    // 2 / sqrt(3) brings the range to [-1, 1]
+    return clamp(value * 1.1547005, -1.0, 1.0);
+}
+
+// 3D gradient noise on integers in 16.16 fixed point, ported to the CPU by
+// `simplex::fixed_perlin`. Integer arithmetic has a single result on every device, so these
+// functions compute the same bits as the CPU, the tests of `simplex.rs` pin them to these
+// samples of `fixed_perlin(to_fixed(point, 1.0), seed)`:
+//
+//   point                    seed        fixed_perlin
+//   (0.3, 1.7, -2.2)         0           -7650
+//   (12.5, -3.25, 7.75)      1337        24426
+//   (-41.1, 0.05, 19.9)      0xdeadbeef  -3738
+//   (100.25, 63.5, -7.125)   42          29884
+
+let FIXED_ONE: i32 = 65536;
+
+// Product of two fixed point values rounded towards negative infinity, like
+// `simplex::fixed_mul`. WGSL has no 64 bit integers, the product is put together from the
+// partial products of the 16 bit halves.
+fn fixed_mul(a: i32, b: i32) -> i32 {
+    let ua = bitcast<u32>(a);
+    let ub = bitcast<u32>(b);
+
+    let low = (ua & 65535u) * (ub & 65535u);
+    let cross_a = (ua & 65535u) * (ub >> 16u);
+    let cross_b = (ua >> 16u) * (ub & 65535u);
+    let high = (ua >> 16u) * (ub >> 16u);
+
+    // Bits 16 to 31 of the unsigned product, and the carry into bit 32
+    let middle = (low >> 16u) + (cross_a & 65535u) + (cross_b & 65535u);
+    var upper = high + (cross_a >> 16u) + (cross_b >> 16u) + (middle >> 16u);
+
+    // The signed product subtracts the other factor from the upper word for every negative one
+    if (a < 0) {
+        upper = upper - ub;
+    }
+    if (b < 0) {
+        upper = upper - ua;
+    }
+
+    return bitcast<i32>((upper << 16u) | (middle & 65535u));
+}
+
+fn fixed_lerp(a: i32, b: i32, t: i32) -> i32 {
+    return a + fixed_mul(b - a, t);
+}
+
+// Quintic fade of an offset within a lattice cell
+fn fixed_fade(t: i32) -> i32 {
+    let cube = fixed_mul(fixed_mul(t, t), t);
+
+    return fixed_mul(cube, fixed_mul(t, t * 6 - 15 * FIXED_ONE) + 10 * FIXED_ONE);
+}
+
+// `gradient_dot` on fixed point offsets
+fn fixed_gradient_dot(seed: u32, primed: vec3<u32>, offset: vec3<i32>) -> i32 {
+    let edge = gradient_edge(seed, primed);
+
+    var first = 1;
+    if ((edge & 1u) != 0u) {
+        first = -1;
+    }
+    var second = 1;
+    if ((edge & 2u) != 0u) {
+        second = -1;
+    }
+
+    if (edge / 4u == 0u) {
+        return offset.y * first + offset.z * second;
+    }
+    if (edge / 4u == 1u) {
+        return offset.x * first + offset.z * second;
+    }
+    return offset.x * first + offset.y * second;
+}
+
+// Gradient of a corner of the lattice cell of `cell`, `step` is 0 or 1 along every axis
+fn fixed_corner(seed: u32, cell: vec3<i32>, offset: vec3<i32>, step: vec3<i32>) -> i32 {
+    let primed = bitcast<vec3<u32>>(cell + step) * vec3<u32>(501125321u, 1136930381u, 1720413743u);
+
+    return fixed_gradient_dot(seed, primed, offset - step * FIXED_ONE);
+}
+
+// `v * scale` in fixed point rounded down, like `simplex::to_fixed`
+fn to_fixed(v: vec3<f32>, scale: f32) -> vec3<i32> {
+    return vec3<i32>(floor(v * (scale * 65536.0)));
+}
+
+fn to_fixed_scalar(value: f32) -> i32 {
+    return i32(floor(value * 65536.0));
+}
+
+fn from_fixed(value: i32) -> f32 {
+    return f32(value) * 0.0000152587890625;
+}
+
+fn fixed_perlin(p: vec3<i32>, seed: u32) -> i32 {
+    let cell = p >> vec3<u32>(16u);
+    let offset = p & vec3<i32>(65535);
+    let fade = vec3<i32>(fixed_fade(offset.x), fixed_fade(offset.y), fixed_fade(offset.z));
+
+    let x00 = fixed_lerp(
+        fixed_corner(seed, cell, offset, vec3<i32>(0, 0, 0)),
+        fixed_corner(seed, cell, offset, vec3<i32>(1, 0, 0)),
+        fade.x,
+    );
+    let x10 = fixed_lerp(
+        fixed_corner(seed, cell, offset, vec3<i32>(0, 1, 0)),
+        fixed_corner(seed, cell, offset, vec3<i32>(1, 1, 0)),
+        fade.x,
+    );
+    let x01 = fixed_lerp(
+        fixed_corner(seed, cell, offset, vec3<i32>(0, 0, 1)),
+        fixed_corner(seed, cell, offset, vec3<i32>(1, 0, 1)),
+        fade.x,
+    );
+    let x11 = fixed_lerp(
+        fixed_corner(seed, cell, offset, vec3<i32>(0, 1, 1)),
+        fixed_corner(seed, cell, offset, vec3<i32>(1, 1, 1)),
+        fade.x,
+    );
+
+    let value = fixed_lerp(fixed_lerp(x00, x10, fade.y), fixed_lerp(x01, x11, fade.y), fade.z);
+
+    // 2 / sqrt(3) in fixed point, like `perlin`
+    return clamp(fixed_mul(value, 75674), -FIXED_ONE, FIXED_ONE);
+}
@@ -0,0 +1,249 @@
+use crate::{
+    density::{DensityField, SimplexDensity, SimplexNoise},
+    vertex_colors::{SurfaceVertex, VertexColorSource},
+};
+use bevy::math::{Vec2, Vec3};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Generation parameters of a region of the terrain picked by a [`BiomeMap`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Biome {
+    pub name: String,
+    /// Octaves of the terrain inside the biome, their amplitude and frequency shape it
+    #[serde(default)]
+    pub noise: SimplexNoise,
+    /// Added to the density, positive values thin the ground out, negative ones fill it in
+    #[serde(default)]
+    pub offset: f32,
+    #[serde(default)]
+    pub palette: BiomePalette,
+}
+
+/// Vertex colors of a biome, see [`BiomeColors`]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BiomePalette {
+    pub ground: [f32; 4],
+    /// Color of surfaces steeper than `cliff_slope`
+    pub cliff: [f32; 4],
+    /// Slope in radians from which on the surface counts as cliff
+    pub cliff_slope: f32,
+}
+
+impl Default for BiomePalette {
+    fn default() -> Self {
+        Self {
+            ground: [0.3, 0.55, 0.2, 1.0],
+            cliff: [0.45, 0.42, 0.4, 1.0],
+            cliff_slope: 0.9,
+        }
+    }
+}
+
+/// Splits the horizontal plane into Voronoi regions, each assigned one of `biomes`, and blends
+/// between the biomes of neighbouring regions near their borders
+///
+/// Every cell of a grid `region_size` wide holds one randomly placed region center, a point
+/// belongs to the region of the closest center. Regions whose center is less than `blend`
+/// further away than the closest one share the point, weighted by how much closer they are.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BiomeMap {
+    pub biomes: Vec<Biome>,
+    /// Average width of a region in world units
+    pub region_size: f32,
+    /// Width of the borders in world units over which neighbouring biomes are blended, 0 makes
+    /// hard borders
+    #[serde(default)]
+    pub blend: f32,
+}
+
+impl BiomeMap {
+    /// Indices of the biomes at the horizontal position `position` with their weights, summing
+    /// to 1, empty without any biome
+    pub fn weights(&self, seed: u32, position: Vec2) -> Vec<(usize, f32)> {
+        if self.biomes.is_empty() {
+            return Vec::new();
+        }
+
+        let region_size = self.region_size.max(f32::EPSILON);
+        let cell = (position / region_size).floor();
+
+        let mut regions = Vec::with_capacity(9);
+
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                let (x, z) = (cell.x as i32 + dx, cell.y as i32 + dz);
+                let hash = hash(seed, x, z);
+
+                // The low and the middle bits place the center, the high bits pick the biome
+                let jitter = Vec2::new(
+                    (hash & 0xff) as f32 / 256.0,
+                    ((hash >> 8) & 0xff) as f32 / 256.0,
+                );
+                let center = (Vec2::new(x as f32, z as f32) + jitter) * region_size;
+                let biome = (hash >> 16) as usize % self.biomes.len();
+
+                regions.push((biome, center.distance(position)));
+            }
+        }
+
+        let closest = regions
+            .iter()
+            .map(|(_, distance)| *distance)
+            .fold(f32::INFINITY, f32::min);
+
+        let mut weights: Vec<(usize, f32)> = Vec::new();
+
+        for (biome, distance) in regions {
+            let weight = if self.blend > 0.0 {
+                let t = (1.0 - (distance - closest) / self.blend).max(0.0);
+
+                t * t * (3.0 - 2.0 * t)
+            } else if distance == closest {
+                1.0
+            } else {
+                0.0
+            };
+
+            if weight <= 0.0 {
+                continue;
+            }
+
+            match weights.iter_mut().find(|(other, _)| *other == biome) {
+                Some((_, total)) => *total += weight,
+                None => weights.push((biome, weight)),
+            }
+        }
+
+        let total: f32 = weights.iter().map(|(_, weight)| weight).sum();
+
+        for (_, weight) in weights.iter_mut() {
+            *weight /= total;
+        }
+
+        weights
+    }
+}
+
+/// Samples the noise of the biomes of a [`BiomeMap`] every point lies in and blends them by
+/// their weights, so amplitudes and frequencies fade from one biome to the next
+#[derive(Clone)]
+pub struct BiomeDensity {
+    map: Arc<BiomeMap>,
+    seed: u32,
+    densities: Arc<Vec<SimplexDensity>>,
+}
+
+impl BiomeDensity {
+    pub fn new(map: BiomeMap, seed: u32) -> Self {
+        let densities = map
+            .biomes
+            .iter()
+            .map(|biome| SimplexDensity::with_noise(seed, biome.noise))
+            .collect();
+
+        Self {
+            map: Arc::new(map),
+            seed,
+            densities: Arc::new(densities),
+        }
+    }
+
+    pub fn map(&self) -> &BiomeMap {
+        &self.map
+    }
+
+    /// Vertex colors blending the palettes of the biomes like the density blends their noise
+    pub fn colors(&self) -> BiomeColors {
+        BiomeColors {
+            map: self.map.clone(),
+            seed: self.seed,
+        }
+    }
+}
+
+impl DensityField for BiomeDensity {
+    /// Without any biome everything is air
+    fn sample(&self, p: Vec3) -> f32 {
+        let weights = self.map.weights(self.seed, Vec2::new(p.x, p.z));
+
+        if weights.is_empty() {
+            return f32::INFINITY;
+        }
+
+        weights
+            .into_iter()
+            .map(|(biome, weight)| {
+                (self.densities[biome].sample(p) + self.map.biomes[biome].offset) * weight
+            })
+            .sum()
+    }
+
+    /// Spans the bounds of every biome, a blend of them can't leave that range
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        if self.densities.is_empty() {
+            return Some((f32::INFINITY, f32::INFINITY));
+        }
+
+        self.densities.iter().zip(self.map.biomes.iter()).try_fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(low, high), (density, biome)| {
+                let (min, max) = density.bounds(min, max)?;
+
+                Some((low.min(min + biome.offset), high.max(max + biome.offset)))
+            },
+        )
+    }
+
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        Some(Arc::new(Self::new((*self.map).clone(), seed)))
+    }
+}
+
+/// Colors vertices with the palettes of the biomes of a [`BiomeDensity`], see
+/// `BiomeDensity::colors`
+pub struct BiomeColors {
+    map: Arc<BiomeMap>,
+    seed: u32,
+}
+
+impl VertexColorSource for BiomeColors {
+    fn color(&self, vertex: &SurfaceVertex) -> [f32; 4] {
+        let slope = vertex.slope();
+        let weights = self
+            .map
+            .weights(self.seed, Vec2::new(vertex.position.x, vertex.position.z));
+
+        let mut color = [0.0; 4];
+
+        for (biome, weight) in weights {
+            let palette = &self.map.biomes[biome].palette;
+            let biome_color = if slope > palette.cliff_slope {
+                palette.cliff
+            } else {
+                palette.ground
+            };
+
+            for (channel, value) in color.iter_mut().zip(biome_color.iter()) {
+                *channel += value * weight;
+            }
+        }
+
+        color
+    }
+}
+
+/// Hash of a region cell, mixed so neighbouring cells and seeds don't correlate
+fn hash(seed: u32, x: i32, z: i32) -> u32 {
+    let mut hash =
+        seed ^ (x as u32).wrapping_mul(0x8da6_b343) ^ (z as u32).wrapping_mul(0xd816_3841);
+
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb_352d);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846c_a68b);
+    hash ^= hash >> 16;
+
+    hash
+}
@@ -0,0 +1,270 @@
+use crate::{
+    chunk::ChunkCoord,
+    density::{DensityGrid, SimplexNoise},
+    mesh::MeshData,
+    terrain::TerrainSettings,
+};
+use bevy::math::UVec3;
+use std::{
+    convert::TryInto,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+const DENSITY_MAGIC: &[u8; 4] = b"MCD1";
+const MESH_MAGIC: &[u8; 4] = b"MCM1";
+
+/// Stores generated chunks on disk, so revisiting an area or restarting the app reads them back
+/// instead of generating them again
+///
+/// Entries are keyed by the seed, the noise, the chunk size, the voxel scale and the chunk
+/// coordinate, clear the cache after swapping the density field of a terrain. Files are written
+/// in native byte order and aren't meant to be shared between machines.
+#[derive(Debug, Clone)]
+pub struct ChunkCache {
+    dir: PathBuf,
+    meshes: bool,
+}
+
+impl ChunkCache {
+    /// Caches density grids in `dir`, which is created on the first write
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            meshes: false,
+        }
+    }
+
+    /// Also caches the meshes before post processing, which skips polygonizing and is the only
+    /// thing cached for chunks generated on the GPU
+    pub fn with_meshes(mut self, meshes: bool) -> Self {
+        self.meshes = meshes;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn caches_meshes(&self) -> bool {
+        self.meshes
+    }
+
+    pub fn load_density(
+        &self,
+        settings: &TerrainSettings,
+        coord: ChunkCoord,
+    ) -> Option<DensityGrid> {
+        let bytes = fs::read(self.density_path(settings, coord)).ok()?;
+        let mut reader = Reader::new(&bytes, DENSITY_MAGIC)?;
+
+        let dims = UVec3::new(reader.u32()?, reader.u32()?, reader.u32()?);
+        let values = reader.f32s((dims.x * dims.y * dims.z) as usize)?;
+
+        Some(DensityGrid::new(dims, values))
+    }
+
+    pub fn store_density(
+        &self,
+        settings: &TerrainSettings,
+        coord: ChunkCoord,
+        grid: &DensityGrid,
+    ) -> io::Result<()> {
+        let dims = grid.dims();
+
+        self.write(
+            self.density_path(settings, coord),
+            DENSITY_MAGIC,
+            |writer| {
+                writer.write_all(bytemuck::cast_slice(&[dims.x, dims.y, dims.z]))?;
+                writer.write_all(bytemuck::cast_slice(grid.values()))
+            },
+        )
+    }
+
+    pub fn load_mesh(&self, settings: &TerrainSettings, coord: ChunkCoord) -> Option<MeshData> {
+        if !self.meshes {
+            return None;
+        }
+
+        let bytes = fs::read(self.mesh_path(settings, coord)).ok()?;
+        let mut reader = Reader::new(&bytes, MESH_MAGIC)?;
+
+        let vertex_count = reader.u32()? as usize;
+        let index_count = reader.u32()? as usize;
+
+        let positions = reader.f32s(vertex_count * 3)?;
+        let normals = reader.f32s(vertex_count * 3)?;
+        let indices = (0..index_count)
+            .map(|_| reader.u32())
+            .collect::<Option<Vec<_>>>()?;
+
+        let to_vectors = |values: Vec<f32>| {
+            values
+                .chunks_exact(3)
+                .map(|vector| [vector[0], vector[1], vector[2]])
+                .collect()
+        };
+
+        Some(MeshData {
+            positions: to_vectors(positions),
+            normals: to_vectors(normals),
+            indices,
+            colors: Vec::new(),
+        })
+    }
+
+    /// Does nothing unless the cache was created `with_meshes`
+    pub fn store_mesh(
+        &self,
+        settings: &TerrainSettings,
+        coord: ChunkCoord,
+        mesh_data: &MeshData,
+    ) -> io::Result<()> {
+        if !self.meshes {
+            return Ok(());
+        }
+
+        self.write(self.mesh_path(settings, coord), MESH_MAGIC, |writer| {
+            writer.write_all(bytemuck::cast_slice(&[
+                mesh_data.positions.len() as u32,
+                mesh_data.indices.len() as u32,
+            ]))?;
+            writer.write_all(bytemuck::cast_slice(&mesh_data.positions))?;
+            writer.write_all(bytemuck::cast_slice(&mesh_data.normals))?;
+            writer.write_all(bytemuck::cast_slice(&mesh_data.indices))
+        })
+    }
+
+    /// Forgets a chunk, for chunks whose density was edited
+    pub fn remove(&self, settings: &TerrainSettings, coord: ChunkCoord) {
+        let _ = fs::remove_file(self.density_path(settings, coord));
+        let _ = fs::remove_file(self.mesh_path(settings, coord));
+    }
+
+    /// Deletes every cached chunk
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    fn key(settings: &TerrainSettings, coord: ChunkCoord) -> String {
+        format!(
+            "{}_{:016x}_{}_{:08x}_{}_{}_{}",
+            settings.seed,
+            noise_hash(&settings.noise),
+            settings.chunk_size,
+            settings.voxel_scale.to_bits(),
+            coord.0.x,
+            coord.0.y,
+            coord.0.z
+        )
+    }
+
+    fn density_path(&self, settings: &TerrainSettings, coord: ChunkCoord) -> PathBuf {
+        self.dir
+            .join(format!("{}.density", Self::key(settings, coord)))
+    }
+
+    /// Meshes also depend on the iso level, the vertex placement and the algorithm
+    fn mesh_path(&self, settings: &TerrainSettings, coord: ChunkCoord) -> PathBuf {
+        self.dir.join(format!(
+            "{}_{:08x}_{}_{}.mesh",
+            Self::key(settings, coord),
+            settings.iso_level.to_bits(),
+            settings.vertex_placement as u8,
+            settings.algorithm as u8
+        ))
+    }
+
+    /// Writes into a temporary file that is renamed afterwards, so a crash never leaves a
+    /// truncated entry behind
+    fn write(
+        &self,
+        path: PathBuf,
+        magic: &[u8; 4],
+        contents: impl FnOnce(&mut fs::File) -> io::Result<()>,
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let temporary_path = path.with_extension("tmp");
+
+        let mut file = fs::File::create(&temporary_path)?;
+        file.write_all(magic)?;
+        contents(&mut file)?;
+        drop(file);
+
+        fs::rename(temporary_path, path)
+    }
+}
+
+/// FNV-1a hash of every field of the noise, stable between runs and builds unlike the hashers of
+/// `std`
+fn noise_hash(noise: &SimplexNoise) -> u64 {
+    let words = [
+        noise.frequency.to_bits(),
+        noise.octaves,
+        noise.amplitude.to_bits(),
+        noise.lacunarity.to_bits(),
+        noise.persistence.to_bits(),
+        noise.octave_seed_offset,
+        noise.ridged_octaves,
+        noise.billow_octaves,
+        noise.warp.layers,
+        noise.warp.strength.to_bits(),
+        noise.warp.frequency.to_bits(),
+        noise.basis as u32,
+    ];
+
+    words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], magic: &[u8; 4]) -> Option<Self> {
+        if bytes.len() < magic.len() || &bytes[..magic.len()] != magic {
+            return None;
+        }
+
+        Some(Self {
+            bytes: &bytes[magic.len()..],
+        })
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_ne_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn f32s(&mut self, len: usize) -> Option<Vec<f32>> {
+        let bytes = self.take(len * 4)?;
+
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|value| f32::from_ne_bytes(value.try_into().unwrap()))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{density::DensityField, simplex};
+use bevy::math::{IVec3, Vec3};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+/// Regions whose worms are kept by a `WormCaveDensity` before the cache starts over
+const MAX_CACHED_REGIONS: usize = 4096;
+
+/// Perlin worms tunneling through the terrain, see [`WormCaveDensity`]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WormCaves {
+    /// Width of the cubic regions of which every one may start a worm, in world units
+    pub region_size: f32,
+    /// Chance of a region to start a worm, from 0 to 1
+    pub chance: f32,
+    /// Number of segments of every worm
+    pub segments: u32,
+    /// Length of a segment in world units
+    pub segment_length: f32,
+    /// Radius of the tunnel in world units
+    pub radius: f32,
+    /// Fraction by which the radius swells and narrows along a worm, from 0 to 1
+    pub radius_variation: f32,
+    /// Largest change of direction between two segments in radians
+    pub turn: f32,
+    /// Steepest a worm climbs or descends in radians, keeps the tunnels walkable
+    pub max_pitch: f32,
+    /// Scale from world units to the noise space steering the worms, lower values bend the
+    /// tunnels in wider curves
+    pub frequency: f32,
+}
+
+impl Default for WormCaves {
+    fn default() -> Self {
+        Self {
+            region_size: 64.0,
+            chance: 0.5,
+            segments: 24,
+            segment_length: 4.0,
+            radius: 3.0,
+            radius_variation: 0.3,
+            turn: 0.6,
+            max_pitch: 0.5,
+            frequency: 1.0 / 32.0,
+        }
+    }
+}
+
+impl WormCaves {
+    /// Farthest a tunnel can reach from the start of its worm
+    fn reach(&self) -> f32 {
+        self.segments as f32 * self.segment_length + self.max_radius()
+    }
+
+    fn max_radius(&self) -> f32 {
+        self.radius * (1.0 + self.radius_variation.abs())
+    }
+}
+
+/// Segment of a tunnel, a capsule from `start` to `end`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WormSegment {
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+}
+
+impl WormSegment {
+    /// Signed distance to the surface of the capsule
+    fn distance(&self, p: Vec3) -> f32 {
+        let axis = self.end - self.start;
+        let t = ((p - self.start).dot(axis) / axis.length_squared().max(f32::EPSILON))
+            .max(0.0)
+            .min(1.0);
+
+        (p - (self.start + axis * t)).length() - self.radius
+    }
+}
+
+/// Worms started by a region and the box containing them
+struct RegionWorms {
+    segments: Vec<WormSegment>,
+    min: Vec3,
+    max: Vec3,
+}
+
+/// Connected cave networks carved by Perlin worms, tunnels made of capsules following paths
+/// steered by noise
+///
+/// Every region of a grid `region_size` wide starts a worm at a random point with a random
+/// heading. At every segment the worm turns by noise sampled at its head, so the tunnels wind
+/// smoothly and cross each other, unlike the isolated pockets noise carves out alone.
+///
+/// The density is the negated distance to the closest tunnel, positive inside the tunnels, so
+/// the caves are subtracted from the terrain by a layer with `LayerBlend::Max`. Far from any
+/// tunnel it stops at `-radius`, which keeps the field bounded.
+///
+/// The worms of a region are generated the first time a sample reaches it and cached, clones
+/// of the density share the cache.
+#[derive(Clone)]
+pub struct WormCaveDensity {
+    caves: WormCaves,
+    seed: u32,
+    regions: Arc<Mutex<HashMap<IVec3, Arc<RegionWorms>>>>,
+}
+
+impl WormCaveDensity {
+    pub fn new(caves: WormCaves, seed: u32) -> Self {
+        Self {
+            caves,
+            seed,
+            regions: Arc::default(),
+        }
+    }
+
+    pub fn caves(&self) -> WormCaves {
+        self.caves
+    }
+
+    /// Signed distance to the closest tunnel, at most `radius`
+    pub fn distance(&self, p: Vec3) -> f32 {
+        let region_size = self.caves.region_size.max(f32::EPSILON);
+        let range = (self.caves.reach() / region_size).ceil() as i32;
+        let region = (p / region_size).floor();
+        let region = IVec3::new(region.x as i32, region.y as i32, region.z as i32);
+
+        let mut closest = self.caves.radius;
+        let mut regions = self.regions.lock().unwrap();
+
+        for z in -range..=range {
+            for y in -range..=range {
+                for x in -range..=range {
+                    let worms = self.region(&mut regions, region + IVec3::new(x, y, z));
+
+                    // Skips regions whose tunnels can't get any closer
+                    if (p.max(worms.min).min(worms.max) - p).length() >= closest {
+                        continue;
+                    }
+
+                    for segment in worms.segments.iter() {
+                        closest = closest.min(segment.distance(p));
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    fn region(
+        &self,
+        regions: &mut HashMap<IVec3, Arc<RegionWorms>>,
+        region: IVec3,
+    ) -> Arc<RegionWorms> {
+        if regions.len() >= MAX_CACHED_REGIONS && !regions.contains_key(&region) {
+            regions.clear();
+        }
+
+        regions
+            .entry(region)
+            .or_insert_with(|| Arc::new(self.generate(region)))
+            .clone()
+    }
+
+    /// Walks the worm of a region, if it has one
+    fn generate(&self, region: IVec3) -> RegionWorms {
+        let caves = &self.caves;
+        let hash = hash(self.seed, region);
+
+        let mut worms = RegionWorms {
+            segments: Vec::new(),
+            min: Vec3::splat(f32::INFINITY),
+            max: Vec3::splat(f32::NEG_INFINITY),
+        };
+
+        if (hash & 0xffff) as f32 / 65536.0 >= caves.chance {
+            return worms;
+        }
+
+        let jitter = |shift: u32| ((hash.rotate_left(shift) >> 24) as f32 + 0.5) / 256.0;
+
+        let mut head =
+            (region.as_vec3() + Vec3::new(jitter(8), jitter(16), jitter(24))) * caves.region_size;
+        let mut yaw = jitter(4) * 2.0 * PI;
+        let mut pitch = 0.0f32;
+
+        // Every steering field samples its own seed
+        let steer = |p: Vec3, field: u32| {
+            simplex::opensimplex2(p * caves.frequency, self.seed.wrapping_add(field))
+        };
+
+        for _ in 0..caves.segments {
+            yaw += steer(head, 1) * caves.turn;
+            pitch = (pitch + steer(head, 2) * caves.turn)
+                .max(-caves.max_pitch)
+                .min(caves.max_pitch);
+
+            let direction = Vec3::new(
+                pitch.cos() * yaw.cos(),
+                pitch.sin(),
+                pitch.cos() * yaw.sin(),
+            );
+            let end = head + direction * caves.segment_length;
+            let radius = caves.radius * (1.0 + steer(head, 3) * caves.radius_variation);
+
+            worms.min = worms.min.min(head.min(end) - Vec3::splat(radius));
+            worms.max = worms.max.max(head.max(end) + Vec3::splat(radius));
+            worms.segments.push(WormSegment {
+                start: head,
+                end,
+                radius,
+            });
+
+            head = end;
+        }
+
+        worms
+    }
+}
+
+impl DensityField for WormCaveDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        -self.distance(p)
+    }
+
+    fn bounds(&self, _min: Vec3, _max: Vec3) -> Option<(f32, f32)> {
+        Some((-self.caves.radius, self.caves.max_radius()))
+    }
+
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        Some(Arc::new(Self::new(self.caves, seed)))
+    }
+}
+
+/// Hash of a region, mixed so neighbouring regions and seeds don't correlate
+fn hash(seed: u32, region: IVec3) -> u32 {
+    let mut hash = seed
+        ^ (region.x as u32).wrapping_mul(0x8da6_b343)
+        ^ (region.y as u32).wrapping_mul(0xd816_3841)
+        ^ (region.z as u32).wrapping_mul(0xcb1a_b31f);
+
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb_352d);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846c_a68b);
+    hash ^= hash >> 16;
+
+    hash
+}
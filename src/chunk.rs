@@ -0,0 +1,229 @@
+use crate::mesh::MeshData;
+use bevy::{
+    ecs::entity::Entity,
+    math::{IVec3, Vec3},
+    reflect::Reflect,
+    transform::components::GlobalTransform,
+};
+use std::collections::HashMap;
+
+use bevy_inspector_egui::{egui, Context, Inspectable};
+
+/// Position of a chunk on the chunk grid
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct ChunkCoord(pub IVec3);
+
+impl ChunkCoord {
+    /// Offsets of the six chunks sharing a face with a chunk
+    pub const FACE_NEIGHBORS: [IVec3; 6] = [
+        IVec3::new(-1, 0, 0),
+        IVec3::new(1, 0, 0),
+        IVec3::new(0, -1, 0),
+        IVec3::new(0, 1, 0),
+        IVec3::new(0, 0, -1),
+        IVec3::new(0, 0, 1),
+    ];
+
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self(IVec3::new(x, y, z))
+    }
+
+    pub fn offset(self, offset: IVec3) -> Self {
+        Self(self.0 + offset)
+    }
+
+    /// Coordinates of the six face neighbors
+    pub fn neighbors(self) -> impl Iterator<Item = ChunkCoord> {
+        Self::FACE_NEIGHBORS
+            .iter()
+            .map(move |offset| self.offset(*offset))
+    }
+
+    /// Squared distance between two chunks, in chunks
+    pub fn distance_squared(self, other: ChunkCoord) -> i32 {
+        let offset = self.0 - other.0;
+
+        offset.x * offset.x + offset.y * offset.y + offset.z * offset.z
+    }
+
+    /// Coordinates of every chunk whose distance to this one is at most `radius` chunks
+    pub fn within_radius(self, radius: u32) -> impl Iterator<Item = ChunkCoord> {
+        let radius = radius as i32;
+
+        (-radius..=radius)
+            .flat_map(move |z| {
+                (-radius..=radius)
+                    .flat_map(move |y| (-radius..=radius).map(move |x| IVec3::new(x, y, z)))
+            })
+            .filter(move |offset| offset.dot(*offset) <= radius * radius)
+            .map(move |offset| self.offset(offset))
+    }
+
+    /// Coordinates of all 26 chunks touching this one, including edges and corners
+    pub fn neighbors_with_diagonals(self) -> impl Iterator<Item = ChunkCoord> {
+        (-1..=1)
+            .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| IVec3::new(x, y, z))))
+            .filter(|offset| *offset != IVec3::ZERO)
+            .map(move |offset| self.offset(offset))
+    }
+}
+
+/// Shown read only, moving a chunk means despawning it and generating another one
+impl Inspectable for ChunkCoord {
+    type Attributes = ();
+
+    fn ui(&mut self, ui: &mut egui::Ui, _options: Self::Attributes, _context: &Context) -> bool {
+        ui.label(format!("{} {} {}", self.0.x, self.0.y, self.0.z));
+
+        false
+    }
+}
+
+impl From<IVec3> for ChunkCoord {
+    fn from(coord: IVec3) -> Self {
+        Self(coord)
+    }
+}
+
+impl From<ChunkCoord> for IVec3 {
+    fn from(coord: ChunkCoord) -> Self {
+        coord.0
+    }
+}
+
+/// Maps chunk coordinates to the entities of the chunks a terrain currently has spawned
+#[derive(Debug, Default)]
+pub struct ChunkMap {
+    chunks: HashMap<ChunkCoord, Entity>,
+}
+
+impl ChunkMap {
+    pub fn get(&self, coord: ChunkCoord) -> Option<Entity> {
+        self.chunks.get(&coord).copied()
+    }
+
+    pub fn contains(&self, coord: ChunkCoord) -> bool {
+        self.chunks.contains_key(&coord)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ChunkCoord, Entity)> + '_ {
+        self.chunks.iter().map(|(coord, entity)| (*coord, *entity))
+    }
+
+    pub fn coords(&self) -> impl Iterator<Item = ChunkCoord> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// Spawned face neighbors of a chunk
+    pub fn neighbors(&self, coord: ChunkCoord) -> impl Iterator<Item = (ChunkCoord, Entity)> + '_ {
+        coord
+            .neighbors()
+            .filter_map(move |neighbor| self.get(neighbor).map(|entity| (neighbor, entity)))
+    }
+
+    pub(crate) fn insert(&mut self, coord: ChunkCoord, entity: Entity) {
+        self.chunks.insert(coord, entity);
+    }
+
+    pub(crate) fn remove(&mut self, coord: ChunkCoord) -> Option<Entity> {
+        self.chunks.remove(&coord)
+    }
+}
+
+/// Axis aligned box around the surface of a chunk in its local space, in cells, inserted on the
+/// chunk entity whenever its mesh is replaced, for culling and picking
+///
+/// Chunks drawn by `MeshingBackend::GpuResident` get the box of the whole chunk, their vertices
+/// never reach the CPU.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkBounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ChunkBounds {
+    /// Box around every vertex of the mesh, `None` for meshes without vertices
+    pub fn from_mesh(mesh: &MeshData) -> Option<Self> {
+        let mut positions = mesh.positions.iter().map(|position| Vec3::from(*position));
+        let first = positions.next()?;
+
+        Some(positions.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |bounds, position| Self {
+                min: bounds.min.min(position),
+                max: bounds.max.max(position),
+            },
+        ))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) / 2.0
+    }
+
+    /// Center and radius of the smallest sphere around the box
+    pub fn bounding_sphere(&self) -> (Vec3, f32) {
+        (self.center(), self.half_extents().length())
+    }
+
+    /// Box around the bounds moved into world space by the transform of the chunk
+    pub fn transformed(&self, transform: &GlobalTransform) -> Self {
+        let matrix = transform.compute_matrix();
+
+        let mut corners = (0..8).map(|corner| {
+            let select = |bit: u32, min: f32, max: f32| if corner & bit == 0 { min } else { max };
+
+            matrix.transform_point3(Vec3::new(
+                select(1, self.min.x, self.max.x),
+                select(2, self.min.y, self.max.y),
+                select(4, self.min.z, self.max.z),
+            ))
+        });
+
+        let first = corners.next().unwrap();
+
+        corners.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |bounds, corner| Self {
+                min: bounds.min.min(corner),
+                max: bounds.max.max(corner),
+            },
+        )
+    }
+
+    /// Distance along the ray to where it enters the box, `None` when it misses it
+    ///
+    /// The ray is given in the same space as the bounds, a ray starting inside hits at 0.
+    pub fn intersect_ray(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let inverse = direction.recip();
+
+        let a = (self.min - origin) * inverse;
+        let b = (self.max - origin) * inverse;
+
+        let near = a.min(b).max_element().max(0.0);
+        let far = a.max(b).min_element();
+
+        if near <= far {
+            Some(near)
+        } else {
+            None
+        }
+    }
+}
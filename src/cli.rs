@@ -0,0 +1,183 @@
+use marching_cubes::{MeshingAlgorithm, MeshingBackend, TerrainSettings};
+use std::{env, fmt, path::PathBuf, str::FromStr};
+
+const USAGE: &str = "\
+Usage: marching_cubes [OPTIONS]
+
+Options:
+    --seed <SEED>            Seed of the terrain noise
+    --chunk-size <CELLS>     Number of cells along each edge of a chunk
+    --world-radius <CHUNKS>  Radius, in chunks, of the generated area
+    --unload-radius <CHUNKS> Radius, in chunks, beyond which chunks are despawned
+    --backend <BACKEND>      Meshing backend, one of auto, gpu, gpu-resident and cpu
+    --algorithm <ALGORITHM>  Meshing algorithm, one of marching-cubes, marching-cubes33,
+                             dual-contouring, surface-nets, marching-tetrahedra and
+                             heightmap
+    --width <PIXELS>         Window width
+    --height <PIXELS>        Window height
+    --vsync <BOOL>           Whether to wait for vertical sync, true or false
+    --config <PATH>          Config file in the assets folder, watched and applied while running,
+                             the terrain options passed here take precedence over it
+    --no-config              Do not load a config file
+    --headless               Write the chunk meshes as OBJ files instead of opening a window
+    --output <DIR>           Directory the headless mode writes to
+    --check-seams            Fail the headless mode when neighbouring chunks leave gaps
+    --cache <DIR>            Directory generated chunks are cached in between runs
+    -h, --help               Print this message";
+
+/// Options of the demo binary, anything not passed keeps the defaults of the library
+pub struct Args {
+    /// Defaults of the library with the `overrides` applied
+    pub settings: TerrainSettings,
+    pub overrides: SettingsOverrides,
+    pub backend: MeshingBackend,
+    pub width: f32,
+    pub height: f32,
+    pub vsync: bool,
+    pub config: Option<String>,
+    pub headless: bool,
+    pub output: PathBuf,
+    pub check_seams: bool,
+    pub cache: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            settings: TerrainSettings::default(),
+            overrides: SettingsOverrides::default(),
+            backend: MeshingBackend::default(),
+            width: 1920.0,
+            height: 1080.0,
+            vsync: true,
+            config: Some("terrain.ron".to_string()),
+            headless: false,
+            output: PathBuf::from("output"),
+            check_seams: false,
+            cache: None,
+        }
+    }
+}
+
+impl Args {
+    /// Parses the arguments of the process, printing the usage and exiting on `--help` or on
+    /// invalid arguments
+    pub fn from_env() -> Self {
+        match Self::parse(env::args().skip(1)) {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(error) => {
+                eprintln!("{}\n\n{}", error, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// Returns `None` when the help was requested
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for `{}`", arg))
+            };
+
+            match arg.as_str() {
+                "--seed" => parsed.overrides.seed = Some(parse_value(&arg, value()?)?),
+                "--chunk-size" => parsed.overrides.chunk_size = Some(parse_value(&arg, value()?)?),
+                "--world-radius" => {
+                    parsed.overrides.world_extent = Some(parse_value(&arg, value()?)?)
+                }
+                "--unload-radius" => {
+                    parsed.overrides.unload_extent = Some(parse_value(&arg, value()?)?)
+                }
+                "--backend" => {
+                    parsed.backend = match value()?.as_str() {
+                        "auto" => MeshingBackend::Auto,
+                        "gpu" => MeshingBackend::Gpu,
+                        "gpu-resident" => MeshingBackend::GpuResident,
+                        "cpu" => MeshingBackend::Cpu,
+                        backend => return Err(format!("unknown backend `{}`", backend)),
+                    }
+                }
+                "--algorithm" => {
+                    parsed.overrides.algorithm = Some(match value()?.as_str() {
+                        "marching-cubes" => MeshingAlgorithm::MarchingCubes,
+                        "marching-cubes33" => MeshingAlgorithm::MarchingCubes33,
+                        "dual-contouring" => MeshingAlgorithm::DualContouring,
+                        "surface-nets" => MeshingAlgorithm::SurfaceNets,
+                        "marching-tetrahedra" => MeshingAlgorithm::MarchingTetrahedra,
+                        "heightmap" => MeshingAlgorithm::Heightmap,
+                        algorithm => return Err(format!("unknown algorithm `{}`", algorithm)),
+                    })
+                }
+                "--width" => parsed.width = parse_value(&arg, value()?)?,
+                "--height" => parsed.height = parse_value(&arg, value()?)?,
+                "--vsync" => parsed.vsync = parse_value(&arg, value()?)?,
+                "--config" => parsed.config = Some(value()?),
+                "--no-config" => parsed.config = None,
+                "--headless" => parsed.headless = true,
+                "--output" => parsed.output = PathBuf::from(value()?),
+                "--check-seams" => parsed.check_seams = true,
+                "--cache" => parsed.cache = Some(PathBuf::from(value()?)),
+                "-h" | "--help" => return Ok(None),
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+
+        parsed.overrides.apply(&mut parsed.settings);
+
+        // Any size works, the dispatches round up to whole workgroups
+        if parsed.settings.chunk_size == 0 {
+            return Err("`--chunk-size` must be positive".to_string());
+        }
+
+        Ok(Some(parsed))
+    }
+}
+
+/// Terrain options passed on the command line, applied on top of the defaults and of every
+/// config file loaded
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SettingsOverrides {
+    pub seed: Option<u32>,
+    pub chunk_size: Option<u32>,
+    pub world_extent: Option<u32>,
+    pub unload_extent: Option<u32>,
+    pub algorithm: Option<MeshingAlgorithm>,
+}
+
+impl SettingsOverrides {
+    pub fn apply(&self, settings: &mut TerrainSettings) {
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(chunk_size) = self.chunk_size {
+            settings.chunk_size = chunk_size;
+        }
+        if let Some(world_extent) = self.world_extent {
+            settings.world_extent = world_extent;
+        }
+        if let Some(unload_extent) = self.unload_extent {
+            settings.unload_extent = unload_extent;
+        }
+        if let Some(algorithm) = self.algorithm {
+            settings.algorithm = algorithm;
+        }
+    }
+}
+
+fn parse_value<T>(arg: &str, value: String) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|error| format!("invalid value `{}` for `{}`: {}", value, arg, error))
+}
@@ -0,0 +1,169 @@
+use crate::{cli::SettingsOverrides, plugins::MovementSettings};
+use bevy::{
+    app::{App, EventReader, Plugin},
+    asset::{
+        AddAsset, AssetEvent, AssetLoader, AssetServer, Assets, Handle, LoadContext, LoadedAsset,
+    },
+    ecs::{
+        query::With,
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use marching_cubes::{
+    density::SimplexDensity, DensityLayerConfig, LayeredDensity, Terrain, TerrainDensity,
+    TerrainSettings,
+};
+use serde::Deserialize;
+
+/// Terrain and camera configuration loaded from a RON file, the file is watched and every change
+/// is applied to the running app
+#[derive(Debug, Default, Deserialize, TypeUuid)]
+#[uuid = "1c43042f-486b-40f8-aadc-d29e7a8ec340"]
+#[serde(default)]
+pub struct Config {
+    pub terrain: TerrainSettings,
+    /// Density layers replacing the density of every terrain, when there are any
+    pub layers: Vec<DensityLayerConfig>,
+    pub camera: CameraConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub sensitivity: f32,
+    pub speed: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        let movement_settings = MovementSettings::default();
+
+        Self {
+            sensitivity: movement_settings.sensitivity,
+            speed: movement_settings.speed,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ConfigLoader;
+
+impl AssetLoader for ConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let config: Config = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// Keeps the loaded config alive
+struct ConfigHandle(Handle<Config>);
+
+struct ConfigPath(String);
+
+/// Loads a `Config` from the assets folder, hot reloading requires `watch_for_changes` in the
+/// `AssetServerSettings`
+///
+/// The terrain settings of the file are applied with the `overrides` of the command line on top,
+/// so a `--seed` or `--chunk-size` passed to the binary survives every reload.
+pub struct ConfigPlugin {
+    path: String,
+    overrides: SettingsOverrides,
+}
+
+impl ConfigPlugin {
+    pub fn new(path: impl Into<String>, overrides: SettingsOverrides) -> Self {
+        Self {
+            path: path.into(),
+            overrides,
+        }
+    }
+}
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Config>()
+            .init_asset_loader::<ConfigLoader>()
+            .insert_resource(ConfigPath(self.path.clone()))
+            .insert_resource(self.overrides)
+            .add_startup_system(load_config)
+            .add_system(apply_config);
+    }
+}
+
+fn load_config(mut commands: Commands, asset_server: Res<AssetServer>, path: Res<ConfigPath>) {
+    commands.insert_resource(ConfigHandle(asset_server.load(path.0.as_str())));
+}
+
+fn apply_config(
+    mut config_events: EventReader<AssetEvent<Config>>,
+    configs: Res<Assets<Config>>,
+    overrides: Res<SettingsOverrides>,
+    mut movement_settings: ResMut<MovementSettings>,
+    mut terrain_query: Query<(&mut TerrainSettings, &mut TerrainDensity), With<Terrain>>,
+    mut applied_layers: Local<Vec<DensityLayerConfig>>,
+) {
+    for event in config_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        let config = match configs.get(handle) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        movement_settings.sensitivity = config.camera.sensitivity;
+        movement_settings.speed = config.camera.speed;
+
+        let mut terrain = config.terrain.clone();
+        overrides.apply(&mut terrain);
+
+        let layers_changed = config.layers != *applied_layers;
+
+        if layers_changed {
+            *applied_layers = config.layers.clone();
+        }
+
+        for (mut settings, mut density) in terrain_query.iter_mut() {
+            // Layers are rebuilt with the new seed, removing them goes back to the default noise
+            if layers_changed || (!config.layers.is_empty() && settings.seed != terrain.seed) {
+                *density = if config.layers.is_empty() {
+                    TerrainDensity::new(SimplexDensity::with_noise(terrain.seed, terrain.noise))
+                } else {
+                    TerrainDensity::new(LayeredDensity::from_config(&config.layers, terrain.seed))
+                };
+                *settings = terrain.clone();
+                continue;
+            }
+
+            // Only touching the settings when they differ keeps camera tweaks from remeshing
+            if *settings == terrain {
+                continue;
+            }
+
+            // Fields without a seed keep sampling like before, on the GPU as well since it
+            // takes the seed from the density
+            if settings.seed != terrain.seed {
+                if let Some(reseeded) = density.0.with_seed(terrain.seed) {
+                    density.0 = reseeded;
+                }
+            }
+
+            *settings = terrain.clone();
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::density::DensityField;
+use bevy::math::{Vec2, Vec3};
+use std::collections::HashMap;
+
+/// Polyline where a horizontal slice of a density field crosses the iso level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    /// Points on the x and z axes of the slice, in the space the density is sampled in
+    pub points: Vec<Vec2>,
+    /// Whether the last point connects back to the first, contours reaching the edge of the
+    /// sliced area stay open
+    pub closed: bool,
+}
+
+/// Edge of the sample lattice, the sample it starts at and whether it runs along z instead of x
+type LatticeEdge = (u32, u32, bool);
+
+/// Slices the density field at `height` and traces where it crosses the iso level inside the
+/// rectangle from `min` to `max` on the x and z axes, for minimaps, top down overlays and
+/// debugging density functions
+///
+/// Samples a square lattice `step` apart and runs marching squares over it, then joins the
+/// segments of neighbouring cells into polylines. Saddle cells are resolved by the density in
+/// their center. Contours thinner than a cell can be missed, like features thinner than a voxel
+/// by the meshers.
+pub fn contours(
+    density: &dyn DensityField,
+    height: f32,
+    min: Vec2,
+    max: Vec2,
+    step: f32,
+    iso_level: f32,
+) -> Vec<Contour> {
+    if step <= 0.0 || max.x <= min.x || max.y <= min.y {
+        return Vec::new();
+    }
+
+    let columns = ((max.x - min.x) / step).ceil() as u32 + 1;
+    let rows = ((max.y - min.y) / step).ceil() as u32 + 1;
+
+    let position = |x: f32, z: f32| min + Vec2::new(x, z) * step;
+    let sample = |x: f32, z: f32| {
+        let position = position(x, z);
+        density.sample(Vec3::new(position.x, height, position.y))
+    };
+
+    let mut samples = Vec::with_capacity((columns * rows) as usize);
+
+    for z in 0..rows {
+        for x in 0..columns {
+            samples.push(sample(x as f32, z as f32));
+        }
+    }
+
+    let value = |x: u32, z: u32| samples[(z * columns + x) as usize];
+    let solid = |x: u32, z: u32| value(x, z) < iso_level;
+
+    let mut points = HashMap::new();
+    let mut segments = Vec::new();
+
+    for z in 0..rows - 1 {
+        for x in 0..columns - 1 {
+            // Counterclockwise from the first corner, edge `i` runs from corner `i` to `i + 1`
+            let corners = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)];
+            let edges = [
+                (x, z, false),
+                (x + 1, z, true),
+                (x, z + 1, false),
+                (x, z, true),
+            ];
+
+            let crossed = (0..4)
+                .filter(|edge| {
+                    let (first, second) = (corners[*edge], corners[(*edge + 1) % 4]);
+                    solid(first.0, first.1) != solid(second.0, second.1)
+                })
+                .collect::<Vec<usize>>();
+
+            let pairs = match crossed.len() {
+                2 => vec![(crossed[0], crossed[1])],
+                4 => {
+                    let center = sample(x as f32 + 0.5, z as f32 + 0.5) < iso_level;
+
+                    // Either the diagonal through the first corner or the other one is
+                    // connected through the center
+                    if center == solid(x, z) {
+                        vec![(0, 1), (2, 3)]
+                    } else {
+                        vec![(3, 0), (1, 2)]
+                    }
+                }
+                _ => continue,
+            };
+
+            for (first, second) in pairs {
+                for edge in [first, second].iter() {
+                    let (start, end) = (corners[*edge], corners[(*edge + 1) % 4]);
+
+                    points.entry(edges[*edge]).or_insert_with(|| {
+                        let (start_value, end_value) =
+                            (value(start.0, start.1), value(end.0, end.1));
+                        let t = ((iso_level - start_value) / (end_value - start_value))
+                            .max(0.0)
+                            .min(1.0);
+
+                        position(start.0 as f32, start.1 as f32)
+                            .lerp(position(end.0 as f32, end.1 as f32), t)
+                    });
+                }
+
+                segments.push([edges[first], edges[second]]);
+            }
+        }
+    }
+
+    join_segments(&segments, &points)
+}
+
+/// Chains segments sharing a lattice edge into polylines, open ones first so they start at
+/// their ends
+fn join_segments(
+    segments: &[[LatticeEdge; 2]],
+    points: &HashMap<LatticeEdge, Vec2>,
+) -> Vec<Contour> {
+    let mut adjacent: HashMap<LatticeEdge, Vec<usize>> = HashMap::new();
+
+    for (segment, ends) in segments.iter().enumerate() {
+        for end in ends.iter() {
+            adjacent.entry(*end).or_default().push(segment);
+        }
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut contours = Vec::new();
+
+    let trace = |start: LatticeEdge, segment: usize, used: &mut [bool]| {
+        let mut contour_points = vec![points[&start]];
+        let (mut current, mut segment) = (start, Some(segment));
+
+        while let Some(next) = segment {
+            used[next] = true;
+
+            let [first, second] = segments[next];
+            current = if first == current { second } else { first };
+            contour_points.push(points[&current]);
+
+            segment = adjacent[&current]
+                .iter()
+                .copied()
+                .find(|segment| !used[*segment]);
+        }
+
+        let closed = current == start && contour_points.len() > 2;
+
+        if closed {
+            contour_points.pop();
+        }
+
+        Contour {
+            points: contour_points,
+            closed,
+        }
+    };
+
+    // Ends of open contours belong to a single segment, on the border of the sliced area
+    for (segment, ends) in segments.iter().enumerate() {
+        for end in ends.iter() {
+            if !used[segment] && adjacent[end].len() == 1 {
+                contours.push(trace(*end, segment, &mut used));
+            }
+        }
+    }
+
+    for (segment, ends) in segments.iter().enumerate() {
+        if !used[segment] {
+            contours.push(trace(ends[0], segment, &mut used));
+        }
+    }
+
+    contours
+}
@@ -0,0 +1,367 @@
+use crate::{
+    density::DensityGrid,
+    mesh::MeshData,
+    tables::{CORNER_INDEX_A_FROM_EDGE, CORNER_INDEX_B_FROM_EDGE, TRI_TABLE},
+    terrain::VertexPlacement,
+};
+use bevy::math::{UVec3, Vec3};
+use std::cmp::Ordering;
+
+pub(crate) const CORNER_OFFSETS: [UVec3; 8] = [
+    UVec3::new(0, 0, 0),
+    UVec3::new(1, 0, 0),
+    UVec3::new(1, 0, 1),
+    UVec3::new(0, 0, 1),
+    UVec3::new(0, 1, 0),
+    UVec3::new(1, 1, 0),
+    UVec3::new(1, 1, 1),
+    UVec3::new(0, 1, 1),
+];
+
+/// Polygonizes a density grid the same way `terrain/mesher.wgsl` does, returning an unindexed
+/// triangle list
+pub fn march_chunk(
+    grid: &DensityGrid,
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> (Vec<[f32; 3]>, Vec<u32>) {
+    march(grid.values(), grid.dims(), iso_level, placement)
+}
+
+/// Polygonizes the cells inside a `border` of ghost samples, which are only read to compute the
+/// normals from the density gradient, so chunks sharing a border get matching normals
+///
+/// Positions are relative to the first sample inside the border.
+pub fn march_chunk_smooth(
+    grid: &DensityGrid,
+    border: u32,
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> MeshData {
+    let dims = grid.dims();
+    let end = |len: u32| len.saturating_sub(border + 1);
+
+    march_cells_smooth(
+        grid,
+        border,
+        UVec3::splat(border),
+        UVec3::new(end(dims.x), end(dims.y), end(dims.z)),
+        iso_level,
+        placement,
+    )
+    .0
+}
+
+/// Like `march_chunk_smooth`, polygonizing only the cells from `min` up to but excluding `max`
+/// and returning the cell of every triangle next to the mesh
+pub(crate) fn march_cells_smooth(
+    grid: &DensityGrid,
+    border: u32,
+    min: UVec3,
+    max: UVec3,
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> (MeshData, Vec<UVec3>) {
+    assert!(
+        border >= 1,
+        "gradient normals need at least one ghost sample"
+    );
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut cells = Vec::new();
+
+    march_cells(
+        grid.values(),
+        grid.dims(),
+        min,
+        max,
+        iso_level,
+        placement,
+        |cell, a, b, position| {
+            // The triangles face towards the higher density
+            let normal = normalize_exactly(gradient(grid, a) + gradient(grid, b));
+
+            if positions.len() % 3 == 0 {
+                cells.push(cell);
+            }
+
+            positions.push((position - Vec3::splat(border as f32)).into());
+            normals.push(normal.into());
+        },
+    );
+
+    let indices = (0..positions.len() as u32).collect();
+
+    let mesh_data = MeshData {
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    };
+
+    (mesh_data, cells)
+}
+
+pub(crate) fn march(
+    density: &[f32],
+    dims: UVec3,
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> (Vec<[f32; 3]>, Vec<u32>) {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+
+    march_region(density, dims, 0, iso_level, placement, |_, _, position| {
+        positions.push(position.into())
+    });
+
+    let indices = (0..positions.len() as u32).collect();
+
+    (positions, indices)
+}
+
+/// Polygonizes every cell at least `border` samples away from the edges of the grid, passing
+/// the grid points of the crossed edge along with each vertex
+fn march_region(
+    density: &[f32],
+    dims: UVec3,
+    border: u32,
+    iso_level: f32,
+    placement: VertexPlacement,
+    mut on_vertex: impl FnMut(UVec3, UVec3, Vec3),
+) {
+    let end = |len: u32| len.saturating_sub(border + 1);
+
+    march_cells(
+        density,
+        dims,
+        UVec3::splat(border),
+        UVec3::new(end(dims.x), end(dims.y), end(dims.z)),
+        iso_level,
+        placement,
+        |_, a, b, position| on_vertex(a, b, position),
+    );
+}
+
+/// Polygonizes the cells from `min` up to but excluding `max`, passing the cell and the grid
+/// points of the crossed edge along with each vertex
+fn march_cells(
+    density: &[f32],
+    dims: UVec3,
+    min: UVec3,
+    max: UVec3,
+    iso_level: f32,
+    placement: VertexPlacement,
+    mut on_vertex: impl FnMut(UVec3, UVec3, UVec3, Vec3),
+) {
+    assert_eq!(
+        density.len(),
+        (dims.x * dims.y * dims.z) as usize,
+        "density length does not match grid dimensions"
+    );
+
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let id = UVec3::new(x, y, z);
+
+                let mut cube_corners = [(UVec3::ZERO, 0.0); 8];
+
+                for (corner, offset) in cube_corners.iter_mut().zip(CORNER_OFFSETS.iter()) {
+                    let point = id + *offset;
+
+                    *corner = (
+                        point,
+                        density[(point.z * dims.y * dims.x + point.y * dims.x + point.x) as usize],
+                    );
+                }
+
+                let mut cube_index = 0;
+
+                for (i, corner) in cube_corners.iter().enumerate() {
+                    if corner.1 < iso_level {
+                        cube_index |= 1 << i;
+                    }
+                }
+
+                for edge in TRI_TABLE[cube_index].iter().take_while(|edge| **edge != -1) {
+                    let a = cube_corners[CORNER_INDEX_A_FROM_EDGE[*edge as usize]];
+                    let b = cube_corners[CORNER_INDEX_B_FROM_EDGE[*edge as usize]];
+
+                    on_vertex(
+                        id,
+                        a.0,
+                        b.0,
+                        interpolate_vertices(a, b, iso_level, placement),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Central difference of the density at a grid point that has a neighbour on every side
+pub(crate) fn gradient(grid: &DensityGrid, point: UVec3) -> Vec3 {
+    let UVec3 { x, y, z } = point;
+
+    Vec3::new(
+        grid.get(x + 1, y, z) - grid.get(x - 1, y, z),
+        grid.get(x, y + 1, z) - grid.get(x, y - 1, z),
+        grid.get(x, y, z + 1) - grid.get(x, y, z - 1),
+    ) / 2.0
+}
+
+/// Steps `interpolate_vertices` rounds the position along an edge to, shared with
+/// `terrain/mesher.wgsl` so chunks meshed on different backends agree on their shared faces
+pub(crate) const INTERPOLATION_STEPS: u32 = 65536;
+
+/// Places the vertex of an edge crossing the surface
+///
+/// Chunks sharing a face sample the same densities on its edges, the ends are ordered by their
+/// position and the interpolation is rounded to `INTERPOLATION_STEPS`, so both chunks place the
+/// vertices of the face at the same bits whichever end the cell walks the edge from.
+pub(crate) fn interpolate_vertices(
+    a: (UVec3, f32),
+    b: (UVec3, f32),
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> Vec3 {
+    let (a, b) = if (a.0.z, a.0.y, a.0.x) <= (b.0.z, b.0.y, b.0.x) {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    let (a_position, b_position) = (a.0.as_vec3(), b.0.as_vec3());
+
+    // Both ends on either side of the iso level never have the same density, unless it is NaN
+    let t = match placement {
+        VertexPlacement::Interpolated if a.1 != b.1 => {
+            interpolation_step(iso_level - a.1, b.1 - a.1) as f32
+                * (1.0 / INTERPOLATION_STEPS as f32)
+        }
+        _ => 0.5,
+    };
+
+    a_position.lerp(b_position, t)
+}
+
+/// `numerator / denominator` clamped to `[0, 1]` and rounded to the nearest of the
+/// `INTERPOLATION_STEPS`, computed exactly from the bits of both floats
+///
+/// The division of the GPU isn't correctly rounded, `interpolation_step` of
+/// `terrain/mesher.wgsl` divides the same way, so both backends pick the same step.
+fn interpolation_step(numerator: f32, denominator: f32) -> u32 {
+    if numerator == 0.0 || (numerator < 0.0) != (denominator < 0.0) {
+        return 0;
+    }
+
+    let (numerator, denominator) = (numerator.abs(), denominator.abs());
+
+    // Also catches NaN
+    if numerator.partial_cmp(&denominator) != Some(Ordering::Less) {
+        return INTERPOLATION_STEPS;
+    }
+
+    // The quotient times twice the steps is the ratio of the mantissas shifted by the difference
+    // of the exponents, which is at most 17 since the numerator is the smaller one
+    let (numerator_mantissa, numerator_exponent) = mantissa_exponent(numerator);
+    let (denominator_mantissa, denominator_exponent) = mantissa_exponent(denominator);
+    let shift = numerator_exponent - denominator_exponent + 17;
+
+    let quotient = if shift < 0 {
+        (numerator_mantissa / denominator_mantissa) >> (-shift).min(31)
+    } else {
+        // Long division, one bit of the quotient at a time
+        let mut quotient = numerator_mantissa / denominator_mantissa;
+        let mut remainder = numerator_mantissa % denominator_mantissa;
+
+        for _ in 0..shift {
+            remainder <<= 1;
+            quotient <<= 1;
+
+            if remainder >= denominator_mantissa {
+                remainder -= denominator_mantissa;
+                quotient |= 1;
+            }
+        }
+
+        quotient
+    };
+
+    (quotient + 1) >> 1
+}
+
+/// Integer mantissa and exponent of a positive float, which is the mantissa times two to the
+/// exponent
+fn mantissa_exponent(value: f32) -> (u32, i32) {
+    let bits = value.to_bits();
+    let exponent = (bits >> 23) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Subnormals have no implicit leading bit
+    if exponent == 0 {
+        (mantissa, -149)
+    } else {
+        (mantissa | 0x80_0000, exponent - 150)
+    }
+}
+
+/// Normalizes a vector in integers, so `normalize_exactly` of `terrain/mesher.wgsl`, unlike
+/// `normalize` of the GPU, computes the same bits
+///
+/// The components are scaled by a power of two until the largest of them lies in
+/// `[2^14, 2^15)` and truncated, the length of the integer vector is its integer square root.
+/// The result is within `2^-13` of unit length, zero vectors, vectors with non-finite
+/// components and subnormal vectors come out as zero.
+pub(crate) fn normalize_exactly(v: Vec3) -> Vec3 {
+    let max = v.abs().max_element();
+    let exponent = (max.to_bits() >> 23) as i32;
+
+    if max == 0.0 || exponent == 255 {
+        return Vec3::ZERO;
+    }
+
+    // Split in two factors, so each of them is a normal float
+    let shift = 141 - exponent;
+    let scaled = v * power_of_two(shift / 2) * power_of_two(shift - shift / 2);
+    let components = scaled.as_ivec3();
+
+    let length = isqrt(
+        (components.x * components.x) as u32
+            + (components.y * components.y) as u32
+            + (components.z * components.z) as u32,
+    ) as i32;
+
+    // Subnormal vectors may truncate to nothing
+    if length == 0 {
+        return Vec3::ZERO;
+    }
+
+    let normalized = components * (1 << 15) / length;
+
+    normalized.as_vec3() * (1.0 / (1 << 15) as f32)
+}
+
+fn power_of_two(exponent: i32) -> f32 {
+    f32::from_bits(((exponent + 127) as u32) << 23)
+}
+
+/// Square root of an integer, rounded down
+fn isqrt(value: u32) -> u32 {
+    let mut remainder = value;
+    let mut root = 0;
+    let mut bit = 1 << 30;
+
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+
+        bit >>= 2;
+    }
+
+    root
+}
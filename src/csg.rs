@@ -0,0 +1,298 @@
+use crate::{
+    density::{DensityField, SimplexDensity, SimplexNoise},
+    sdf::{self, Primitive},
+};
+use bevy::math::Vec3;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Node of a CSG tree of primitives and noise, see [`CsgDensity`]
+///
+/// Smoothness is the width in world units over which the children are blended, 0 keeps the
+/// seams sharp. Values below the iso level are solid, so designed structures are best built
+/// from primitives, whose densities are distances.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CsgNode {
+    Primitive(Primitive),
+    /// Octaves of noise like those of `SimplexDensity`, seeded with the seed of the tree plus
+    /// `seed_offset`
+    Noise {
+        noise: SimplexNoise,
+        #[serde(default)]
+        seed_offset: u32,
+    },
+    /// Solid wherever any child is solid, air without any child
+    Union {
+        children: Vec<CsgNode>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    /// `base` with every child carved out of it
+    Subtract {
+        base: Box<CsgNode>,
+        children: Vec<CsgNode>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    /// Solid only where every child is solid, air without any child
+    Intersect {
+        children: Vec<CsgNode>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    /// Sums the children, for noise displacing the surface of a shape
+    Add {
+        children: Vec<CsgNode>,
+    },
+    /// Moves the child by `offset`
+    Translate {
+        offset: [f32; 3],
+        child: Box<CsgNode>,
+    },
+}
+
+impl CsgNode {
+    /// Parses a tree from RON, like the `Csg` source of a layer config
+    pub fn from_ron(source: &str) -> Result<Self, ron::Error> {
+        ron::de::from_str(source)
+    }
+
+    /// Writes the tree as RON, for saving structures built in code
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+}
+
+/// A [`CsgNode`] with the noise of its noise nodes seeded
+enum Node {
+    Primitive(Primitive),
+    Noise(SimplexDensity),
+    Union(Vec<Node>, f32),
+    Subtract(Box<Node>, Vec<Node>, f32),
+    Intersect(Vec<Node>, f32),
+    Add(Vec<Node>),
+    Translate(Vec3, Box<Node>),
+}
+
+impl Node {
+    fn new(node: &CsgNode, seed: u32) -> Self {
+        let children = |children: &[CsgNode]| {
+            children
+                .iter()
+                .map(|child| Node::new(child, seed))
+                .collect::<Vec<Node>>()
+        };
+
+        match node {
+            CsgNode::Primitive(primitive) => Node::Primitive(*primitive),
+            CsgNode::Noise { noise, seed_offset } => Node::Noise(SimplexDensity::with_noise(
+                seed.wrapping_add(*seed_offset),
+                *noise,
+            )),
+            CsgNode::Union {
+                children: nodes,
+                smoothness,
+            } => Node::Union(children(nodes), *smoothness),
+            CsgNode::Subtract {
+                base,
+                children: nodes,
+                smoothness,
+            } => Node::Subtract(
+                Box::new(Node::new(base, seed)),
+                children(nodes),
+                *smoothness,
+            ),
+            CsgNode::Intersect {
+                children: nodes,
+                smoothness,
+            } => Node::Intersect(children(nodes), *smoothness),
+            CsgNode::Add { children: nodes } => Node::Add(children(nodes)),
+            CsgNode::Translate { offset, child } => {
+                Node::Translate(Vec3::from(*offset), Box::new(Node::new(child, seed)))
+            }
+        }
+    }
+
+    fn sample(&self, p: Vec3) -> f32 {
+        match self {
+            Node::Primitive(primitive) => primitive.distance(p),
+            Node::Noise(noise) => noise.sample(p),
+            Node::Union(children, k) => children
+                .iter()
+                .map(|child| child.sample(p))
+                .reduce(|a, b| sdf::smooth_union(a, b, *k))
+                .unwrap_or(f32::INFINITY),
+            Node::Subtract(base, children, k) => {
+                children.iter().fold(base.sample(p), |a, child| {
+                    sdf::smooth_subtract(a, child.sample(p), *k)
+                })
+            }
+            Node::Intersect(children, k) => children
+                .iter()
+                .map(|child| child.sample(p))
+                .reduce(|a, b| sdf::smooth_intersect(a, b, *k))
+                .unwrap_or(f32::INFINITY),
+            Node::Add(children) => children.iter().map(|child| child.sample(p)).sum(),
+            Node::Translate(offset, child) => child.sample(p - *offset),
+        }
+    }
+
+    /// Smooth unions lie up to a quarter of the smoothness below the sharp ones, smooth
+    /// intersections and subtractions up to a quarter above
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        let children_bounds = |children: &[Node]| {
+            children
+                .iter()
+                .map(|child| child.bounds(min, max))
+                .collect::<Option<Vec<(f32, f32)>>>()
+        };
+
+        match self {
+            Node::Primitive(primitive) => primitive.bounds(min, max),
+            Node::Noise(noise) => noise.bounds(min, max),
+            Node::Union(children, k) => {
+                let bounds = children_bounds(children)?;
+                let (low, high) = bounds
+                    .into_iter()
+                    .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1)))
+                    .unwrap_or((f32::INFINITY, f32::INFINITY));
+
+                Some((low - k.max(0.0) * 0.25, high))
+            }
+            Node::Subtract(base, children, k) => {
+                let bounds = children_bounds(children)?;
+
+                Some(bounds.into_iter().fold(base.bounds(min, max)?, |a, b| {
+                    (a.0.max(-b.1), a.1.max(-b.0) + k.max(0.0) * 0.25)
+                }))
+            }
+            Node::Intersect(children, k) => {
+                let bounds = children_bounds(children)?;
+
+                Some(
+                    bounds
+                        .into_iter()
+                        .reduce(|a, b| (a.0.max(b.0), a.1.max(b.1) + k.max(0.0) * 0.25))
+                        .unwrap_or((f32::INFINITY, f32::INFINITY)),
+                )
+            }
+            Node::Add(children) => {
+                let bounds = children_bounds(children)?;
+
+                Some(
+                    bounds
+                        .into_iter()
+                        .fold((0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1)),
+                )
+            }
+            Node::Translate(offset, child) => child.bounds(min - *offset, max - *offset),
+        }
+    }
+}
+
+/// Density of a CSG tree, for designed structures like arches, towers or roads mixed into
+/// procedural terrain
+///
+/// A union of a noise node and a few primitives places them on top of the terrain, a subtract
+/// digs them in.
+pub struct CsgDensity {
+    tree: Arc<CsgNode>,
+    root: Node,
+}
+
+impl CsgDensity {
+    pub fn new(tree: CsgNode, seed: u32) -> Self {
+        Self {
+            root: Node::new(&tree, seed),
+            tree: Arc::new(tree),
+        }
+    }
+
+    pub fn tree(&self) -> &CsgNode {
+        &self.tree
+    }
+}
+
+impl DensityField for CsgDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        self.root.sample(p)
+    }
+
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        self.root.bounds(min, max)
+    }
+
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        Some(Arc::new(Self::new((*self.tree).clone(), seed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::density::NoiseBasis;
+
+    /// Tree with a node of every kind
+    fn tree() -> CsgNode {
+        CsgNode::Subtract {
+            base: Box::new(CsgNode::Union {
+                children: vec![
+                    CsgNode::Primitive(Primitive::Sphere {
+                        center: [0.0, 4.5, -2.0],
+                        radius: 12.0,
+                    }),
+                    CsgNode::Translate {
+                        offset: [16.0, 0.0, 0.25],
+                        child: Box::new(CsgNode::Primitive(Primitive::Capsule {
+                            start: [0.0, -8.0, 0.0],
+                            end: [0.0, 8.0, 0.0],
+                            radius: 3.5,
+                        })),
+                    },
+                ],
+                smoothness: 2.0,
+            }),
+            children: vec![CsgNode::Intersect {
+                children: vec![
+                    CsgNode::Primitive(Primitive::Box {
+                        center: [0.0, 0.0, 0.0],
+                        half_extents: [6.0, 6.0, 6.0],
+                    }),
+                    CsgNode::Add {
+                        children: vec![
+                            CsgNode::Primitive(Primitive::Plane {
+                                normal: [0.0, 1.0, 0.0],
+                                offset: -1.0,
+                            }),
+                            CsgNode::Noise {
+                                noise: SimplexNoise {
+                                    octaves: 3,
+                                    basis: NoiseBasis::Perlin,
+                                    ..SimplexNoise::default()
+                                },
+                                seed_offset: 7,
+                            },
+                        ],
+                    },
+                ],
+                smoothness: 0.0,
+            }],
+            smoothness: 0.5,
+        }
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let tree = tree();
+        let source = ron::to_string(&tree).unwrap();
+
+        assert_eq!(CsgNode::from_ron(&source).unwrap(), tree);
+    }
+
+    #[test]
+    fn pretty_ron_round_trip() {
+        let tree = tree();
+
+        assert_eq!(CsgNode::from_ron(&tree.to_ron().unwrap()).unwrap(), tree);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::{
+    mesh::MeshData,
+    post_process::{MeshPostProcessor, WeldVertices},
+};
+use bevy::math::Vec3;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+/// Collapses edges in order of their quadric error until at most `ratio` of the triangles are
+/// left, or no edge can be collapsed without folding the mesh over
+///
+/// The mesh is welded first. Vertices on the open border of the mesh never move, so chunks keep
+/// lining up with their neighbours and `MeshData::add_skirts` still finds the chunk faces.
+pub fn decimate(mesh: MeshData, ratio: f32) -> MeshData {
+    let mesh = WeldVertices::default().process(mesh);
+    let target = (mesh.triangle_count() as f32 * ratio.clamp(0.0, 1.0)).ceil() as usize;
+
+    if mesh.triangle_count() <= target {
+        return mesh;
+    }
+
+    Decimation::new(mesh).run(target)
+}
+
+/// Sum of the squared distances to a set of planes, weighted by the area of the triangles
+/// spanning them
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn triangle(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        let cross = (b - a).cross(c - a);
+        let length = cross.length();
+
+        if length == 0.0 {
+            return Self::default();
+        }
+
+        let normal = cross / length;
+        let area = length as f64 / 2.0;
+
+        let [x, y, z] = [normal.x as f64, normal.y as f64, normal.z as f64];
+        let d = -normal.dot(a) as f64;
+
+        let mut quadric = [
+            x * x,
+            x * y,
+            x * z,
+            x * d,
+            y * y,
+            y * z,
+            y * d,
+            z * z,
+            z * d,
+            d * d,
+        ];
+
+        for value in quadric.iter_mut() {
+            *value *= area;
+        }
+
+        Self(quadric)
+    }
+
+    fn add(self, other: Quadric) -> Self {
+        let mut sum = self.0;
+
+        for (value, other) in sum.iter_mut().zip(other.0.iter()) {
+            *value += other;
+        }
+
+        Self(sum)
+    }
+
+    fn error(&self, point: Vec3) -> f64 {
+        let q = &self.0;
+        let [x, y, z] = [point.x as f64, point.y as f64, point.z as f64];
+
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// Collapse of the vertex `from` into `into`, which is moved to `position`
+struct Collapse {
+    cost: f64,
+    from: u32,
+    into: u32,
+    position: Vec3,
+    normal: Vec3,
+    /// Versions of both vertices the collapse was computed for, it is outdated once either of
+    /// them changed
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed, so the `BinaryHeap` pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+struct Decimation {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    colors: Vec<[f32; 4]>,
+    triangles: Vec<[u32; 3]>,
+    removed: Vec<bool>,
+    vertex_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    locked: Vec<bool>,
+    versions: Vec<u32>,
+}
+
+impl Decimation {
+    fn new(mesh: MeshData) -> Self {
+        let positions: Vec<Vec3> = mesh.positions.iter().copied().map(Vec3::from).collect();
+        let normals = mesh.normals.iter().copied().map(Vec3::from).collect();
+        let triangles: Vec<[u32; 3]> = mesh
+            .indices
+            .chunks(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+
+        let mut vertex_triangles = vec![Vec::new(); positions.len()];
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut edge_uses: HashMap<(u32, u32), u32> = HashMap::new();
+
+        for (index, triangle) in triangles.iter().enumerate() {
+            let quadric = Quadric::triangle(
+                positions[triangle[0] as usize],
+                positions[triangle[1] as usize],
+                positions[triangle[2] as usize],
+            );
+
+            for corner in 0..3 {
+                let vertex = triangle[corner];
+                let next = triangle[(corner + 1) % 3];
+
+                vertex_triangles[vertex as usize].push(index);
+                quadrics[vertex as usize] = quadrics[vertex as usize].add(quadric);
+
+                *edge_uses
+                    .entry((vertex.min(next), vertex.max(next)))
+                    .or_default() += 1;
+            }
+        }
+
+        // Edges of a single triangle lie on the open border
+        let mut locked = vec![false; positions.len()];
+
+        for ((a, b), uses) in edge_uses.iter() {
+            if *uses == 1 {
+                locked[*a as usize] = true;
+                locked[*b as usize] = true;
+            }
+        }
+
+        Self {
+            removed: vec![false; triangles.len()],
+            versions: vec![0; positions.len()],
+            positions,
+            normals,
+            colors: mesh.colors,
+            triangles,
+            vertex_triangles,
+            quadrics,
+            locked,
+        }
+    }
+
+    fn run(mut self, target: usize) -> MeshData {
+        let mut heap = BinaryHeap::new();
+
+        for vertex in 0..self.positions.len() as u32 {
+            for neighbor in self.neighbors(vertex) {
+                if vertex < neighbor {
+                    heap.extend(self.collapse(vertex, neighbor));
+                }
+            }
+        }
+
+        let mut live = self.triangles.len();
+
+        while live > target {
+            let collapse = match heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+
+            let versions = (
+                self.versions[collapse.from as usize],
+                self.versions[collapse.into as usize],
+            );
+
+            if versions != collapse.versions || !self.can_collapse(&collapse) {
+                continue;
+            }
+
+            live -= self.apply(&collapse);
+
+            for neighbor in self.neighbors(collapse.into) {
+                heap.extend(self.collapse(collapse.into, neighbor));
+            }
+        }
+
+        self.into_mesh()
+    }
+
+    /// Vertices sharing a remaining triangle with `vertex`
+    fn neighbors(&self, vertex: u32) -> HashSet<u32> {
+        self.vertex_triangles[vertex as usize]
+            .iter()
+            .filter(|triangle| !self.removed[**triangle])
+            .flat_map(|triangle| self.triangles[*triangle].iter().copied())
+            .filter(|neighbor| *neighbor != vertex)
+            .collect()
+    }
+
+    /// Cheapest way to collapse the edge between `a` and `b`, onto either end or its midpoint,
+    /// `None` when both ends are locked
+    fn collapse(&self, a: u32, b: u32) -> Option<Collapse> {
+        let quadric = self.quadrics[a as usize].add(self.quadrics[b as usize]);
+        let (position_a, position_b) = (self.positions[a as usize], self.positions[b as usize]);
+        let (normal_a, normal_b) = (self.normals[a as usize], self.normals[b as usize]);
+
+        let candidates = match (self.locked[a as usize], self.locked[b as usize]) {
+            (true, true) => return None,
+            (true, false) => vec![(b, a, position_a, normal_a)],
+            (false, true) => vec![(a, b, position_b, normal_b)],
+            (false, false) => vec![
+                (b, a, position_a, normal_a),
+                (a, b, position_b, normal_b),
+                (
+                    a,
+                    b,
+                    (position_a + position_b) / 2.0,
+                    (normal_a + normal_b).normalize_or_zero(),
+                ),
+            ],
+        };
+
+        candidates
+            .into_iter()
+            .map(|(from, into, position, normal)| Collapse {
+                cost: quadric.error(position),
+                from,
+                into,
+                position,
+                normal,
+                versions: (self.versions[from as usize], self.versions[into as usize]),
+            })
+            .min_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap_or(Ordering::Equal))
+    }
+
+    /// Whether the collapse keeps the mesh manifold and flips no triangle
+    fn can_collapse(&self, collapse: &Collapse) -> bool {
+        // Ends of an edge sharing more than the two vertices opposite it would pinch the mesh
+        let shared = self
+            .neighbors(collapse.from)
+            .intersection(&self.neighbors(collapse.into))
+            .count();
+
+        if shared > 2 {
+            return false;
+        }
+
+        let moved = [collapse.from, collapse.into];
+
+        moved
+            .iter()
+            .flat_map(|vertex| self.vertex_triangles[*vertex as usize].iter())
+            .filter(|triangle| !self.removed[**triangle])
+            .all(|triangle| {
+                let triangle = self.triangles[*triangle];
+
+                // Triangles along the edge are removed by the collapse
+                if moved.iter().all(|vertex| triangle.contains(vertex)) {
+                    return true;
+                }
+
+                let corner = |index: usize| self.positions[triangle[index] as usize];
+                let moved_corner = |index: usize| {
+                    if moved.contains(&triangle[index]) {
+                        collapse.position
+                    } else {
+                        corner(index)
+                    }
+                };
+
+                let normal = (corner(1) - corner(0)).cross(corner(2) - corner(0));
+                let moved_normal =
+                    (moved_corner(1) - moved_corner(0)).cross(moved_corner(2) - moved_corner(0));
+
+                normal.dot(moved_normal) > 0.0
+            })
+    }
+
+    /// Collapses the edge and returns the number of removed triangles
+    fn apply(&mut self, collapse: &Collapse) -> usize {
+        let (from, into) = (collapse.from as usize, collapse.into as usize);
+        let mut removed = 0;
+
+        self.positions[into] = collapse.position;
+        self.normals[into] = collapse.normal;
+        self.quadrics[into] = self.quadrics[into].add(self.quadrics[from]);
+
+        for triangle in std::mem::take(&mut self.vertex_triangles[from]) {
+            if self.removed[triangle] {
+                continue;
+            }
+
+            if self.triangles[triangle].contains(&collapse.into) {
+                self.removed[triangle] = true;
+                removed += 1;
+                continue;
+            }
+
+            for vertex in self.triangles[triangle].iter_mut() {
+                if *vertex == collapse.from {
+                    *vertex = collapse.into;
+                }
+            }
+
+            self.vertex_triangles[into].push(triangle);
+        }
+
+        self.versions[from] += 1;
+        self.versions[into] += 1;
+
+        removed
+    }
+
+    /// Drops the removed triangles and the vertices no triangle uses anymore
+    fn into_mesh(self) -> MeshData {
+        let mut mesh = MeshData::default();
+        let mut remap = vec![None; self.positions.len()];
+
+        for (triangle, removed) in self.triangles.iter().zip(self.removed.iter()) {
+            if *removed {
+                continue;
+            }
+
+            for vertex in triangle.iter() {
+                let index = *remap[*vertex as usize].get_or_insert_with(|| {
+                    mesh.positions.push(self.positions[*vertex as usize].into());
+                    mesh.normals.push(self.normals[*vertex as usize].into());
+                    if let Some(color) = self.colors.get(*vertex as usize) {
+                        mesh.colors.push(*color);
+                    }
+
+                    (mesh.positions.len() - 1) as u32
+                });
+
+                mesh.indices.push(index);
+            }
+        }
+
+        mesh
+    }
+}
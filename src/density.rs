@@ -0,0 +1,764 @@
+use crate::{modifiers::DensityModifier, simplex};
+use bevy::math::{UVec3, Vec3};
+use bevy_inspector_egui::Inspectable;
+use noise::{NoiseFn, Perlin, Seedable};
+use serde::{Deserialize, Serialize};
+use std::{ops::Range, sync::Arc};
+
+/// Scalar field the terrain surface is extracted from, values below the iso level are solid
+pub trait DensityField: Send + Sync {
+    fn sample(&self, p: Vec3) -> f32;
+
+    /// Returns the same field generated from a different seed, `None` for unseeded fields
+    fn with_seed(&self, _seed: u32) -> Option<Arc<dyn DensityField>> {
+        None
+    }
+
+    /// Conservative range of the values inside the box from `min` to `max`, `None` when unknown
+    ///
+    /// Chunks whose range lies entirely on one side of the iso level are never meshed.
+    fn bounds(&self, _min: Vec3, _max: Vec3) -> Option<(f32, f32)> {
+        None
+    }
+
+    /// Noise the compute shader evaluates for this field with the seed the field samples it
+    /// with, `None` for fields it can't express
+    ///
+    /// The compute shader only sums a single set of octaves, optionally run through the stack of
+    /// `modifiers`. Chunks of fields returning `None` are meshed on the CPU by
+    /// `MeshingBackend::Auto` and fail with `TerrainError::UnsupportedGpuDensity` when a GPU
+    /// backend is forced. The seed of the field is uploaded instead of `TerrainSettings::seed`,
+    /// so both backends generate the same terrain for a field seeded differently.
+    fn simplex_noise(&self) -> Option<(SimplexNoise, u32)> {
+        None
+    }
+
+    /// Modifiers the compute shader applies on top of the noise of `simplex_noise`, each with
+    /// the seed its seed offset is added to, see `ModifiedDensity`
+    fn modifiers(&self) -> Vec<(DensityModifier, u32)> {
+        Vec::new()
+    }
+}
+
+impl<F> DensityField for F
+where
+    F: Fn(Vec3) -> f32 + Send + Sync,
+{
+    fn sample(&self, p: Vec3) -> f32 {
+        self(p)
+    }
+}
+
+/// Density field used by the terrain plugin
+#[derive(Clone)]
+pub struct TerrainDensity(pub Arc<dyn DensityField>);
+
+impl TerrainDensity {
+    pub fn new(density: impl DensityField + 'static) -> Self {
+        Self(Arc::new(density))
+    }
+}
+
+impl Default for TerrainDensity {
+    fn default() -> Self {
+        Self::new(SimplexDensity::new(5225))
+    }
+}
+
+/// Octaves of simplex noise summed by `SimplexDensity` as fractal Brownian motion, uploaded with
+/// every dispatch of the compute shader
+#[derive(Debug, Clone, Copy, PartialEq, Inspectable, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimplexNoise {
+    /// Scale from world units to noise space of the first octave
+    #[inspectable(min = 0.0001, speed = 0.001)]
+    pub frequency: f32,
+    #[inspectable(min = 1, max = 12)]
+    pub octaves: u32,
+    /// Amplitude of the first octave
+    #[inspectable(speed = 0.01)]
+    pub amplitude: f32,
+    /// Frequency multiplier between octaves
+    #[inspectable(min = 1.0, max = 4.0, speed = 0.01)]
+    pub lacunarity: f32,
+    /// Amplitude multiplier between octaves, also known as gain
+    #[serde(alias = "gain")]
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub persistence: f32,
+    /// Added to the seed once more for every octave, so the octaves sample unrelated noise
+    /// instead of lining up their features at the seed offset. 0 samples every octave with the
+    /// seed itself.
+    pub octave_seed_offset: u32,
+    /// Bit `n` turns octave `n` into `NoiseVariant::Ridged`, see `SimplexNoise::variant`
+    pub ridged_octaves: u32,
+    /// Bit `n` turns octave `n` into `NoiseVariant::Billow` unless it is ridged already
+    pub billow_octaves: u32,
+    /// Displacement of the sample positions before the octaves are summed
+    pub warp: DomainWarp,
+    /// Noise every octave and warp field samples
+    pub basis: NoiseBasis,
+}
+
+/// Gradient noise the octaves of `SimplexNoise` are built from, both run on the CPU and in the
+/// compute shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Serialize, Deserialize)]
+pub enum NoiseBasis {
+    /// Classic simplex noise, `simplex::snoise`
+    Simplex,
+    /// OpenSimplex2 on rotated lattices, `simplex::opensimplex2`, which doesn't line its
+    /// features up along the world axes
+    OpenSimplex2,
+    /// Perlin noise of the `noise` crate, `noise::Perlin`, which the compute shader evaluates
+    /// from the permutation tables of `simplex::perlin_table`
+    Perlin,
+    /// Gradient noise on integers, `simplex::fixed_perlin`, for terrain that has to come out
+    /// byte identical on every machine, like the terrain of multiplayer clients generating it
+    /// on their own
+    ///
+    /// `SimplexDensity` sums the octaves and warps of this basis in fixed point as well, so the
+    /// CPU and the compute shader sample the same bits and, with `cpu::march_chunk_smooth`
+    /// mirroring the compute shader exactly, mesh the same triangles. The sampled density has to
+    /// stay below 256 and the positions within 32768 units of noise space of the origin.
+    /// Modifiers sampling this basis still blend it in float arithmetic.
+    FixedPoint,
+}
+
+impl Default for NoiseBasis {
+    fn default() -> Self {
+        NoiseBasis::Simplex
+    }
+}
+
+impl NoiseBasis {
+    /// Single sample of the noise, in the range `[-1, 1]`
+    ///
+    /// Perlin noise builds its permutation table for every call, `SeededBasis` keeps it around
+    /// for repeated samples.
+    pub fn sample(self, p: Vec3, seed: u32) -> f32 {
+        SeededBasis::new(self, seed).sample(p)
+    }
+}
+
+/// A `NoiseBasis` bound to the seed it is sampled with
+#[derive(Debug, Clone)]
+pub struct SeededBasis(BasisSampler);
+
+#[derive(Debug, Clone)]
+enum BasisSampler {
+    /// Offset of the seed into the repeating volume
+    Simplex(Vec3),
+    OpenSimplex2(u32),
+    /// Boxed, the permutation table takes up 256 bytes
+    Perlin(Box<Perlin>),
+    FixedPoint(u32),
+}
+
+impl SeededBasis {
+    pub fn new(basis: NoiseBasis, seed: u32) -> Self {
+        Self(match basis {
+            NoiseBasis::Simplex => BasisSampler::Simplex(simplex::seed_offset(seed)),
+            NoiseBasis::OpenSimplex2 => BasisSampler::OpenSimplex2(seed),
+            NoiseBasis::Perlin => BasisSampler::Perlin(Box::new(Perlin::new().set_seed(seed))),
+            NoiseBasis::FixedPoint => BasisSampler::FixedPoint(seed),
+        })
+    }
+
+    /// Single sample of the noise, in the range `[-1, 1]`
+    pub fn sample(&self, p: Vec3) -> f32 {
+        match &self.0 {
+            BasisSampler::Simplex(offset) => simplex::snoise(p + *offset),
+            BasisSampler::OpenSimplex2(seed) => simplex::opensimplex2(p, *seed),
+            BasisSampler::Perlin(perlin) => perlin.get([p.x as f64, p.y as f64, p.z as f64]) as f32,
+            BasisSampler::FixedPoint(seed) => {
+                simplex::from_fixed(simplex::fixed_perlin(simplex::to_fixed(p, 1.0), *seed))
+            }
+        }
+    }
+}
+
+/// Seeds of the warp fields are spread this far apart from the terrain seed and each other
+const WARP_SEED_STEP: u32 = 7919;
+
+/// Warps the positions `SimplexNoise` is sampled at by secondary simplex noise fields, which
+/// turns its blobs into swirling, folded shapes
+///
+/// Every layer moves the position by `strength` times a vector of three simplex samples taken
+/// at the position the layer before left it at, so the second layer warps the warp.
+#[derive(Debug, Clone, Copy, PartialEq, Inspectable, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainWarp {
+    /// Number of warp fields applied one after another, 0 disables warping, at most 2
+    #[inspectable(max = 2)]
+    pub layers: u32,
+    /// Largest displacement of a layer in world units
+    #[inspectable(min = 0.0, speed = 0.1)]
+    pub strength: f32,
+    /// Scale from world units to noise space of the warp fields
+    #[inspectable(min = 0.0001, speed = 0.001)]
+    pub frequency: f32,
+}
+
+impl Default for DomainWarp {
+    fn default() -> Self {
+        Self {
+            layers: 0,
+            strength: 8.0,
+            frequency: 1.0 / 64.0,
+        }
+    }
+}
+
+impl DomainWarp {
+    /// Layers actually applied, `layers` clamped to the two the compute shader supports
+    pub fn layer_count(&self) -> u32 {
+        self.layers.min(2)
+    }
+
+    /// Seed of the warp field moving the position along `axis` in layer `layer`
+    pub fn seed(&self, seed: u32, layer: u32, axis: u32) -> u32 {
+        seed.wrapping_add(WARP_SEED_STEP.wrapping_mul(1 + layer * 3 + axis))
+    }
+}
+
+/// How an octave of `SimplexNoise` shapes its simplex sample before it is summed, every variant
+/// stays within `[-1, 1]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseVariant {
+    /// The plain sample, round blobs
+    Smooth,
+    /// Folds the sample into sharp crests along its zero crossings, like mountain ridges, and
+    /// scales down the detail of the following ridged octaves in the valleys between them
+    Ridged,
+    /// Folds the sample into rounded bumps meeting in creases, like hills and clouds
+    Billow,
+}
+
+impl Default for SimplexNoise {
+    fn default() -> Self {
+        Self {
+            frequency: 1.0 / 32.0,
+            octaves: 1,
+            amplitude: 1.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            octave_seed_offset: 0,
+            ridged_octaves: 0,
+            billow_octaves: 0,
+            warp: DomainWarp::default(),
+            basis: NoiseBasis::default(),
+        }
+    }
+}
+
+impl SimplexNoise {
+    /// Largest absolute value the summed octaves can reach
+    pub fn max_amplitude(&self) -> f32 {
+        let mut amplitude = self.amplitude;
+        let mut max = 0.0;
+
+        for _ in 0..self.octaves {
+            max += amplitude.abs();
+            amplitude *= self.persistence;
+        }
+
+        max
+    }
+
+    /// Seed the octave with index `octave` is sampled with
+    pub fn octave_seed(&self, seed: u32, octave: u32) -> u32 {
+        seed.wrapping_add(octave.wrapping_mul(self.octave_seed_offset))
+    }
+
+    /// Variant of the octave with index `octave`, octaves past the 32nd are smooth
+    pub fn variant(&self, octave: u32) -> NoiseVariant {
+        let bit = 1u32.checked_shl(octave).unwrap_or(0);
+
+        if self.ridged_octaves & bit != 0 {
+            NoiseVariant::Ridged
+        } else if self.billow_octaves & bit != 0 {
+            NoiseVariant::Billow
+        } else {
+            NoiseVariant::Smooth
+        }
+    }
+
+    /// Makes every octave from `octaves.start` up to but excluding `octaves.end` use `variant`
+    pub fn with_variant(mut self, octaves: Range<u32>, variant: NoiseVariant) -> Self {
+        for octave in octaves.filter(|octave| *octave < 32) {
+            let bit = 1 << octave;
+
+            self.ridged_octaves &= !bit;
+            self.billow_octaves &= !bit;
+
+            match variant {
+                NoiseVariant::Smooth => {}
+                NoiseVariant::Ridged => self.ridged_octaves |= bit,
+                NoiseVariant::Billow => self.billow_octaves |= bit,
+            }
+        }
+
+        self
+    }
+}
+
+/// The noise `density` of `terrain/density.wgsl` evaluates with the functions of
+/// `terrain/noise.wgsl`
+///
+/// This is the default density of the terrain plugin, so for a given seed the CPU mesher builds
+/// the same triangles as the compute shader, in the same order.
+pub struct SimplexDensity {
+    seed: u32,
+    /// Basis of every octave with its seed
+    octaves: Vec<SeededBasis>,
+    /// Bases of the three axes of every warp layer
+    warps: Vec<[SeededBasis; 3]>,
+    noise: SimplexNoise,
+}
+
+impl SimplexDensity {
+    pub fn new(seed: u32) -> Self {
+        Self::with_noise(seed, SimplexNoise::default())
+    }
+
+    pub fn with_noise(seed: u32, noise: SimplexNoise) -> Self {
+        Self {
+            seed,
+            octaves: (0..noise.octaves)
+                .map(|octave| SeededBasis::new(noise.basis, noise.octave_seed(seed, octave)))
+                .collect(),
+            warps: (0..noise.warp.layer_count())
+                .map(|layer| {
+                    let basis =
+                        |axis| SeededBasis::new(noise.basis, noise.warp.seed(seed, layer, axis));
+
+                    [basis(0), basis(1), basis(2)]
+                })
+                .collect(),
+            noise,
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn noise(&self) -> SimplexNoise {
+        self.noise
+    }
+
+    /// `sample` of `NoiseBasis::FixedPoint`, which sums the octaves in fixed point in the same
+    /// order as `fixed_density` of the shader
+    ///
+    /// The frequencies and amplitudes are stepped in floats like they are for the other bases,
+    /// only multiplications, which are correctly rounded everywhere, and converted to fixed point
+    /// before they touch a sample.
+    fn sample_fixed(&self, p: Vec3) -> f32 {
+        let noise = &self.noise;
+        let strength = simplex::to_fixed_scalar(noise.warp.strength);
+        let mut p = p;
+
+        for layer in 0..noise.warp.layer_count() {
+            let q = simplex::to_fixed(p, noise.warp.frequency);
+            let displacement = |axis| {
+                let sample = simplex::fixed_perlin(q, noise.warp.seed(self.seed, layer, axis));
+
+                simplex::from_fixed(simplex::fixed_mul(sample, strength))
+            };
+
+            p += Vec3::new(displacement(0), displacement(1), displacement(2));
+        }
+
+        let mut value = 0;
+        let mut frequency = noise.frequency;
+        let mut amplitude = noise.amplitude;
+        let mut weight = simplex::FIXED_ONE;
+
+        for octave in 0..noise.octaves {
+            let sample = simplex::fixed_perlin(
+                simplex::to_fixed(p, frequency),
+                noise.octave_seed(self.seed, octave),
+            );
+
+            let sample = match noise.variant(octave) {
+                NoiseVariant::Smooth => sample,
+                NoiseVariant::Ridged => {
+                    let fold = simplex::FIXED_ONE - sample.abs();
+                    let ridge = simplex::fixed_mul(simplex::fixed_mul(fold, fold), weight);
+                    weight = (ridge * 2).max(0).min(simplex::FIXED_ONE);
+
+                    ridge * 2 - simplex::FIXED_ONE
+                }
+                NoiseVariant::Billow => sample.abs() * 2 - simplex::FIXED_ONE,
+            };
+
+            value += simplex::fixed_mul(sample, simplex::to_fixed_scalar(amplitude));
+            frequency *= noise.lacunarity;
+            amplitude *= noise.persistence;
+        }
+
+        simplex::from_fixed(value)
+    }
+}
+
+impl DensityField for SimplexDensity {
+    /// Sums the octaves in the same order as the shader
+    fn sample(&self, p: Vec3) -> f32 {
+        if self.noise.basis == NoiseBasis::FixedPoint {
+            return self.sample_fixed(p);
+        }
+
+        let mut p = p;
+
+        for bases in self.warps.iter() {
+            let q = p * self.noise.warp.frequency;
+
+            p += Vec3::new(bases[0].sample(q), bases[1].sample(q), bases[2].sample(q))
+                * self.noise.warp.strength;
+        }
+
+        let mut value = 0.0;
+        let mut frequency = self.noise.frequency;
+        let mut amplitude = self.noise.amplitude;
+
+        // Ridged octaves fade out where the ridged octaves before them are low
+        let mut weight = 1.0;
+
+        for (octave, basis) in self.octaves.iter().enumerate() {
+            let sample = basis.sample(p * frequency);
+
+            let sample = match self.noise.variant(octave as u32) {
+                NoiseVariant::Smooth => sample,
+                NoiseVariant::Ridged => {
+                    let ridge = (1.0 - sample.abs()) * (1.0 - sample.abs()) * weight;
+                    weight = (ridge * 2.0).max(0.0).min(1.0);
+
+                    ridge * 2.0 - 1.0
+                }
+                NoiseVariant::Billow => sample.abs() * 2.0 - 1.0,
+            };
+
+            value += sample * amplitude;
+            frequency *= self.noise.lacunarity;
+            amplitude *= self.noise.persistence;
+        }
+
+        value
+    }
+
+    fn bounds(&self, _min: Vec3, _max: Vec3) -> Option<(f32, f32)> {
+        let max = self.noise.max_amplitude();
+
+        Some((-max, max))
+    }
+
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        Some(Arc::new(Self::with_noise(seed, self.noise)))
+    }
+
+    fn simplex_noise(&self) -> Option<(SimplexNoise, u32)> {
+        Some((self.noise, self.seed))
+    }
+}
+
+/// 3D Perlin noise in the range `[-1, 1]`
+///
+/// Sampled with `noise::Perlin`, the compute shader evaluates it as a single octave of
+/// `NoiseBasis::Perlin`.
+pub struct PerlinDensity {
+    perlin: Perlin,
+    seed: u32,
+    frequency: f64,
+}
+
+impl PerlinDensity {
+    pub fn new(seed: u32, frequency: f64) -> Self {
+        Self {
+            perlin: Perlin::new().set_seed(seed),
+            seed,
+            frequency,
+        }
+    }
+}
+
+impl DensityField for PerlinDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        self.perlin.get([
+            p.x as f64 * self.frequency,
+            p.y as f64 * self.frequency,
+            p.z as f64 * self.frequency,
+        ]) as f32
+    }
+
+    fn bounds(&self, _min: Vec3, _max: Vec3) -> Option<(f32, f32)> {
+        Some((-1.0, 1.0))
+    }
+
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        Some(Arc::new(Self::new(seed, self.frequency)))
+    }
+
+    fn simplex_noise(&self) -> Option<(SimplexNoise, u32)> {
+        let noise = SimplexNoise {
+            frequency: self.frequency as f32,
+            basis: NoiseBasis::Perlin,
+            ..Default::default()
+        };
+
+        Some((noise, self.seed))
+    }
+}
+
+/// Infinite flat ground with its surface at `height`
+pub struct FlatDensity {
+    pub height: f32,
+}
+
+impl DensityField for FlatDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        p.y - self.height
+    }
+
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        Some((min.y - self.height, max.y - self.height))
+    }
+}
+
+/// Solid sphere, the density is the signed distance to its surface
+pub struct SphereDensity {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl DensityField for SphereDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        let nearest = self.center.max(min).min(max);
+        let farthest = Vec3::select((self.center - min).cmpgt(max - self.center), min, max);
+
+        Some((
+            (nearest - self.center).length() - self.radius,
+            (farthest - self.center).length() - self.radius,
+        ))
+    }
+}
+
+/// Density values sampled on a regular grid, stored x-major
+#[derive(Debug, Clone)]
+pub struct DensityGrid {
+    dims: UVec3,
+    values: Vec<f32>,
+}
+
+impl DensityGrid {
+    pub fn new(dims: UVec3, values: Vec<f32>) -> Self {
+        assert_eq!(
+            values.len(),
+            (dims.x * dims.y * dims.z) as usize,
+            "density length does not match grid dimensions"
+        );
+
+        Self { dims, values }
+    }
+
+    pub fn from_fn(dims: UVec3, mut f: impl FnMut(UVec3) -> f32) -> Self {
+        let mut values = Vec::with_capacity((dims.x * dims.y * dims.z) as usize);
+
+        for z in 0..dims.z {
+            for y in 0..dims.y {
+                for x in 0..dims.x {
+                    values.push(f(UVec3::new(x, y, z)));
+                }
+            }
+        }
+
+        Self { dims, values }
+    }
+
+    /// Samples `field` at `(origin + grid position) * scale` for every grid point
+    pub fn from_field(field: &dyn DensityField, dims: UVec3, origin: Vec3, scale: f32) -> Self {
+        Self::from_fn(dims, |point| {
+            field.sample((origin + point.as_vec3()) * scale)
+        })
+    }
+
+    pub fn dims(&self) -> UVec3 {
+        self.dims
+    }
+
+    /// Smallest and largest value of the grid
+    pub fn range(&self) -> (f32, f32) {
+        self.values
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                (min.min(*value), max.max(*value))
+            })
+    }
+
+    /// Whether the values cross `iso_level` anywhere, grids that don't polygonize to nothing
+    pub fn crosses(&self, iso_level: f32) -> bool {
+        let (min, max) = self.range();
+
+        min < iso_level && max >= iso_level
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        (z * self.dims.y * self.dims.x + y * self.dims.x + x) as usize
+    }
+
+    pub fn get(&self, x: u32, y: u32, z: u32) -> f32 {
+        self.values[self.index(x, y, z)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, z: u32, value: f32) {
+        let index = self.index(x, y, z);
+
+        self.values[index] = value;
+    }
+
+    /// Every `step`th sample inside a `border` of samples, the last sample before the border is
+    /// always kept so the coarser grid covers the same volume
+    pub fn downsample(&self, border: u32, step: u32) -> Self {
+        let step = step.max(1);
+        let last = self.dims - UVec3::splat(2 * border + 1);
+        let samples = |last: u32| (last + step - 1) / step + 1;
+
+        Self::from_fn(
+            UVec3::new(samples(last.x), samples(last.y), samples(last.z)),
+            |point| {
+                let sample = |point: u32, last: u32| (point * step).min(last) + border;
+
+                self.get(
+                    sample(point.x, last.x),
+                    sample(point.y, last.y),
+                    sample(point.z, last.z),
+                )
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples `density()` of `terrain/density.wgsl` takes for the noise at the points,
+    /// `SimplexDensity` has to stay within `simplex::GPU_TOLERANCE` of every octave of them
+    fn assert_matches_shader(seed: u32, noise: SimplexNoise, expected: [f32; 3]) {
+        let density = SimplexDensity::with_noise(seed, noise);
+        let tolerance = simplex::GPU_TOLERANCE * noise.max_amplitude().max(1.0);
+        let points = [
+            Vec3::new(13.0, -7.5, 42.25),
+            Vec3::new(-120.5, 33.0, 7.75),
+            Vec3::new(250.0, 64.0, -96.5),
+        ];
+
+        for (point, expected) in points.iter().zip(&expected) {
+            let value = density.sample(*point);
+
+            assert!(
+                (value - expected).abs() <= tolerance,
+                "{:?} sampled {}, the shader samples {}",
+                point,
+                value,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn ridged_and_billow_octaves_match_shader() {
+        let noise = SimplexNoise {
+            octaves: 5,
+            octave_seed_offset: 101,
+            ridged_octaves: 0b0110,
+            billow_octaves: 0b1000,
+            ..SimplexNoise::default()
+        };
+
+        assert_matches_shader(5225, noise, [0.048927665, -0.9168167, -0.51229674]);
+    }
+
+    #[test]
+    fn warped_opensimplex2_matches_shader() {
+        let noise = SimplexNoise {
+            octaves: 3,
+            warp: DomainWarp {
+                layers: 2,
+                ..DomainWarp::default()
+            },
+            basis: NoiseBasis::OpenSimplex2,
+            ..SimplexNoise::default()
+        };
+
+        assert_matches_shader(1337, noise, [0.12080331, -0.25332335, -0.5394399]);
+    }
+
+    #[test]
+    fn perlin_octaves_match_shader() {
+        let noise = SimplexNoise {
+            octaves: 4,
+            amplitude: 12.0,
+            lacunarity: 2.1,
+            persistence: 0.6,
+            basis: NoiseBasis::Perlin,
+            ..SimplexNoise::default()
+        };
+
+        assert_matches_shader(0xdead_beef, noise, [-6.0278893, -2.3726366, -2.224815]);
+    }
+
+    #[test]
+    fn fixed_point_octaves_match_shader_exactly() {
+        let noise = SimplexNoise {
+            octaves: 4,
+            amplitude: 12.0,
+            octave_seed_offset: 101,
+            ridged_octaves: 0b0010,
+            billow_octaves: 0b0100,
+            warp: DomainWarp {
+                layers: 1,
+                ..DomainWarp::default()
+            },
+            basis: NoiseBasis::FixedPoint,
+            ..SimplexNoise::default()
+        };
+        let density = SimplexDensity::with_noise(1337, noise);
+        let points = [
+            Vec3::new(13.0, -7.5, 42.25),
+            Vec3::new(-120.5, 33.0, 7.75),
+            Vec3::new(250.0, 64.0, -96.5),
+        ];
+
+        // The samples are whole steps of the fixed point, no tolerance involved
+        for (point, expected) in points.iter().zip(&[840124, 67579, 53673]) {
+            assert_eq!(simplex::to_fixed_scalar(density.sample(*point)), *expected);
+        }
+    }
+
+    #[test]
+    fn perlin_density_matches_its_gpu_noise() {
+        let density = PerlinDensity::new(42, 0.05);
+        let (noise, seed) = density.simplex_noise().unwrap();
+        let gpu_density = SimplexDensity::with_noise(seed, noise);
+
+        for step in 0..100 {
+            let p = Vec3::new(
+                step as f32 * 3.7 - 150.0,
+                step as f32 * 0.9,
+                80.0 - step as f32,
+            );
+
+            assert!(
+                (density.sample(p) - gpu_density.sample(p)).abs()
+                    <= simplex::perlin_gpu_tolerance(p * 0.05)
+            );
+        }
+    }
+}
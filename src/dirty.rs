@@ -0,0 +1,153 @@
+#[cfg(feature = "gpu-compute")]
+use crate::terrain_gpu::GpuChunkJobs;
+use crate::{
+    cache::ChunkCache,
+    chunk::{ChunkCoord, ChunkMap},
+    density::TerrainDensity,
+    patch::ChunkPatch,
+    post_process::MeshPostProcessors,
+    provider::TerrainChunkProvider,
+    terrain::{
+        ChunkGenerator, ChunkMeshTask, ChunkQueued, MeshingBackend, Terrain, TerrainChunk,
+        TerrainSettings, TerrainStatus,
+    },
+    vertex_colors::MeshVertexColors,
+};
+use bevy::{
+    app::{EventReader, EventWriter},
+    ecs::{
+        entity::Entity,
+        query::{With, Without},
+        system::{Commands, Query, Res},
+    },
+    math::Vec3,
+    render2::renderer::RenderDevice,
+    tasks::AsyncComputeTaskPool,
+};
+use std::collections::HashSet;
+
+/// Insert on a chunk whose density changed to generate its mesh again, the old mesh stays
+/// visible until the new one is ready
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChunkDirty {
+    /// Also remeshes the 26 chunks around it, for edits touching the border samples
+    pub neighbors: bool,
+}
+
+/// Send after the density of a terrain changed inside the box from `min` to `max`, given in the
+/// space the density is sampled in, to remesh every chunk with samples inside the box
+#[derive(Debug, Clone)]
+pub struct DensityChanged {
+    pub terrain: Entity,
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+pub(crate) fn remesh_dirty_chunks(
+    mut commands: Commands,
+    backend: Res<MeshingBackend>,
+    status: Res<TerrainStatus>,
+    render_device: Option<Res<RenderDevice>>,
+    #[cfg(feature = "gpu-compute")] gpu_jobs: Res<GpuChunkJobs>,
+    task_pool: Res<AsyncComputeTaskPool>,
+    post_processors: Res<MeshPostProcessors>,
+    vertex_colors: Res<MeshVertexColors>,
+    cache: Option<Res<ChunkCache>>,
+    terrain_query: Query<
+        (
+            &TerrainSettings,
+            &TerrainDensity,
+            Option<&TerrainChunkProvider>,
+            &ChunkMap,
+        ),
+        With<Terrain>,
+    >,
+    dirty_query: Query<(Entity, &TerrainChunk, &ChunkDirty)>,
+    chunk_query: Query<&TerrainChunk>,
+    patched_query: Query<(), (With<ChunkPatch>, Without<ChunkMeshTask>)>,
+    mut density_changed_events: EventReader<DensityChanged>,
+    mut chunk_queued_events: EventWriter<ChunkQueued>,
+) {
+    let mut dirty_chunks: HashSet<(Entity, ChunkCoord)> = HashSet::new();
+
+    for (entity, chunk, dirty) in dirty_query.iter() {
+        commands.entity(entity).remove::<ChunkDirty>();
+
+        dirty_chunks.insert((chunk.terrain(), chunk.coord()));
+
+        if dirty.neighbors {
+            for neighbor in chunk.coord().neighbors_with_diagonals() {
+                dirty_chunks.insert((chunk.terrain(), neighbor));
+            }
+        }
+    }
+
+    for event in density_changed_events.iter() {
+        if let Ok((settings, _, _, chunk_map)) = terrain_query.get(event.terrain) {
+            for coord in settings.chunks_overlapping(event.min, event.max) {
+                // Patched by `patch_edited_chunks` instead
+                let patched = chunk_map
+                    .get(coord)
+                    .map_or(false, |entity| patched_query.get(entity).is_ok());
+
+                if patched {
+                    continue;
+                }
+
+                dirty_chunks.insert((event.terrain, coord));
+            }
+        }
+    }
+
+    if dirty_chunks.is_empty() {
+        return;
+    }
+
+    let generator = ChunkGenerator::new(
+        *backend,
+        &status,
+        render_device.as_deref(),
+        #[cfg(feature = "gpu-compute")]
+        &gpu_jobs,
+        &task_pool,
+        &post_processors,
+        &vertex_colors,
+        cache.as_deref(),
+    );
+
+    for (terrain, coord) in dirty_chunks {
+        let (settings, density, provider, chunk_map) = match terrain_query.get(terrain) {
+            Ok(terrain) => terrain,
+            Err(_) => continue,
+        };
+
+        // Chunks that are not spawned yet get generated from the new density anyway
+        let entity = match chunk_map.get(coord) {
+            Some(entity) => entity,
+            None => continue,
+        };
+
+        let (lod, transitions) = match chunk_query.get(entity) {
+            Ok(chunk) => (chunk.lod(), chunk.transitions()),
+            Err(_) => continue,
+        };
+
+        // The cached data of every detail level predates the change
+        if let Some(cache) = &cache {
+            for lod in 0..=settings.max_lod() {
+                cache.remove(&settings.for_lod(lod), coord);
+            }
+        }
+
+        // Replacing the task of a chunk that is still generating drops the outdated one
+        let task = generator.generate(density, provider, settings, coord, lod, transitions);
+
+        commands.entity(entity).insert(task);
+
+        chunk_queued_events.send(ChunkQueued {
+            terrain,
+            entity,
+            coord,
+        });
+    }
+}
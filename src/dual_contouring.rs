@@ -0,0 +1,224 @@
+use crate::{
+    cpu::CORNER_OFFSETS,
+    density::DensityGrid,
+    mesh::MeshData,
+    tables::{CORNER_INDEX_A_FROM_EDGE, CORNER_INDEX_B_FROM_EDGE},
+};
+use bevy::math::{Mat3, UVec3, Vec3};
+
+/// Pull of every vertex towards the average of its edge crossings, keeps the QEF solvable on
+/// flat and curved surfaces where the crossing normals don't pin down a single point
+const MASS_POINT_BIAS: f32 = 0.05;
+
+/// Marker of cells without a vertex
+const NO_VERTEX: u32 = u32::MAX;
+
+const AXES: [UVec3; 3] = [
+    UVec3::new(1, 0, 0),
+    UVec3::new(0, 1, 0),
+    UVec3::new(0, 0, 1),
+];
+
+/// Contours the cells inside a `border` of ghost samples with one vertex per cell crossing the
+/// surface, which keeps the sharp features marching cubes rounds off
+///
+/// Every vertex minimizes the quadratic error to the tangent planes at the edge crossings of its
+/// cell, the normals come from the trilinear density gradient of the cell. Both only read the
+/// corners of the cell, so chunks sharing a border place the vertices of the border cells alike.
+/// Every edge crossing the surface whose first grid point lies inside the border emits a quad
+/// between the four cells around it, which reaches one cell into the ghost samples on the
+/// negative sides.
+///
+/// Positions are relative to the first sample inside the border, the mesh comes out indexed.
+pub fn dual_contour(grid: &DensityGrid, border: u32, iso_level: f32) -> MeshData {
+    contour_cells(grid, border, iso_level, qef_vertex)
+}
+
+/// Connects the vertices placed in the cells around every edge crossing the surface, shared by
+/// the algorithms placing one vertex per cell
+///
+/// `place_vertex` returns the vertex of a cell relative to its first corner from the densities
+/// at the corners, ordered like `CORNER_OFFSETS`, and is only called for cells crossing the
+/// surface. Border handling and normals are described on `dual_contour`.
+pub(crate) fn contour_cells(
+    grid: &DensityGrid,
+    border: u32,
+    iso_level: f32,
+    place_vertex: impl Fn(&[f32; 8], f32) -> Vec3,
+) -> MeshData {
+    assert!(
+        border >= 1,
+        "quads along the border need a layer of ghost cells"
+    );
+
+    let dims = grid.dims();
+    let cells = dims - UVec3::splat(1);
+    let end = |len: u32| len.saturating_sub(border + 1);
+
+    let mut mesh = MeshData::default();
+    let mut cell_vertices = vec![None; (cells.x * cells.y * cells.z) as usize];
+
+    let mut vertex = |mesh: &mut MeshData, cell: UVec3| {
+        let slot =
+            &mut cell_vertices[(cell.z * cells.y * cells.x + cell.y * cells.x + cell.x) as usize];
+
+        *slot.get_or_insert_with(|| match solve_cell(grid, cell, iso_level, &place_vertex) {
+            Some((position, normal)) => {
+                mesh.positions
+                    .push((position - Vec3::splat(border as f32)).into());
+                mesh.normals.push(normal.into());
+
+                (mesh.positions.len() - 1) as u32
+            }
+            None => NO_VERTEX,
+        })
+    };
+
+    for z in border..end(dims.z) {
+        for y in border..end(dims.y) {
+            for x in border..end(dims.x) {
+                let point = UVec3::new(x, y, z);
+                let inside = grid.get(x, y, z) < iso_level;
+
+                for axis in 0..3 {
+                    let next = point + AXES[axis];
+
+                    if (grid.get(next.x, next.y, next.z) < iso_level) == inside {
+                        continue;
+                    }
+
+                    // The cells around the edge, counterclockwise when looking down the axis
+                    let u = AXES[(axis + 1) % 3];
+                    let v = AXES[(axis + 2) % 3];
+
+                    let quad = [
+                        vertex(&mut mesh, point - u - v),
+                        vertex(&mut mesh, point - v),
+                        vertex(&mut mesh, point),
+                        vertex(&mut mesh, point - u),
+                    ];
+
+                    // Every cell around a crossed edge crosses the surface as well
+                    debug_assert!(quad.iter().all(|index| *index != NO_VERTEX));
+
+                    // The quads face towards the higher density, like the marching cubes mesh
+                    if inside {
+                        mesh.indices.extend_from_slice(&[
+                            quad[0], quad[1], quad[2], quad[0], quad[2], quad[3],
+                        ]);
+                    } else {
+                        mesh.indices.extend_from_slice(&[
+                            quad[0], quad[2], quad[1], quad[0], quad[3], quad[2],
+                        ]);
+                    }
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+/// Places the vertex of a cell and its normal, `None` when the cell doesn't cross the surface
+fn solve_cell(
+    grid: &DensityGrid,
+    cell: UVec3,
+    iso_level: f32,
+    place_vertex: &impl Fn(&[f32; 8], f32) -> Vec3,
+) -> Option<(Vec3, Vec3)> {
+    let mut corners = [0.0; 8];
+
+    for (corner, offset) in corners.iter_mut().zip(CORNER_OFFSETS.iter()) {
+        let point = cell + *offset;
+
+        *corner = grid.get(point.x, point.y, point.z);
+    }
+
+    let inside = corners.iter().filter(|value| **value < iso_level).count();
+
+    if inside == 0 || inside == corners.len() {
+        return None;
+    }
+
+    let position = place_vertex(&corners, iso_level);
+    let normal = gradient(&corners, position).normalize_or_zero();
+
+    Some((cell.as_vec3() + position, normal))
+}
+
+/// Points where the edges of a cell cross the iso level, relative to its first corner
+pub(crate) fn edge_crossings(
+    corners: &[f32; 8],
+    iso_level: f32,
+) -> impl Iterator<Item = Vec3> + '_ {
+    CORNER_INDEX_A_FROM_EDGE
+        .iter()
+        .zip(CORNER_INDEX_B_FROM_EDGE.iter())
+        .filter_map(move |(a, b)| {
+            let (value_a, value_b) = (corners[*a], corners[*b]);
+
+            if (value_a < iso_level) == (value_b < iso_level) {
+                return None;
+            }
+
+            let t = ((iso_level - value_a) / (value_b - value_a)).clamp(0.0, 1.0);
+
+            Some(
+                CORNER_OFFSETS[*a]
+                    .as_vec3()
+                    .lerp(CORNER_OFFSETS[*b].as_vec3(), t),
+            )
+        })
+}
+
+/// Minimizes the quadratic error to the tangent planes at the edge crossings
+fn qef_vertex(corners: &[f32; 8], iso_level: f32) -> Vec3 {
+    let mut ata = Mat3::ZERO;
+    let mut atb = Vec3::ZERO;
+    let mut mass_point = Vec3::ZERO;
+    let mut crossings = 0;
+
+    for crossing in edge_crossings(corners, iso_level) {
+        let normal = gradient(corners, crossing).normalize_or_zero();
+
+        ata = ata + Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z);
+        atb += normal * normal.dot(crossing);
+        mass_point += crossing;
+        crossings += 1;
+    }
+
+    let mass_point = mass_point / crossings as f32;
+
+    let a = ata + Mat3::from_diagonal(Vec3::splat(MASS_POINT_BIAS));
+    let b = atb + mass_point * MASS_POINT_BIAS;
+
+    // Solutions are kept inside the cell, features pointing out of it get clipped
+    let solution = a.inverse() * b;
+
+    if solution.is_finite() {
+        solution.max(Vec3::ZERO).min(Vec3::ONE)
+    } else {
+        mass_point
+    }
+}
+
+/// Gradient of the trilinear interpolation of the corners at a point inside the cell
+pub(crate) fn gradient(corners: &[f32; 8], point: Vec3) -> Vec3 {
+    let weight = |bit: u32, t: f32| if bit == 0 { 1.0 - t } else { t };
+    let sign = |bit: u32| if bit == 0 { -1.0 } else { 1.0 };
+
+    let mut gradient = Vec3::ZERO;
+
+    for (value, offset) in corners.iter().zip(CORNER_OFFSETS.iter()) {
+        let UVec3 { x, y, z } = *offset;
+
+        gradient += *value
+            * Vec3::new(
+                sign(x) * weight(y, point.y) * weight(z, point.z),
+                weight(x, point.x) * sign(y) * weight(z, point.z),
+                weight(x, point.x) * weight(y, point.y) * sign(z),
+            );
+    }
+
+    gradient
+}
@@ -0,0 +1,45 @@
+use std::{error::Error, fmt};
+
+/// Failures while generating a chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerrainError {
+    /// A single layer of cells of a chunk outputs more than fits into a storage buffer binding of
+    /// the device, larger chunks are split into slabs of layers
+    BufferTooLarge { size: u64, max: u64 },
+    /// The density volume of a chunk is larger than a 3D texture of the device along an axis
+    TextureTooLarge { size: u32, max: u32 },
+    /// Mapping the readback buffer of a chunk failed
+    BufferMapFailed,
+    /// The render world dropped the job of a chunk without dispatching it
+    GpuJobDropped,
+    /// The GPU backend was forced for a density field the compute shader can't express, see
+    /// `DensityField::simplex_noise`
+    UnsupportedGpuDensity,
+}
+
+impl fmt::Display for TerrainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TerrainError::BufferTooLarge { size, max } => write!(
+                f,
+                "chunk output buffer of {} bytes exceeds the device limit of {} bytes",
+                size, max
+            ),
+            TerrainError::TextureTooLarge { size, max } => write!(
+                f,
+                "chunk density volume of {} samples per axis exceeds the device limit of {}",
+                size, max
+            ),
+            TerrainError::BufferMapFailed => write!(f, "failed to map the chunk readback buffer"),
+            TerrainError::GpuJobDropped => {
+                write!(f, "the chunk job was dropped before it was dispatched")
+            }
+            TerrainError::UnsupportedGpuDensity => write!(
+                f,
+                "the density field can't be evaluated by the compute shader, mesh it on the CPU"
+            ),
+        }
+    }
+}
+
+impl Error for TerrainError {}
@@ -0,0 +1,112 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// GPU memory allocated by the terrain in bytes, updated every frame
+///
+/// Only covers what the terrain allocates itself, meshes read back into `Mesh` assets are
+/// counted by the renderer.
+#[derive(Debug, Clone, Default)]
+pub struct TerrainGpuMemory {
+    /// Buffers allocated by the `BufferPool`, in use or waiting to be reused
+    pub buffers: u64,
+    /// Part of `buffers` waiting in the pool
+    pub pooled: u64,
+    /// Density volumes of the chunks being dispatched
+    pub textures: u64,
+    /// Vertex buffers of the chunks drawn by `MeshingBackend::GpuResident`
+    pub meshes: u64,
+}
+
+impl TerrainGpuMemory {
+    pub fn total(&self) -> u64 {
+        self.buffers + self.textures + self.meshes
+    }
+}
+
+/// Thresholds applied to `TerrainGpuMemory`, insert it as a resource to change them
+#[derive(Debug, Clone, Default)]
+pub struct GpuMemoryLimits {
+    /// Logs a warning when the total exceeds this many bytes
+    pub warning_threshold: Option<u64>,
+    /// Destroys the buffers waiting in the pool every frame the total exceeds the warning
+    /// threshold, or every frame without a threshold, trading allocations for memory
+    pub aggressive_trimming: bool,
+}
+
+impl GpuMemoryLimits {
+    /// Whether the pool should be emptied at the given usage
+    pub fn should_trim(&self, memory: &TerrainGpuMemory) -> bool {
+        self.aggressive_trimming
+            && self
+                .warning_threshold
+                .map_or(true, |threshold| memory.total() > threshold)
+    }
+}
+
+/// What an allocation is counted as, see `TerrainGpuMemory`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MemoryKind {
+    Buffers,
+    Pooled,
+    Textures,
+    Meshes,
+}
+
+/// Counts the bytes behind `TerrainGpuMemory`, shared by everything allocating for the terrain
+#[derive(Clone, Default)]
+pub(crate) struct MemoryTracker {
+    inner: Arc<[AtomicU64; 4]>,
+}
+
+impl MemoryTracker {
+    pub fn add(&self, kind: MemoryKind, bytes: u64) {
+        self.inner[kind as usize].fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, kind: MemoryKind, bytes: u64) {
+        self.inner[kind as usize].fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Counts an allocation until the returned guard is dropped
+    pub fn allocate(&self, kind: MemoryKind, bytes: u64) -> TrackedAllocation {
+        self.add(kind, bytes);
+
+        TrackedAllocation {
+            tracker: self.clone(),
+            kind,
+            bytes,
+        }
+    }
+
+    pub fn snapshot(&self) -> TerrainGpuMemory {
+        let load = |kind: MemoryKind| self.inner[kind as usize].load(Ordering::Relaxed);
+
+        TerrainGpuMemory {
+            buffers: load(MemoryKind::Buffers),
+            pooled: load(MemoryKind::Pooled),
+            textures: load(MemoryKind::Textures),
+            meshes: load(MemoryKind::Meshes),
+        }
+    }
+}
+
+/// Keeps an allocation counted, kept next to the texture or buffer it stands for
+pub(crate) struct TrackedAllocation {
+    tracker: MemoryTracker,
+    kind: MemoryKind,
+    bytes: u64,
+}
+
+impl TrackedAllocation {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for TrackedAllocation {
+    fn drop(&mut self) {
+        self.tracker.sub(self.kind, self.bytes);
+    }
+}
@@ -0,0 +1,16 @@
+mod memory;
+mod pool;
+mod precision;
+mod readback;
+mod stats;
+mod submission;
+mod workgroup;
+
+pub use memory::{GpuMemoryLimits, TerrainGpuMemory};
+pub(crate) use memory::{MemoryKind, TrackedAllocation};
+pub use pool::{BufferPool, PooledBuffer};
+pub use precision::VertexPrecision;
+pub use readback::GpuReadback;
+pub use stats::GpuTerrainStats;
+pub use submission::GpuSubmission;
+pub use workgroup::WorkgroupSize;
@@ -0,0 +1,205 @@
+use super::memory::{MemoryKind, MemoryTracker};
+use bevy::render2::{
+    render_resource::{Buffer, BufferAddress, BufferDescriptor, BufferUsages},
+    renderer::RenderDevice,
+};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+/// Buffers kept per size and usage by default, anything returned beyond that is destroyed
+const DEFAULT_CAPACITY: usize = 8;
+
+/// Smallest size class, below it buffers aren't worth telling apart
+const MIN_SIZE_CLASS: BufferAddress = 256;
+
+/// Recycles GPU buffers between chunk jobs instead of allocating new ones for every chunk
+///
+/// Cloning is cheap and every clone shares the same buffers, so jobs running on the task pool
+/// return their buffers as soon as they are done with them. The terrain plugin keeps as many
+/// buffers per size and usage as chunks are scheduled per frame, a ring covering every job in
+/// flight.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<Mutex<PoolInner>>,
+    tracker: MemoryTracker,
+}
+
+struct PoolInner {
+    buffers: HashMap<(BufferAddress, BufferUsages), Vec<Buffer>>,
+    capacity: usize,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl BufferPool {
+    /// Keeps up to `capacity` buffers per size and usage
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(PoolInner {
+                buffers: HashMap::new(),
+                capacity,
+            })),
+            tracker: MemoryTracker::default(),
+        }
+    }
+
+    /// Counts the buffers of the pool, shared with the other allocations of the terrain
+    pub(crate) fn tracker(&self) -> &MemoryTracker {
+        &self.tracker
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.lock().unwrap().capacity
+    }
+
+    /// Changes how many buffers are kept per size and usage, destroying the ones beyond it
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.capacity = capacity;
+
+        for ((size, _), buffers) in inner.buffers.iter_mut() {
+            while buffers.len() > capacity {
+                buffers.pop().unwrap().destroy();
+                self.tracker.sub(MemoryKind::Pooled, *size);
+                self.tracker.sub(MemoryKind::Buffers, *size);
+            }
+        }
+    }
+
+    /// Rounds `size` up to a power of two, so buffers of similar sizes, like the staging
+    /// buffers of chunks with different vertex counts, share the same pooled buffers
+    pub fn size_class(size: BufferAddress) -> BufferAddress {
+        size.max(MIN_SIZE_CLASS).next_power_of_two()
+    }
+
+    /// Takes a pooled buffer matching `size` and `usage` or creates a new one
+    pub fn get(
+        &self,
+        render_device: &RenderDevice,
+        size: BufferAddress,
+        usage: BufferUsages,
+    ) -> Buffer {
+        let pooled = self
+            .inner
+            .lock()
+            .unwrap()
+            .buffers
+            .get_mut(&(size, usage))
+            .and_then(|buffers| buffers.pop());
+
+        if pooled.is_some() {
+            self.tracker.sub(MemoryKind::Pooled, size);
+        }
+
+        pooled.unwrap_or_else(|| {
+            self.tracker.add(MemoryKind::Buffers, size);
+
+            render_device.create_buffer(&BufferDescriptor {
+                label: None,
+                usage,
+                mapped_at_creation: false,
+                size,
+            })
+        })
+    }
+
+    /// Takes a buffer like `get` that is recycled once the returned guard is dropped
+    pub fn take(
+        &self,
+        render_device: &RenderDevice,
+        size: BufferAddress,
+        usage: BufferUsages,
+    ) -> PooledBuffer {
+        PooledBuffer {
+            pool: self.clone(),
+            size,
+            usage,
+            buffer: Some(self.get(render_device, size, usage)),
+        }
+    }
+
+    /// Returns a buffer taken with `get`, it must not be in use by the GPU anymore
+    pub fn recycle(&self, size: BufferAddress, usage: BufferUsages, buffer: Buffer) {
+        let mut inner = self.inner.lock().unwrap();
+        let capacity = inner.capacity;
+        let pooled = inner.buffers.entry((size, usage)).or_default();
+
+        if pooled.len() < capacity {
+            pooled.push(buffer);
+            self.tracker.add(MemoryKind::Pooled, size);
+        } else {
+            buffer.destroy();
+            self.tracker.sub(MemoryKind::Buffers, size);
+        }
+    }
+
+    /// Destroys a buffer taken with `get` that can't be returned, like a staging buffer whose
+    /// mapping was cancelled
+    pub fn discard(&self, size: BufferAddress, buffer: Buffer) {
+        buffer.destroy();
+        self.tracker.sub(MemoryKind::Buffers, size);
+    }
+
+    /// Number of buffers waiting to be reused
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap()
+            .buffers
+            .values()
+            .map(Vec::len)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Destroys every pooled buffer
+    pub fn clear(&self) {
+        for ((size, _), buffers) in self.inner.lock().unwrap().buffers.drain() {
+            for buffer in buffers {
+                buffer.destroy();
+                self.tracker.sub(MemoryKind::Pooled, size);
+                self.tracker.sub(MemoryKind::Buffers, size);
+            }
+        }
+    }
+}
+
+/// Buffer taken from a [`BufferPool`] that goes back to it when dropped, also when the job
+/// using it is cancelled halfway
+///
+/// Buffers may be returned as soon as the commands using them were submitted, later queue
+/// writes and submissions using them are ordered after the pending work. Mapped buffers have
+/// to wait for their mapping instead.
+pub struct PooledBuffer {
+    pool: BufferPool,
+    size: BufferAddress,
+    usage: BufferUsages,
+    buffer: Option<Buffer>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.recycle(self.size, self.usage, buffer);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+/// Largest chunk whose vertices `VertexPrecision::Auto` quantizes, the steps of a 16 bit
+/// position are below a 256th of a voxel up to this size
+const MAX_QUANTIZED_CHUNK_SIZE: u32 = 256;
+
+/// Precision of the vertices written by the terrain compute shader
+///
+/// Insert it as a resource before adding `TerrainPlugin`. Quantized vertices store the position
+/// relative to the chunk size and the normal as 16 bit integers, which halves the vertex buffers
+/// and the readback of every chunk. The packing builtins and 16 bit vertex formats are part of
+/// core WGSL and wgpu, so every device running the compute shader supports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexPrecision {
+    /// Quantizes the vertices of chunks up to 256 voxels and falls back to `Full` for larger
+    /// chunks
+    Auto,
+    /// Writes 32 bit floats
+    Full,
+    /// Quantizes the vertices of every chunk
+    Quantized,
+}
+
+impl Default for VertexPrecision {
+    fn default() -> Self {
+        VertexPrecision::Auto
+    }
+}
+
+impl VertexPrecision {
+    /// Whether the vertices of chunks of the given size are quantized
+    pub fn quantizes(self, chunk_size: u32) -> bool {
+        match self {
+            VertexPrecision::Auto => chunk_size <= MAX_QUANTIZED_CHUNK_SIZE,
+            VertexPrecision::Full => false,
+            VertexPrecision::Quantized => true,
+        }
+    }
+}
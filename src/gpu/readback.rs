@@ -0,0 +1,113 @@
+use crate::gpu::BufferPool;
+use bevy::render2::{
+    render_resource::{
+        Buffer, BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder,
+        MapMode,
+    },
+    renderer::RenderDevice,
+};
+use bytemuck::Pod;
+use std::{marker::PhantomData, mem};
+
+/// Staging buffer that reads the contents of a GPU buffer back as a slice of `T`
+pub struct GpuReadback<T: Pod> {
+    buffer: Buffer,
+    size: BufferAddress,
+    buffer_size: BufferAddress,
+    pool: Option<BufferPool>,
+    finished: bool,
+    marker: PhantomData<T>,
+}
+
+impl<T: Pod> GpuReadback<T> {
+    const USAGE: BufferUsages = BufferUsages::from_bits_truncate(
+        BufferUsages::COPY_DST.bits() | BufferUsages::MAP_READ.bits(),
+    );
+
+    /// Creates a staging buffer holding `len` elements
+    pub fn new(render_device: &RenderDevice, len: usize) -> Self {
+        let size = (len * mem::size_of::<T>()) as BufferAddress;
+
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: None,
+            usage: Self::USAGE,
+            mapped_at_creation: false,
+            size,
+        });
+
+        Self {
+            buffer,
+            size,
+            buffer_size: size,
+            pool: None,
+            finished: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// Takes the staging buffer from `pool`, it is returned to the pool after reading
+    ///
+    /// The buffer is allocated in the size class of `len` elements, so readbacks of varying
+    /// lengths reuse each other's buffers.
+    pub fn from_pool(pool: &BufferPool, render_device: &RenderDevice, len: usize) -> Self {
+        let size = (len * mem::size_of::<T>()) as BufferAddress;
+        let buffer_size = BufferPool::size_class(size);
+
+        Self {
+            buffer: pool.get(render_device, buffer_size, Self::USAGE),
+            size,
+            buffer_size,
+            pool: Some(pool.clone()),
+            finished: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// Size of the data read back in bytes
+    pub fn size(&self) -> BufferAddress {
+        self.size
+    }
+
+    /// Records a copy of the beginning of `source` into the staging buffer
+    pub fn copy_from(&self, command_encoder: &mut CommandEncoder, source: &Buffer) {
+        command_encoder.copy_buffer_to_buffer(source, 0, &self.buffer, 0, self.size);
+    }
+
+    /// Maps the staging buffer once the submitted copy is done and passes its contents to
+    /// `on_ready`, the buffer is recycled or destroyed afterwards
+    ///
+    /// Dropping the future before it resolves cancels the read and destroys the buffer.
+    pub async fn read<R>(
+        mut self,
+        on_ready: impl FnOnce(&[T]) -> R,
+    ) -> Result<R, BufferAsyncError> {
+        let buffer_slice = self.buffer.slice(..self.size);
+
+        let result = buffer_slice.map_async(MapMode::Read).await.map(|_| {
+            let buffer_data = buffer_slice.get_mapped_range();
+
+            let result = on_ready(bytemuck::cast_slice(&buffer_data));
+
+            drop(buffer_data);
+
+            result
+        });
+
+        self.buffer.unmap();
+        self.finished = true;
+
+        result
+    }
+}
+
+impl<T: Pod> Drop for GpuReadback<T> {
+    fn drop(&mut self) {
+        // A buffer dropped while it is being mapped can not be reused, the mapping may still
+        // complete after it would have been handed to another job
+        match (&self.pool, self.finished) {
+            (Some(pool), true) => pool.recycle(self.buffer_size, Self::USAGE, self.buffer.clone()),
+            (Some(pool), false) => pool.discard(self.buffer_size, self.buffer.clone()),
+            (None, _) => self.buffer.destroy(),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+/// GPU time spent by the terrain compute shader in the last measured frame
+///
+/// Measured with timestamp queries, so it is only updated when the render device was created
+/// with `WgpuFeatures::TIMESTAMP_QUERY`. A frame is measured once the one before has been read
+/// back, which takes a frame or two.
+#[derive(Debug, Clone, Default)]
+pub struct GpuTerrainStats {
+    /// Time of the density passes
+    pub density: Duration,
+    /// Time of the passes counting, scanning and writing the triangles
+    pub marching: Duration,
+    /// Slabs dispatched in the measured frame, a chunk is a single slab unless its vertices
+    /// exceed a storage buffer binding
+    pub slabs: usize,
+    /// Cells polygonized in the measured frame
+    pub cells: u64,
+    /// Frames measured since the app started
+    pub frames: u64,
+}
+
+impl GpuTerrainStats {
+    /// Time of all passes
+    pub fn total(&self) -> Duration {
+        self.density + self.marching
+    }
+
+    /// Average GPU time per cell, which shows how generation scales with the chunk size
+    pub fn time_per_cell(&self) -> Option<Duration> {
+        if self.cells == 0 {
+            return None;
+        }
+
+        Some(Duration::from_secs_f64(
+            self.total().as_secs_f64() / self.cells as f64,
+        ))
+    }
+}
@@ -0,0 +1,19 @@
+/// How the terrain compute work of a frame reaches the GPU
+///
+/// Insert it as a resource before adding `TerrainPlugin`. wgpu exposes a single queue per
+/// device, so there is no separate compute queue to submit to, but submitting the terrain on its
+/// own keeps long generation bursts out of the command buffer of the render graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuSubmission {
+    /// Records the terrain into the encoder of the render graph, ahead of the main pass
+    RenderGraph,
+    /// Submits the terrain in a command buffer of its own as soon as the jobs of the frame are
+    /// prepared, so the GPU starts on it while the render graph is still being recorded
+    Separate,
+}
+
+impl Default for GpuSubmission {
+    fn default() -> Self {
+        GpuSubmission::RenderGraph
+    }
+}
@@ -0,0 +1,56 @@
+use bevy::{log::warn, render2::renderer::RenderDevice};
+
+/// Edge lengths tried by `WorkgroupSize::Auto`, largest first
+const AUTO_SIZES: [u32; 4] = [8, 4, 2, 1];
+
+/// Edge length of the cubic workgroups of the terrain compute shader
+///
+/// Insert it as a resource before adding `TerrainPlugin` to override the automatic choice. The
+/// scan of the triangle counts runs one invocation per cell of a workgroup, so it uses as many
+/// invocations as a cubic workgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkgroupSize {
+    /// Picks 8³ and falls back to smaller workgroups on devices that can't run them
+    Auto,
+    /// Uses the given edge length, falls back to `Auto` when the device can't run it
+    Fixed(u32),
+}
+
+impl Default for WorkgroupSize {
+    fn default() -> Self {
+        WorkgroupSize::Auto
+    }
+}
+
+impl WorkgroupSize {
+    /// Whether the device can run cubic workgroups with the given edge length
+    pub fn is_supported(render_device: &RenderDevice, edge: u32) -> bool {
+        let limits = render_device.limits();
+
+        edge > 0
+            && edge <= limits.max_compute_workgroup_size_x
+            && edge <= limits.max_compute_workgroup_size_y
+            && edge <= limits.max_compute_workgroup_size_z
+            && edge.pow(3) <= limits.max_compute_invocations_per_workgroup
+    }
+
+    /// Picks the edge length that is actually used
+    pub fn resolve(self, render_device: &RenderDevice) -> u32 {
+        if let WorkgroupSize::Fixed(edge) = self {
+            if Self::is_supported(render_device, edge) {
+                return edge;
+            }
+
+            warn!(
+                "Workgroups of {0}x{0}x{0} exceed the device limits, picking a smaller size",
+                edge
+            );
+        }
+
+        AUTO_SIZES
+            .iter()
+            .copied()
+            .find(|edge| Self::is_supported(render_device, *edge))
+            .unwrap_or(1)
+    }
+}
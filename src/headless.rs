@@ -0,0 +1,105 @@
+use bevy::{math::IVec3, tasks::TaskPool};
+use marching_cubes::{
+    density::SimplexDensity, mesh_validate::MeshValidator, terrain::generate_chunk_mesh_data,
+    ChunkCoord, MeshData, TerrainSettings,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::Path,
+};
+
+/// Meshes every chunk within `world_extent` of the origin on the CPU and writes them into
+/// `output_dir` as OBJ files, without creating a window or a renderer
+///
+/// With `check_seams` the faces shared by neighbouring chunks are compared afterwards, any edge
+/// only one of the chunks has is a crack and fails the run.
+pub fn run(settings: &TerrainSettings, output_dir: &Path, check_seams: bool) -> io::Result<()> {
+    fs::create_dir_all(output_dir)?;
+
+    let density = SimplexDensity::with_noise(settings.seed, settings.noise);
+    let coords = ChunkCoord::default()
+        .within_radius(settings.world_extent)
+        .filter(|coord| settings.is_within_vertical_bounds(*coord))
+        .collect::<Vec<_>>();
+
+    let task_pool = TaskPool::new();
+
+    let results = task_pool.scope(|scope| {
+        for coord in coords.iter().copied() {
+            let density = &density;
+
+            scope.spawn(async move {
+                let mesh_data = generate_chunk_mesh_data(density, coord, settings);
+
+                if mesh_data.is_empty() {
+                    return Ok((coord, mesh_data));
+                }
+
+                let mut world_mesh_data = mesh_data.clone();
+
+                world_mesh_data
+                    .transform(settings.voxel_scale, settings.get_chunk_translation(coord));
+
+                let path = output_dir.join(format!(
+                    "chunk_{}_{}_{}.obj",
+                    coord.0.x, coord.0.y, coord.0.z
+                ));
+
+                world_mesh_data.write_obj(&mut BufWriter::new(File::create(path)?))?;
+
+                Ok((coord, mesh_data))
+            });
+        }
+    });
+
+    let meshes = results
+        .into_iter()
+        .collect::<io::Result<HashMap<ChunkCoord, MeshData>>>()?;
+
+    if !check_seams {
+        return Ok(());
+    }
+
+    let validator = MeshValidator {
+        chunk_size: Some(settings.chunk_size as f32),
+        ..Default::default()
+    };
+
+    let mut gaps = 0;
+
+    for (coord, mesh_data) in meshes.iter() {
+        for axis in [IVec3::X, IVec3::Y, IVec3::Z].iter() {
+            let neighbor = match meshes.get(&ChunkCoord(coord.0 + *axis)) {
+                Some(neighbor) => neighbor,
+                None => continue,
+            };
+
+            let offset = axis.as_vec3() * settings.chunk_size as f32;
+            let seam_gaps = validator.seam_gaps(mesh_data, neighbor, offset);
+
+            if seam_gaps > 0 {
+                eprintln!(
+                    "{} gaps between chunks {:?} and {:?}",
+                    seam_gaps,
+                    coord,
+                    coord.0 + *axis
+                );
+            }
+
+            gaps += seam_gaps;
+        }
+    }
+
+    if gaps > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} edges along the chunk seams have no counterpart", gaps),
+        ));
+    }
+
+    println!("checked the seams of {} chunks, no gaps", meshes.len());
+
+    Ok(())
+}
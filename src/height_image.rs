@@ -0,0 +1,201 @@
+use crate::density::{DensityField, SimplexDensity, SimplexNoise};
+use bevy::math::Vec3;
+use image::{DynamicImage, ImageResult};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Heightmap image sculpted outside the engine, see [`HeightmapImageDensity`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HeightmapImage {
+    /// PNG or EXR file, relative to the working directory
+    pub path: String,
+    /// World units between two pixels on the x and z axes
+    #[serde(default = "default_pixel_size")]
+    pub pixel_size: f32,
+    /// Height of a pixel value of 1, the top of the range of a PNG
+    pub vertical_scale: f32,
+    /// Height of a pixel value of 0
+    #[serde(default)]
+    pub base_height: f32,
+    /// World position of the first pixel on the x and z axes
+    #[serde(default)]
+    pub origin: [f32; 2],
+    /// 3D noise added to the distance to the surface, for detail and overhangs the image can't
+    /// hold, `None` keeps the plain surface
+    #[serde(default)]
+    pub detail: Option<SimplexNoise>,
+}
+
+fn default_pixel_size() -> f32 {
+    1.0
+}
+
+/// Pixel values of a heightmap image, row by row
+struct Heights {
+    width: usize,
+    depth: usize,
+    values: Vec<f32>,
+}
+
+impl Heights {
+    fn get(&self, x: usize, z: usize) -> f32 {
+        self.values[z * self.width + x]
+    }
+
+    /// Bilinear interpolation between the pixels around a position in pixels, clamped to the
+    /// edges of the image
+    fn sample(&self, x: f32, z: f32) -> f32 {
+        let x = x.max(0.0).min((self.width - 1) as f32);
+        let z = z.max(0.0).min((self.depth - 1) as f32);
+        let (x0, z0) = (x.floor() as usize, z.floor() as usize);
+        let (x1, z1) = ((x0 + 1).min(self.width - 1), (z0 + 1).min(self.depth - 1));
+        let (tx, tz) = (x - x0 as f32, z - z0 as f32);
+
+        let near = self.get(x0, z0) + (self.get(x1, z0) - self.get(x0, z0)) * tx;
+        let far = self.get(x0, z1) + (self.get(x1, z1) - self.get(x0, z1)) * tx;
+
+        near + (far - near) * tz
+    }
+
+    /// Smallest and largest pixel between two positions in pixels, which bound the bilinear
+    /// samples in between
+    fn range(&self, min: (f32, f32), max: (f32, f32)) -> (f32, f32) {
+        let index = |x: f32, last: usize| (x.max(0.0) as usize).min(last);
+        let (x0, x1) = (
+            index(min.0.floor(), self.width - 1),
+            index(max.0.ceil(), self.width - 1),
+        );
+        let (z0, z1) = (
+            index(min.1.floor(), self.depth - 1),
+            index(max.1.ceil(), self.depth - 1),
+        );
+
+        (z0..=z1).flat_map(|z| (x0..=x1).map(move |x| (x, z))).fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(low, high), (x, z)| {
+                let height = self.get(x, z);
+
+                (low.min(height), high.max(height))
+            },
+        )
+    }
+}
+
+/// Terrain shaped by a heightmap image, for macro shapes sculpted in an external tool
+///
+/// The density is the distance above the surface, `y - height(x, z)`, with the detail noise
+/// added on top. Heights are read from the red channel and interpolated bilinearly between the
+/// pixels, PNG values range from 0 to 1 while EXR values are kept as they are stored. Outside of
+/// the image the edge pixels are extended.
+#[derive(Clone)]
+pub struct HeightmapImageDensity {
+    image: Arc<HeightmapImage>,
+    heights: Arc<Heights>,
+    detail: Option<Arc<SimplexDensity>>,
+}
+
+impl HeightmapImageDensity {
+    /// Reads and decodes the file at `path`
+    pub fn load(image: HeightmapImage, seed: u32) -> ImageResult<Self> {
+        let pixels = image::open(&image.path)?;
+
+        Ok(Self::from_image(image, &pixels, seed))
+    }
+
+    /// Heightmap of an image decoded already, `path` is left unread
+    pub fn from_image(image: HeightmapImage, pixels: &DynamicImage, seed: u32) -> Self {
+        let pixels = pixels.to_rgb32f();
+
+        // An empty image is a single pixel at the base height
+        let heights = if pixels.width() == 0 || pixels.height() == 0 {
+            Heights {
+                width: 1,
+                depth: 1,
+                values: vec![0.0],
+            }
+        } else {
+            Heights {
+                width: pixels.width() as usize,
+                depth: pixels.height() as usize,
+                values: pixels.pixels().map(|pixel| pixel.0[0]).collect(),
+            }
+        };
+
+        Self::with_heights(Arc::new(image), Arc::new(heights), seed)
+    }
+
+    fn with_heights(image: Arc<HeightmapImage>, heights: Arc<Heights>, seed: u32) -> Self {
+        Self {
+            detail: image
+                .detail
+                .map(|noise| Arc::new(SimplexDensity::with_noise(seed, noise))),
+            image,
+            heights,
+        }
+    }
+
+    pub fn image(&self) -> &HeightmapImage {
+        &self.image
+    }
+
+    /// Position of a world point in pixels of the image
+    fn pixel(&self, x: f32, z: f32) -> (f32, f32) {
+        let pixel_size = self.image.pixel_size.max(f32::EPSILON);
+
+        (
+            (x - self.image.origin[0]) / pixel_size,
+            (z - self.image.origin[1]) / pixel_size,
+        )
+    }
+
+    /// Height of the surface at a horizontal position, before the detail noise
+    pub fn surface_height(&self, x: f32, z: f32) -> f32 {
+        let (x, z) = self.pixel(x, z);
+
+        self.image.base_height + self.heights.sample(x, z) * self.image.vertical_scale
+    }
+}
+
+impl DensityField for HeightmapImageDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        let value = p.y - self.surface_height(p.x, p.z);
+
+        match self.detail {
+            Some(ref detail) => value + detail.sample(p),
+            None => value,
+        }
+    }
+
+    /// The surface stays between the lowest and the highest pixel the box covers
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        let (low, high) = self
+            .heights
+            .range(self.pixel(min.x, min.z), self.pixel(max.x, max.z));
+
+        // A negative scale turns the image upside down
+        let (low, high) = (
+            low * self.image.vertical_scale,
+            high * self.image.vertical_scale,
+        );
+        let (low, high) = (
+            self.image.base_height + low.min(high),
+            self.image.base_height + low.max(high),
+        );
+
+        let (detail_min, detail_max) = match self.detail {
+            Some(ref detail) => detail.bounds(min, max)?,
+            None => (0.0, 0.0),
+        };
+
+        Some((min.y - high + detail_min, max.y - low + detail_max))
+    }
+
+    /// Shares the pixels instead of reading the image again
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        Some(Arc::new(Self::with_heights(
+            self.image.clone(),
+            self.heights.clone(),
+            seed,
+        )))
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{cpu, density::DensityGrid, mesh::MeshData, terrain::VertexPlacement};
+use bevy::math::{UVec3, Vec3};
+
+/// Extracts the top surface of every column of a density grid as a heightmap, one vertex per
+/// column and two triangles per square of four columns
+///
+/// Meant for 2.5D terrain without overhangs or caves, it only produces a fraction of the
+/// triangles of marching cubes. Every column is scanned from the top for the first sample
+/// below the iso level under one above it, deeper crossings are dropped. Columns whose top
+/// sample is already solid or whose bottom sample is still air have no vertex, and squares
+/// missing a corner stay open.
+///
+/// Columns are read inside the `border` on the horizontal axes and over the full height of the
+/// grid, ghost samples included. Every square belongs to the chunk its lowest corner lies in, so
+/// chunks stacked on top of each other split the squares crossing their shared face, as long as
+/// the surface doesn't climb more than the ghost samples over a single cell. Normals come from
+/// the density gradient like the ones of `cpu::march_chunk_smooth`.
+///
+/// Positions are relative to the first sample inside the border, the mesh comes out indexed.
+pub fn heightmap(
+    grid: &DensityGrid,
+    border: u32,
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> MeshData {
+    assert!(
+        border >= 1,
+        "gradient normals need at least one ghost sample"
+    );
+
+    let dims = grid.dims();
+    let columns = dims.x - 2 * border;
+    let rows = dims.z - 2 * border;
+
+    let mut mesh = MeshData::default();
+    let mut column_vertices = vec![None; (columns * rows) as usize];
+
+    for z in 0..rows {
+        for x in 0..columns {
+            let (grid_x, grid_z) = (x + border, z + border);
+
+            let crossing = (1..dims.y).rev().find(|y| {
+                grid.get(grid_x, *y, grid_z) >= iso_level
+                    && grid.get(grid_x, y - 1, grid_z) < iso_level
+            });
+
+            let y = match crossing {
+                Some(y) => y,
+                None => continue,
+            };
+
+            let below = UVec3::new(grid_x, y - 1, grid_z);
+            let above = UVec3::new(grid_x, y, grid_z);
+
+            let position = cpu::interpolate_vertices(
+                (below, grid.get(below.x, below.y, below.z)),
+                (above, grid.get(above.x, above.y, above.z)),
+                iso_level,
+                placement,
+            );
+
+            // The triangles face towards the higher density
+            let normal =
+                (column_gradient(grid, below) + column_gradient(grid, above)).normalize_or_zero();
+
+            column_vertices[(z * columns + x) as usize] = Some(mesh.positions.len() as u32);
+
+            mesh.positions
+                .push((position - Vec3::splat(border as f32)).into());
+            mesh.normals.push(normal.into());
+        }
+    }
+
+    let height = |vertex: u32| mesh.positions[vertex as usize][1];
+    let chunk_height = (dims.y - 1 - 2 * border) as f32;
+
+    let mut indices = Vec::new();
+
+    for z in 0..rows - 1 {
+        for x in 0..columns - 1 {
+            let corner = |dx: u32, dz: u32| column_vertices[((z + dz) * columns + x + dx) as usize];
+
+            let (a, b, c, d) = match (corner(0, 0), corner(1, 0), corner(0, 1), corner(1, 1)) {
+                (Some(a), Some(b), Some(c), Some(d)) => (a, b, c, d),
+                _ => continue,
+            };
+
+            let lowest = height(a).min(height(b)).min(height(c)).min(height(d));
+
+            if lowest < 0.0 || lowest >= chunk_height {
+                continue;
+            }
+
+            indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+    }
+
+    mesh.indices = indices;
+
+    mesh
+}
+
+/// Central difference of the density at a grid point, one sided on the top and bottom of the
+/// grid where the columns reach into the last samples
+fn column_gradient(grid: &DensityGrid, point: UVec3) -> Vec3 {
+    let UVec3 { x, y, z } = point;
+    let below = y.saturating_sub(1);
+    let above = (y + 1).min(grid.dims().y - 1);
+
+    Vec3::new(
+        (grid.get(x + 1, y, z) - grid.get(x - 1, y, z)) / 2.0,
+        (grid.get(x, above, z) - grid.get(x, below, z)) / (above - below) as f32,
+        (grid.get(x, y, z + 1) - grid.get(x, y, z - 1)) / 2.0,
+    )
+}
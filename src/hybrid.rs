@@ -0,0 +1,183 @@
+use crate::density::{DensityField, SimplexDensity, SimplexNoise};
+use bevy::math::Vec3;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Seed of the carving noise is offset this far from the seed of the surface noise
+const CARVING_SEED_OFFSET: u32 = 104_729;
+
+/// Weights of the two terms of a [`HybridDensity`] at a height, linearly interpolated between
+/// the bands above and below
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct AltitudeBand {
+    pub height: f32,
+    /// Scale of the distance to the heightmap surface, 0 leaves only the carving noise
+    pub surface: f32,
+    /// Scale of the carving noise, 0 leaves a plain heightmap without overhangs or caves
+    pub carving: f32,
+}
+
+/// Description of a [`HybridDensity`], for config files
+///
+/// Bands are sorted by height when the density is built, bands without a finite height are
+/// dropped. Heights below the lowest band use its weights, the same goes for heights above the
+/// highest one, and without any band both terms keep a weight of 1.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HybridTerrain {
+    /// Height of the surface where the heightmap noise is 0
+    #[serde(default)]
+    pub base_height: f32,
+    /// 2D noise sampled on the x and z axes, its value in world units is added to the height of
+    /// the surface, so its amplitude is the height of the hills
+    #[serde(default)]
+    pub surface: SimplexNoise,
+    /// 3D noise added to the distance to the surface, which pushes the ground out into overhangs
+    /// and hollows caves out of it
+    #[serde(default)]
+    pub carving: SimplexNoise,
+    #[serde(default)]
+    pub bands: Vec<AltitudeBand>,
+}
+
+/// Heightmap surface with 3D noise carved into it, the usual recipe of voxel terrain
+///
+/// The density is `surface * (y - height(x, z)) + carving * noise(p)`, with the weights picked
+/// by the altitude bands, so the carving noise can for example only break through deep down for
+/// caves and high up for overhanging cliffs while the lowlands stay smooth.
+#[derive(Clone)]
+pub struct HybridDensity {
+    terrain: Arc<HybridTerrain>,
+    surface: Arc<SimplexDensity>,
+    carving: Arc<SimplexDensity>,
+}
+
+impl HybridDensity {
+    pub fn new(mut terrain: HybridTerrain, seed: u32) -> Self {
+        terrain.bands.retain(|band| band.height.is_finite());
+        terrain.bands.sort_by(|a, b| a.height.total_cmp(&b.height));
+
+        Self {
+            surface: Arc::new(SimplexDensity::with_noise(seed, terrain.surface)),
+            carving: Arc::new(SimplexDensity::with_noise(
+                seed.wrapping_add(CARVING_SEED_OFFSET),
+                terrain.carving,
+            )),
+            terrain: Arc::new(terrain),
+        }
+    }
+
+    pub fn terrain(&self) -> &HybridTerrain {
+        &self.terrain
+    }
+
+    /// Height of the heightmap surface at a horizontal position, before any carving
+    pub fn surface_height(&self, x: f32, z: f32) -> f32 {
+        self.terrain.base_height + self.surface.sample(Vec3::new(x, 0.0, z))
+    }
+
+    /// Weights of the surface and the carving term at height `y`
+    pub fn weights(&self, y: f32) -> (f32, f32) {
+        let bands = &self.terrain.bands;
+
+        let (first, last) = match (bands.first(), bands.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return (1.0, 1.0),
+        };
+
+        // NaN fails both comparisons with the bands, infinities end up at the first or last
+        if y.is_nan() || y <= first.height {
+            return (first.surface, first.carving);
+        }
+
+        if y >= last.height {
+            return (last.surface, last.carving);
+        }
+
+        let upper = bands.iter().position(|band| band.height > y).unwrap();
+        let (below, above) = (bands[upper - 1], bands[upper]);
+        let t = (y - below.height) / (above.height - below.height).max(f32::EPSILON);
+
+        (
+            below.surface + (above.surface - below.surface) * t,
+            below.carving + (above.carving - below.carving) * t,
+        )
+    }
+
+    /// Smallest and largest weights between the heights `min` and `max`, reached either at the
+    /// ends or at a band in between since the weights are linear in between the bands
+    fn weight_bounds(&self, min: f32, max: f32) -> ((f32, f32), (f32, f32)) {
+        let heights = self
+            .terrain
+            .bands
+            .iter()
+            .map(|band| band.height)
+            .filter(|height| *height > min && *height < max);
+
+        std::iter::once(min)
+            .chain(std::iter::once(max))
+            .chain(heights)
+            .fold(
+                (
+                    (f32::INFINITY, f32::NEG_INFINITY),
+                    (f32::INFINITY, f32::NEG_INFINITY),
+                ),
+                |(surface, carving), height| {
+                    let weights = self.weights(height);
+
+                    (
+                        (surface.0.min(weights.0), surface.1.max(weights.0)),
+                        (carving.0.min(weights.1), carving.1.max(weights.1)),
+                    )
+                },
+            )
+    }
+}
+
+/// Range of the product of two ranges
+fn multiply_bounds(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    let products = [a.0 * b.0, a.0 * b.1, a.1 * b.0, a.1 * b.1];
+
+    products
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), product| {
+            (min.min(*product), max.max(*product))
+        })
+}
+
+impl DensityField for HybridDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        let (surface, carving) = self.weights(p.y);
+
+        let mut value = 0.0;
+
+        if surface != 0.0 {
+            value += surface * (p.y - self.surface_height(p.x, p.z));
+        }
+
+        if carving != 0.0 {
+            value += carving * self.carving.sample(p);
+        }
+
+        value
+    }
+
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        let (surface_weight, carving_weight) = self.weight_bounds(min.y, max.y);
+
+        let (surface_min, surface_max) = self.surface.bounds(min, max)?;
+        let surface = (
+            min.y - self.terrain.base_height - surface_max,
+            max.y - self.terrain.base_height - surface_min,
+        );
+        let carving = self.carving.bounds(min, max)?;
+
+        let surface = multiply_bounds(surface_weight, surface);
+        let carving = multiply_bounds(carving_weight, carving);
+
+        Some((surface.0 + carving.0, surface.1 + carving.1))
+    }
+
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        Some(Arc::new(Self::new((*self.terrain).clone(), seed)))
+    }
+}
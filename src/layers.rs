@@ -0,0 +1,291 @@
+use crate::{
+    biome::{BiomeDensity, BiomeMap},
+    caves::{WormCaveDensity, WormCaves},
+    csg::{CsgDensity, CsgNode},
+    density::{
+        DensityField, FlatDensity, PerlinDensity, SimplexDensity, SimplexNoise, SphereDensity,
+    },
+    height_image::{HeightmapImage, HeightmapImageDensity},
+    hybrid::{HybridDensity, HybridTerrain},
+    modifiers::{DensityModifier, ModifiedDensity},
+    sdf::Primitive,
+};
+use bevy::{log::warn, math::Vec3};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// How a layer is combined with the layers before it, values below the iso level are solid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LayerBlend {
+    /// Solid wherever either is solid, stacks terrain on top of the previous layers
+    Min,
+    /// Solid only where both are solid, carves caves out of the previous layers
+    Max,
+    /// Sums both values, for detail noise on top of a base shape
+    Add,
+}
+
+impl LayerBlend {
+    fn apply(self, a: f32, b: f32) -> f32 {
+        match self {
+            LayerBlend::Min => a.min(b),
+            LayerBlend::Max => a.max(b),
+            LayerBlend::Add => a + b,
+        }
+    }
+
+    fn apply_bounds(self, a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+        match self {
+            LayerBlend::Min => (a.0.min(b.0), a.1.min(b.1)),
+            LayerBlend::Max => (a.0.max(b.0), a.1.max(b.1)),
+            LayerBlend::Add => (a.0 + b.0, a.1 + b.1),
+        }
+    }
+}
+
+/// Single density source of a [`LayeredDensity`]
+#[derive(Clone)]
+pub struct DensityLayer {
+    pub name: String,
+    pub field: Arc<dyn DensityField>,
+    /// Ignored for the first enabled layer
+    pub blend: LayerBlend,
+    pub enabled: bool,
+    /// Added to the seed when the layered density is reseeded, so layers built from the same
+    /// noise don't repeat each other
+    pub seed_offset: u32,
+}
+
+impl DensityLayer {
+    pub fn new(
+        name: impl Into<String>,
+        field: impl DensityField + 'static,
+        blend: LayerBlend,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            field: Arc::new(field),
+            blend,
+            enabled: true,
+            seed_offset: 0,
+        }
+    }
+}
+
+/// Density made of layers combined in order, like a surface layer with a cave layer carved out
+///
+/// Layers are toggled by replacing the `TerrainDensity` of a terrain with the result of
+/// `with_layer_enabled`, which regenerates its chunks.
+#[derive(Clone, Default)]
+pub struct LayeredDensity {
+    layers: Vec<DensityLayer>,
+}
+
+impl LayeredDensity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the layers described by a config, seeding them with `seed`
+    pub fn from_config(layers: &[DensityLayerConfig], seed: u32) -> Self {
+        Self {
+            layers: layers.iter().map(|layer| layer.build(seed)).collect(),
+        }
+    }
+
+    pub fn with_layer(mut self, layer: DensityLayer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn layers(&self) -> &[DensityLayer] {
+        &self.layers
+    }
+
+    /// Copy of the density with the layers called `name` turned on or off
+    pub fn with_layer_enabled(&self, name: &str, enabled: bool) -> Self {
+        let mut density = self.clone();
+
+        for layer in density.layers.iter_mut().filter(|layer| layer.name == name) {
+            layer.enabled = enabled;
+        }
+
+        density
+    }
+
+    fn enabled_layers(&self) -> impl Iterator<Item = &DensityLayer> {
+        self.layers.iter().filter(|layer| layer.enabled)
+    }
+
+    /// The only enabled layer if it is seeded like the terrain, the compute shader evaluates
+    /// such a density like the field of the layer
+    fn single_layer(&self) -> Option<&DensityLayer> {
+        let mut layers = self.enabled_layers();
+
+        match (layers.next(), layers.next()) {
+            (Some(layer), None) if layer.seed_offset == 0 => Some(layer),
+            _ => None,
+        }
+    }
+}
+
+impl DensityField for LayeredDensity {
+    /// Without any enabled layer everything is air
+    fn sample(&self, p: Vec3) -> f32 {
+        let mut layers = self.enabled_layers();
+
+        let first = match layers.next() {
+            Some(layer) => layer.field.sample(p),
+            None => return f32::INFINITY,
+        };
+
+        layers.fold(first, |value, layer| {
+            layer.blend.apply(value, layer.field.sample(p))
+        })
+    }
+
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        let mut layers = self.enabled_layers();
+
+        let first = match layers.next() {
+            Some(layer) => layer.field.bounds(min, max)?,
+            None => return Some((f32::INFINITY, f32::INFINITY)),
+        };
+
+        layers.try_fold(first, |bounds, layer| {
+            Some(
+                layer
+                    .blend
+                    .apply_bounds(bounds, layer.field.bounds(min, max)?),
+            )
+        })
+    }
+
+    /// Reseeds every layer that supports it and keeps the others
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        let mut density = self.clone();
+
+        for layer in density.layers.iter_mut() {
+            if let Some(field) = layer.field.with_seed(seed.wrapping_add(layer.seed_offset)) {
+                layer.field = field;
+            }
+        }
+
+        Some(Arc::new(density))
+    }
+
+    fn simplex_noise(&self) -> Option<(SimplexNoise, u32)> {
+        self.single_layer()?.field.simplex_noise()
+    }
+
+    fn modifiers(&self) -> Vec<(DensityModifier, u32)> {
+        self.single_layer()
+            .map_or_else(Vec::new, |layer| layer.field.modifiers())
+    }
+}
+
+/// Declarative description of a [`DensityLayer`], for config files
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DensityLayerConfig {
+    pub name: String,
+    pub source: DensitySource,
+    #[serde(default = "default_blend")]
+    pub blend: LayerBlend,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub seed_offset: u32,
+}
+
+fn default_blend() -> LayerBlend {
+    LayerBlend::Min
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl DensityLayerConfig {
+    pub fn build(&self, seed: u32) -> DensityLayer {
+        DensityLayer {
+            name: self.name.clone(),
+            field: self.source.build(seed.wrapping_add(self.seed_offset)),
+            blend: self.blend,
+            enabled: self.enabled,
+            seed_offset: self.seed_offset,
+        }
+    }
+}
+
+/// Density fields a [`DensityLayerConfig`] can be made of
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum DensitySource {
+    Simplex,
+    Perlin {
+        frequency: f64,
+    },
+    Flat {
+        height: f32,
+    },
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+    },
+    Biomes(BiomeMap),
+    Hybrid(HybridTerrain),
+    /// Best blended with `LayerBlend::Max`, which carves the tunnels out of the layers before
+    WormCaves(WormCaves),
+    /// Another source run through a stack of modifiers, see `ModifiedDensity`
+    Modified {
+        base: Box<DensitySource>,
+        modifiers: Vec<DensityModifier>,
+    },
+    /// Signed distance to a single shape, a plane for flat ground, a sphere for a planet
+    Primitive(Primitive),
+    /// Tree of primitives and noise, see `CsgDensity`
+    Csg(CsgNode),
+    /// Surface of a heightmap image, read once when the layer is built, see
+    /// `HeightmapImageDensity`. An image that fails to load leaves flat ground at the base height.
+    HeightmapImage(HeightmapImage),
+}
+
+impl DensitySource {
+    pub fn build(&self, seed: u32) -> Arc<dyn DensityField> {
+        match *self {
+            DensitySource::Simplex => Arc::new(SimplexDensity::new(seed)),
+            DensitySource::Perlin { frequency } => Arc::new(PerlinDensity::new(seed, frequency)),
+            DensitySource::Flat { height } => Arc::new(FlatDensity { height }),
+            DensitySource::Sphere { center, radius } => Arc::new(SphereDensity {
+                center: Vec3::from(center),
+                radius,
+            }),
+            DensitySource::Biomes(ref map) => Arc::new(BiomeDensity::new(map.clone(), seed)),
+            DensitySource::Hybrid(ref terrain) => {
+                Arc::new(HybridDensity::new(terrain.clone(), seed))
+            }
+            DensitySource::WormCaves(caves) => Arc::new(WormCaveDensity::new(caves, seed)),
+            DensitySource::Modified {
+                ref base,
+                ref modifiers,
+            } => Arc::new(ModifiedDensity::new(
+                base.build(seed),
+                modifiers.clone(),
+                seed,
+            )),
+            DensitySource::Primitive(primitive) => Arc::new(primitive),
+            DensitySource::Csg(ref tree) => Arc::new(CsgDensity::new(tree.clone(), seed)),
+            DensitySource::HeightmapImage(ref image) => {
+                match HeightmapImageDensity::load(image.clone(), seed) {
+                    Ok(density) => Arc::new(density),
+                    Err(error) => {
+                        warn!("Failed to load the heightmap {}: {}", image.path, error);
+
+                        Arc::new(FlatDensity {
+                            height: image.base_height,
+                        })
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+pub mod biome;
+pub mod cache;
+pub mod caves;
+pub mod chunk;
+pub mod contour;
+pub mod cpu;
+pub mod csg;
+pub mod decimate;
+pub mod density;
+pub mod dirty;
+pub mod dual_contouring;
+pub mod error;
+#[cfg(feature = "gpu-compute")]
+pub mod gpu;
+pub mod height_image;
+pub mod heightmap;
+pub mod hybrid;
+pub mod layers;
+pub mod marching_cubes;
+pub mod marching_cubes33;
+pub mod marching_tetrahedra;
+pub mod mesh;
+pub mod mesh_validate;
+pub mod modifiers;
+pub mod occlusion;
+pub mod octree;
+pub mod optimize;
+pub mod origin;
+pub mod patch;
+pub mod post_process;
+pub mod progress;
+pub mod provider;
+pub mod sdf;
+pub mod simplex;
+pub mod stats;
+pub mod surface_nets;
+pub mod tables;
+pub mod terrain;
+pub mod transition;
+pub mod vertex_colors;
+
+mod queue;
+#[cfg(feature = "gpu-compute")]
+mod terrain_gpu;
+
+pub use crate::{
+    biome::{Biome, BiomeColors, BiomeDensity, BiomeMap, BiomePalette},
+    cache::ChunkCache,
+    caves::{WormCaveDensity, WormCaves},
+    chunk::{ChunkBounds, ChunkCoord, ChunkMap},
+    contour::{contours, Contour},
+    csg::{CsgDensity, CsgNode},
+    density::{
+        DensityField, DensityGrid, DomainWarp, NoiseBasis, NoiseVariant, SimplexNoise,
+        TerrainDensity,
+    },
+    dirty::{ChunkDirty, DensityChanged},
+    error::TerrainError,
+    height_image::{HeightmapImage, HeightmapImageDensity},
+    hybrid::{AltitudeBand, HybridDensity, HybridTerrain},
+    layers::{DensityLayer, DensityLayerConfig, DensitySource, LayerBlend, LayeredDensity},
+    marching_cubes::{polygonize, polygonize_with_placement},
+    mesh::MeshData,
+    modifiers::{DensityModifier, ModifiedDensity},
+    occlusion::AmbientOcclusion,
+    octree::ChunkOctree,
+    origin::{
+        FloatingOrigin, FloatingOriginPlugin, FloatingOriginSettings, OriginShifted, WorldOffset,
+    },
+    patch::ChunkPatch,
+    post_process::{MeshPostProcessor, MeshPostProcessors},
+    progress::GenerationProgress,
+    provider::{ChunkData, ChunkProvider, TerrainChunkProvider},
+    sdf::{Brush, CsgOp, Primitive},
+    stats::{ChunkMeshStats, TerrainMeshStats},
+    terrain::{
+        ChunkCollisionMesh, ChunkDensityReady, ChunkDespawned, ChunkLayout, ChunkMeshed,
+        ChunkQueued, ChunkWireframe, GenerationBudget, GenerationOrder, MeshingAlgorithm,
+        MeshingBackend, RegenerateTerrain, SeamMode, Terrain, TerrainBundle, TerrainChunk,
+        TerrainPlugin, TerrainPluginBuilder, TerrainSettings, TerrainStatus, VertexPlacement,
+        VerticalBounds,
+    },
+    transition::ChunkTransitions,
+    vertex_colors::{MeshVertexColors, SurfaceVertex, VertexColorSource},
+};
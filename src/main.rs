@@ -1,10 +1,13 @@
-mod marching_cubes;
+mod cli;
+mod config;
+#[cfg(feature = "cpu-mesher")]
+mod headless;
 mod plugins;
-mod terrain;
 
 use crate::{
+    cli::Args,
+    config::ConfigPlugin,
     plugins::{FlyCam, NoCameraPlayerPlugin},
-    terrain::TerrainPlugin,
 };
 use bevy::{
     pbr2::{DirectionalLight, DirectionalLightBundle},
@@ -13,6 +16,7 @@ use bevy::{
 
 use bevy::{
     app::App,
+    asset::AssetServerSettings,
     ecs::system::Commands,
     log::*,
     math::Vec3,
@@ -25,25 +29,66 @@ use bevy::{
 
 use bevy_inspector_egui::WorldInspectorPlugin;
 
+use marching_cubes::{ChunkCache, FloatingOrigin, FloatingOriginPlugin, TerrainPlugin};
+
 fn main() {
-    App::new()
-        .insert_resource(WindowDescriptor {
-            width: 1920.0,
-            height: 1080.0,
-            title: "Lulw".to_string(),
-            vsync: true,
-            ..Default::default()
-        })
-        .insert_resource(LogSettings {
-            level: Level::ERROR,
-            ..Default::default()
-        })
-        .add_plugins(PipelinedDefaultPlugins)
-        .add_plugin(WorldInspectorPlugin::new())
-        .add_plugin(NoCameraPlayerPlugin)
-        .add_plugin(TerrainPlugin)
-        .add_startup_system(setup_environment)
-        .run();
+    let args = Args::from_env();
+
+    if args.headless {
+        run_headless(&args);
+        return;
+    }
+
+    let mut terrain_plugin = TerrainPlugin::builder().settings(args.settings);
+
+    if let Some(cache) = args.cache {
+        terrain_plugin = terrain_plugin.cache(ChunkCache::new(cache).with_meshes(true));
+    }
+
+    let mut app = App::new();
+
+    app.insert_resource(WindowDescriptor {
+        width: args.width,
+        height: args.height,
+        title: "Lulw".to_string(),
+        vsync: args.vsync,
+        ..Default::default()
+    })
+    .insert_resource(AssetServerSettings {
+        watch_for_changes: true,
+        ..Default::default()
+    })
+    .insert_resource(args.backend)
+    .insert_resource(LogSettings {
+        level: Level::ERROR,
+        ..Default::default()
+    })
+    .add_plugins(PipelinedDefaultPlugins)
+    .add_plugin(WorldInspectorPlugin::new())
+    .add_plugin(NoCameraPlayerPlugin)
+    .add_plugin(terrain_plugin.build())
+    .add_plugin(FloatingOriginPlugin)
+    .add_startup_system(setup_environment);
+
+    if let Some(config) = args.config {
+        app.add_plugin(ConfigPlugin::new(config, args.overrides));
+    }
+
+    app.run();
+}
+
+#[cfg(feature = "cpu-mesher")]
+fn run_headless(args: &Args) {
+    if let Err(error) = headless::run(&args.settings, &args.output, args.check_seams) {
+        eprintln!("headless run failed: {}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "cpu-mesher"))]
+fn run_headless(_args: &Args) {
+    eprintln!("headless mode requires the `cpu-mesher` feature");
+    std::process::exit(1);
 }
 
 fn setup_environment(mut commands: Commands) {
@@ -57,7 +102,8 @@ fn setup_environment(mut commands: Commands) {
             transform: Transform::from_xyz(-40.0, 40.0, 40.0).looking_at(Vec3::ZERO, Vec3::Y),
             ..Default::default()
         })
-        .insert(FlyCam);
+        .insert(FlyCam)
+        .insert(FloatingOrigin);
 
     commands.spawn_bundle(DirectionalLightBundle {
         directional_light: DirectionalLight {
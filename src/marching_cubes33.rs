@@ -0,0 +1,388 @@
+use crate::{
+    cpu::{self, CORNER_OFFSETS},
+    density::DensityGrid,
+    dual_contouring,
+    mesh::MeshData,
+    tables::{CORNER_INDEX_A_FROM_EDGE, CORNER_INDEX_B_FROM_EDGE},
+    terrain::VertexPlacement,
+};
+use bevy::math::{UVec3, Vec3};
+
+/// Corners of every cell face in order around it, followed by the edges from each corner to
+/// the next, as indices into `CORNER_OFFSETS` and the edge tables
+const FACES: [([usize; 4], [usize; 4]); 6] = [
+    ([0, 1, 2, 3], [0, 1, 2, 3]),
+    ([4, 5, 6, 7], [4, 5, 6, 7]),
+    ([0, 1, 5, 4], [0, 9, 4, 8]),
+    ([1, 2, 6, 5], [1, 10, 5, 9]),
+    ([2, 3, 7, 6], [2, 11, 6, 10]),
+    ([3, 0, 4, 7], [3, 8, 7, 11]),
+];
+
+/// Points sampled between two groups of corners to decide whether a tunnel connects them
+const TUNNEL_SAMPLES: u32 = 7;
+
+/// Polygonizes the cells inside a `border` of ghost samples with the topology of the trilinear
+/// interpolation of their corners, like the MC33 variant of marching cubes
+///
+/// Instead of looking the triangles up, the contour of every cell is traced around its faces.
+/// Ambiguous faces are resolved with the asymptotic decider, so neighbouring cells always agree
+/// and the surface has no holes. Cells whose contour is two loops get a tunnel between them when
+/// the interpolation stays on the side of the loops along the line between the corners they
+/// enclose, which approximates the interior test of MC33 and keeps thin tunnels and saddles
+/// open. Several tunnels in one cell are not resolved.
+///
+/// Slower than `cpu::march_chunk_smooth`. Normals, positions and the border work like there.
+pub fn march_cubes33(
+    grid: &DensityGrid,
+    border: u32,
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> MeshData {
+    assert!(
+        border >= 1,
+        "gradient normals need at least one ghost sample"
+    );
+
+    let dims = grid.dims();
+    let end = |len: u32| len.saturating_sub(border + 1);
+
+    let mut mesh = MeshData::default();
+
+    for z in border..end(dims.z) {
+        for y in border..end(dims.y) {
+            for x in border..end(dims.x) {
+                let cell = UVec3::new(x, y, z);
+
+                let mut corners = [(UVec3::ZERO, 0.0); 8];
+
+                for (corner, offset) in corners.iter_mut().zip(CORNER_OFFSETS.iter()) {
+                    let point = cell + *offset;
+
+                    *corner = (point, grid.get(point.x, point.y, point.z));
+                }
+
+                let below = |corner: usize| corners[corner].1 < iso_level;
+
+                if (0..8).all(below) || !(0..8).any(below) {
+                    continue;
+                }
+
+                // Vertices of the crossed edges, shared by the loops of the cell
+                let mut edge_vertices: [Option<u32>; 12] = [None; 12];
+                let mut local: [Vec3; 12] = [Vec3::ZERO; 12];
+
+                for edge in 0..12 {
+                    let (a, b) = (
+                        CORNER_INDEX_A_FROM_EDGE[edge],
+                        CORNER_INDEX_B_FROM_EDGE[edge],
+                    );
+
+                    if below(a) == below(b) {
+                        continue;
+                    }
+
+                    let position =
+                        cpu::interpolate_vertices(corners[a], corners[b], iso_level, placement);
+                    let normal = (cpu::gradient(grid, corners[a].0)
+                        + cpu::gradient(grid, corners[b].0))
+                    .normalize_or_zero();
+
+                    local[edge] = position - cell.as_vec3();
+                    edge_vertices[edge] = Some(mesh.positions.len() as u32);
+
+                    mesh.positions
+                        .push((position - Vec3::splat(border as f32)).into());
+                    mesh.normals.push(normal.into());
+                }
+
+                // Densities relative to the iso level
+                let mut values = [0.0; 8];
+
+                for (value, corner) in values.iter_mut().zip(corners.iter()) {
+                    *value = corner.1 - iso_level;
+                }
+
+                let contour = Contour::trace(&values, &edge_vertices);
+
+                let mut triangles = Vec::new();
+
+                if contour.tunnel(&values) {
+                    tube(&contour.loops[0], &contour.loops[1], &local, &mut triangles);
+                } else {
+                    for edge_loop in contour.loops.iter() {
+                        for i in 1..edge_loop.len() - 1 {
+                            triangles.push([edge_loop[0], edge_loop[i], edge_loop[i + 1]]);
+                        }
+                    }
+                }
+
+                for [a, b, c] in triangles {
+                    let (position_a, position_b, position_c) = (local[a], local[b], local[c]);
+
+                    // The triangles face towards the higher density, like the marching cubes mesh
+                    let centroid = (position_a + position_b + position_c) / 3.0;
+                    let facing = (position_b - position_a)
+                        .cross(position_c - position_a)
+                        .dot(dual_contouring::gradient(&values, centroid));
+
+                    let vertex = |edge: usize| edge_vertices[edge].unwrap();
+
+                    if facing >= 0.0 {
+                        mesh.indices
+                            .extend_from_slice(&[vertex(a), vertex(b), vertex(c)]);
+                    } else {
+                        mesh.indices
+                            .extend_from_slice(&[vertex(a), vertex(c), vertex(b)]);
+                    }
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+/// Contour of a cell on its faces, as loops of crossed edges
+struct Contour {
+    loops: Vec<Vec<usize>>,
+    /// Component of every corner, corners are connected along the edges that don't cross the
+    /// surface and across the ambiguous faces the asymptotic decider joins them on
+    components: [usize; 8],
+}
+
+impl Contour {
+    fn trace(values: &[f32; 8], edge_vertices: &[Option<u32>; 12]) -> Self {
+        let below = |corner: usize| values[corner] < 0.0;
+
+        let mut links: [Vec<usize>; 12] = Default::default();
+        let mut components = [0, 1, 2, 3, 4, 5, 6, 7];
+
+        for edge in 0..12 {
+            if edge_vertices[edge].is_none() {
+                union(
+                    &mut components,
+                    CORNER_INDEX_A_FROM_EDGE[edge],
+                    CORNER_INDEX_B_FROM_EDGE[edge],
+                );
+            }
+        }
+
+        let mut link = |a: usize, b: usize| {
+            links[a].push(b);
+            links[b].push(a);
+        };
+
+        for (face_corners, face_edges) in FACES.iter() {
+            let crossed = (0..4)
+                .filter(|side| edge_vertices[face_edges[*side]].is_some())
+                .collect::<Vec<usize>>();
+
+            match crossed.as_slice() {
+                [] => {}
+                [a, b] => link(face_edges[*a], face_edges[*b]),
+                _ => {
+                    let [f0, f1, f2, f3] = [
+                        values[face_corners[0]],
+                        values[face_corners[1]],
+                        values[face_corners[2]],
+                        values[face_corners[3]],
+                    ];
+
+                    // Corners of the same sign alternate around an ambiguous face, so the
+                    // denominator never vanishes
+                    let saddle = (f0 * f2 - f1 * f3) / (f0 + f2 - f1 - f3);
+
+                    // The diagonal whose sign the bilinear interpolation has at its saddle
+                    // is connected through the face, the segments cut off the other corners
+                    if (saddle < 0.0) == below(face_corners[0]) {
+                        link(face_edges[0], face_edges[1]);
+                        link(face_edges[2], face_edges[3]);
+                        union(&mut components, face_corners[0], face_corners[2]);
+                    } else {
+                        link(face_edges[3], face_edges[0]);
+                        link(face_edges[1], face_edges[2]);
+                        union(&mut components, face_corners[1], face_corners[3]);
+                    }
+                }
+            }
+        }
+
+        let mut loops = Vec::new();
+        let mut visited = [false; 12];
+
+        for start in 0..12 {
+            if edge_vertices[start].is_none() || visited[start] {
+                continue;
+            }
+
+            let mut edge_loop = vec![start];
+            let (mut previous, mut current) = (start, links[start][0]);
+
+            visited[start] = true;
+
+            while current != start {
+                edge_loop.push(current);
+                visited[current] = true;
+
+                let next = if links[current][0] == previous {
+                    links[current][1]
+                } else {
+                    links[current][0]
+                };
+
+                previous = current;
+                current = next;
+            }
+
+            loops.push(edge_loop);
+        }
+
+        for corner in 0..8 {
+            components[corner] = find(&mut components, corner);
+        }
+
+        Self { loops, components }
+    }
+
+    /// Whether the two loops of the cell are joined by a tunnel, tested along the line between
+    /// the centers of the two corner groups they enclose
+    fn tunnel(&self, values: &[f32; 8]) -> bool {
+        if self.loops.len() != 2 {
+            return false;
+        }
+
+        // Two loops split the faces into two disks of one sign, each holding a group of corners,
+        // and a ring of the other
+        for sign in [true, false].iter() {
+            let mut groups: Vec<(usize, Vec3, u32)> = Vec::new();
+
+            for corner in (0..8).filter(|corner| (values[*corner] < 0.0) == *sign) {
+                let component = self.components[corner];
+                let offset = CORNER_OFFSETS[corner].as_vec3();
+
+                match groups.iter_mut().find(|group| group.0 == component) {
+                    Some(group) => {
+                        group.1 += offset;
+                        group.2 += 1;
+                    }
+                    None => groups.push((component, offset, 1)),
+                }
+            }
+
+            if groups.len() != 2 {
+                continue;
+            }
+
+            let start = groups[0].1 / groups[0].2 as f32;
+            let end = groups[1].1 / groups[1].2 as f32;
+
+            return (1..=TUNNEL_SAMPLES).all(|i| {
+                let t = i as f32 / (TUNNEL_SAMPLES + 1) as f32;
+
+                (trilinear(values, start.lerp(end, t)) < 0.0) == *sign
+            });
+        }
+
+        false
+    }
+}
+
+fn find(components: &mut [usize; 8], corner: usize) -> usize {
+    let mut root = corner;
+
+    while components[root] != root {
+        root = components[root];
+    }
+
+    components[corner] = root;
+
+    root
+}
+
+fn union(components: &mut [usize; 8], a: usize, b: usize) {
+    let (a, b) = (find(components, a), find(components, b));
+
+    components[a.max(b)] = a.min(b);
+}
+
+/// Triangulates a tube between two loops, walking both in the same direction around the axis
+/// between them and always taking the shorter diagonal
+fn tube(a: &[usize], b: &[usize], local: &[Vec3; 12], triangles: &mut Vec<[usize; 3]>) {
+    let center = |edge_loop: &[usize]| {
+        edge_loop
+            .iter()
+            .fold(Vec3::ZERO, |sum, edge| sum + local[*edge])
+            / edge_loop.len() as f32
+    };
+    let axis = center(b) - center(a);
+
+    let around_axis = |edge_loop: &[usize]| {
+        let center = center(edge_loop);
+        let area = (0..edge_loop.len()).fold(Vec3::ZERO, |area, i| {
+            let next = edge_loop[(i + 1) % edge_loop.len()];
+
+            area + (local[edge_loop[i]] - center).cross(local[next] - center)
+        });
+
+        let mut edge_loop = edge_loop.to_vec();
+
+        if area.dot(axis) < 0.0 {
+            edge_loop.reverse();
+        }
+
+        edge_loop
+    };
+
+    let a = around_axis(a);
+    let mut b = around_axis(b);
+
+    // Both walks start at the closest points
+    let closest = (0..b.len())
+        .min_by(|i, j| {
+            let distance = |k: &usize| local[b[*k]].distance_squared(local[a[0]]);
+
+            distance(i)
+                .partial_cmp(&distance(j))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0);
+
+    b.rotate_left(closest);
+
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        let (a_current, a_next) = (a[i % a.len()], a[(i + 1) % a.len()]);
+        let (b_current, b_next) = (b[j % b.len()], b[(j + 1) % b.len()]);
+
+        let advance_a = j == b.len()
+            || (i < a.len()
+                && local[a_next].distance_squared(local[b_current])
+                    <= local[a_current].distance_squared(local[b_next]));
+
+        if advance_a {
+            triangles.push([a_current, a_next, b_current]);
+            i += 1;
+        } else {
+            triangles.push([a_current, b_next, b_current]);
+            j += 1;
+        }
+    }
+}
+
+/// Trilinear interpolation of the corners at a point of the cell
+fn trilinear(values: &[f32; 8], point: Vec3) -> f32 {
+    let weight = |bit: u32, t: f32| if bit == 0 { 1.0 - t } else { t };
+
+    values
+        .iter()
+        .zip(CORNER_OFFSETS.iter())
+        .map(|(value, offset)| {
+            value
+                * weight(offset.x, point.x)
+                * weight(offset.y, point.y)
+                * weight(offset.z, point.z)
+        })
+        .sum()
+}
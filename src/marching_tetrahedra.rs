@@ -0,0 +1,137 @@
+use crate::{
+    cpu::{self, CORNER_OFFSETS},
+    density::DensityGrid,
+    mesh::MeshData,
+    terrain::VertexPlacement,
+};
+use bevy::math::{UVec3, Vec3};
+
+/// Tetrahedra every cell is split into, as indices into `CORNER_OFFSETS`
+///
+/// All of them share the diagonal from the first to the opposite corner, so the faces of
+/// neighbouring cells are split along the same diagonals and the surface stays closed.
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 5, 6],
+    [0, 1, 2, 6],
+    [0, 3, 2, 6],
+    [0, 3, 7, 6],
+    [0, 4, 7, 6],
+    [0, 4, 5, 6],
+];
+
+/// Polygonizes the cells inside a `border` of ghost samples by splitting every cell into six
+/// tetrahedra, which have no ambiguous configurations and so never leave the pinholes marching
+/// cubes can leave between cells
+///
+/// Produces about twice the triangles of marching cubes. Normals, positions and the border work
+/// like `cpu::march_chunk_smooth`.
+pub fn march_tetrahedra(
+    grid: &DensityGrid,
+    border: u32,
+    iso_level: f32,
+    placement: VertexPlacement,
+) -> MeshData {
+    assert!(
+        border >= 1,
+        "gradient normals need at least one ghost sample"
+    );
+
+    let dims = grid.dims();
+    let end = |len: u32| len.saturating_sub(border + 1);
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut push_triangle = |vertices: [(UVec3, UVec3, Vec3); 3], outward: Vec3| {
+        let [a, b, c] = vertices;
+
+        // The triangles face towards the higher density, like the marching cubes mesh
+        let facing = (b.2 - a.2).cross(c.2 - a.2).dot(outward);
+        let vertices = if facing >= 0.0 { [a, b, c] } else { [a, c, b] };
+
+        for (corner_a, corner_b, position) in vertices.iter() {
+            let normal = (cpu::gradient(grid, *corner_a) + cpu::gradient(grid, *corner_b))
+                .normalize_or_zero();
+
+            positions.push((*position - Vec3::splat(border as f32)).into());
+            normals.push(normal.into());
+        }
+    };
+
+    for z in border..end(dims.z) {
+        for y in border..end(dims.y) {
+            for x in border..end(dims.x) {
+                let cell = UVec3::new(x, y, z);
+
+                for tetrahedron in TETRAHEDRA.iter() {
+                    let mut corners = [(UVec3::ZERO, 0.0); 4];
+
+                    for (corner, index) in corners.iter_mut().zip(tetrahedron.iter()) {
+                        let point = cell + CORNER_OFFSETS[*index];
+
+                        *corner = (point, grid.get(point.x, point.y, point.z));
+                    }
+
+                    let (inside, outside): (Vec<_>, Vec<_>) =
+                        corners.iter().partition(|(_, value)| *value < iso_level);
+
+                    if inside.is_empty() || outside.is_empty() {
+                        continue;
+                    }
+
+                    let centroid = |points: &[&(UVec3, f32)]| {
+                        points
+                            .iter()
+                            .fold(Vec3::ZERO, |sum, (point, _)| sum + point.as_vec3())
+                            / points.len() as f32
+                    };
+                    let outward = centroid(outside.as_slice()) - centroid(inside.as_slice());
+
+                    let vertex = |a: &(UVec3, f32), b: &(UVec3, f32)| {
+                        (
+                            a.0,
+                            b.0,
+                            cpu::interpolate_vertices(*a, *b, iso_level, placement),
+                        )
+                    };
+
+                    match (inside.as_slice(), outside.as_slice()) {
+                        // One corner on its own side cuts a triangle off the tetrahedron
+                        ([single], others) | (others, [single]) => {
+                            push_triangle(
+                                [
+                                    vertex(*single, others[0]),
+                                    vertex(*single, others[1]),
+                                    vertex(*single, others[2]),
+                                ],
+                                outward,
+                            );
+                        }
+                        // Two corners on each side cut a quad, ordered around its edge loop
+                        ([a, b], [c, d]) => {
+                            let quad = [
+                                vertex(*a, *c),
+                                vertex(*a, *d),
+                                vertex(*b, *d),
+                                vertex(*b, *c),
+                            ];
+
+                            push_triangle([quad[0], quad[1], quad[2]], outward);
+                            push_triangle([quad[0], quad[2], quad[3]], outward);
+                        }
+                        _ => unreachable!("a tetrahedron has four corners"),
+                    }
+                }
+            }
+        }
+    }
+
+    let indices = (0..positions.len() as u32).collect();
+
+    MeshData {
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    }
+}
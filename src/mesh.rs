@@ -0,0 +1,406 @@
+use bevy::{
+    math::{Vec2, Vec3},
+    render2::{
+        mesh::{Indices, Mesh},
+        render_resource::PrimitiveTopology,
+    },
+};
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+/// Renderer independent mesh produced by the meshers
+#[derive(Debug, Default, Clone)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+    /// Per vertex colors laid out like `Mesh::ATTRIBUTE_COLOR`, either empty or one per vertex
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl MeshData {
+    /// Area, in cells, below which `remove_degenerate_triangles` drops triangles by default
+    pub const MIN_TRIANGLE_AREA: f32 = 1e-6;
+
+    /// Computes face normals for a triangle list where no vertex is shared between triangles,
+    /// degenerate triangles get zero normals
+    pub fn with_flat_normals(positions: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+        let mut normals: Vec<[f32; 3]> = vec![[0.0; 3]; positions.len()];
+
+        for triangle in indices.chunks(3) {
+            let a = Vec3::from(positions[triangle[0] as usize]);
+            let b = Vec3::from(positions[triangle[1] as usize]);
+            let c = Vec3::from(positions[triangle[2] as usize]);
+
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+
+            for index in triangle {
+                normals[*index as usize] = normal.into();
+            }
+        }
+
+        Self {
+            positions,
+            normals,
+            indices,
+            colors: Vec::new(),
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Drops the triangles with less than `min_area` or non-finite corners and the vertices no
+    /// triangle uses anymore, returning the number of dropped triangles
+    ///
+    /// Iso crossings on grid points collapse triangles into lines or points, which have no
+    /// normal and make physics engines fail to cook the mesh.
+    pub fn remove_degenerate_triangles(&mut self, min_area: f32) -> usize {
+        let triangle_count = self.triangle_count();
+        let indices = (0..triangle_count)
+            .filter(|triangle| !self.is_degenerate(*triangle, min_area))
+            .flat_map(|triangle| self.indices[triangle * 3..triangle * 3 + 3].iter().copied())
+            .collect::<Vec<u32>>();
+
+        let removed = triangle_count - indices.len() / 3;
+
+        if removed == 0 {
+            return 0;
+        }
+
+        let mut remap = vec![None; self.positions.len()];
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut colors = Vec::new();
+
+        self.indices = indices
+            .into_iter()
+            .map(|index| {
+                *remap[index as usize].get_or_insert_with(|| {
+                    positions.push(self.positions[index as usize]);
+                    normals.push(self.normals[index as usize]);
+                    if let Some(color) = self.colors.get(index as usize) {
+                        colors.push(*color);
+                    }
+
+                    (positions.len() - 1) as u32
+                })
+            })
+            .collect();
+        self.positions = positions;
+        self.normals = normals;
+        self.colors = colors;
+
+        removed
+    }
+
+    /// Whether the triangle at the given index has less than `min_area` or non-finite corners,
+    /// see `remove_degenerate_triangles`
+    pub fn is_degenerate(&self, triangle: usize, min_area: f32) -> bool {
+        let corner = |corner: usize| {
+            Vec3::from(self.positions[self.indices[triangle * 3 + corner] as usize])
+        };
+        let (a, b, c) = (corner(0), corner(1), corner(2));
+
+        let area = (b - a).cross(c - a).length() / 2.0;
+
+        !area.is_finite() || area < min_area
+    }
+
+    /// Scales and then translates every vertex
+    pub fn transform(&mut self, scale: f32, translation: Vec3) {
+        for position in self.positions.iter_mut() {
+            *position = (Vec3::from(*position) * scale + translation).into();
+        }
+    }
+
+    /// Hangs a strip of triangles below every edge lying on a face of the chunk, covering the
+    /// cracks between chunks meshed at different detail levels
+    ///
+    /// `chunk_size` is the number of cells along each edge of the chunk and `depth` how far, in
+    /// cells, the skirts reach into the surface.
+    pub fn add_skirts(&mut self, chunk_size: f32, depth: f32) {
+        const EPSILON: f32 = 1e-4;
+
+        const AXES: [Vec3; 3] = [Vec3::X, Vec3::Y, Vec3::Z];
+
+        // Outward normal of the chunk face both positions lie on
+        let face_normal = |a: [f32; 3], b: [f32; 3]| {
+            for axis in 0..3 {
+                if a[axis].abs() < EPSILON && b[axis].abs() < EPSILON {
+                    return Some(-AXES[axis]);
+                }
+
+                if (a[axis] - chunk_size).abs() < EPSILON && (b[axis] - chunk_size).abs() < EPSILON
+                {
+                    return Some(AXES[axis]);
+                }
+            }
+
+            None
+        };
+
+        let triangle_count = self.triangle_count();
+
+        for triangle in 0..triangle_count {
+            for edge in 0..3 {
+                let a = self.indices[triangle * 3 + edge] as usize;
+                let b = self.indices[triangle * 3 + (edge + 1) % 3] as usize;
+
+                let outward = match face_normal(self.positions[a], self.positions[b]) {
+                    Some(outward) => outward,
+                    None => continue,
+                };
+
+                let position_a = Vec3::from(self.positions[a]);
+                let position_b = Vec3::from(self.positions[b]);
+                let normal_a = self.normals[a];
+                let normal_b = self.normals[b];
+
+                let skirt_a = position_a - Vec3::from(normal_a) * depth;
+                let skirt_b = position_b - Vec3::from(normal_b) * depth;
+
+                let first = self.positions.len() as u32;
+
+                self.positions.extend_from_slice(&[
+                    position_a.into(),
+                    position_b.into(),
+                    skirt_a.into(),
+                    skirt_b.into(),
+                ]);
+                self.normals
+                    .extend_from_slice(&[normal_a, normal_b, normal_a, normal_b]);
+                if !self.colors.is_empty() {
+                    let (color_a, color_b) = (self.colors[a], self.colors[b]);
+
+                    self.colors
+                        .extend_from_slice(&[color_a, color_b, color_a, color_b]);
+                }
+
+                // Face the skirt out of the chunk, towards the neighbour it has to cover
+                if (position_b - position_a)
+                    .cross(skirt_a - position_a)
+                    .dot(outward)
+                    >= 0.0
+                {
+                    self.indices.extend_from_slice(&[
+                        first,
+                        first + 1,
+                        first + 2,
+                        first + 1,
+                        first + 3,
+                        first + 2,
+                    ]);
+                } else {
+                    self.indices.extend_from_slice(&[
+                        first,
+                        first + 2,
+                        first + 1,
+                        first + 1,
+                        first + 2,
+                        first + 3,
+                    ]);
+                }
+            }
+        }
+    }
+
+    /// Per vertex tangents for normal mapping, laid out like `Mesh::ATTRIBUTE_TANGENT` with the
+    /// handedness of the bitangent in `w`
+    ///
+    /// Sums the tangents of the triangles around every vertex from their texture coordinate
+    /// derivatives and orthogonalizes them against the vertex normal, like mikktspace does.
+    /// Vertices without a texture coordinate gradient, like the ones of the all zero UVs, get
+    /// the x axis projected onto their surface instead.
+    pub fn tangents(&self, uvs: &[[f32; 2]]) -> Vec<[f32; 4]> {
+        let mut tangents = vec![Vec3::ZERO; self.positions.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.positions.len()];
+
+        for triangle in self.indices.chunks(3) {
+            let position = |corner: usize| Vec3::from(self.positions[triangle[corner] as usize]);
+            let uv = |corner: usize| Vec2::from(uvs[triangle[corner] as usize]);
+
+            let (edge_a, edge_b) = (position(1) - position(0), position(2) - position(0));
+            let (uv_a, uv_b) = (uv(1) - uv(0), uv(2) - uv(0));
+
+            let determinant = uv_a.x * uv_b.y - uv_b.x * uv_a.y;
+
+            if determinant.abs() < f32::EPSILON {
+                continue;
+            }
+
+            let tangent = (edge_a * uv_b.y - edge_b * uv_a.y) / determinant;
+            let bitangent = (edge_b * uv_a.x - edge_a * uv_b.x) / determinant;
+
+            for index in triangle {
+                tangents[*index as usize] += tangent;
+                bitangents[*index as usize] += bitangent;
+            }
+        }
+
+        self.normals
+            .iter()
+            .zip(tangents.into_iter().zip(bitangents.into_iter()))
+            .map(|(normal, (tangent, bitangent))| {
+                let normal = Vec3::from(*normal);
+
+                let project = |axis: Vec3| axis - normal * normal.dot(axis);
+
+                let mut tangent = project(tangent);
+
+                if tangent.length_squared() < f32::EPSILON {
+                    tangent = project(Vec3::X);
+                }
+
+                // Surfaces facing along the x axis project it to nothing
+                if tangent.length_squared() < f32::EPSILON {
+                    tangent = project(Vec3::Z);
+                }
+
+                let tangent = tangent.normalize_or_zero();
+                let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                [tangent.x, tangent.y, tangent.z, handedness]
+            })
+            .collect()
+    }
+
+    /// Texture coordinates projected along the axis each normal points the most along, so
+    /// textured materials can be applied without a triplanar shader
+    ///
+    /// Positions are mapped to `position * scale + offset`. Triangles whose vertices pick
+    /// different axes get stretched, which mostly shows on smooth shaded meshes.
+    pub fn box_projected_uvs(&self, scale: f32, offset: Vec3) -> Vec<[f32; 2]> {
+        self.positions
+            .iter()
+            .zip(self.normals.iter())
+            .map(|(position, normal)| {
+                let position = Vec3::from(*position) * scale + offset;
+                let normal = Vec3::from(*normal).abs();
+
+                if normal.x >= normal.y && normal.x >= normal.z {
+                    [position.z, position.y]
+                } else if normal.y >= normal.z {
+                    [position.x, position.z]
+                } else {
+                    [position.x, position.y]
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the mesh as a Wavefront OBJ file
+    pub fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        for position in self.positions.iter() {
+            writeln!(writer, "v {} {} {}", position[0], position[1], position[2])?;
+        }
+
+        for normal in self.normals.iter() {
+            writeln!(writer, "vn {} {} {}", normal[0], normal[1], normal[2])?;
+        }
+
+        for triangle in self.indices.chunks(3) {
+            writeln!(
+                writer,
+                "f {0}//{0} {1}//{1} {2}//{2}",
+                triangle[0] + 1,
+                triangle[1] + 1,
+                triangle[2] + 1
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Edges of the triangles laid out for `PrimitiveTopology::LineList`, edges shared by
+    /// several triangles are listed once
+    ///
+    /// Only welded meshes share the vertices of their edges, the edges between the triangles of
+    /// an unindexed mesh come out twice on top of each other.
+    pub fn edge_indices(&self) -> Vec<u32> {
+        let mut edges = HashSet::new();
+        let mut lines = Vec::new();
+
+        for triangle in self.indices.chunks_exact(3) {
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+
+                if edges.insert((a.min(b), a.max(b))) {
+                    lines.extend_from_slice(&[a, b]);
+                }
+            }
+        }
+
+        lines
+    }
+
+    /// Builds a `PrimitiveTopology::LineList` mesh of the edges of the triangles, which shows
+    /// the topology of the meshers without a wireframe polygon mode of the renderer
+    pub fn into_wireframe_mesh(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+
+        let uvs = vec![[0.0, 0.0]; self.positions.len()];
+
+        mesh.set_indices(Some(Indices::U32(self.edge_indices())));
+
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+
+        mesh
+    }
+
+    /// Builds a `Mesh` with all zero texture coordinates
+    pub fn into_mesh(self) -> Mesh {
+        let uvs = vec![[0.0, 0.0]; self.positions.len()];
+
+        self.into_mesh_with_uvs(uvs)
+    }
+
+    /// Builds a `Mesh` with the given texture coordinates, one per vertex
+    pub fn into_mesh_with_uvs(self, uvs: Vec<[f32; 2]>) -> Mesh {
+        assert_eq!(
+            uvs.len(),
+            self.positions.len(),
+            "every vertex needs a texture coordinate"
+        );
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+        let tangents = self.tangents(&uvs);
+
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_attribute(Mesh::ATTRIBUTE_TANGENT, tangents);
+
+        if !self.colors.is_empty() {
+            mesh.set_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        }
+
+        mesh
+    }
+}
+
+impl From<MeshData> for Mesh {
+    fn from(mesh_data: MeshData) -> Self {
+        mesh_data.into_mesh()
+    }
+}
@@ -0,0 +1,287 @@
+use crate::mesh::MeshData;
+use bevy::math::Vec3;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// Checks meshes for holes, non-manifold edges and degenerate triangles before they are handed
+/// to physics engines or exported for 3D printing
+///
+/// Vertices closer than `epsilon` count as one, so the unindexed meshes of the meshers are
+/// checked like welded ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshValidator {
+    pub epsilon: f32,
+    /// Triangles with a smaller area are reported as degenerate
+    pub min_area: f32,
+    /// Size of the chunk the mesh was generated for, open edges lying on its faces continue in
+    /// the neighbouring chunks and are not reported as holes. `None` expects a closed mesh.
+    pub chunk_size: Option<f32>,
+}
+
+impl Default for MeshValidator {
+    fn default() -> Self {
+        Self {
+            epsilon: 1e-4,
+            min_area: MeshData::MIN_TRIANGLE_AREA,
+            chunk_size: None,
+        }
+    }
+}
+
+/// Problems found by `MeshValidator::validate`, edges are given as pairs of vertex indices and
+/// triangles as their index in the index buffer divided by 3
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshDiagnostics {
+    /// Indices pointing past the vertices, their triangles are not checked any further
+    pub invalid_indices: Vec<usize>,
+    /// Vertices with a NaN or infinite position or normal
+    pub non_finite_vertices: Vec<u32>,
+    /// Triangles with repeated vertices or an area below `MeshValidator::min_area`
+    pub degenerate_triangles: Vec<usize>,
+    /// Edges of a single triangle, the borders of holes
+    pub open_edges: Vec<(u32, u32)>,
+    /// Edges shared by more than two triangles
+    pub non_manifold_edges: Vec<(u32, u32)>,
+    /// Edges two triangles run along in the same direction, so one of them faces the wrong way
+    pub flipped_edges: Vec<(u32, u32)>,
+}
+
+impl MeshDiagnostics {
+    /// Whether every edge is shared by at most two triangles agreeing on their winding
+    pub fn is_manifold(&self) -> bool {
+        self.non_manifold_edges.is_empty() && self.flipped_edges.is_empty()
+    }
+
+    /// Whether the mesh is manifold and has no holes
+    pub fn is_watertight(&self) -> bool {
+        self.is_manifold() && self.open_edges.is_empty()
+    }
+
+    /// Whether no problem was found at all
+    pub fn is_valid(&self) -> bool {
+        self.is_watertight()
+            && self.invalid_indices.is_empty()
+            && self.non_finite_vertices.is_empty()
+            && self.degenerate_triangles.is_empty()
+    }
+}
+
+impl fmt::Display for MeshDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "valid mesh");
+        }
+
+        let problems = [
+            (self.invalid_indices.len(), "invalid indices"),
+            (self.non_finite_vertices.len(), "non-finite vertices"),
+            (self.degenerate_triangles.len(), "degenerate triangles"),
+            (self.open_edges.len(), "open edges"),
+            (self.non_manifold_edges.len(), "non-manifold edges"),
+            (self.flipped_edges.len(), "flipped edges"),
+        ];
+
+        let mut first = true;
+
+        for (count, problem) in problems.iter().filter(|(count, _)| *count > 0) {
+            if !first {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{} {}", count, problem)?;
+            first = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl MeshValidator {
+    pub fn validate(&self, mesh: &MeshData) -> MeshDiagnostics {
+        let mut diagnostics = MeshDiagnostics::default();
+
+        let vertex_count = mesh.positions.len();
+
+        for (vertex, position) in mesh.positions.iter().enumerate() {
+            let finite = |vector: &[f32; 3]| vector.iter().all(|value| value.is_finite());
+
+            if !finite(position)
+                || mesh
+                    .normals
+                    .get(vertex)
+                    .map_or(false, |normal| !finite(normal))
+            {
+                diagnostics.non_finite_vertices.push(vertex as u32);
+            }
+        }
+
+        // Every vertex is replaced by the first one at the same position
+        let mut first_at: HashMap<[i64; 3], u32> = HashMap::new();
+        let welded = mesh
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(vertex, position)| {
+                let key = [
+                    (position[0] / self.epsilon).round() as i64,
+                    (position[1] / self.epsilon).round() as i64,
+                    (position[2] / self.epsilon).round() as i64,
+                ];
+
+                *first_at.entry(key).or_insert(vertex as u32)
+            })
+            .collect::<Vec<u32>>();
+
+        // Number of triangles running along every undirected edge in each direction
+        let mut edges: HashMap<(u32, u32), (u32, u32)> = HashMap::new();
+
+        for (triangle, indices) in mesh.indices.chunks(3).enumerate() {
+            if indices.len() < 3 || indices.iter().any(|index| *index as usize >= vertex_count) {
+                diagnostics.invalid_indices.push(triangle);
+                continue;
+            }
+
+            let corners = [
+                welded[indices[0] as usize],
+                welded[indices[1] as usize],
+                welded[indices[2] as usize],
+            ];
+
+            let position = |corner: usize| Vec3::from(mesh.positions[corners[corner] as usize]);
+            let area = (position(1) - position(0))
+                .cross(position(2) - position(0))
+                .length()
+                / 2.0;
+
+            let repeated =
+                corners[0] == corners[1] || corners[1] == corners[2] || corners[2] == corners[0];
+
+            if repeated || area.is_nan() || area < self.min_area {
+                diagnostics.degenerate_triangles.push(triangle);
+
+                if repeated {
+                    continue;
+                }
+            }
+
+            for corner in 0..3 {
+                let (a, b) = (corners[corner], corners[(corner + 1) % 3]);
+                let uses = edges.entry((a.min(b), a.max(b))).or_default();
+
+                if a < b {
+                    uses.0 += 1;
+                } else {
+                    uses.1 += 1;
+                }
+            }
+        }
+
+        for (edge, (forward, backward)) in edges {
+            match forward + backward {
+                1 => {
+                    if !self.on_chunk_face(mesh, edge) {
+                        diagnostics.open_edges.push(edge);
+                    }
+                }
+                2 if forward == 1 => {}
+                2 => diagnostics.flipped_edges.push(edge),
+                _ => diagnostics.non_manifold_edges.push(edge),
+            }
+        }
+
+        // The hash map iterates in a random order
+        diagnostics.open_edges.sort_unstable();
+        diagnostics.non_manifold_edges.sort_unstable();
+        diagnostics.flipped_edges.sort_unstable();
+
+        diagnostics
+    }
+
+    /// Number of edges on the face shared by two neighbouring chunk meshes that only one of them
+    /// has, 0 when the seam between them is free of cracks
+    ///
+    /// `offset` is the position of the second chunk relative to the first one in cells, like
+    /// `(chunk_size, 0, 0)` for the neighbour on the positive x side. Only meshes of the same
+    /// detail level line up, the skirts hiding the cracks towards another level aren't matched.
+    pub fn seam_gaps(&self, first: &MeshData, second: &MeshData, offset: Vec3) -> usize {
+        let axis = match (0..3).find(|axis| offset[*axis] != 0.0) {
+            Some(axis) => axis,
+            None => return 0,
+        };
+
+        let face = if offset[axis] > 0.0 {
+            offset[axis]
+        } else {
+            0.0
+        };
+
+        let first_edges = self.face_edges(first, Vec3::ZERO, axis, face);
+        let second_edges = self.face_edges(second, offset, axis, face);
+
+        first_edges.symmetric_difference(&second_edges).count()
+    }
+
+    /// Undirected edges of the triangles of a mesh moved by `offset` with both ends on the plane
+    /// at `face` along `axis`, as pairs of positions rounded to `epsilon`
+    fn face_edges(
+        &self,
+        mesh: &MeshData,
+        offset: Vec3,
+        axis: usize,
+        face: f32,
+    ) -> HashSet<([i64; 3], [i64; 3])> {
+        let key = |vertex: u32| {
+            let position = Vec3::from(mesh.positions[vertex as usize]) + offset;
+
+            [
+                (position.x / self.epsilon).round() as i64,
+                (position.y / self.epsilon).round() as i64,
+                (position.z / self.epsilon).round() as i64,
+            ]
+        };
+        let on_face = |vertex: u32| {
+            (mesh.positions[vertex as usize][axis] + offset[axis] - face).abs() < self.epsilon
+        };
+
+        let mut edges = HashSet::new();
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+
+                if on_face(a) && on_face(b) {
+                    let (a, b) = (key(a), key(b));
+
+                    if a != b {
+                        edges.insert((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// Whether both ends of the edge lie on the same face of the chunk
+    fn on_chunk_face(&self, mesh: &MeshData, (a, b): (u32, u32)) -> bool {
+        let chunk_size = match self.chunk_size {
+            Some(chunk_size) => chunk_size,
+            None => return false,
+        };
+
+        let (a, b) = (mesh.positions[a as usize], mesh.positions[b as usize]);
+
+        (0..3).any(|axis| {
+            [0.0, chunk_size].iter().any(|face| {
+                (a[axis] - face).abs() < self.epsilon && (b[axis] - face).abs() < self.epsilon
+            })
+        })
+    }
+}
+
+/// Validates a closed mesh with the default thresholds
+pub fn validate(mesh: &MeshData) -> MeshDiagnostics {
+    MeshValidator::default().validate(mesh)
+}
@@ -0,0 +1,498 @@
+use crate::{
+    density::{DensityField, NoiseBasis, SeededBasis, SimplexDensity, SimplexNoise},
+    sdf::{self, CsgOp, Primitive},
+};
+use bevy::math::Vec3;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Seeds of the three axes of a warp modifier are spread this far apart, like the ones of
+/// `DomainWarp::seed`
+const WARP_AXIS_SEED_STEP: u32 = 7919;
+
+/// Words a modifier takes up in the storage buffer of the compute shader, see
+/// `to_storage_buffer`
+pub(crate) const MODIFIER_WORDS: usize = 12;
+
+/// Step of a [`ModifiedDensity`]
+///
+/// Distances are measured against an iso level of 0, the sharp and smooth set operations match
+/// the terrain exactly only at that level.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum DensityModifier {
+    /// Adds octaves of noise halving their amplitude and doubling their frequency, for detail on
+    /// top of a base shape
+    AddNoise {
+        frequency: f32,
+        amplitude: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        basis: NoiseBasis,
+        #[serde(default)]
+        seed_offset: u32,
+    },
+    /// Limits the density to the range from `min` to `max`, which flattens the field away from
+    /// the surface
+    Clamp { min: f32, max: f32 },
+    /// Steps the height the density is sampled at, which turns slopes into terraces
+    /// `step_height` apart
+    ///
+    /// `sharpness` of 0 leaves the slopes alone, the default of 1 rounds the steps off and
+    /// higher values flatten them into ledges with steep walls in between.
+    Terrace {
+        step_height: f32,
+        #[serde(default = "default_sharpness")]
+        sharpness: f32,
+    },
+    /// Pushes the terrain above `height` down into flat topped tablelands, `sharpness` being
+    /// how fast the density grows with the height above it, 0 leaves the terrain alone
+    Plateau {
+        height: f32,
+        #[serde(default = "default_sharpness")]
+        sharpness: f32,
+    },
+    /// Moves the position by a vector of noise samples up to `strength` long
+    Warp {
+        strength: f32,
+        frequency: f32,
+        #[serde(default)]
+        basis: NoiseBasis,
+        #[serde(default)]
+        seed_offset: u32,
+    },
+    /// Turns everything below `min` and above `max` into air, blended over `falloff` world units
+    HeightMask {
+        min: f32,
+        max: f32,
+        #[serde(default)]
+        falloff: f32,
+    },
+    /// Combines a primitive with the density, blended over `smoothness` world units
+    Csg {
+        op: CsgOp,
+        primitive: Primitive,
+        #[serde(default)]
+        smoothness: f32,
+    },
+}
+
+fn default_octaves() -> u32 {
+    1
+}
+
+fn default_sharpness() -> f32 {
+    1.0
+}
+
+impl DensityModifier {
+    /// Kind of the modifier in the storage buffer of the compute shader
+    fn kind(&self) -> u32 {
+        match self {
+            DensityModifier::AddNoise { .. } => 0,
+            DensityModifier::Clamp { .. } => 1,
+            DensityModifier::Terrace { .. } => 2,
+            DensityModifier::Warp { .. } => 3,
+            DensityModifier::HeightMask { .. } => 4,
+            DensityModifier::Csg { .. } => 5,
+            DensityModifier::Plateau { .. } => 6,
+        }
+    }
+}
+
+/// Density of a base field run through a stack of modifiers, evaluated the same way by the CPU
+/// mesher and the compute shader
+///
+/// Warps move the position first, in the order they appear in, wherever they are in the stack.
+/// The base field is sampled at the warped position and every other modifier is applied to its
+/// value in order, sampling at the warped position as well.
+///
+/// A base that is a modified density itself flattens into a single stack for the compute
+/// shader, its modifiers going first. The shader warps the position once before any other
+/// modifier, so a stack on top of a base with warps of its own is meshed on the CPU, like one
+/// on top of a base the shader can't express.
+pub struct ModifiedDensity {
+    base: Arc<dyn DensityField>,
+    modifiers: Vec<DensityModifier>,
+    seed: u32,
+    /// Noise of every `DensityModifier::AddNoise`, `None` for the other modifiers
+    noises: Vec<Option<SimplexDensity>>,
+    /// Noise of the three axes of every `DensityModifier::Warp`, `None` for the other modifiers
+    warps: Vec<Option<[SeededBasis; 3]>>,
+}
+
+impl ModifiedDensity {
+    /// Seeds the noise of the modifiers with `seed` plus their seed offsets
+    pub fn new(base: Arc<dyn DensityField>, modifiers: Vec<DensityModifier>, seed: u32) -> Self {
+        let noises = modifiers
+            .iter()
+            .map(|modifier| modifier_noise(modifier, seed))
+            .collect();
+        let warps = modifiers
+            .iter()
+            .map(|modifier| modifier_warp(modifier, seed))
+            .collect();
+
+        Self {
+            base,
+            modifiers,
+            seed,
+            noises,
+            warps,
+        }
+    }
+
+    /// Appends a modifier to the top of the stack
+    pub fn with_modifier(mut self, modifier: DensityModifier) -> Self {
+        self.noises.push(modifier_noise(&modifier, self.seed));
+        self.warps.push(modifier_warp(&modifier, self.seed));
+        self.modifiers.push(modifier);
+        self
+    }
+
+    pub fn base(&self) -> &Arc<dyn DensityField> {
+        &self.base
+    }
+
+    /// Position the base field and the modifiers are sampled at
+    fn warp(&self, p: Vec3) -> Vec3 {
+        let mut p = p;
+
+        for (modifier, bases) in self.modifiers.iter().zip(self.warps.iter()) {
+            if let (
+                DensityModifier::Warp {
+                    strength,
+                    frequency,
+                    ..
+                },
+                Some(bases),
+            ) = (*modifier, bases)
+            {
+                let q = p * frequency;
+
+                p += Vec3::new(bases[0].sample(q), bases[1].sample(q), bases[2].sample(q))
+                    * strength;
+            }
+        }
+
+        p
+    }
+
+    /// Largest distance the warps move a position
+    fn warp_reach(&self) -> f32 {
+        self.modifiers
+            .iter()
+            .map(|modifier| match *modifier {
+                DensityModifier::Warp { strength, .. } => strength.abs() * 3f32.sqrt(),
+                _ => 0.0,
+            })
+            .sum()
+    }
+}
+
+impl DensityField for ModifiedDensity {
+    fn sample(&self, p: Vec3) -> f32 {
+        let p = self.warp(p);
+        let mut value = self.base.sample(p);
+
+        for (modifier, noise) in self.modifiers.iter().zip(self.noises.iter()) {
+            value = match *modifier {
+                DensityModifier::AddNoise { .. } => {
+                    value + noise.as_ref().map_or(0.0, |noise| noise.sample(p))
+                }
+                DensityModifier::Clamp { min, max } => value.max(min).min(max),
+                DensityModifier::Terrace {
+                    step_height,
+                    sharpness,
+                } => value + terrace(p.y, step_height, sharpness) - p.y,
+                DensityModifier::Plateau { height, sharpness } => {
+                    value + (p.y - height).max(0.0) * sharpness.max(0.0)
+                }
+                DensityModifier::Warp { .. } => value,
+                DensityModifier::HeightMask { min, max, falloff } => {
+                    sdf::smooth_intersect(value, (min - p.y).max(p.y - max), falloff)
+                }
+                DensityModifier::Csg {
+                    op,
+                    primitive,
+                    smoothness,
+                } => op.apply(value, primitive.distance(p), smoothness),
+            };
+        }
+
+        value
+    }
+
+    /// Widens the bounds of the base field by how far every modifier can move them, the bounds
+    /// of the distance fields come from their distance to the center of the box
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        let reach = Vec3::splat(self.warp_reach());
+        let (min, max) = (min - reach, max + reach);
+        let center = (min + max) * 0.5;
+        let radius = (max - min).length() * 0.5;
+
+        // Distance fields change by at most the distance moved
+        let distance_bounds = |distance: f32| (distance - radius, distance + radius);
+
+        let mut bounds = self.base.bounds(min, max)?;
+
+        for (modifier, noise) in self.modifiers.iter().zip(self.noises.iter()) {
+            bounds = match *modifier {
+                DensityModifier::AddNoise { .. } => {
+                    let (low, high) = noise
+                        .as_ref()
+                        .and_then(|noise| noise.bounds(min, max))
+                        .unwrap_or((0.0, 0.0));
+
+                    (bounds.0 + low, bounds.1 + high)
+                }
+                DensityModifier::Clamp {
+                    min: low,
+                    max: high,
+                } => (bounds.0.max(low).min(high), bounds.1.max(low).min(high)),
+                // A terrace never moves the height by more than a step
+                DensityModifier::Terrace { step_height, .. } => {
+                    (bounds.0 - step_height.abs(), bounds.1 + step_height.abs())
+                }
+                // The added height only grows upwards
+                DensityModifier::Plateau { height, sharpness } => {
+                    let sharpness = sharpness.max(0.0);
+
+                    (
+                        bounds.0 + (min.y - height).max(0.0) * sharpness,
+                        bounds.1 + (max.y - height).max(0.0) * sharpness,
+                    )
+                }
+                DensityModifier::Warp { .. } => bounds,
+                DensityModifier::HeightMask {
+                    min: low,
+                    max: high,
+                    falloff,
+                } => {
+                    let mask = (
+                        (low - max.y).max(min.y - high),
+                        (low - min.y).max(max.y - high),
+                    );
+
+                    smooth_max_bounds(bounds, mask, falloff)
+                }
+                DensityModifier::Csg {
+                    op,
+                    primitive,
+                    smoothness,
+                } => {
+                    let (low, high) = distance_bounds(primitive.distance(center));
+
+                    match op {
+                        CsgOp::Union => {
+                            let (low, high) = smooth_max_bounds(
+                                (-bounds.1, -bounds.0),
+                                (-high, -low),
+                                smoothness,
+                            );
+
+                            (-high, -low)
+                        }
+                        CsgOp::Subtract => smooth_max_bounds(bounds, (-high, -low), smoothness),
+                        CsgOp::Intersect => smooth_max_bounds(bounds, (low, high), smoothness),
+                    }
+                }
+            };
+        }
+
+        Some(bounds)
+    }
+
+    fn with_seed(&self, seed: u32) -> Option<Arc<dyn DensityField>> {
+        let base = self
+            .base
+            .with_seed(seed)
+            .unwrap_or_else(|| self.base.clone());
+
+        Some(Arc::new(Self::new(base, self.modifiers.clone(), seed)))
+    }
+
+    /// `None` when the base warps its own position, the outer modifiers sample the position
+    /// before those warps
+    fn simplex_noise(&self) -> Option<(SimplexNoise, u32)> {
+        let base_warps = self
+            .base
+            .modifiers()
+            .iter()
+            .any(|(modifier, _)| matches!(modifier, DensityModifier::Warp { .. }));
+
+        if base_warps {
+            return None;
+        }
+
+        self.base.simplex_noise()
+    }
+
+    /// The modifiers of the base with their own seeds followed by the ones of this stack
+    fn modifiers(&self) -> Vec<(DensityModifier, u32)> {
+        let mut modifiers = self.base.modifiers();
+        modifiers.extend(self.modifiers.iter().map(|modifier| (*modifier, self.seed)));
+        modifiers
+    }
+}
+
+/// Steps `y` into terraces `step_height` apart, flat where the steps meet and steep in between,
+/// like `terrace` in `terrain/density.wgsl`
+///
+/// Within a step the height follows `t^p / (t^p + (1 - t)^p)` with `p = 1 + sharpness`, which
+/// is a straight slope at a sharpness of 0 and close to a sharp step at high ones.
+pub fn terrace(y: f32, step_height: f32, sharpness: f32) -> f32 {
+    let step_height = step_height.max(f32::EPSILON);
+    let steps = y / step_height;
+    let floor = steps.floor();
+    let t = steps - floor;
+
+    let power = 1.0 + sharpness.max(0.0);
+    let rise = t.powf(power);
+
+    (floor + rise / (rise + (1.0 - t).powf(power))) * step_height
+}
+
+/// Noise sampled by a `DensityModifier::AddNoise`, `None` for the other modifiers
+fn modifier_noise(modifier: &DensityModifier, seed: u32) -> Option<SimplexDensity> {
+    match *modifier {
+        DensityModifier::AddNoise {
+            frequency,
+            amplitude,
+            octaves,
+            basis,
+            seed_offset,
+        } => Some(SimplexDensity::with_noise(
+            seed.wrapping_add(seed_offset),
+            SimplexNoise {
+                frequency,
+                amplitude,
+                octaves,
+                basis,
+                ..Default::default()
+            },
+        )),
+        _ => None,
+    }
+}
+
+/// Seeds of the three axes of a `DensityModifier::Warp`, `None` for the other modifiers
+fn warp_seeds(modifier: &DensityModifier, seed: u32) -> Option<(NoiseBasis, [u32; 3])> {
+    match *modifier {
+        DensityModifier::Warp {
+            basis, seed_offset, ..
+        } => {
+            let seed = seed.wrapping_add(seed_offset);
+            let axis_seed = |axis: u32| seed.wrapping_add(WARP_AXIS_SEED_STEP.wrapping_mul(axis));
+
+            Some((basis, [axis_seed(0), axis_seed(1), axis_seed(2)]))
+        }
+        _ => None,
+    }
+}
+
+/// Noise of the three axes of a `DensityModifier::Warp`, `None` for the other modifiers
+fn modifier_warp(modifier: &DensityModifier, seed: u32) -> Option<[SeededBasis; 3]> {
+    warp_seeds(modifier, seed).map(|(basis, seeds)| seeds.map(|seed| SeededBasis::new(basis, seed)))
+}
+
+/// Seeds a modifier of a stack seeded with `seed` samples `NoiseBasis::Perlin` with, the
+/// compute shader needs the permutation table of each
+pub(crate) fn perlin_seeds(modifier: &DensityModifier, seed: u32) -> Vec<u32> {
+    match *modifier {
+        DensityModifier::AddNoise {
+            basis: NoiseBasis::Perlin,
+            seed_offset,
+            ..
+        } => vec![seed.wrapping_add(seed_offset)],
+        _ => match warp_seeds(modifier, seed) {
+            Some((NoiseBasis::Perlin, seeds)) => seeds.to_vec(),
+            _ => Vec::new(),
+        },
+    }
+}
+
+/// The smooth maximum lies between the sharp maximum and a quarter of `k` above it
+fn smooth_max_bounds(a: (f32, f32), b: (f32, f32), k: f32) -> (f32, f32) {
+    (a.0.max(b.0), a.1.max(b.1) + k.max(0.0) * 0.25)
+}
+
+/// Contents of the storage buffer the compute shader reads the modifiers from, every modifier
+/// with the seed of the stack it belongs to like `DensityField::modifiers`
+///
+/// Laid out like the `Modifier` struct of `terrain/density.wgsl`, every modifier is its kind, an
+/// operation or basis, its seed and an octave or primitive count followed by two `vec4<f32>` of
+/// parameters. Storage buffers can't be empty, so an empty stack still takes up one modifier the
+/// shader never reads.
+pub(crate) fn to_storage_buffer(modifiers: &[(DensityModifier, u32)]) -> Vec<u32> {
+    let mut words = Vec::with_capacity(modifiers.len().max(1) * MODIFIER_WORDS);
+
+    for (modifier, seed) in modifiers.iter() {
+        let (op, seed_offset, count, params) = match *modifier {
+            DensityModifier::AddNoise {
+                frequency,
+                amplitude,
+                octaves,
+                basis,
+                seed_offset,
+            } => (
+                basis as u32,
+                seed_offset,
+                octaves,
+                [frequency, amplitude, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            ),
+            DensityModifier::Clamp { min, max } => {
+                (0, 0, 0, [min, max, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+            }
+            DensityModifier::Terrace {
+                step_height,
+                sharpness,
+            } => (
+                0,
+                0,
+                0,
+                [step_height, sharpness, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            ),
+            DensityModifier::Plateau { height, sharpness } => {
+                (0, 0, 0, [height, sharpness, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+            }
+            DensityModifier::Warp {
+                strength,
+                frequency,
+                basis,
+                seed_offset,
+            } => (
+                basis as u32,
+                seed_offset,
+                0,
+                [strength, frequency, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            ),
+            DensityModifier::HeightMask { min, max, falloff } => {
+                (0, 0, 0, [min, max, falloff, 0.0, 0.0, 0.0, 0.0, 0.0])
+            }
+            DensityModifier::Csg {
+                op,
+                primitive,
+                smoothness,
+            } => {
+                let (kind, a, b) = primitive.to_gpu();
+
+                (
+                    op as u32,
+                    0,
+                    kind,
+                    [a[0], a[1], a[2], smoothness, b[0], b[1], b[2], b[3]],
+                )
+            }
+        };
+
+        words.extend_from_slice(&[modifier.kind(), op, seed.wrapping_add(seed_offset), count]);
+        words.extend(params.iter().map(|param| param.to_bits()));
+    }
+
+    if words.is_empty() {
+        words.resize(MODIFIER_WORDS, 0);
+    }
+
+    words
+}
@@ -0,0 +1,120 @@
+use crate::{density::DensityField, mesh::MeshData};
+use bevy::math::Vec3;
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+/// Ambient occlusion baked into the vertex colors of the chunk meshes by tracing rays through
+/// the density field, which gives caves and crevices depth without screen space occlusion
+///
+/// Every ray samples the density `steps` times, so baking costs `rays * steps` density samples
+/// per vertex.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AmbientOcclusion {
+    /// Rays traced over the hemisphere around every vertex normal
+    pub rays: u32,
+    /// Density samples along every ray
+    pub steps: u32,
+    /// Length of the rays in world units
+    pub distance: f32,
+    /// How dark fully occluded vertices get, from 0 to 1
+    pub strength: f32,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            rays: 12,
+            steps: 4,
+            distance: 4.0,
+            strength: 0.8,
+        }
+    }
+}
+
+impl AmbientOcclusion {
+    /// Occlusion of every vertex, from 0 under open sky to 1 where every ray hits solid ground
+    ///
+    /// Vertices are placed in the density field at `(position + origin) * scale`, like the
+    /// density samples of a chunk.
+    pub fn bake(
+        &self,
+        mesh: &MeshData,
+        density: &dyn DensityField,
+        iso_level: f32,
+        origin: Vec3,
+        scale: f32,
+    ) -> Vec<f32> {
+        let directions = hemisphere(self.rays);
+        let step = self.distance / self.steps.max(1) as f32;
+
+        mesh.positions
+            .iter()
+            .zip(mesh.normals.iter())
+            .map(|(position, normal)| {
+                let position = (Vec3::from(*position) + origin) * scale;
+                let normal = Vec3::from(*normal);
+
+                if directions.is_empty() || normal == Vec3::ZERO {
+                    return 0.0;
+                }
+
+                let (tangent, bitangent) = basis(normal);
+
+                let occluded = directions
+                    .iter()
+                    .filter(|direction| {
+                        let ray =
+                            tangent * direction.x + bitangent * direction.y + normal * direction.z;
+
+                        // The first sample is a step away from the vertex, which always lies on
+                        // the surface
+                        (1..=self.steps)
+                            .any(|i| density.sample(position + ray * (step * i as f32)) < iso_level)
+                    })
+                    .count();
+
+                occluded as f32 / directions.len() as f32
+            })
+            .collect()
+    }
+
+    /// Grey vertex colors darkened by the occlusion, laid out like `Mesh::ATTRIBUTE_COLOR`
+    pub fn colors(&self, occlusion: &[f32]) -> Vec<[f32; 4]> {
+        occlusion
+            .iter()
+            .map(|occlusion| {
+                let light = 1.0 - occlusion * self.strength.clamp(0.0, 1.0);
+
+                [light, light, light, 1.0]
+            })
+            .collect()
+    }
+}
+
+/// Directions spread evenly over the hemisphere around the z axis, along a Fibonacci spiral
+fn hemisphere(count: u32) -> Vec<Vec3> {
+    let golden_angle = PI * (3.0 - 5f32.sqrt());
+
+    (0..count)
+        .map(|i| {
+            let z = 1.0 - (i as f32 + 0.5) / count as f32;
+            let radius = (1.0 - z * z).sqrt();
+            let angle = golden_angle * i as f32;
+
+            Vec3::new(radius * angle.cos(), radius * angle.sin(), z)
+        })
+        .collect()
+}
+
+/// Two unit vectors perpendicular to the normal and each other
+fn basis(normal: Vec3) -> (Vec3, Vec3) {
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let tangent = normal.cross(axis).normalize();
+
+    (tangent, normal.cross(tangent))
+}
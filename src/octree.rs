@@ -0,0 +1,108 @@
+use crate::{chunk::ChunkCoord, density::DensityField, terrain::TerrainSettings};
+use bevy::math::{IVec3, Vec3};
+use std::collections::{HashMap, HashSet};
+
+/// Levels above the chunks, the root nodes span `2^OCTREE_DEPTH` chunks along each axis
+const OCTREE_DEPTH: u32 = 4;
+
+/// Density samples taken along each axis of a node to decide whether it holds surface
+const NODE_SAMPLES: u32 = 5;
+
+/// Sparse octree over the chunk grid of a terrain, remembering which nodes hold surface
+///
+/// Nodes are tested top down, so large regions of air or solid ground are rejected with a
+/// handful of density samples and none of their chunks is ever dispatched.
+#[derive(Debug, Default)]
+pub struct ChunkOctree {
+    nodes: HashMap<(u32, IVec3), bool>,
+}
+
+impl ChunkOctree {
+    /// Forgets every tested node, needed whenever the settings or the density change
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Number of nodes tested so far
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Removes the chunks whose octree nodes hold no surface
+    pub fn retain_surface_chunks(
+        &mut self,
+        coords: &mut HashSet<ChunkCoord>,
+        density: &dyn DensityField,
+        settings: &TerrainSettings,
+    ) {
+        coords.retain(|coord| self.has_surface(*coord, density, settings));
+    }
+
+    fn has_surface(
+        &mut self,
+        coord: ChunkCoord,
+        density: &dyn DensityField,
+        settings: &TerrainSettings,
+    ) -> bool {
+        for level in (0..=OCTREE_DEPTH).rev() {
+            let node_size = 1 << level;
+            let node = IVec3::new(
+                coord.0.x.div_euclid(node_size),
+                coord.0.y.div_euclid(node_size),
+                coord.0.z.div_euclid(node_size),
+            );
+
+            let has_surface = *self
+                .nodes
+                .entry((level, node))
+                .or_insert_with(|| node_has_surface(node, node_size, density, settings));
+
+            if !has_surface {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Samples a lattice over the node and checks whether it crosses the iso level within
+/// `surface_margin`, the margin accounts for surface between the samples
+fn node_has_surface(
+    node: IVec3,
+    node_size: i32,
+    density: &dyn DensityField,
+    settings: &TerrainSettings,
+) -> bool {
+    let origin = settings.get_chunk_origin(ChunkCoord(node * node_size));
+    let extent = (settings.chunk_size as i32 * node_size) as f32;
+    let step = extent / (NODE_SAMPLES - 1) as f32;
+
+    // Wider nodes leave more room between the samples
+    let margin = settings.surface_margin * node_size as f32;
+
+    let mut below = false;
+    let mut above = false;
+
+    for z in 0..NODE_SAMPLES {
+        for y in 0..NODE_SAMPLES {
+            for x in 0..NODE_SAMPLES {
+                let offset = Vec3::new(x as f32, y as f32, z as f32) * step;
+                let value = density.sample((origin + offset) * settings.voxel_scale);
+
+                below |= value < settings.iso_level + margin;
+                above |= value >= settings.iso_level - margin;
+
+                if below && above {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
@@ -0,0 +1,183 @@
+use crate::mesh::MeshData;
+
+/// Weight of the vertices of the last triangle, lower than the start of the cache so the next
+/// triangle doesn't just reuse the edge shared with it
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const CACHE_DECAY_POWER: f32 = 1.5;
+/// Boost of vertices with few triangles left, which keeps the order from leaving single
+/// triangles behind that have to be picked up later without any of their vertices cached
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Reorders the triangles of an indexed mesh for the post transform vertex cache of the GPU,
+/// then the vertices in the order the triangles first use them
+///
+/// Uses Tom Forsyth's linear speed vertex cache optimization: triangles are emitted greedily by
+/// the score of their vertices, which favours vertices high in a simulated LRU cache of
+/// `cache_size` entries and vertices with few triangles left. Reordering the vertices afterwards
+/// makes the vertex fetches mostly sequential and drops the vertices no triangle uses.
+///
+/// Only helps indexed meshes, the triangle soups of the meshers share no vertex and come out in
+/// the same order, so weld them first.
+pub fn optimize_vertex_cache(mesh: MeshData, cache_size: usize) -> MeshData {
+    let cache_size = cache_size.max(4);
+    let triangle_count = mesh.triangle_count();
+    let vertex_count = mesh.vertex_count();
+
+    let mut vertex_triangles = vec![Vec::new(); vertex_count];
+
+    for (triangle, indices) in mesh.indices.chunks_exact(3).enumerate() {
+        for index in indices {
+            vertex_triangles[*index as usize].push(triangle);
+        }
+    }
+
+    let mut remaining = vertex_triangles
+        .iter()
+        .map(|triangles| triangles.len())
+        .collect::<Vec<usize>>();
+    let mut vertex_scores = (0..vertex_count)
+        .map(|vertex| vertex_score(None, remaining[vertex], cache_size))
+        .collect::<Vec<f32>>();
+
+    let triangle_score = |triangle: usize, vertex_scores: &[f32]| {
+        mesh.indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|index| vertex_scores[*index as usize])
+            .sum::<f32>()
+    };
+
+    let mut triangle_scores = (0..triangle_count)
+        .map(|triangle| triangle_score(triangle, &vertex_scores))
+        .collect::<Vec<f32>>();
+    let mut emitted = vec![false; triangle_count];
+
+    // Vertices of the simulated cache, most recently used first
+    let mut cache: Vec<u32> = Vec::with_capacity(cache_size + 3);
+    let mut indices = Vec::with_capacity(mesh.indices.len());
+    let mut next_unemitted = 0;
+    let mut best = None;
+
+    for _ in 0..triangle_count {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                // Nothing in the cache has triangles left, start over from the best of the rest
+                while emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+
+                (next_unemitted..triangle_count)
+                    .filter(|triangle| !emitted[*triangle])
+                    .fold(next_unemitted, |best, triangle| {
+                        if triangle_scores[triangle] > triangle_scores[best] {
+                            triangle
+                        } else {
+                            best
+                        }
+                    })
+            }
+        };
+
+        emitted[triangle] = true;
+
+        let corners = [
+            mesh.indices[triangle * 3],
+            mesh.indices[triangle * 3 + 1],
+            mesh.indices[triangle * 3 + 2],
+        ];
+
+        indices.extend_from_slice(&corners);
+
+        for vertex in corners.iter() {
+            remaining[*vertex as usize] -= 1;
+
+            let triangles = &mut vertex_triangles[*vertex as usize];
+
+            if let Some(position) = triangles.iter().position(|other| *other == triangle) {
+                triangles.swap_remove(position);
+            }
+
+            if let Some(position) = cache.iter().position(|cached| cached == vertex) {
+                cache.remove(position);
+            }
+        }
+
+        for vertex in corners.iter().rev() {
+            cache.insert(0, *vertex);
+        }
+
+        let evicted = cache
+            .drain(cache_size.min(cache.len())..)
+            .collect::<Vec<u32>>();
+
+        for vertex in evicted.iter() {
+            vertex_scores[*vertex as usize] =
+                vertex_score(None, remaining[*vertex as usize], cache_size);
+        }
+
+        for (position, vertex) in cache.iter().enumerate() {
+            vertex_scores[*vertex as usize] =
+                vertex_score(Some(position), remaining[*vertex as usize], cache_size);
+        }
+
+        // Only the triangles around the cached and just evicted vertices changed their score
+        best = None;
+
+        for vertex in cache.iter().chain(evicted.iter()) {
+            for other in vertex_triangles[*vertex as usize].iter() {
+                triangle_scores[*other] = triangle_score(*other, &vertex_scores);
+
+                if best.map_or(true, |best| triangle_scores[*other] > triangle_scores[best]) {
+                    best = Some(*other);
+                }
+            }
+        }
+    }
+
+    reorder_vertices(&mesh, indices)
+}
+
+/// Score of a vertex at the given position of the cache with `remaining` triangles left to emit
+fn vertex_score(cache_position: Option<usize>, remaining: usize, cache_size: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (cache_size - 3) as f32;
+
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+    };
+
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Lays the vertices out in the order `indices` first uses them
+fn reorder_vertices(mesh: &MeshData, indices: Vec<u32>) -> MeshData {
+    let mut reordered = MeshData::default();
+    let mut remap = vec![None; mesh.vertex_count()];
+
+    let indices = indices
+        .into_iter()
+        .map(|index| {
+            *remap[index as usize].get_or_insert_with(|| {
+                reordered.positions.push(mesh.positions[index as usize]);
+                reordered.normals.push(mesh.normals[index as usize]);
+                if let Some(color) = mesh.colors.get(index as usize) {
+                    reordered.colors.push(*color);
+                }
+
+                (reordered.positions.len() - 1) as u32
+            })
+        })
+        .collect();
+
+    reordered.indices = indices;
+
+    reordered
+}
@@ -0,0 +1,108 @@
+use bevy::{
+    app::{App, CoreStage, EventWriter, Plugin},
+    ecs::{
+        query::Without,
+        schedule::ParallelSystemDescriptorCoercion,
+        system::{Query, Res, ResMut},
+    },
+    math::{DVec3, IVec3, Vec3},
+    transform::{
+        components::{Parent, Transform},
+        TransformSystem,
+    },
+};
+
+/// Marks the entity the world is kept centered around, usually the camera, it has to be a root
+/// entity
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FloatingOrigin;
+
+/// How far the [`FloatingOrigin`] may get from the origin before the world is shifted back
+///
+/// Shifts are whole multiples of `cell_size`, which should be a multiple of the world size of
+/// the chunks of every terrain so chunk translations stay exact after shifting.
+#[derive(Debug, Clone, Copy)]
+pub struct FloatingOriginSettings {
+    pub threshold: f32,
+    pub cell_size: f32,
+}
+
+impl Default for FloatingOriginSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1024.0,
+            cell_size: 32.0,
+        }
+    }
+}
+
+/// Number of cells the world has been shifted by in total
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WorldOffset {
+    pub cells: IVec3,
+}
+
+impl WorldOffset {
+    /// Position of `translation` relative to the original origin, in double precision as it can
+    /// be far away from it
+    pub fn to_absolute(&self, settings: &FloatingOriginSettings, translation: Vec3) -> DVec3 {
+        self.cells.as_dvec3() * settings.cell_size as f64 + translation.as_dvec3()
+    }
+}
+
+/// Sent after every root entity was moved by `translation` to bring the [`FloatingOrigin`] back
+/// close to the origin
+#[derive(Debug, Clone, Copy)]
+pub struct OriginShifted {
+    pub cells: IVec3,
+    pub translation: Vec3,
+}
+
+/// Re-centers the world around the [`FloatingOrigin`] entity so rendering keeps its precision
+/// far away from where the world started
+#[derive(Default)]
+pub struct FloatingOriginPlugin;
+
+impl Plugin for FloatingOriginPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FloatingOriginSettings>();
+        app.init_resource::<WorldOffset>();
+        app.add_event::<OriginShifted>();
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            recenter_world.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Moving the roots is enough, terrains carry their chunks along as children
+fn recenter_world(
+    settings: Res<FloatingOriginSettings>,
+    mut offset: ResMut<WorldOffset>,
+    mut root_query: Query<(&mut Transform, Option<&FloatingOrigin>), Without<Parent>>,
+    mut origin_shifted_events: EventWriter<OriginShifted>,
+) {
+    let origin = match root_query
+        .iter_mut()
+        .find_map(|(transform, origin)| origin.map(|_| transform.translation))
+    {
+        Some(origin) => origin,
+        None => return,
+    };
+
+    if origin.abs().max_element() <= settings.threshold {
+        return;
+    }
+
+    let cells = (origin / settings.cell_size).round();
+    let cells = IVec3::new(cells.x as i32, cells.y as i32, cells.z as i32);
+    let translation = -cells.as_vec3() * settings.cell_size;
+
+    for (mut transform, _) in root_query.iter_mut() {
+        transform.translation += translation;
+    }
+
+    offset.cells += cells;
+
+    origin_shifted_events.send(OriginShifted { cells, translation });
+}
@@ -0,0 +1,326 @@
+#[cfg(feature = "cpu-mesher")]
+use crate::terrain::ChunkCollisionMesh;
+use crate::{
+    cache::ChunkCache,
+    chunk::{ChunkBounds, ChunkCoord, ChunkMap},
+    cpu,
+    density::{DensityField, DensityGrid, TerrainDensity},
+    dirty::DensityChanged,
+    mesh::MeshData,
+    stats::ChunkMeshStats,
+    terrain::{
+        self, ChunkMeshTask, ChunkMeshed, ChunkWireframe, Terrain, TerrainChunk, TerrainSettings,
+    },
+    vertex_colors::MeshVertexColors,
+};
+use bevy::{
+    app::{EventReader, EventWriter},
+    asset::{Assets, Handle},
+    ecs::{
+        query::{With, Without},
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{UVec3, Vec3},
+    render2::mesh::Mesh,
+};
+use std::time::{Duration, Instant};
+
+/// Density samples and marching cubes mesh of a chunk, kept with
+/// `TerrainSettings::incremental_edits` so edits only polygonize the cells around them again
+///
+/// The mesh stays an unindexed triangle list with the cell of every triangle, patching drops the
+/// triangles of the cells reading changed samples and appends the triangles marched from the
+/// new ones. The `Mesh` asset of the chunk is modified in place, which the renderer uploads
+/// again, but the chunk skips its generation task and its other samples.
+pub struct ChunkPatch {
+    grid: DensityGrid,
+    mesh_data: MeshData,
+    cells: Vec<UVec3>,
+}
+
+impl ChunkPatch {
+    /// Polygonizes every cell of a grid returned by `terrain::sample_chunk_density`
+    pub(crate) fn new(
+        grid: DensityGrid,
+        density: &dyn DensityField,
+        settings: &TerrainSettings,
+        coord: ChunkCoord,
+        vertex_colors: &MeshVertexColors,
+    ) -> Self {
+        let border = TerrainSettings::GHOST;
+        let max = grid.dims() - UVec3::splat(border + 1);
+
+        let mut patch = Self {
+            grid,
+            mesh_data: MeshData::default(),
+            cells: Vec::new(),
+        };
+
+        patch.march(
+            UVec3::splat(border),
+            max,
+            density,
+            settings,
+            coord,
+            vertex_colors,
+        );
+
+        patch
+    }
+
+    /// Density samples of the chunk, laid out like `terrain::sample_chunk_density` returns them
+    pub fn grid(&self) -> &DensityGrid {
+        &self.grid
+    }
+
+    /// Mesh of the chunk before its UVs are projected
+    pub fn mesh_data(&self) -> &MeshData {
+        &self.mesh_data
+    }
+
+    /// Samples the density again inside the box from `min` to `max`, in the space the density
+    /// is sampled in, and polygonizes the cells reading the changed samples, returns whether the
+    /// mesh changed
+    pub(crate) fn patch(
+        &mut self,
+        density: &dyn DensityField,
+        settings: &TerrainSettings,
+        coord: ChunkCoord,
+        min: Vec3,
+        max: Vec3,
+        vertex_colors: &MeshVertexColors,
+    ) -> bool {
+        let border = TerrainSettings::GHOST;
+        let dims = self.grid.dims();
+        let origin = settings.get_chunk_origin(coord) - Vec3::splat(border as f32);
+
+        let first = (min / settings.voxel_scale - origin).ceil();
+        let last = (max / settings.voxel_scale - origin).floor();
+
+        let outside = |first: f32, last: f32, len: u32| last < 0.0 || first > (len - 1) as f32;
+
+        if outside(first.x, last.x, dims.x)
+            || outside(first.y, last.y, dims.y)
+            || outside(first.z, last.z, dims.z)
+        {
+            return false;
+        }
+
+        let clamp = |value: f32, len: u32| value.max(0.0).min((len - 1) as f32) as u32;
+
+        let first = UVec3::new(
+            clamp(first.x, dims.x),
+            clamp(first.y, dims.y),
+            clamp(first.z, dims.z),
+        );
+        let last = UVec3::new(
+            clamp(last.x, dims.x),
+            clamp(last.y, dims.y),
+            clamp(last.z, dims.z),
+        );
+
+        for z in first.z..=last.z {
+            for y in first.y..=last.y {
+                for x in first.x..=last.x {
+                    let point = UVec3::new(x, y, z);
+
+                    self.grid.set(
+                        x,
+                        y,
+                        z,
+                        density.sample((origin + point.as_vec3()) * settings.voxel_scale),
+                    );
+                }
+            }
+        }
+
+        // Cells read their corners and the neighbours of their corners for the gradient normals
+        let cell_min = |first: u32| first.saturating_sub(2).max(border);
+        let cell_max = |last: u32, len: u32| (last + 2).min(len - border - 1);
+
+        let cell_min = UVec3::new(cell_min(first.x), cell_min(first.y), cell_min(first.z));
+        let cell_max = UVec3::new(
+            cell_max(last.x, dims.x),
+            cell_max(last.y, dims.y),
+            cell_max(last.z, dims.z),
+        );
+
+        if cell_min.x >= cell_max.x || cell_min.y >= cell_max.y || cell_min.z >= cell_max.z {
+            return false;
+        }
+
+        let inside = |cell: UVec3| {
+            cell.x >= cell_min.x
+                && cell.x < cell_max.x
+                && cell.y >= cell_min.y
+                && cell.y < cell_max.y
+                && cell.z >= cell_min.z
+                && cell.z < cell_max.z
+        };
+
+        let mesh_data = std::mem::take(&mut self.mesh_data);
+        let cells = std::mem::take(&mut self.cells);
+
+        self.append(&mesh_data, &cells, |triangle| !inside(cells[triangle]));
+        self.march(cell_min, cell_max, density, settings, coord, vertex_colors);
+
+        true
+    }
+
+    /// Marches the cells from `min` up to but excluding `max` and appends their triangles
+    fn march(
+        &mut self,
+        min: UVec3,
+        max: UVec3,
+        density: &dyn DensityField,
+        settings: &TerrainSettings,
+        coord: ChunkCoord,
+        vertex_colors: &MeshVertexColors,
+    ) {
+        let (mut mesh_data, cells) = cpu::march_cells_smooth(
+            &self.grid,
+            TerrainSettings::GHOST,
+            min,
+            max,
+            settings.iso_level,
+            settings.vertex_placement,
+        );
+
+        terrain::color_chunk_mesh(&mut mesh_data, density, settings, coord, vertex_colors);
+
+        self.append(&mesh_data, &cells, |triangle| {
+            !mesh_data.is_degenerate(triangle, MeshData::MIN_TRIANGLE_AREA)
+        });
+    }
+
+    /// Appends the triangles of an unindexed mesh for which `keep` returns true
+    fn append(&mut self, mesh_data: &MeshData, cells: &[UVec3], keep: impl Fn(usize) -> bool) {
+        for (triangle, cell) in cells.iter().enumerate() {
+            if !keep(triangle) {
+                continue;
+            }
+
+            for vertex in triangle * 3..triangle * 3 + 3 {
+                self.mesh_data
+                    .indices
+                    .push(self.mesh_data.positions.len() as u32);
+                self.mesh_data.positions.push(mesh_data.positions[vertex]);
+                self.mesh_data.normals.push(mesh_data.normals[vertex]);
+
+                if let Some(color) = mesh_data.colors.get(vertex) {
+                    self.mesh_data.colors.push(*color);
+                }
+            }
+
+            self.cells.push(*cell);
+        }
+    }
+}
+
+/// Patches the chunks keeping a `ChunkPatch` whose density changed, `remesh_dirty_chunks` skips
+/// them
+///
+/// Chunks still generating are remeshed instead, their task sampled the density before the
+/// change.
+pub(crate) fn patch_edited_chunks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    vertex_colors: Res<MeshVertexColors>,
+    cache: Option<Res<ChunkCache>>,
+    terrain_query: Query<(&TerrainSettings, &TerrainDensity, &ChunkMap), With<Terrain>>,
+    mut chunk_query: Query<
+        (
+            &TerrainChunk,
+            &mut ChunkPatch,
+            &Handle<Mesh>,
+            Option<&ChunkWireframe>,
+        ),
+        Without<ChunkMeshTask>,
+    >,
+    mut density_changed_events: EventReader<DensityChanged>,
+    mut chunk_meshed_events: EventWriter<ChunkMeshed>,
+) {
+    for event in density_changed_events.iter() {
+        let (terrain_settings, density, chunk_map) = match terrain_query.get(event.terrain) {
+            Ok(terrain) => terrain,
+            Err(_) => continue,
+        };
+
+        for coord in terrain_settings.chunks_overlapping(event.min, event.max) {
+            let entity = match chunk_map.get(coord) {
+                Some(entity) => entity,
+                None => continue,
+            };
+
+            let (chunk, mut patch, handle, wireframe) = match chunk_query.get_mut(entity) {
+                Ok(chunk) => chunk,
+                Err(_) => continue,
+            };
+
+            let settings = terrain_settings.for_lod(chunk.lod());
+            let started = Instant::now();
+
+            if !patch.patch(
+                &*density.0,
+                &settings,
+                coord,
+                event.min,
+                event.max,
+                &vertex_colors,
+            ) {
+                continue;
+            }
+
+            // The cached data of every detail level predates the change
+            if let Some(cache) = &cache {
+                for lod in 0..=terrain_settings.max_lod() {
+                    cache.remove(&terrain_settings.for_lod(lod), coord);
+                }
+            }
+
+            let (mesh, bounds) =
+                terrain::build_chunk_mesh(patch.mesh_data().clone(), &settings, coord);
+
+            commands.entity(entity).insert(ChunkMeshStats::from_mesh(
+                &mesh,
+                started.elapsed(),
+                Duration::ZERO,
+            ));
+
+            // Keeps the handle, and with it the material and the entities pointing at the mesh
+            if let Some(chunk_mesh) = meshes.get_mut(handle) {
+                *chunk_mesh = mesh;
+            }
+
+            let wireframe_mesh =
+                wireframe.zip(terrain::wireframe_mesh(patch.mesh_data(), &settings));
+
+            if let Some((wireframe, wireframe_mesh)) = wireframe_mesh {
+                if let Some(mesh) = meshes.get_mut(&wireframe.mesh) {
+                    *mesh = wireframe_mesh;
+                }
+            }
+
+            match bounds {
+                Some(bounds) => commands.entity(entity).insert(bounds),
+                None => commands.entity(entity).remove::<ChunkBounds>(),
+            };
+
+            #[cfg(feature = "cpu-mesher")]
+            {
+                if let Some(cell_size) = settings.collision_cell_size {
+                    commands.entity(entity).insert(ChunkCollisionMesh(
+                        terrain::mesh_chunk_density_at(patch.grid(), &settings, cell_size),
+                    ));
+                }
+            }
+
+            chunk_meshed_events.send(ChunkMeshed {
+                terrain: event.terrain,
+                entity,
+                coord,
+                mesh: Some(handle.clone()),
+            });
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{decimate, mesh::MeshData, optimize};
+use bevy::math::Vec3;
+use std::{collections::HashMap, sync::Arc};
+
+/// Transform applied to every chunk mesh between polygonization and its insertion into
+/// `Assets<Mesh>`, runs on the async compute task pool
+pub trait MeshPostProcessor: Send + Sync {
+    fn process(&self, mesh: MeshData) -> MeshData;
+}
+
+impl<F> MeshPostProcessor for F
+where
+    F: Fn(MeshData) -> MeshData + Send + Sync,
+{
+    fn process(&self, mesh: MeshData) -> MeshData {
+        self(mesh)
+    }
+}
+
+/// Post processors run on every chunk mesh, in insertion order
+#[derive(Clone, Default)]
+pub struct MeshPostProcessors(Vec<Arc<dyn MeshPostProcessor>>);
+
+impl MeshPostProcessors {
+    pub fn push(&mut self, post_processor: impl MeshPostProcessor + 'static) {
+        self.0.push(Arc::new(post_processor));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn process(&self, mesh: MeshData) -> MeshData {
+        self.0
+            .iter()
+            .fold(mesh, |mesh, post_processor| post_processor.process(mesh))
+    }
+}
+
+/// Merges vertices closer than `epsilon` into an indexed mesh and drops the triangles that
+/// collapse
+///
+/// The meshers emit three vertices per triangle, welding shares them between the triangles
+/// around each vertex, which shrinks most meshes about six times. Merged vertices get the
+/// average of their normals, so flat shaded meshes come out smooth shaded.
+pub struct WeldVertices {
+    pub epsilon: f32,
+}
+
+impl Default for WeldVertices {
+    fn default() -> Self {
+        Self { epsilon: 1e-4 }
+    }
+}
+
+impl MeshPostProcessor for WeldVertices {
+    fn process(&self, mesh: MeshData) -> MeshData {
+        let mut positions = Vec::new();
+        let mut normals: Vec<Vec3> = Vec::new();
+        let mut colors = Vec::new();
+        let mut vertices: HashMap<[i32; 3], u32> = HashMap::new();
+
+        // Merged vertices keep the color of the first one
+        let remap = mesh
+            .positions
+            .iter()
+            .zip(mesh.normals.iter())
+            .enumerate()
+            .map(|(vertex, (position, normal))| {
+                let key = [
+                    (position[0] / self.epsilon).round() as i32,
+                    (position[1] / self.epsilon).round() as i32,
+                    (position[2] / self.epsilon).round() as i32,
+                ];
+
+                let index = *vertices.entry(key).or_insert_with(|| {
+                    positions.push(*position);
+                    normals.push(Vec3::ZERO);
+                    if let Some(color) = mesh.colors.get(vertex) {
+                        colors.push(*color);
+                    }
+
+                    (positions.len() - 1) as u32
+                });
+
+                normals[index as usize] += Vec3::from(*normal);
+
+                index
+            })
+            .collect::<Vec<u32>>();
+
+        let mut indices = Vec::with_capacity(mesh.indices.len());
+
+        for triangle in mesh.indices.chunks(3) {
+            let a = remap[triangle[0] as usize];
+            let b = remap[triangle[1] as usize];
+            let c = remap[triangle[2] as usize];
+
+            if a != b && b != c && c != a {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+
+        MeshData {
+            positions,
+            normals: normals.into_iter().map(normalize_or_up).collect(),
+            indices,
+            colors,
+        }
+    }
+}
+
+/// Reduces the triangles to `ratio` of their count with quadric error decimation, see
+/// `decimate::decimate`
+pub struct Decimate {
+    pub ratio: f32,
+}
+
+impl Default for Decimate {
+    fn default() -> Self {
+        Self { ratio: 0.5 }
+    }
+}
+
+impl MeshPostProcessor for Decimate {
+    fn process(&self, mesh: MeshData) -> MeshData {
+        decimate::decimate(mesh, self.ratio)
+    }
+}
+
+/// Reorders the triangles and vertices for the vertex cache of the GPU, see
+/// `optimize::optimize_vertex_cache`, push it after `WeldVertices` and everything else changing
+/// the triangles
+///
+/// Terrain meshes are drawn in large numbers, fewer vertex shader runs mostly pay off on mobile
+/// and older GPUs with small caches.
+pub struct OptimizeVertexCache {
+    /// Entries of the simulated cache, 32 suits most GPUs
+    pub cache_size: usize,
+}
+
+impl Default for OptimizeVertexCache {
+    fn default() -> Self {
+        Self { cache_size: 32 }
+    }
+}
+
+impl MeshPostProcessor for OptimizeVertexCache {
+    fn process(&self, mesh: MeshData) -> MeshData {
+        optimize::optimize_vertex_cache(mesh, self.cache_size)
+    }
+}
+
+/// Replaces the normals with the area weighted average of the faces sharing each vertex, only
+/// has a visible effect on welded meshes
+///
+/// Faces meeting at more than `crease_angle`, in radians, keep a hard edge between them, their
+/// shared vertices are split so cliffs stay crisp while rolling hills shade smoothly. The
+/// default smooths every edge.
+pub struct SmoothNormals {
+    pub crease_angle: f32,
+}
+
+impl Default for SmoothNormals {
+    fn default() -> Self {
+        Self {
+            crease_angle: std::f32::consts::PI,
+        }
+    }
+}
+
+impl SmoothNormals {
+    pub fn with_crease_angle(crease_angle: f32) -> Self {
+        Self { crease_angle }
+    }
+}
+
+impl MeshPostProcessor for SmoothNormals {
+    fn process(&self, mut mesh: MeshData) -> MeshData {
+        // The cross product is proportional to the area of the triangle
+        let face_normals = mesh
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let a = Vec3::from(mesh.positions[triangle[0] as usize]);
+                let b = Vec3::from(mesh.positions[triangle[1] as usize]);
+                let c = Vec3::from(mesh.positions[triangle[2] as usize]);
+
+                (b - a).cross(c - a)
+            })
+            .collect::<Vec<Vec3>>();
+
+        if self.crease_angle >= std::f32::consts::PI {
+            let mut normals = vec![Vec3::ZERO; mesh.positions.len()];
+
+            for (triangle, normal) in mesh.indices.chunks(3).zip(face_normals.iter()) {
+                for index in triangle {
+                    normals[*index as usize] += *normal;
+                }
+            }
+
+            mesh.normals = normals.into_iter().map(normalize_or_up).collect();
+
+            return mesh;
+        }
+
+        let min_cos = self.crease_angle.cos();
+        let unit_normals = face_normals
+            .iter()
+            .map(|normal| normal.normalize_or_zero())
+            .collect::<Vec<Vec3>>();
+
+        let mut vertex_faces = vec![Vec::new(); mesh.positions.len()];
+
+        for (face, triangle) in mesh.indices.chunks(3).enumerate() {
+            for index in triangle {
+                vertex_faces[*index as usize].push(face);
+            }
+        }
+
+        // Every corner averages the faces around its vertex within the crease angle of its own
+        // face, corners of a vertex ending up with different normals get their own vertex
+        let mut smoothed = MeshData::default();
+        let mut split: Vec<Vec<(Vec3, u32)>> = vec![Vec::new(); mesh.positions.len()];
+
+        for (face, triangle) in mesh.indices.chunks(3).enumerate() {
+            for index in triangle {
+                let normal = normalize_or_up(
+                    vertex_faces[*index as usize]
+                        .iter()
+                        .filter(|other| unit_normals[face].dot(unit_normals[**other]) >= min_cos)
+                        .fold(Vec3::ZERO, |sum, other| sum + face_normals[*other]),
+                );
+
+                let vertices = &mut split[*index as usize];
+
+                let vertex = match vertices
+                    .iter()
+                    .find(|(vertex_normal, _)| vertex_normal.dot(Vec3::from(normal)) > 0.9999)
+                {
+                    Some((_, vertex)) => *vertex,
+                    None => {
+                        let vertex = smoothed.positions.len() as u32;
+
+                        smoothed.positions.push(mesh.positions[*index as usize]);
+                        smoothed.normals.push(normal);
+                        if let Some(color) = mesh.colors.get(*index as usize) {
+                            smoothed.colors.push(*color);
+                        }
+                        vertices.push((Vec3::from(normal), vertex));
+
+                        vertex
+                    }
+                };
+
+                smoothed.indices.push(vertex);
+            }
+        }
+
+        smoothed
+    }
+}
+
+/// Normalizes a summed normal, normals that cancelled out point up
+fn normalize_or_up(normal: Vec3) -> [f32; 3] {
+    let length = normal.length();
+
+    if length > 0.0 {
+        (normal / length).into()
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{chunk::ChunkMap, terrain::ChunkMeshTask, ChunkMeshed, Terrain};
+use bevy::{
+    app::EventReader,
+    core::Time,
+    ecs::{
+        query::With,
+        system::{Query, Res, ResMut},
+    },
+};
+use std::time::Duration;
+
+/// How much of the previous rate is kept every second
+const RATE_SMOOTHING: f32 = 0.25;
+
+/// Progress of the chunk generation over every terrain, for showing loading bars while the world
+/// streams in
+#[derive(Debug, Default, Clone)]
+pub struct GenerationProgress {
+    /// Chunks waiting to be scheduled for generation, held back by the `GenerationBudget`
+    pub queued: usize,
+    /// Chunks being generated
+    pub meshing: usize,
+    /// Chunks that finished generating
+    pub done: usize,
+    /// Chunks finishing per second, smoothed over the last frames
+    pub chunks_per_second: f32,
+}
+
+impl GenerationProgress {
+    pub fn total(&self) -> usize {
+        self.queued + self.meshing + self.done
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.queued + self.meshing
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Finished fraction of the chunks in `0..=1`, `1` when there is nothing to generate
+    pub fn fraction(&self) -> f32 {
+        match self.total() {
+            0 => 1.0,
+            total => self.done as f32 / total as f32,
+        }
+    }
+
+    /// Estimated time until every chunk is generated, `None` before any chunk finished
+    pub fn eta(&self) -> Option<Duration> {
+        if self.is_done() {
+            return Some(Duration::ZERO);
+        }
+
+        if self.chunks_per_second <= 0.0 {
+            return None;
+        }
+
+        Some(Duration::from_secs_f32(
+            self.remaining() as f32 / self.chunks_per_second,
+        ))
+    }
+}
+
+pub(crate) fn update_generation_progress(
+    time: Res<Time>,
+    mut progress: ResMut<GenerationProgress>,
+    terrain_query: Query<&ChunkMap, With<Terrain>>,
+    chunk_task_query: Query<(), With<ChunkMeshTask>>,
+    mut chunk_meshed_events: EventReader<ChunkMeshed>,
+) {
+    let total: usize = terrain_query.iter().map(|chunk_map| chunk_map.len()).sum();
+    let meshing = chunk_task_query.iter().count();
+
+    progress.meshing = meshing;
+    progress.done = total.saturating_sub(meshing);
+
+    let delta = time.delta_seconds();
+
+    if delta > 0.0 {
+        let rate = chunk_meshed_events.iter().count() as f32 / delta;
+        let keep = RATE_SMOOTHING.powf(delta);
+
+        progress.chunks_per_second = progress.chunks_per_second * keep + rate * (1.0 - keep);
+    }
+}
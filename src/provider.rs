@@ -0,0 +1,32 @@
+use crate::{chunk::ChunkCoord, density::DensityGrid};
+use std::sync::Arc;
+
+/// Data of a chunk exchanged with a [`ChunkProvider`]
+#[derive(Debug, Clone)]
+pub struct ChunkData {
+    /// Density samples of the chunk, laid out like `sample_chunk_density` returns them
+    pub density: DensityGrid,
+}
+
+/// Backs a terrain with the persistence of a game, like a database or a server, instead of
+/// generating every chunk from its density field
+///
+/// Only full detail chunks meshed on the CPU go through the provider, chunks further away are
+/// generated from the density field of the terrain.
+pub trait ChunkProvider: Send + Sync {
+    /// Returns the stored data of a chunk, `None` generates it from the density field
+    fn load(&self, coord: ChunkCoord) -> Option<ChunkData>;
+
+    /// Called with the data of every chunk that was generated instead of loaded
+    fn save(&self, coord: ChunkCoord, data: &ChunkData);
+}
+
+/// Chunk provider of a terrain, insert it next to the `TerrainBundle`
+#[derive(Clone)]
+pub struct TerrainChunkProvider(pub Arc<dyn ChunkProvider>);
+
+impl TerrainChunkProvider {
+    pub fn new(provider: impl ChunkProvider + 'static) -> Self {
+        Self(Arc::new(provider))
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{chunk::ChunkCoord, terrain::GenerationOrder};
+use bevy::math::{Mat4, Vec3, Vec4};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// Distance from the center of a chunk to its corners, in chunks
+const CHUNK_RADIUS: f32 = 0.8660254;
+
+/// Position, view direction and frustum of a camera, in chunks relative to a terrain
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkViewer {
+    pub position: Vec3,
+    pub forward: Vec3,
+    pub frustum: ChunkFrustum,
+}
+
+/// Side planes of a camera frustum, the near and far planes are left out as chunks are already
+/// limited by distance and they depend on the depth convention of the projection
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkFrustum {
+    planes: [Vec4; 4],
+}
+
+impl ChunkFrustum {
+    /// Extracts the planes from a matrix taking chunk coordinates to clip space
+    pub fn from_matrix(chunk_to_clip: Mat4) -> Self {
+        let rows = chunk_to_clip.transpose();
+        let (x, y, w) = (rows.x_axis, rows.y_axis, rows.w_axis);
+
+        let normalize = |plane: Vec4| plane / plane.truncate().length().max(f32::EPSILON);
+
+        Self {
+            planes: [
+                normalize(w + x),
+                normalize(w - x),
+                normalize(w + y),
+                normalize(w - y),
+            ],
+        }
+    }
+
+    /// Whether any part of the chunk at `coord`, grown by `margin` chunks, is inside
+    pub fn contains_chunk(&self, coord: ChunkCoord, margin: f32) -> bool {
+        let center = coord.0.as_vec3().extend(1.0);
+
+        self.planes
+            .iter()
+            .all(|plane| plane.dot(center) >= -(CHUNK_RADIUS + margin))
+    }
+}
+
+/// Generation priority of a chunk, lower is generated first
+///
+/// The squared distance to the closest camera, with chunks behind a camera counting as up to
+/// twice as far as chunks in front of it.
+pub(crate) fn chunk_priority(coord: ChunkCoord, viewers: &[ChunkViewer]) -> f32 {
+    let center = coord.0.as_vec3();
+
+    viewers
+        .iter()
+        .map(|viewer| {
+            let offset = center - viewer.position;
+            let distance_squared = offset.length_squared();
+
+            if distance_squared == 0.0 {
+                return 0.0;
+            }
+
+            let alignment = offset.dot(viewer.forward) / distance_squared.sqrt();
+
+            distance_squared * (1.5 - 0.5 * alignment)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Generation priority of a chunk in `GenerationOrder::Spiral`, lower is generated first
+///
+/// Orders chunks by the shell of the cube around the closest camera chunk they are on, then by
+/// their height difference to it, with chunks below before chunks above, and then by their
+/// angle around it.
+pub(crate) fn spiral_priority(coord: ChunkCoord, viewers: &[ChunkViewer]) -> f32 {
+    viewers
+        .iter()
+        .map(|viewer| {
+            let offset = coord.0.as_vec3() - viewer.position.round();
+            let shell = offset.abs().max_element();
+            let height = offset.y.abs() * 2.0 + if offset.y > 0.0 { 1.0 } else { 0.0 };
+            let turn = (offset.z.atan2(offset.x) / std::f32::consts::TAU).rem_euclid(1.0);
+
+            shell * 10000.0 + height * 10.0 + turn
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+struct QueuedChunk {
+    visible: bool,
+    priority: f32,
+    coord: ChunkCoord,
+}
+
+impl PartialEq for QueuedChunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedChunk {}
+
+impl PartialOrd for QueuedChunk {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedChunk {
+    // Reversed so the heap pops visible chunks and then the lowest priority first
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.visible.cmp(&other.visible).then_with(|| {
+            other
+                .priority
+                .partial_cmp(&self.priority)
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+}
+
+/// Chunks waiting for generation, popped nearest first
+///
+/// Chunks outside of every camera frustum are held back while any visible chunk is waiting, so
+/// they are only generated in frames with nothing on screen left to do.
+#[derive(Default)]
+pub(crate) struct ChunkQueue {
+    heap: BinaryHeap<QueuedChunk>,
+    idle: bool,
+}
+
+impl ChunkQueue {
+    /// `margin` grows the chunks, in chunks, when testing them against the frustums, the spiral
+    /// order ignores the frustums to stay the same wherever the cameras look
+    pub fn from_coords(
+        coords: HashSet<ChunkCoord>,
+        viewers: &[ChunkViewer],
+        order: GenerationOrder,
+        margin: f32,
+    ) -> Self {
+        let heap: BinaryHeap<QueuedChunk> = coords
+            .into_iter()
+            .map(|coord| match order {
+                GenerationOrder::Nearest => QueuedChunk {
+                    visible: viewers
+                        .iter()
+                        .any(|viewer| viewer.frustum.contains_chunk(coord, margin)),
+                    priority: chunk_priority(coord, viewers),
+                    coord,
+                },
+                GenerationOrder::Spiral => QueuedChunk {
+                    visible: true,
+                    priority: spiral_priority(coord, viewers),
+                    coord,
+                },
+            })
+            .collect();
+
+        let idle = heap.peek().map_or(true, |queued| !queued.visible);
+
+        Self { heap, idle }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Next chunk to generate, `None` once only deferred off-screen chunks are left
+    pub fn pop(&mut self) -> Option<ChunkCoord> {
+        match self.heap.peek() {
+            Some(queued) if queued.visible || self.idle => {
+                self.heap.pop().map(|queued| queued.coord)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Counts the work a system did this frame against a `GenerationBudget` limit
+pub(crate) struct FrameBudget {
+    start: Instant,
+    spent: usize,
+    max_count: usize,
+    max_time: Duration,
+}
+
+impl FrameBudget {
+    pub fn new(max_count: usize, max_time: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            spent: 0,
+            max_count,
+            max_time,
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.spent >= self.max_count || self.start.elapsed() >= self.max_time
+    }
+
+    pub fn spend(&mut self) {
+        self.spent += 1;
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    density::DensityField,
+    dirty::DensityChanged,
+    modifiers::{DensityModifier, ModifiedDensity},
+};
+use bevy::{ecs::entity::Entity, math::Vec3};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Analytic solid shape, its density is the signed distance to its surface, negative inside
+///
+/// Every distance is exact, so a primitive can serve as a density source of its own, be
+/// combined with the terrain by `DensityModifier::Csg` or be painted into it with a [`Brush`].
+/// `terrain/density.wgsl` evaluates the same shapes in `primitive_distance`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Primitive {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+    },
+    Box {
+        center: [f32; 3],
+        half_extents: [f32; 3],
+    },
+    /// Ring lying in the horizontal plane
+    Torus {
+        center: [f32; 3],
+        /// Distance from the center to the middle of the tube
+        major_radius: f32,
+        /// Radius of the tube
+        minor_radius: f32,
+    },
+    /// Cylinder from `start` to `end` capped with hemispheres
+    Capsule {
+        start: [f32; 3],
+        end: [f32; 3],
+        radius: f32,
+    },
+    /// Half space below the plane through `normal * offset`, solid on the side opposite to
+    /// `normal`
+    Plane {
+        normal: [f32; 3],
+        offset: f32,
+    },
+    /// Upright cylinder with flat caps
+    Cylinder {
+        center: [f32; 3],
+        radius: f32,
+        half_height: f32,
+    },
+}
+
+impl Primitive {
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match *self {
+            Primitive::Sphere { center, radius } => (p - Vec3::from(center)).length() - radius,
+            Primitive::Box {
+                center,
+                half_extents,
+            } => {
+                let q = (p - Vec3::from(center)).abs() - Vec3::from(half_extents);
+
+                q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+            }
+            Primitive::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let q = p - Vec3::from(center);
+                let ring = (q.x * q.x + q.z * q.z).sqrt() - major_radius;
+
+                (ring * ring + q.y * q.y).sqrt() - minor_radius
+            }
+            Primitive::Capsule { start, end, radius } => {
+                let (start, end) = (Vec3::from(start), Vec3::from(end));
+                let axis = end - start;
+                let t = ((p - start).dot(axis) / axis.length_squared().max(f32::EPSILON))
+                    .max(0.0)
+                    .min(1.0);
+
+                (p - (start + axis * t)).length() - radius
+            }
+            Primitive::Plane { normal, offset } => {
+                p.dot(Vec3::from(normal).normalize_or_zero()) - offset
+            }
+            Primitive::Cylinder {
+                center,
+                radius,
+                half_height,
+            } => {
+                let q = p - Vec3::from(center);
+                let side = (q.x * q.x + q.z * q.z).sqrt() - radius;
+                let cap = q.y.abs() - half_height;
+
+                side.max(cap).min(0.0) + (side.max(0.0).powi(2) + cap.max(0.0).powi(2)).sqrt()
+            }
+        }
+    }
+
+    /// Smallest box containing the shape, `None` for planes
+    pub fn aabb(&self) -> Option<(Vec3, Vec3)> {
+        let extents = |center: [f32; 3], extents: Vec3| {
+            let center = Vec3::from(center);
+
+            Some((center - extents, center + extents))
+        };
+
+        match *self {
+            Primitive::Sphere { center, radius } => extents(center, Vec3::splat(radius)),
+            Primitive::Box {
+                center,
+                half_extents,
+            } => extents(center, Vec3::from(half_extents)),
+            Primitive::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+
+                extents(center, Vec3::new(outer, minor_radius, outer))
+            }
+            Primitive::Capsule { start, end, radius } => {
+                let (start, end) = (Vec3::from(start), Vec3::from(end));
+
+                Some((
+                    start.min(end) - Vec3::splat(radius),
+                    start.max(end) + Vec3::splat(radius),
+                ))
+            }
+            Primitive::Plane { .. } => None,
+            Primitive::Cylinder {
+                center,
+                radius,
+                half_height,
+            } => extents(center, Vec3::new(radius, half_height, radius)),
+        }
+    }
+
+    /// Kind and parameters of the shape in `primitive_distance` of `terrain/density.wgsl`, the
+    /// first three parameters go into `a.xyz` of a modifier and the rest into `b`
+    pub(crate) fn to_gpu(&self) -> (u32, [f32; 3], [f32; 4]) {
+        match *self {
+            Primitive::Sphere { center, radius } => (0, center, [radius, 0.0, 0.0, 0.0]),
+            Primitive::Box {
+                center,
+                half_extents,
+            } => (
+                1,
+                center,
+                [half_extents[0], half_extents[1], half_extents[2], 0.0],
+            ),
+            Primitive::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => (2, center, [major_radius, minor_radius, 0.0, 0.0]),
+            Primitive::Capsule { start, end, radius } => {
+                (3, start, [end[0], end[1], end[2], radius])
+            }
+            Primitive::Plane { normal, offset } => (
+                4,
+                Vec3::from(normal).normalize_or_zero().into(),
+                [offset, 0.0, 0.0, 0.0],
+            ),
+            Primitive::Cylinder {
+                center,
+                radius,
+                half_height,
+            } => (5, center, [radius, half_height, 0.0, 0.0]),
+        }
+    }
+}
+
+impl DensityField for Primitive {
+    fn sample(&self, p: Vec3) -> f32 {
+        self.distance(p)
+    }
+
+    /// Distances change by at most the distance moved, so the box can't get further from the
+    /// distance at its center than its half diagonal
+    fn bounds(&self, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+        let distance = self.distance((min + max) * 0.5);
+        let radius = (max - min).length() * 0.5;
+
+        Some((distance - radius, distance + radius))
+    }
+}
+
+/// How a [`Primitive`] is combined with another density, values below the iso level are solid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CsgOp {
+    /// Adds the primitive to the terrain
+    Union,
+    /// Carves the primitive out of the terrain
+    Subtract,
+    /// Keeps the terrain inside the primitive only
+    Intersect,
+}
+
+impl CsgOp {
+    /// Combines the density `a` with the distance `b`, blended over `k`
+    pub fn apply(self, a: f32, b: f32, k: f32) -> f32 {
+        match self {
+            CsgOp::Union => smooth_union(a, b, k),
+            CsgOp::Subtract => smooth_subtract(a, b, k),
+            CsgOp::Intersect => smooth_intersect(a, b, k),
+        }
+    }
+}
+
+/// Polynomial smooth minimum of two distances blending over `k` world units, the sharp union
+/// when `k` is 0
+pub fn smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+
+    let h = (0.5 + 0.5 * (b - a) / k).max(0.0).min(1.0);
+
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+/// `a` with `b` carved out of it, blended over `k` world units
+pub fn smooth_subtract(a: f32, b: f32, k: f32) -> f32 {
+    smooth_intersect(a, -b, k)
+}
+
+/// Where both `a` and `b` are solid, blended over `k` world units
+pub fn smooth_intersect(a: f32, b: f32, k: f32) -> f32 {
+    -smooth_union(-a, -b, k)
+}
+
+/// Edit painting a primitive into the density of a terrain
+///
+/// Append `modifier` to the `ModifiedDensity` of the terrain, or wrap the density with `apply`,
+/// then send `changed` to remesh the chunks the brush touched.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Brush {
+    pub primitive: Primitive,
+    pub op: CsgOp,
+    /// Width in world units over which the edit blends into the terrain
+    #[serde(default)]
+    pub smoothness: f32,
+}
+
+impl Brush {
+    pub fn modifier(&self) -> DensityModifier {
+        DensityModifier::Csg {
+            op: self.op,
+            primitive: self.primitive,
+            smoothness: self.smoothness,
+        }
+    }
+
+    /// Density of `base` with the brush applied
+    ///
+    /// A base that is a `ModifiedDensity` with warps is better extended by `modifier`, wrapping it
+    /// moves its chunks to the CPU mesher, see `ModifiedDensity`.
+    pub fn apply(&self, base: Arc<dyn DensityField>) -> ModifiedDensity {
+        ModifiedDensity::new(base, vec![self.modifier()], 0)
+    }
+
+    /// Box in which the brush changes the density, `None` for planes and intersections which
+    /// change it everywhere
+    pub fn region(&self) -> Option<(Vec3, Vec3)> {
+        if self.op == CsgOp::Intersect {
+            return None;
+        }
+
+        let (min, max) = self.primitive.aabb()?;
+        let margin = Vec3::splat(self.smoothness.max(0.0));
+
+        Some((min - margin, max + margin))
+    }
+
+    /// Event remeshing the chunks of `terrain` the brush touched
+    pub fn changed(&self, terrain: Entity) -> Option<DensityChanged> {
+        let (min, max) = self.region()?;
+
+        Some(DensityChanged { terrain, min, max })
+    }
+}
@@ -0,0 +1,553 @@
+use bevy::math::{IVec3, Vec2, Vec3, Vec4};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+/// Largest difference between a float sample of the CPU and of the GPU backend
+///
+/// Every float noise of this module has a twin in `terrain/noise.wgsl` evaluating the same
+/// operations in the same order on 32 bit floats, but the GPU is free to fuse multiplies and adds
+/// and its transcendental functions aren't correctly rounded, so samples disagree by this much. A
+/// chunk only meshes differently where a sample lands that close to the iso level. Terrain that
+/// has to come out byte identical on every machine uses `fixed_perlin` instead, see
+/// `NoiseBasis::FixedPoint`.
+pub const GPU_TOLERANCE: f32 = 1e-5;
+
+/// One in the 16.16 fixed point `fixed_perlin` and `NoiseBasis::FixedPoint` compute in
+pub const FIXED_ONE: i32 = 1 << 16;
+
+/// Largest difference between a sample of `noise::Perlin` and of `perlin` in
+/// `terrain/noise.wgsl` at `p`, in the space the noise is sampled in
+///
+/// The crate evaluates in 64 bit floats, so on top of `GPU_TOLERANCE` the 32 bit shader loses
+/// precision of the offset within the lattice cell as the position grows, about `1.5e-7` per
+/// unit from the origin, which this bounds by `2e-7`.
+pub fn perlin_gpu_tolerance(p: Vec3) -> f32 {
+    GPU_TOLERANCE + p.abs().max_element() * 2e-7
+}
+
+/// Port of `snoise` from `terrain/noise.wgsl`
+pub fn snoise(v: Vec3) -> f32 {
+    let c = Vec2::new(1.0 / 6.0, 1.0 / 3.0);
+    let d = Vec4::new(0.0, 0.5, 1.0, 2.0);
+
+    // First corner
+    let mut i = (v + Vec3::splat(v.dot(Vec3::splat(c.y)))).floor();
+    let x0 = v - i + Vec3::splat(i.dot(Vec3::splat(c.x)));
+
+    // Other corners
+    let g = step3(Vec3::new(x0.y, x0.z, x0.x), x0);
+    let l = Vec3::ONE - g;
+    let l_zxy = Vec3::new(l.z, l.x, l.y);
+    let i1 = g.min(l_zxy);
+    let i2 = g.max(l_zxy);
+
+    let x1 = x0 - i1 + Vec3::splat(c.x);
+    let x2 = x0 - i2 + Vec3::splat(c.y);
+    let x3 = x0 - Vec3::splat(d.y);
+
+    // Permutations
+    i = mod289_vec3(i);
+    let p = permute(
+        permute(
+            permute(Vec4::splat(i.z) + Vec4::new(0.0, i1.z, i2.z, 1.0))
+                + Vec4::splat(i.y)
+                + Vec4::new(0.0, i1.y, i2.y, 1.0),
+        ) + Vec4::splat(i.x)
+            + Vec4::new(0.0, i1.x, i2.x, 1.0),
+    );
+
+    let n_: f32 = 0.142857142857;
+    let ns = n_ * Vec3::new(d.w, d.y, d.z) - Vec3::new(d.x, d.z, d.x);
+
+    let j = p - 49.0 * (p * ns.z * ns.z).floor();
+
+    let x_ = (j * ns.z).floor();
+    let y_ = (j - 7.0 * x_).floor();
+
+    let x = x_ * ns.x + Vec4::splat(ns.y);
+    let y = y_ * ns.x + Vec4::splat(ns.y);
+    let h = Vec4::ONE - x.abs() - y.abs();
+
+    let b0 = Vec4::new(x.x, x.y, y.x, y.y);
+    let b1 = Vec4::new(x.z, x.w, y.z, y.w);
+
+    let s0 = b0.floor() * 2.0 + Vec4::ONE;
+    let s1 = b1.floor() * 2.0 + Vec4::ONE;
+    let sh = -step4(h, Vec4::ZERO);
+
+    let a0 = xzyw(b0) + xzyw(s0) * Vec4::new(sh.x, sh.x, sh.y, sh.y);
+    let a1 = xzyw(b1) + xzyw(s1) * Vec4::new(sh.z, sh.z, sh.w, sh.w);
+
+    let mut p0 = Vec3::new(a0.x, a0.y, h.x);
+    let mut p1 = Vec3::new(a0.z, a0.w, h.y);
+    let mut p2 = Vec3::new(a1.x, a1.y, h.z);
+    let mut p3 = Vec3::new(a1.z, a1.w, h.w);
+
+    // Normalise gradients
+    let norm = taylor_inv_sqrt(Vec4::new(p0.dot(p0), p1.dot(p1), p2.dot(p2), p3.dot(p3)));
+    p0 *= norm.x;
+    p1 *= norm.y;
+    p2 *= norm.z;
+    p3 *= norm.w;
+
+    // Mix final noise value
+    let mut m = (Vec4::splat(0.6) - Vec4::new(x0.dot(x0), x1.dot(x1), x2.dot(x2), x3.dot(x3)))
+        .max(Vec4::ZERO);
+    m = m * m;
+
+    42.0 * (m * m).dot(Vec4::new(p0.dot(x0), p1.dot(x1), p2.dot(x2), p3.dot(x3)))
+}
+
+const PRIME_X: u32 = 501125321;
+const PRIME_Y: u32 = 1136930381;
+const PRIME_Z: u32 = 1720413743;
+const HASH_MULTIPLIER: u32 = 0x27d4eb2d;
+/// Brings the summed kernels to about `[-1, 1]`
+const OPENSIMPLEX2_SCALE: f32 = 32.694283;
+/// `2 / sqrt(3)`, brings trilinear Perlin noise to `[-1, 1]` like the `noise` crate
+const PERLIN_SCALE: f32 = 1.154_700_5;
+/// `PERLIN_SCALE` in fixed point
+const FIXED_PERLIN_SCALE: i32 = 75674;
+
+/// 3D OpenSimplex2 noise in the range `[-1, 1]`, the variant of FastNoiseLite hashing with 32
+/// bit integers, mirrored by `opensimplex2` in `terrain/noise.wgsl`
+///
+/// Sums the kernels of the closest points of two offset cubic lattices, which together form a
+/// body centered lattice rotated so none of its axes line up with the world axes. That hides the
+/// axis aligned ridges simplex and Perlin noise show on 3D terrain. Unlike `snoise` it takes its
+/// seed directly instead of an offset into a repeating volume.
+pub fn opensimplex2(v: Vec3, seed: u32) -> f32 {
+    // Rotation, not skew
+    let r = (v.x + v.y + v.z) * (2.0 / 3.0);
+    let p = Vec3::splat(r) - v;
+
+    let base = (p + Vec3::splat(0.5)).floor();
+    let primes = [PRIME_X, PRIME_Y, PRIME_Z];
+
+    let mut offset = <[f32; 3]>::from(p - base);
+    // Points from the offset back towards the closest lattice point, -1 for positive offsets
+    let mut sign = offset.map(|offset| if offset >= 0.0 { -1.0 } else { 1.0 });
+    let mut distance = offset.map(f32::abs);
+    let mut primed = [
+        (base.x as i32 as u32).wrapping_mul(PRIME_X),
+        (base.y as i32 as u32).wrapping_mul(PRIME_Y),
+        (base.z as i32 as u32).wrapping_mul(PRIME_Z),
+    ];
+    let mut seed = seed;
+
+    let mut value = 0.0;
+    let mut a = (0.6 - offset[0] * offset[0]) - (offset[1] * offset[1] + offset[2] * offset[2]);
+
+    for lattice in 0..2 {
+        // Closest point of the lattice
+        if a > 0.0 {
+            value += (a * a) * (a * a) * gradient_dot(seed, primed, offset);
+        }
+
+        // Second closest point, one step along the axis the offset is largest on
+        let axis = if distance[0] >= distance[1] && distance[0] >= distance[2] {
+            0
+        } else if distance[1] > distance[0] && distance[1] >= distance[2] {
+            1
+        } else {
+            2
+        };
+
+        let b = a + distance[axis] * 2.0;
+
+        if b > 1.0 {
+            let b = b - 1.0;
+
+            let mut neighbour_primed = primed;
+            let mut neighbour_offset = offset;
+
+            neighbour_primed[axis] = if sign[axis] < 0.0 {
+                primed[axis].wrapping_add(primes[axis])
+            } else {
+                primed[axis].wrapping_sub(primes[axis])
+            };
+            neighbour_offset[axis] += sign[axis];
+
+            value += (b * b) * (b * b) * gradient_dot(seed, neighbour_primed, neighbour_offset);
+        }
+
+        if lattice == 1 {
+            break;
+        }
+
+        // Moves over to the lattice offset by half a cell
+        distance = distance.map(|distance| 0.5 - distance);
+
+        for axis in 0..3 {
+            offset[axis] = sign[axis] * distance[axis];
+
+            if sign[axis] < 0.0 {
+                primed[axis] = primed[axis].wrapping_add(primes[axis]);
+            }
+
+            sign[axis] = -sign[axis];
+        }
+
+        a += (0.75 - distance[0]) - (distance[1] + distance[2]);
+        seed = !seed;
+    }
+
+    (value * OPENSIMPLEX2_SCALE).max(-1.0).min(1.0)
+}
+
+/// 3D Perlin noise of `noise::Perlin` in the range `[-1, 1]` on 32 bit floats, mirrored by
+/// `perlin` in `terrain/noise.wgsl`
+///
+/// The CPU backends sample `noise::Perlin` itself, this port only exists to check the shader
+/// against the crate. `table` is the permutation table of the seed, see `perlin_table`, the
+/// corners of the lattice cell are hashed through it and blended with quintic fades like
+/// `perlin_3d` of the crate.
+pub fn perlin(v: Vec3, table: &[u8; 256]) -> f32 {
+    let base = v.floor();
+    let corner = [base.x as i32, base.y as i32, base.z as i32];
+    let near = v - base;
+    let far = near - Vec3::ONE;
+
+    let hash = |x: i32, y: i32, z: i32| {
+        let xy = table[(x & 255) as usize] as i32 ^ (y & 255);
+
+        table[(table[xy as usize] as i32 ^ (z & 255)) as usize]
+    };
+    let gradient = |dx: i32, dy: i32, dz: i32, offset: Vec3| {
+        perlin_gradient_dot(hash(corner[0] + dx, corner[1] + dy, corner[2] + dz), offset)
+    };
+
+    let g000 = gradient(0, 0, 0, near);
+    let g100 = gradient(1, 0, 0, Vec3::new(far.x, near.y, near.z));
+    let g010 = gradient(0, 1, 0, Vec3::new(near.x, far.y, near.z));
+    let g110 = gradient(1, 1, 0, Vec3::new(far.x, far.y, near.z));
+    let g001 = gradient(0, 0, 1, Vec3::new(near.x, near.y, far.z));
+    let g101 = gradient(1, 0, 1, Vec3::new(far.x, near.y, far.z));
+    let g011 = gradient(0, 1, 1, Vec3::new(near.x, far.y, far.z));
+    let g111 = gradient(1, 1, 1, far);
+
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (a, b, c) = (fade(near.x), fade(near.y), fade(near.z));
+
+    let k0 = g000;
+    let k1 = g100 - g000;
+    let k2 = g010 - g000;
+    let k3 = g001 - g000;
+    let k4 = g000 + g110 - g100 - g010;
+    let k5 = g000 + g101 - g100 - g001;
+    let k6 = g000 + g011 - g010 - g001;
+    let k7 = g100 + g010 + g001 + g111 - g000 - g110 - g101 - g011;
+
+    let value =
+        k0 + k1 * a + k2 * b + k3 * c + k4 * a * b + k5 * a * c + k6 * b * c + k7 * a * b * c;
+
+    (value * PERLIN_SCALE).max(-1.0).min(1.0)
+}
+
+/// Permutation table `noise::Perlin` builds for `seed`, uploaded to the compute shader for every
+/// seed a chunk samples Perlin noise with
+///
+/// The crate keeps its table private, so this repeats `PermutationTable::new`, which shuffles
+/// the bytes with a xorshift generator seeded from the little endian bytes of the seed.
+pub fn perlin_table(seed: u32) -> [u8; 256] {
+    let mut rng_seed = [0; 16];
+    rng_seed[0] = 1;
+
+    for chunk in rng_seed[4..].chunks_mut(4) {
+        chunk.copy_from_slice(&seed.to_le_bytes());
+    }
+
+    let mut table = [0; 256];
+
+    for (index, value) in table.iter_mut().enumerate() {
+        *value = index as u8;
+    }
+
+    table.shuffle(&mut XorShiftRng::from_seed(rng_seed));
+    table
+}
+
+/// Dot product of the offset from a lattice corner with one of the 12 cube edges of Perlin's
+/// improved noise, picked by the low four bits of the hash of the corner
+fn perlin_gradient_dot(hash: u8, offset: Vec3) -> f32 {
+    let (x, y, z) = (offset.x, offset.y, offset.z);
+
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 13 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 14 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// 3D gradient noise on integers in 16.16 fixed point, in the range `[-FIXED_ONE, FIXED_ONE]`,
+/// mirrored by `fixed_perlin` in `terrain/noise.wgsl`
+///
+/// Integer arithmetic has a single result on every device, so unlike the float noises the shader
+/// computes the same bits as this function. `p` is the position in fixed point, see `to_fixed`.
+/// The corners of its lattice cell are hashed like `opensimplex2` and blended with quintic fades
+/// like `perlin`.
+pub fn fixed_perlin(p: IVec3, seed: u32) -> i32 {
+    let cell = [p.x >> 16, p.y >> 16, p.z >> 16];
+    let offset = [p.x & 0xffff, p.y & 0xffff, p.z & 0xffff];
+    let fade = offset.map(fixed_fade);
+    let primes = [PRIME_X, PRIME_Y, PRIME_Z];
+
+    let corner = |x: i32, y: i32, z: i32| {
+        let step = [x, y, z];
+        let mut primed = [0; 3];
+        let mut corner_offset = [0; 3];
+
+        for axis in 0..3 {
+            primed[axis] = ((cell[axis] + step[axis]) as u32).wrapping_mul(primes[axis]);
+            corner_offset[axis] = offset[axis] - step[axis] * FIXED_ONE;
+        }
+
+        fixed_gradient_dot(seed, primed, corner_offset)
+    };
+
+    let x00 = fixed_lerp(corner(0, 0, 0), corner(1, 0, 0), fade[0]);
+    let x10 = fixed_lerp(corner(0, 1, 0), corner(1, 1, 0), fade[0]);
+    let x01 = fixed_lerp(corner(0, 0, 1), corner(1, 0, 1), fade[0]);
+    let x11 = fixed_lerp(corner(0, 1, 1), corner(1, 1, 1), fade[0]);
+
+    let value = fixed_lerp(
+        fixed_lerp(x00, x10, fade[1]),
+        fixed_lerp(x01, x11, fade[1]),
+        fade[2],
+    );
+
+    fixed_mul(value, FIXED_PERLIN_SCALE)
+        .max(-FIXED_ONE)
+        .min(FIXED_ONE)
+}
+
+/// `v * scale` in the fixed point of `fixed_perlin`, rounded down
+///
+/// The scale is a float like the frequencies it comes from. Both multiplications are correctly
+/// rounded on every device and the first one is exact, so the shader converts to the same
+/// integers. `v * scale` has to stay within 32768 of the origin.
+pub fn to_fixed(v: Vec3, scale: f32) -> IVec3 {
+    (v * (scale * FIXED_ONE as f32)).floor().as_ivec3()
+}
+
+/// `value` in fixed point, rounded down like `to_fixed`
+pub fn to_fixed_scalar(value: f32) -> i32 {
+    (value * FIXED_ONE as f32).floor() as i32
+}
+
+/// Float of a fixed point value, exact as long as its magnitude stays below 256
+pub fn from_fixed(value: i32) -> f32 {
+    value as f32 * (1.0 / FIXED_ONE as f32)
+}
+
+/// Product of two fixed point values, rounded towards negative infinity
+pub fn fixed_mul(a: i32, b: i32) -> i32 {
+    ((a as i64 * b as i64) >> 16) as i32
+}
+
+fn fixed_lerp(a: i32, b: i32, t: i32) -> i32 {
+    a + fixed_mul(b - a, t)
+}
+
+/// Quintic fade `6t^5 - 15t^4 + 10t^3` of an offset within a lattice cell
+fn fixed_fade(t: i32) -> i32 {
+    let cube = fixed_mul(fixed_mul(t, t), t);
+
+    fixed_mul(cube, fixed_mul(t, t * 6 - 15 * FIXED_ONE) + 10 * FIXED_ONE)
+}
+
+/// `gradient_dot` on fixed point offsets
+fn fixed_gradient_dot(seed: u32, primed: [u32; 3], offset: [i32; 3]) -> i32 {
+    let edge = gradient_edge(seed, primed);
+
+    let first = if edge & 1 != 0 { -1 } else { 1 };
+    let second = if edge & 2 != 0 { -1 } else { 1 };
+
+    match edge / 4 {
+        0 => offset[1] * first + offset[2] * second,
+        1 => offset[0] * first + offset[2] * second,
+        _ => offset[0] * first + offset[1] * second,
+    }
+}
+
+/// Cube edge of the gradient a lattice point hashes to
+///
+/// Picks one of FastNoiseLite's 64 gradients, the 12 edges of a cube repeated five times and
+/// four of them once more, computed instead of looked up so the shader needs no table.
+fn gradient_edge(seed: u32, primed: [u32; 3]) -> u32 {
+    let mut hash = (seed ^ primed[0] ^ primed[1] ^ primed[2]).wrapping_mul(HASH_MULTIPLIER);
+    hash ^= hash >> 15;
+
+    match (hash >> 2) & 63 {
+        60 => 8,
+        61 => 1,
+        62 => 9,
+        63 => 3,
+        index => index % 12,
+    }
+}
+
+/// Dot product of the offset from a lattice point with the gradient its hash picks, see
+/// `gradient_edge`
+fn gradient_dot(seed: u32, primed: [u32; 3], offset: [f32; 3]) -> f32 {
+    let edge = gradient_edge(seed, primed);
+
+    let first = if edge & 1 != 0 { -1.0 } else { 1.0 };
+    let second = if edge & 2 != 0 { -1.0 } else { 1.0 };
+
+    // Every group of four edges leaves out one axis
+    match edge / 4 {
+        0 => offset[1] * first + offset[2] * second,
+        1 => offset[0] * first + offset[2] * second,
+        _ => offset[0] * first + offset[1] * second,
+    }
+}
+
+/// Offset `terrain/density.wgsl` adds to the noise input for a seed, the noise repeats every 289 units
+pub fn seed_offset(seed: u32) -> Vec3 {
+    Vec3::new(
+        (seed % 289) as f32,
+        ((seed / 289) % 289) as f32,
+        ((seed / 83521) % 289) as f32,
+    )
+}
+
+fn mod289_vec3(x: Vec3) -> Vec3 {
+    x - (x * (1. / 289.0)).floor() * 289.0
+}
+
+fn mod289_vec4(x: Vec4) -> Vec4 {
+    x - (x * (1. / 289.0)).floor() * 289.0
+}
+
+fn permute(x: Vec4) -> Vec4 {
+    mod289_vec4(((x * 34.0) + Vec4::ONE) * x)
+}
+
+fn taylor_inv_sqrt(r: Vec4) -> Vec4 {
+    Vec4::splat(1.79284291400159) - 0.85373472095314 * r
+}
+
+/// WGSL `step`, 1 where `x >= edge` and 0 elsewhere
+fn step3(edge: Vec3, x: Vec3) -> Vec3 {
+    Vec3::new(
+        if x.x >= edge.x { 1.0 } else { 0.0 },
+        if x.y >= edge.y { 1.0 } else { 0.0 },
+        if x.z >= edge.z { 1.0 } else { 0.0 },
+    )
+}
+
+fn step4(edge: Vec4, x: Vec4) -> Vec4 {
+    Vec4::new(
+        if x.x >= edge.x { 1.0 } else { 0.0 },
+        if x.y >= edge.y { 1.0 } else { 0.0 },
+        if x.z >= edge.z { 1.0 } else { 0.0 },
+        if x.w >= edge.w { 1.0 } else { 0.0 },
+    )
+}
+
+fn xzyw(v: Vec4) -> Vec4 {
+    Vec4::new(v.x, v.z, v.y, v.w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noise::{NoiseFn, Perlin, Seedable};
+
+    /// Points sampled by the tests, the same values are listed in `terrain/noise.wgsl`
+    const POINTS: [(f32, f32, f32); 4] = [
+        (0.3, 1.7, -2.2),
+        (12.5, -3.25, 7.75),
+        (-41.1, 0.05, 19.9),
+        (100.25, 63.5, -7.125),
+    ];
+    const SEEDS: [u32; 4] = [0, 1337, 0xdead_beef, 42];
+
+    fn assert_samples(noise: impl Fn(Vec3, u32) -> f32, expected: [f32; 4]) {
+        for ((&(x, y, z), &seed), &expected) in POINTS.iter().zip(SEEDS.iter()).zip(&expected) {
+            let value = noise(Vec3::new(x, y, z), seed);
+
+            assert!(
+                (value - expected).abs() <= GPU_TOLERANCE,
+                "({}, {}, {}) with seed {} sampled {}, the shader samples {}",
+                x,
+                y,
+                z,
+                seed,
+                value,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn snoise_matches_shader() {
+        assert_samples(
+            |p, _| snoise(p),
+            [0.06471298, -0.21659881, 0.17174031, 0.058684137],
+        );
+    }
+
+    #[test]
+    fn opensimplex2_matches_shader() {
+        assert_samples(
+            opensimplex2,
+            [0.38860315, 0.34963715, 6.9360062e-6, -0.0010504593],
+        );
+    }
+
+    #[test]
+    fn perlin_matches_shader() {
+        assert_samples(
+            |p, seed| perlin(p, &perlin_table(seed)),
+            [0.24206811, 0.28558183, 0.073885836, 0.29722273],
+        );
+    }
+
+    #[test]
+    fn fixed_perlin_matches_shader() {
+        let expected = [-7650, 24426, -3738, 29884];
+
+        for ((&(x, y, z), &seed), &expected) in POINTS.iter().zip(SEEDS.iter()).zip(&expected) {
+            assert_eq!(
+                fixed_perlin(to_fixed(Vec3::new(x, y, z), 1.0), seed),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn perlin_matches_noise_crate() {
+        for &seed in SEEDS.iter() {
+            let table = perlin_table(seed);
+            let crate_perlin = Perlin::new().set_seed(seed);
+
+            for step in 0..1000 {
+                let t = step as f32;
+                let p = Vec3::new(t * 0.173 - 87.0, (t * 0.61).sin() * 40.0, 95.0 - t * 0.191);
+
+                let expected = crate_perlin.get([p.x as f64, p.y as f64, p.z as f64]) as f32;
+                let value = perlin(p, &table);
+
+                assert!(
+                    (value - expected).abs() <= perlin_gpu_tolerance(p),
+                    "{} with seed {} sampled {}, noise::Perlin samples {}",
+                    p,
+                    seed,
+                    value,
+                    expected
+                );
+            }
+        }
+    }
+}
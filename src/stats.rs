@@ -0,0 +1,91 @@
+use bevy::{
+    ecs::system::{Query, ResMut},
+    reflect::Reflect,
+    render2::mesh::Mesh,
+};
+use bevy_inspector_egui::Inspectable;
+use std::time::Duration;
+
+/// Size of the mesh of a chunk and the time its generation took, kept up to date on every chunk
+/// entity that has been meshed
+#[derive(Debug, Clone, Default, PartialEq, Reflect, Inspectable)]
+pub struct ChunkMeshStats {
+    pub triangles: usize,
+    pub vertices: usize,
+    /// Milliseconds the generation task ran on the CPU, sampling, polygonizing, post processing
+    /// and building the mesh
+    pub cpu_ms: f32,
+    /// Milliseconds the generation task waited for the compute shader and the readback of its
+    /// vertices, which includes the frames the job was queued for
+    pub gpu_ms: f32,
+    /// Size of the vertex and index buffers the chunk is drawn from
+    pub buffer_bytes: usize,
+}
+
+impl ChunkMeshStats {
+    /// Sizes of a chunk drawn from a `Mesh` asset
+    pub(crate) fn from_mesh(mesh: &Mesh, cpu_time: Duration, gpu_time: Duration) -> Self {
+        let vertices = mesh.count_vertices();
+        let indices = mesh.indices().map_or(vertices, |indices| indices.len());
+        let index_bytes = mesh.get_index_buffer_bytes().map_or(0, |bytes| bytes.len());
+
+        Self {
+            triangles: indices / 3,
+            vertices,
+            cpu_ms: cpu_time.as_secs_f32() * 1000.0,
+            gpu_ms: gpu_time.as_secs_f32() * 1000.0,
+            buffer_bytes: vertices * mesh.get_vertex_size() as usize + index_bytes,
+        }
+    }
+}
+
+/// Totals of the `ChunkMeshStats` of every chunk currently spawned, updated by `TerrainPlugin`
+/// every frame
+#[derive(Debug, Clone, Default)]
+pub struct TerrainMeshStats {
+    /// Chunks that have been meshed
+    pub chunks: usize,
+    pub triangles: usize,
+    pub vertices: usize,
+    pub buffer_bytes: usize,
+    /// Summed milliseconds the generation tasks of the chunks ran on the CPU
+    pub cpu_ms: f32,
+    /// Summed milliseconds the generation tasks of the chunks waited for the GPU
+    pub gpu_ms: f32,
+}
+
+impl TerrainMeshStats {
+    /// Average CPU time of the chunks in milliseconds, `0` before any chunk has been meshed
+    pub fn average_cpu_ms(&self) -> f32 {
+        match self.chunks {
+            0 => 0.0,
+            chunks => self.cpu_ms / chunks as f32,
+        }
+    }
+
+    /// Average GPU time of the chunks in milliseconds, `0` before any chunk has been meshed
+    pub fn average_gpu_ms(&self) -> f32 {
+        match self.chunks {
+            0 => 0.0,
+            chunks => self.gpu_ms / chunks as f32,
+        }
+    }
+}
+
+pub(crate) fn update_terrain_mesh_stats(
+    mut stats: ResMut<TerrainMeshStats>,
+    chunk_query: Query<&ChunkMeshStats>,
+) {
+    let mut totals = TerrainMeshStats::default();
+
+    for chunk in chunk_query.iter() {
+        totals.chunks += 1;
+        totals.triangles += chunk.triangles;
+        totals.vertices += chunk.vertices;
+        totals.buffer_bytes += chunk.buffer_bytes;
+        totals.cpu_ms += chunk.cpu_ms;
+        totals.gpu_ms += chunk.gpu_ms;
+    }
+
+    *stats = totals;
+}
@@ -0,0 +1,23 @@
+use crate::{
+    density::DensityGrid,
+    dual_contouring::{self, edge_crossings},
+    mesh::MeshData,
+};
+use bevy::math::Vec3;
+
+/// Naive surface nets, places the vertex of every cell crossing the surface at the average of
+/// its edge crossings
+///
+/// Cheaper than `dual_contouring::dual_contour` and with the same connectivity, about half the
+/// triangles of marching cubes, but rounds sharp features off. Border handling, normals and
+/// positions work like `dual_contouring::dual_contour`.
+pub fn surface_net(grid: &DensityGrid, border: u32, iso_level: f32) -> MeshData {
+    dual_contouring::contour_cells(grid, border, iso_level, |corners, iso_level| {
+        let (sum, count) = edge_crossings(corners, iso_level)
+            .fold((Vec3::ZERO, 0), |(sum, count), crossing| {
+                (sum + crossing, count + 1)
+            });
+
+        sum / count as f32
+    })
+}
@@ -1,560 +1,1940 @@
-use crate::marching_cubes::{polygonise, Triangle as OtherTriangle};
-use bevy::render2::render_resource::{
-    BindGroupDescriptor, BindGroupEntry, CommandEncoderDescriptor, ComputePassDescriptor,
+#[cfg(feature = "gpu-compute")]
+use crate::terrain_gpu::{self, GpuChunkJobs, GpuChunkMesh, TerrainComputePlugin};
+use crate::{
+    cache::ChunkCache,
+    chunk::{ChunkBounds, ChunkCoord, ChunkMap},
+    decimate,
+    density::{DensityField, SimplexDensity, SimplexNoise, TerrainDensity},
+    dirty::{remesh_dirty_chunks, DensityChanged},
+    error::TerrainError,
+    mesh::MeshData,
+    occlusion::AmbientOcclusion,
+    octree::ChunkOctree,
+    patch::{patch_edited_chunks, ChunkPatch},
+    post_process::{MeshPostProcessor, MeshPostProcessors, OptimizeVertexCache, WeldVertices},
+    progress::{update_generation_progress, GenerationProgress},
+    provider::TerrainChunkProvider,
+    queue::{ChunkFrustum, ChunkQueue, ChunkViewer, FrameBudget},
+    stats::{update_terrain_mesh_stats, ChunkMeshStats, TerrainMeshStats},
+    transition::ChunkTransitions,
+    vertex_colors::{MeshVertexColors, VertexColorSource},
+};
+#[cfg(feature = "cpu-mesher")]
+use crate::{
+    cpu,
+    density::DensityGrid,
+    dual_contouring, heightmap, marching_cubes33, marching_tetrahedra,
+    provider::{ChunkData, ChunkProvider},
+    surface_nets, transition,
 };
-
-use bytemuck::{Pod, Zeroable};
-
-use crevice::std140::AsStd140;
-
 use bevy::{
-    app::{App, Plugin},
-    asset::Assets,
-    core::{bytes_of, Time},
+    app::{App, EventReader, EventWriter, Plugin},
+    asset::{Assets, Handle},
+    core::Name,
     ecs::{
+        bundle::Bundle,
         entity::Entity,
+        query::{ChangeTrackers, Changed, With},
         schedule::SystemLabel,
-        system::{Commands, Query, Res, ResMut},
-        world::{FromWorld, World},
+        system::{Commands, Local, Query, Res, ResMut},
     },
-    math::Vec3,
+    log::warn,
+    math::{IVec3, Mat4, UVec3, Vec3},
     pbr2::{PbrBundle, StandardMaterial},
     prelude::ParallelSystemDescriptorCoercion,
-    render2::{
-        camera::Camera,
-        color::Color,
-        mesh::{Indices, Mesh},
-        render_resource::{
-            BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
-            BufferAddress, BufferBindingType, BufferDescriptor, BufferInitDescriptor, BufferUsages,
-            ComputePipeline, ComputePipelineDescriptor, MapMode, PipelineLayoutDescriptor,
-            PrimitiveTopology, ShaderStages,
-        },
-        renderer::{RenderDevice, RenderQueue},
-        shader::Shader,
-    },
+    reflect::Reflect,
+    render2::{camera::Camera, color::Color, mesh::Mesh, renderer::RenderDevice},
     tasks::{AsyncComputeTaskPool, Task},
-    transform::components::Transform,
+    transform::{
+        components::{GlobalTransform, Transform},
+        hierarchy::{BuildChildren, DespawnRecursiveExt},
+    },
 };
 
-use std::collections::{HashMap, HashSet};
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+use serde::Deserialize;
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(not(any(feature = "gpu-compute", feature = "cpu-mesher")))]
+compile_error!("at least one of the `gpu-compute` and `cpu-mesher` features must be enabled");
+
+/// Generation task of a chunk, dropping it cancels the generation
+///
+/// The task stores its result once it finishes, so `handle_terrain_chunk_tasks` only checks for
+/// it instead of polling the future from the main schedule.
+pub(crate) struct ChunkMeshTask {
+    task: Task<()>,
+    result: Arc<Mutex<Option<Result<GeneratedChunk, TerrainError>>>>,
+}
+
+impl ChunkMeshTask {
+    /// Spawns a future returning the mesh of the chunk together with the time it waited for
+    /// the GPU, the rest of its run time counts as CPU time
+    fn spawn(
+        task_pool: &AsyncComputeTaskPool,
+        future: impl Future<Output = Result<(ChunkMesh, Duration), TerrainError>> + Send + 'static,
+    ) -> Self {
+        let result = Arc::new(Mutex::new(None));
+        let slot = result.clone();
+
+        let task = task_pool.spawn(async move {
+            let started = Instant::now();
+
+            let result = future.await.map(|(mesh, gpu_time)| GeneratedChunk {
+                mesh,
+                cpu_time: started.elapsed().saturating_sub(gpu_time),
+                gpu_time,
+            });
+
+            *slot.lock().unwrap() = Some(result);
+        });
+
+        Self { task, result }
+    }
+
+    /// Task that fails right away
+    #[cfg(feature = "gpu-compute")]
+    fn failed(task_pool: &AsyncComputeTaskPool, error: TerrainError) -> Self {
+        Self::spawn(task_pool, async move { Err(error) })
+    }
+
+    /// Cancels the generation and waits until the task stopped running
+    #[cfg(feature = "gpu-compute")]
+    pub(crate) async fn cancel(self) {
+        self.task.cancel().await;
+    }
+
+    fn take_result(&self) -> Option<Result<GeneratedChunk, TerrainError>> {
+        self.result.lock().unwrap().take()
+    }
+}
 
-use noise::{NoiseFn, Perlin, Seedable, SuperSimplex};
+/// Mesh finished by a `ChunkMeshTask` and the time it took
+pub(crate) struct GeneratedChunk {
+    mesh: ChunkMesh,
+    cpu_time: Duration,
+    gpu_time: Duration,
+}
 
-use futures_lite::future;
+/// Output of a chunk generation task
+pub(crate) enum ChunkMesh {
+    Mesh {
+        mesh: Mesh,
+        bounds: Option<ChunkBounds>,
+        collision: Option<MeshData>,
+        patch: Option<ChunkPatch>,
+        /// Line list of the triangle edges with `TerrainSettings::wireframe`
+        wireframe: Option<Mesh>,
+    },
+    /// Vertices kept on the GPU by `MeshingBackend::GpuResident`, `None` for chunks without a
+    /// surface
+    #[cfg(feature = "gpu-compute")]
+    Gpu(Option<GpuChunkMesh>),
+}
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
 enum TerrainSystemLabels {
     UpdateChunks,
+    HandleChunkTasks,
 }
 
-#[repr(C)]
-#[derive(Debug, AsStd140, Copy, Clone, Zeroable, Pod)]
-struct Triangle {
-    pub a: Vec3,
-    pub b: Vec3,
-    pub c: Vec3,
+/// Parameters used to generate a terrain, changing them at runtime regenerates all of its chunks
+#[derive(Debug, Clone, PartialEq, Reflect, Inspectable, Deserialize)]
+#[serde(default)]
+pub struct TerrainSettings {
+    /// Number of cells along each edge of a chunk
+    #[inspectable(min = 8, max = 256)]
+    pub chunk_size: u32,
+    /// World space size of a single cell
+    #[inspectable(min = 0.01, speed = 0.01)]
+    pub voxel_scale: f32,
+    /// Density value at which the surface is extracted
+    #[inspectable(min = -1.0, max = 1.0, speed = 0.01)]
+    pub iso_level: f32,
+    pub seed: u32,
+    /// Octaves of the default `SimplexDensity`, terrains whose density is a `SimplexDensity` are
+    /// given a new one whenever they change. Other densities ignore it.
+    #[reflect(ignore)]
+    pub noise: SimplexNoise,
+    /// Radius, in chunks, of the area generated around each camera
+    #[inspectable(max = 64)]
+    pub world_extent: u32,
+    /// Radius, in chunks, beyond which chunks are despawned, the margin over `world_extent` keeps
+    /// chunks at the border from being regenerated while a camera moves back and forth
+    #[inspectable(max = 64)]
+    pub unload_extent: u32,
+    /// Number of detail levels, chunks at level `n` are meshed with `2^n` times larger cells
+    #[inspectable(min = 1, max = 4)]
+    pub lod_levels: u32,
+    /// Distance, in chunks, covered by each detail level
+    #[inspectable(min = 1, max = 64)]
+    pub lod_distance: u32,
+    /// How cracks between chunks of different detail levels are hidden
+    #[reflect(ignore)]
+    pub seams: SeamMode,
+    /// How the chunks to generate are picked within `world_extent`
+    #[reflect(ignore)]
+    pub layout: ChunkLayout,
+    /// Order missing chunks are generated in
+    #[reflect(ignore)]
+    pub order: GenerationOrder,
+    /// Density distance from the iso level within which an octree node counts as holding
+    /// surface, raise it when `ChunkLayout::Octree` drops chunks with thin features
+    #[inspectable(min = 0.0, speed = 0.01)]
+    pub surface_margin: f32,
+    /// Height band chunks are generated in
+    #[reflect(ignore)]
+    #[inspectable(ignore)]
+    pub vertical_bounds: VerticalBounds,
+    /// Where vertices are placed along the cell edges crossing the surface, only used by
+    /// the marching cubes, tetrahedra and heightmap algorithms
+    #[reflect(ignore)]
+    pub vertex_placement: VertexPlacement,
+    /// How the surface is extracted from the density
+    #[reflect(ignore)]
+    pub algorithm: MeshingAlgorithm,
+    /// Share of their triangles kept by chunks beyond the first detail level, which are
+    /// decimated by quadric error after the post processors, 1 keeps every triangle. Chunks
+    /// drawn by `MeshingBackend::GpuResident` are never decimated.
+    #[inspectable(min = 0.05, max = 1.0, speed = 0.01)]
+    pub lod_decimation: f32,
+    /// World units covered by one repeat of a texture, the UVs of the chunk meshes are box
+    /// projected from the terrain space positions. Chunks drawn by `MeshingBackend::GpuResident`
+    /// have no UVs.
+    #[inspectable(min = 0.01, speed = 0.1)]
+    pub texture_scale: f32,
+    /// Occlusion baked into `Mesh::ATTRIBUTE_COLOR` of the chunk meshes for materials reading
+    /// vertex colors, multiplied into the colors of the `VertexColorSource` when there is one,
+    /// nothing is baked when `None`. Chunks drawn by
+    /// `MeshingBackend::GpuResident` have no vertex colors.
+    #[reflect(ignore)]
+    #[inspectable(ignore)]
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    /// Cell size, in cells of the chunk, of a coarser mesh extracted from the same density
+    /// samples in the same pass and inserted as `ChunkCollisionMesh`, for physics colliders or
+    /// navmeshes. Should divide `chunk_size`, nothing is extracted when `None`. Only chunks
+    /// meshed on the CPU get one.
+    #[reflect(ignore)]
+    #[inspectable(ignore)]
+    pub collision_cell_size: Option<u32>,
+    /// Keeps the density samples of the chunks around as `ChunkPatch`, so `DensityChanged` only
+    /// polygonizes the cells around an edit again instead of generating the whole chunk. Costs
+    /// the memory of the samples and only applies to full detail chunks meshed with marching
+    /// cubes on the CPU, without post processors or skirts.
+    pub incremental_edits: bool,
+    /// Draws the edges of the triangles of every chunk as lines on top of it, for debugging the
+    /// topology of the meshers. Chunks drawn by `MeshingBackend::GpuResident` have no wireframe.
+    pub wireframe: bool,
 }
 
-#[repr(C)]
-#[derive(Debug, AsStd140, Copy, Clone, Zeroable, Pod)]
-struct Cube {
-    pub triangle_count: u32,
-    pub triangles: [Triangle; 5],
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            chunk_size: 32,
+            voxel_scale: 1.0,
+            iso_level: 0.3,
+            seed: 5225,
+            noise: SimplexNoise::default(),
+            world_extent: 10,
+            unload_extent: 12,
+            lod_levels: 4,
+            lod_distance: 3,
+            seams: SeamMode::default(),
+            layout: ChunkLayout::default(),
+            order: GenerationOrder::default(),
+            surface_margin: 0.25,
+            vertical_bounds: VerticalBounds::default(),
+            vertex_placement: VertexPlacement::default(),
+            algorithm: MeshingAlgorithm::default(),
+            lod_decimation: 1.0,
+            texture_scale: 4.0,
+            ambient_occlusion: None,
+            collision_cell_size: None,
+            incremental_edits: false,
+            wireframe: false,
+        }
+    }
 }
 
-#[repr(C)]
-#[derive(Debug, AsStd140, Copy, Clone, Zeroable, Pod)]
-struct InputBuffer {
-    pub chunk_size: u32,
-    pub position: Vec3,
+/// Selects which chunks within range of a camera are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Deserialize)]
+pub enum ChunkLayout {
+    /// Every chunk of the grid
+    Grid,
+    /// Only chunks whose `ChunkOctree` nodes hold surface, skipping air and solid ground
+    Octree,
+}
+
+impl Default for ChunkLayout {
+    fn default() -> Self {
+        ChunkLayout::Grid
+    }
+}
+
+/// Order in which missing chunks around the cameras are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Deserialize)]
+pub enum GenerationOrder {
+    /// Nearest chunks first, favouring chunks on screen and in front of the cameras
+    Nearest,
+    /// Shell after shell around the chunk of the closest camera, each starting at the height of
+    /// the camera and working down before up, winding around it the same way every time
+    Spiral,
+}
+
+impl Default for GenerationOrder {
+    fn default() -> Self {
+        GenerationOrder::Nearest
+    }
+}
+
+/// Hides the cracks between neighbouring chunks meshed at different detail levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Deserialize)]
+pub enum SeamMode {
+    /// Leaves the cracks open, cheapest when `lod_levels` is 1
+    Disabled,
+    /// Hangs a strip of triangles reaching one cell into the surface below every border edge
+    Skirts,
+    /// Replaces the cells of a chunk touching finer chunks by transition cells, which sample the
+    /// faces and edges they share at the resolution of the finer chunk, closing the cracks
+    /// without overlapping geometry
+    ///
+    /// Chunks are meshed again whenever a chunk around them changes detail level. Transition
+    /// cells are built by the marching cubes of the CPU mesher, chunks with finer neighbours
+    /// are meshed on the CPU whatever the backend, and match the neighbours meshed on the GPU
+    /// exactly with `NoiseBasis::FixedPoint` only. Terrains using another algorithm fall back to
+    /// skirts.
+    TransitionCells,
+}
+
+impl Default for SeamMode {
+    fn default() -> Self {
+        SeamMode::Skirts
+    }
+}
+
+/// Algorithm extracting the surface of a chunk from its density samples
+///
+/// Only marching cubes runs on the GPU, chunks of a terrain using another algorithm are meshed on
+/// the CPU and fall back to marching cubes without the `cpu-mesher` feature. The vertices of the
+/// algorithms placing one vertex per cell don't lie on the chunk faces, so `SeamMode::Skirts`
+/// has no effect on them, and only marching cubes builds `SeamMode::TransitionCells`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Deserialize)]
+pub enum MeshingAlgorithm {
+    /// Triangulates every cell from a lookup table, runs on every meshing backend
+    MarchingCubes,
+    /// Places one vertex per cell, solving a QEF over the edge crossings and density gradients,
+    /// which keeps sharp features
+    DualContouring,
+    /// Places one vertex per cell at the average of its edge crossings, faster than both and
+    /// with fewer triangles, but smoother than marching cubes
+    SurfaceNets,
+    /// Splits every cell into six tetrahedra, which avoids the ambiguous configurations of
+    /// marching cubes and their pinholes at the cost of about twice the triangles
+    MarchingTetrahedra,
+    /// Marching cubes following the topology of the trilinear interpolation like MC33, slower
+    /// but keeps thin tunnels and saddles topologically correct
+    MarchingCubes33,
+    /// Extracts the top surface of every column as a heightmap, for 2.5D terrain without
+    /// overhangs or caves, with two triangles per column and far fewer than every other
+    /// algorithm
+    Heightmap,
+}
+
+impl Default for MeshingAlgorithm {
+    fn default() -> Self {
+        MeshingAlgorithm::MarchingCubes
+    }
+}
+
+impl MeshingAlgorithm {
+    /// Whether the GPU backends can run the algorithm
+    pub fn runs_on_gpu(self) -> bool {
+        self == MeshingAlgorithm::MarchingCubes
+    }
+}
+
+/// Where the meshers place the vertex of a cell edge crossing the surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Inspectable, Deserialize)]
+pub enum VertexPlacement {
+    /// Halfway along the edge, every surface snaps to the grid for a blocky, stylized look
+    Midpoint,
+    /// Where the density linearly interpolated between both ends reaches the iso level
+    Interpolated,
+}
+
+impl Default for VertexPlacement {
+    fn default() -> Self {
+        VertexPlacement::Interpolated
+    }
+}
+
+/// Limits how far up and down from the terrain origin chunks are generated
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum VerticalBounds {
+    /// Generates chunks in every direction, for cave systems and other fully 3D worlds
+    Unbounded,
+    /// Only generates chunks reaching into the band between `min` and `max`, given in world
+    /// units relative to the terrain, deep underground and high in the sky stay empty
+    Limited { min: f32, max: f32 },
+}
+
+impl Default for VerticalBounds {
+    fn default() -> Self {
+        VerticalBounds::Unbounded
+    }
+}
+
+impl TerrainSettings {
+    /// Extra layer of samples on the positive side of every chunk, shared with the next chunk so
+    /// surfaces stay continuous across chunk borders
+    pub const APRON: u32 = 1;
+
+    /// Layer of ghost samples around the CPU grid of a chunk, read from the neighbouring chunks
+    /// for the density gradient only so normals match across chunk borders
+    pub const GHOST: u32 = 1;
+
+    /// Number of density samples along each edge of a chunk, including the apron
+    pub fn samples_per_axis(&self) -> u32 {
+        self.chunk_size + Self::APRON
+    }
+
+    /// Number of density samples along each edge of the CPU grid of a chunk, including the apron
+    /// and the ghost samples on both sides
+    pub fn padded_samples_per_axis(&self) -> u32 {
+        self.samples_per_axis() + 2 * Self::GHOST
+    }
+
+    /// Highest detail level the chunk size allows, chunks keep at least 4 cells along each edge
+    pub fn max_lod(&self) -> u32 {
+        let mut lod = 0;
+
+        while lod + 1 < self.lod_levels
+            && self.chunk_size % (2 << lod) == 0
+            && self.chunk_size / (2 << lod) >= 4
+        {
+            lod += 1;
+        }
+
+        lod
+    }
+
+    /// Detail level of a chunk `distance_squared` chunks away from the closest camera
+    pub fn lod_at_distance_squared(&self, distance_squared: i32) -> u32 {
+        let distance = (distance_squared as f32).sqrt();
+
+        ((distance / self.lod_distance.max(1) as f32) as u32).min(self.max_lod())
+    }
+
+    /// Settings meshing a chunk at detail level `lod`, with `2^lod` times fewer and larger cells
+    ///
+    /// Chunks keep their world space size and coordinates, so everything derived from the
+    /// returned settings lines up with the full resolution chunks.
+    pub fn for_lod(&self, lod: u32) -> TerrainSettings {
+        let factor = 1 << lod;
+
+        TerrainSettings {
+            chunk_size: self.chunk_size / factor,
+            voxel_scale: self.voxel_scale * factor as f32,
+            ..self.clone()
+        }
+    }
+
+    /// Whether the density bounds prove a chunk holds no surface, so it can be skipped
+    pub fn is_chunk_empty(&self, density: &dyn DensityField, coord: ChunkCoord) -> bool {
+        let min = self.get_chunk_origin(coord);
+        let max = min + Vec3::splat(self.chunk_size as f32);
+
+        match density.bounds(min * self.voxel_scale, max * self.voxel_scale) {
+            Some((low, high)) => high < self.iso_level || low >= self.iso_level,
+            None => false,
+        }
+    }
+
+    /// Coordinates of the chunks with samples inside the box from `min` to `max`, given in the
+    /// space the density is sampled in, chunks sharing border samples with the box included
+    ///
+    /// The ghost samples count as well, an edit just outside a chunk still changes the gradient
+    /// normals along its border.
+    pub fn chunks_overlapping(&self, min: Vec3, max: Vec3) -> impl Iterator<Item = ChunkCoord> {
+        let chunk_size = self.chunk_size as f32;
+        let half = chunk_size / 2.0;
+
+        // Past its cells a chunk reads the apron and the ghost samples, before them the ghost
+        // samples only
+        let min = min / self.voxel_scale - Vec3::splat((Self::APRON + Self::GHOST) as f32);
+        let max = max / self.voxel_scale + Vec3::splat(Self::GHOST as f32);
+
+        let first = ((min - Vec3::splat(half)) / chunk_size).floor() + Vec3::ONE;
+        let last = ((max + Vec3::splat(half)) / chunk_size).floor();
+
+        let (first, last) = (
+            IVec3::new(first.x as i32, first.y as i32, first.z as i32),
+            IVec3::new(last.x as i32, last.y as i32, last.z as i32),
+        );
+
+        (first.z..=last.z).flat_map(move |z| {
+            (first.y..=last.y)
+                .flat_map(move |y| (first.x..=last.x).map(move |x| ChunkCoord::new(x, y, z)))
+        })
+    }
+
+    /// Whether a chunk reaches into the `vertical_bounds`
+    pub fn is_within_vertical_bounds(&self, coord: ChunkCoord) -> bool {
+        match self.vertical_bounds {
+            VerticalBounds::Unbounded => true,
+            VerticalBounds::Limited { min, max } => {
+                let center = self.get_chunk_translation(coord).y;
+                let half = self.chunk_world_size() / 2.0;
+
+                center + half >= min && center - half <= max
+            }
+        }
+    }
+
+    /// World space size of a chunk edge
+    pub fn chunk_world_size(&self) -> f32 {
+        self.chunk_size as f32 * self.voxel_scale
+    }
+
+    /// Position of the first sample of a chunk, in cells
+    pub(crate) fn get_chunk_origin(&self, coord: ChunkCoord) -> Vec3 {
+        let chunk_size = self.chunk_size as f32;
+
+        coord.0.as_vec3() * chunk_size - Vec3::splat(chunk_size / 2.0)
+    }
+
+    /// Translation of a chunk relative to its terrain
+    pub fn get_chunk_translation(&self, coord: ChunkCoord) -> Vec3 {
+        coord.0.as_vec3() * self.chunk_world_size()
+    }
+
+    pub fn get_chunk_coord_at_translation(&self, translation: &Vec3) -> ChunkCoord {
+        let coord = (*translation / self.chunk_world_size()).round();
+
+        ChunkCoord::new(coord.x as i32, coord.y as i32, coord.z as i32)
+    }
+}
+
+/// Selects how chunk meshes are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshingBackend {
+    /// Uses the compute shader when the device supports it and the CPU otherwise, chunks of
+    /// density fields the compute shader can't express are meshed on the CPU
+    Auto,
+    /// Runs the compute passes of `terrain/density.wgsl` and `terrain/mesher.wgsl`, requires the
+    /// `gpu-compute` feature
+    ///
+    /// Chunks of density fields the compute shader can't express fail with
+    /// `TerrainError::UnsupportedGpuDensity`.
+    Gpu,
+    /// Runs the compute shader and draws its vertex buffers with `draw_indirect`, without
+    /// reading them back or building a `Mesh`
+    ///
+    /// Suits static terrain, post processors, seams and the mesh cache don't apply and
+    /// `ChunkMeshed` carries no mesh. Density fields fail like they do with `Gpu`.
+    GpuResident,
+    /// Runs the CPU mesher on the async compute task pool, requires the `cpu-mesher` feature
+    Cpu,
+}
+
+impl Default for MeshingBackend {
+    fn default() -> Self {
+        MeshingBackend::Auto
+    }
+}
+
+impl MeshingBackend {
+    /// Picks the backend that is actually used, never returns `Auto`
+    pub fn resolve(self, render_device: Option<&RenderDevice>) -> Self {
+        #[cfg(feature = "gpu-compute")]
+        let gpu_available = render_device.map_or(false, terrain_gpu::is_supported);
+        #[cfg(not(feature = "gpu-compute"))]
+        let gpu_available = {
+            let _ = render_device;
+            false
+        };
+
+        match self {
+            MeshingBackend::Gpu if gpu_available => MeshingBackend::Gpu,
+            MeshingBackend::GpuResident if gpu_available => MeshingBackend::GpuResident,
+            MeshingBackend::Cpu if cfg!(feature = "cpu-mesher") => MeshingBackend::Cpu,
+            _ if gpu_available || !cfg!(feature = "cpu-mesher") => MeshingBackend::Gpu,
+            _ => MeshingBackend::Cpu,
+        }
+    }
+}
+
+/// Health of the terrain generation, updated whenever a chunk fails to generate
+#[derive(Debug, Default)]
+pub struct TerrainStatus {
+    /// Most recent failure
+    pub last_error: Option<TerrainError>,
+    /// Set once the GPU backend failed, chunks are meshed on the CPU from then on
+    pub gpu_fallback: bool,
+}
+
+/// Limits the generation work done per frame so streaming in many chunks doesn't hitch, the rest
+/// carries over to the next frames
+#[derive(Debug, Clone)]
+pub struct GenerationBudget {
+    /// Chunks scheduled for generation per frame, over every terrain
+    pub chunks_per_frame: usize,
+    /// Generated meshes added to `Assets<Mesh>` per frame
+    pub uploads_per_frame: usize,
+    /// Time scheduling and uploading may each take per frame
+    pub frame_time: Duration,
+    /// Distance, in chunks, by which chunks may lie outside a camera frustum and still be
+    /// scheduled before those off screen
+    pub frustum_margin: f32,
+}
+
+impl Default for GenerationBudget {
+    fn default() -> Self {
+        Self {
+            chunks_per_frame: 32,
+            uploads_per_frame: 32,
+            frame_time: Duration::from_millis(4),
+            frustum_margin: 1.0,
+        }
+    }
+}
+
+/// Marks the root entity of a terrain, its chunks are spawned as children
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Terrain;
+
+/// Components of a terrain, every terrain streams its own chunks around the cameras
+#[derive(Bundle, Default)]
+pub struct TerrainBundle {
+    pub terrain: Terrain,
+    pub settings: TerrainSettings,
+    pub density: TerrainDensity,
+    pub chunk_map: ChunkMap,
+    pub octree: ChunkOctree,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+}
+
+impl TerrainBundle {
+    pub fn new(settings: TerrainSettings, density: TerrainDensity) -> Self {
+        Self {
+            settings,
+            density,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct TerrainPlugin {
+    settings: TerrainSettings,
+    density: Option<TerrainDensity>,
+    post_processors: MeshPostProcessors,
+    vertex_colors: MeshVertexColors,
+    cache: Option<ChunkCache>,
+    spawn_terrain: bool,
+}
+
+impl Default for TerrainPlugin {
+    fn default() -> Self {
+        TerrainPluginBuilder::default().build()
+    }
+}
+
+impl TerrainPlugin {
+    pub fn builder() -> TerrainPluginBuilder {
+        TerrainPluginBuilder::default()
+    }
 }
 
-pub struct TerrainPlugin;
 impl Plugin for TerrainPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Terrain::new());
+        if self.spawn_terrain {
+            let density = self.density.clone().unwrap_or_else(|| {
+                TerrainDensity::new(SimplexDensity::with_noise(
+                    self.settings.seed,
+                    self.settings.noise,
+                ))
+            });
+
+            app.world
+                .spawn()
+                .insert_bundle(TerrainBundle::new(self.settings.clone(), density))
+                .insert(Name::new("Terrain"));
+        }
+
+        app.register_type::<TerrainSettings>();
+        app.register_type::<TerrainChunk>();
+        app.register_type::<ChunkMeshStats>();
+        app.register_inspectable::<TerrainSettings>();
+        app.register_inspectable::<TerrainChunk>();
+        app.register_inspectable::<ChunkMeshStats>();
+        app.insert_resource(self.post_processors.clone());
+        app.insert_resource(self.vertex_colors.clone());
+        if let Some(cache) = self.cache.clone() {
+            app.insert_resource(cache);
+        }
+        #[cfg(feature = "gpu-compute")]
+        app.add_plugin(TerrainComputePlugin);
+        app.init_resource::<MeshingBackend>();
+        app.init_resource::<TerrainStatus>();
+        app.init_resource::<GenerationProgress>();
+        app.init_resource::<TerrainMeshStats>();
+        app.init_resource::<GenerationBudget>();
+        app.add_event::<ChunkQueued>();
+        app.add_event::<ChunkDensityReady>();
+        app.add_event::<ChunkMeshed>();
+        app.add_event::<ChunkDespawned>();
+        app.add_event::<RegenerateTerrain>();
+        app.add_event::<DensityChanged>();
+        app.add_system(regenerate_terrain.before(TerrainSystemLabels::UpdateChunks));
+        app.add_system(apply_noise_settings.before(TerrainSystemLabels::UpdateChunks));
         app.add_system(update_chunks.label(TerrainSystemLabels::UpdateChunks));
-        app.add_system(handle_terrain_chunk_tasks.after(TerrainSystemLabels::UpdateChunks));
+        app.add_system(
+            remesh_dirty_chunks
+                .after(TerrainSystemLabels::UpdateChunks)
+                .before(TerrainSystemLabels::HandleChunkTasks),
+        );
+        app.add_system(
+            patch_edited_chunks
+                .after(TerrainSystemLabels::UpdateChunks)
+                .before(TerrainSystemLabels::HandleChunkTasks),
+        );
+        app.add_system(
+            handle_terrain_chunk_tasks
+                .label(TerrainSystemLabels::HandleChunkTasks)
+                .after(TerrainSystemLabels::UpdateChunks),
+        );
+        app.add_system(update_generation_progress.after(TerrainSystemLabels::HandleChunkTasks));
+        app.add_system(update_terrain_mesh_stats.after(TerrainSystemLabels::HandleChunkTasks));
     }
 }
 
-struct Terrain {
-    chunk_view_distance: u32,
-    chunk_size: u32,
-    chunks: HashMap<(i32, i32, i32), Entity>,
+/// Configures a `TerrainPlugin` at `App` construction time
+pub struct TerrainPluginBuilder {
+    settings: TerrainSettings,
+    density: Option<TerrainDensity>,
+    post_processors: MeshPostProcessors,
+    vertex_colors: MeshVertexColors,
+    cache: Option<ChunkCache>,
+    spawn_terrain: bool,
 }
 
-impl Terrain {
-    fn new() -> Self {
+impl Default for TerrainPluginBuilder {
+    fn default() -> Self {
         Self {
-            chunk_view_distance: 10,
-            chunk_size: 64,
-            chunks: HashMap::new(),
+            settings: TerrainSettings::default(),
+            density: None,
+            post_processors: MeshPostProcessors::default(),
+            vertex_colors: MeshVertexColors::default(),
+            cache: None,
+            spawn_terrain: true,
         }
     }
+}
 
-    fn get_chunk_coords_at_translation(&self, translation: &Vec3) -> (i32, i32, i32) {
-        (
-            (translation.x / self.chunk_size as f32).round() as i32,
-            (translation.y / self.chunk_size as f32).round() as i32,
-            (translation.z / self.chunk_size as f32).round() as i32,
-        )
+impl TerrainPluginBuilder {
+    pub fn settings(mut self, settings: TerrainSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn chunk_size(mut self, chunk_size: u32) -> Self {
+        self.settings.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn voxel_scale(mut self, voxel_scale: f32) -> Self {
+        self.settings.voxel_scale = voxel_scale;
+        self
+    }
+
+    pub fn iso_level(mut self, iso_level: f32) -> Self {
+        self.settings.iso_level = iso_level;
+        self
+    }
+
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.settings.seed = seed;
+        self
+    }
+
+    /// Octaves of the default simplex noise
+    pub fn noise(mut self, noise: SimplexNoise) -> Self {
+        self.settings.noise = noise;
+        self
+    }
+
+    pub fn lod_levels(mut self, lod_levels: u32) -> Self {
+        self.settings.lod_levels = lod_levels;
+        self
+    }
+
+    pub fn lod_distance(mut self, lod_distance: u32) -> Self {
+        self.settings.lod_distance = lod_distance;
+        self
+    }
+
+    pub fn world_extent(mut self, world_extent: u32) -> Self {
+        self.settings.world_extent = world_extent;
+        self
+    }
+
+    pub fn unload_extent(mut self, unload_extent: u32) -> Self {
+        self.settings.unload_extent = unload_extent;
+        self
+    }
+
+    pub fn vertical_bounds(mut self, vertical_bounds: VerticalBounds) -> Self {
+        self.settings.vertical_bounds = vertical_bounds;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: MeshingAlgorithm) -> Self {
+        self.settings.algorithm = algorithm;
+        self
+    }
+
+    pub fn vertex_placement(mut self, vertex_placement: VertexPlacement) -> Self {
+        self.settings.vertex_placement = vertex_placement;
+        self
+    }
+
+    /// Replaces the default simplex noise, which is otherwise seeded with `seed`
+    pub fn density(mut self, density: impl DensityField + 'static) -> Self {
+        self.density = Some(TerrainDensity::new(density));
+        self
+    }
+
+    /// Appends a transform run on every chunk mesh, after the ones added before
+    pub fn post_processor(mut self, post_processor: impl MeshPostProcessor + 'static) -> Self {
+        self.post_processors.push(post_processor);
+        self
+    }
+
+    /// Appends `WeldVertices` with its default epsilon, turning the triangle soup of the meshers
+    /// into smooth shaded indexed meshes
+    pub fn weld_vertices(self) -> Self {
+        self.post_processor(WeldVertices::default())
+    }
+
+    /// Colors the vertices of every chunk mesh meshed on the CPU or read back from the GPU,
+    /// replacing the source set before
+    pub fn vertex_colors(mut self, source: impl VertexColorSource + 'static) -> Self {
+        self.vertex_colors = MeshVertexColors::new(source);
+        self
     }
 
-    fn get_chunk(&self, x: i32, y: i32, z: i32) -> Option<&Entity> {
-        self.chunks.get(&(x, y, z))
+    /// Appends `OptimizeVertexCache` with its default cache size, after the post processors
+    /// added before
+    pub fn optimize_vertex_cache(self) -> Self {
+        self.post_processor(OptimizeVertexCache::default())
     }
 
-    fn has_chunk(&self, x: i32, y: i32, z: i32) -> bool {
-        self.chunks.contains_key(&(x, y, z))
+    /// Reads generated chunks from `cache` and writes new ones into it
+    pub fn cache(mut self, cache: ChunkCache) -> Self {
+        self.cache = Some(cache);
+        self
     }
 
-    fn set_chunk(&mut self, x: i32, y: i32, z: i32, chunk: Entity) {
-        self.chunks.insert((x, y, z), chunk);
+    /// Only registers the terrain systems, terrains are then spawned with `TerrainBundle`
+    pub fn without_default_terrain(mut self) -> Self {
+        self.spawn_terrain = false;
+        self
     }
 
-    fn remove_chunk(&mut self, x: i32, y: i32, z: i32) {
-        self.chunks.remove(&(x, y, z));
+    pub fn build(self) -> TerrainPlugin {
+        TerrainPlugin {
+            settings: self.settings,
+            density: self.density,
+            post_processors: self.post_processors,
+            vertex_colors: self.vertex_colors,
+            cache: self.cache,
+            spawn_terrain: self.spawn_terrain,
+        }
     }
 }
 
+#[derive(Debug, Reflect, Inspectable)]
 pub struct TerrainChunk {
-    coords: (i32, i32, i32),
+    terrain: Entity,
+    coord: ChunkCoord,
+    lod: u32,
+    #[reflect(ignore)]
+    #[inspectable(ignore)]
+    transitions: ChunkTransitions,
+}
+
+/// Sent when a chunk entity has been spawned and its generation scheduled
+pub struct ChunkQueued {
+    pub terrain: Entity,
+    pub entity: Entity,
+    pub coord: ChunkCoord,
+}
+
+/// Sent when the generated data of a chunk has been read back from the GPU
+pub struct ChunkDensityReady {
+    pub terrain: Entity,
+    pub entity: Entity,
+    pub coord: ChunkCoord,
+}
+
+/// Sent when a chunk received its mesh
+pub struct ChunkMeshed {
+    pub terrain: Entity,
+    pub entity: Entity,
+    pub coord: ChunkCoord,
+    /// `None` for chunks drawn straight from GPU buffers
+    pub mesh: Option<Handle<Mesh>>,
+}
+
+/// Send to throw away generated chunks and generate them again
+#[derive(Debug, Clone, Default)]
+pub struct RegenerateTerrain {
+    /// Terrain to regenerate, every terrain is regenerated when `None`
+    pub terrain: Option<Entity>,
+    /// Seed to regenerate with, a new seed always regenerates every chunk
+    pub seed: Option<u32>,
+    /// Chunks to regenerate with the current seed, every chunk is regenerated when `None`
+    pub chunks: Option<Vec<ChunkCoord>>,
+}
+
+/// Coarse mesh of a chunk extracted at `TerrainSettings::collision_cell_size`, positions are in
+/// cells relative to the chunk like the render mesh
+///
+/// Always marched cubes with flat normals, whatever the algorithm of the render mesh, and left
+/// out of post processing, decimation and skirts.
+#[derive(Debug, Clone)]
+pub struct ChunkCollisionMesh(pub MeshData);
+
+/// Child of a chunk drawing the edges of its triangles, see `TerrainSettings::wireframe`
+#[derive(Debug, Clone)]
+pub struct ChunkWireframe {
+    pub entity: Entity,
+    pub mesh: Handle<Mesh>,
+}
+
+/// Sent when a chunk left the generated area and its entity was despawned
+pub struct ChunkDespawned {
+    pub terrain: Entity,
+    pub entity: Entity,
+    pub coord: ChunkCoord,
 }
 
 impl TerrainChunk {
-    fn value_from_noise(noise: SuperSimplex, translation: Vec3) -> f32 {
-        1.0 - (noise.get([
-            translation.x as f64 / 32.0,
-            translation.y as f64 / 32.0,
-            translation.z as f64 / 32.0,
-        ]) * 2.0) as f32
+    /// Entity of the terrain the chunk belongs to
+    pub fn terrain(&self) -> Entity {
+        self.terrain
     }
 
-    fn generate_mesh(chunk_coords: (i32, i32, i32), chunk_size: u32) -> Mesh {
-        let noise = SuperSimplex::new();
-
-        noise.set_seed(5225);
+    pub fn coord(&self) -> ChunkCoord {
+        self.coord
+    }
 
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    /// Detail level the chunk was meshed at, see `TerrainSettings::for_lod`
+    pub fn lod(&self) -> u32 {
+        self.lod
+    }
 
-        let mut triangles: Vec<OtherTriangle> = Vec::new();
+    /// Finer chunks around the chunk it was stitched to, empty unless the seams are
+    /// `SeamMode::TransitionCells`
+    pub fn transitions(&self) -> ChunkTransitions {
+        self.transitions
+    }
+}
 
-        let cell_size = 1.0;
-        let iso_level = 0.7;
+/// Generates the mesh of a chunk on the CPU, positions are in cells relative to the chunk
+#[cfg(feature = "cpu-mesher")]
+pub fn generate_chunk_mesh_data(
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+) -> MeshData {
+    mesh_chunk_density(&sample_chunk_density(density, coord, settings), settings)
+}
 
-        let translation_offset = Vec3::new(
-            chunk_coords.0 as f32 * chunk_size as f32 - chunk_size as f32 / 2.0,
-            chunk_coords.1 as f32 * chunk_size as f32 - chunk_size as f32 / 2.0,
-            chunk_coords.2 as f32 * chunk_size as f32 - chunk_size as f32 / 2.0,
-        );
+/// Samples the density of a chunk including its apron and ghost samples
+#[cfg(feature = "cpu-mesher")]
+pub fn sample_chunk_density(
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+) -> DensityGrid {
+    DensityGrid::from_field(
+        density,
+        UVec3::splat(settings.padded_samples_per_axis()),
+        settings.get_chunk_origin(coord) - Vec3::splat(TerrainSettings::GHOST as f32),
+        settings.voxel_scale,
+    )
+}
 
-        for z in 0..chunk_size {
-            for y in 0..chunk_size {
-                for x in 0..chunk_size {
-                    let translation = Vec3::new(
-                        x as f32 * cell_size - chunk_size as f32 / 2.0 * cell_size
-                            + cell_size / 2.0,
-                        y as f32 * cell_size - chunk_size as f32 / 2.0 * cell_size
-                            + cell_size / 2.0,
-                        z as f32 * cell_size - chunk_size as f32 / 2.0 * cell_size
-                            + cell_size / 2.0,
-                    );
+/// Polygonizes a grid returned by `sample_chunk_density`
+#[cfg(feature = "cpu-mesher")]
+pub fn mesh_chunk_density(grid: &DensityGrid, settings: &TerrainSettings) -> MeshData {
+    // Cheap pre-pass, grids entirely on one side of the iso level have no triangles
+    if !grid.crosses(settings.iso_level) {
+        return MeshData::default();
+    }
 
-                    let cell_points = [
-                        translation
-                            + Vec3::new(-cell_size / 2.0, -cell_size / 2.0, -cell_size / 2.0),
-                        translation
-                            + Vec3::new(cell_size / 2.0, -cell_size / 2.0, -cell_size / 2.0),
-                        translation + Vec3::new(cell_size / 2.0, -cell_size / 2.0, cell_size / 2.0),
-                        translation
-                            + Vec3::new(-cell_size / 2.0, -cell_size / 2.0, cell_size / 2.0),
-                        translation
-                            + Vec3::new(-cell_size / 2.0, cell_size / 2.0, -cell_size / 2.0),
-                        translation + Vec3::new(cell_size / 2.0, cell_size / 2.0, -cell_size / 2.0),
-                        translation + Vec3::new(cell_size / 2.0, cell_size / 2.0, cell_size / 2.0),
-                        translation + Vec3::new(-cell_size / 2.0, cell_size / 2.0, cell_size / 2.0),
-                    ];
-
-                    let cell_points = [
-                        (
-                            cell_points[0],
-                            Self::value_from_noise(noise, cell_points[0] + translation_offset),
-                        ),
-                        (
-                            cell_points[1],
-                            Self::value_from_noise(noise, cell_points[1] + translation_offset),
-                        ),
-                        (
-                            cell_points[2],
-                            Self::value_from_noise(noise, cell_points[2] + translation_offset),
-                        ),
-                        (
-                            cell_points[3],
-                            Self::value_from_noise(noise, cell_points[3] + translation_offset),
-                        ),
-                        (
-                            cell_points[4],
-                            Self::value_from_noise(noise, cell_points[4] + translation_offset),
-                        ),
-                        (
-                            cell_points[5],
-                            Self::value_from_noise(noise, cell_points[5] + translation_offset),
-                        ),
-                        (
-                            cell_points[6],
-                            Self::value_from_noise(noise, cell_points[6] + translation_offset),
-                        ),
-                        (
-                            cell_points[7],
-                            Self::value_from_noise(noise, cell_points[7] + translation_offset),
-                        ),
-                    ];
-                    triangles.append(&mut polygonise(cell_points, iso_level));
-                }
-            }
+    match settings.algorithm {
+        MeshingAlgorithm::MarchingCubes => cpu::march_chunk_smooth(
+            grid,
+            TerrainSettings::GHOST,
+            settings.iso_level,
+            settings.vertex_placement,
+        ),
+        MeshingAlgorithm::DualContouring => {
+            dual_contouring::dual_contour(grid, TerrainSettings::GHOST, settings.iso_level)
+        }
+        MeshingAlgorithm::SurfaceNets => {
+            surface_nets::surface_net(grid, TerrainSettings::GHOST, settings.iso_level)
         }
+        MeshingAlgorithm::MarchingTetrahedra => marching_tetrahedra::march_tetrahedra(
+            grid,
+            TerrainSettings::GHOST,
+            settings.iso_level,
+            settings.vertex_placement,
+        ),
+        MeshingAlgorithm::MarchingCubes33 => marching_cubes33::march_cubes33(
+            grid,
+            TerrainSettings::GHOST,
+            settings.iso_level,
+            settings.vertex_placement,
+        ),
+        MeshingAlgorithm::Heightmap => heightmap::heightmap(
+            grid,
+            TerrainSettings::GHOST,
+            settings.iso_level,
+            settings.vertex_placement,
+        ),
+    }
+}
 
-        let vertices = triangles
-            .iter()
-            .map(|triangle| [triangle.a, triangle.b, triangle.c])
-            .flatten()
-            .map(|vector| [vector.x, vector.y, vector.z])
-            .collect::<Vec<_>>();
-        let indices = (0..vertices.len())
-            .map(|index| index as u32)
-            .collect::<Vec<u32>>();
-        let uvs = (0..vertices.len())
-            .map(|_| [0.0, 0.0])
-            .collect::<Vec<[f32; 2]>>();
-
-        let mut normals: Vec<[f32; 3]> = Vec::new();
-
-        for triangle in indices.chunks(3) {
-            let a = Vec3::from(vertices[(triangle)[0] as usize]);
-            let b = Vec3::from(vertices[(triangle)[1] as usize]);
-            let c = Vec3::from(vertices[(triangle)[2] as usize]);
-
-            let normal = (b - a).cross(c - a).normalize();
-
-            normals.push(normal.into());
-            normals.push(normal.into());
-            normals.push(normal.into());
+/// Like `mesh_chunk_density`, stitching the chunk to the finer chunks around it with transition
+/// cells under `SeamMode::TransitionCells`, the faces and edges they share are sampled from
+/// `density`
+#[cfg(feature = "cpu-mesher")]
+pub fn mesh_chunk_density_with_transitions(
+    grid: &DensityGrid,
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+    transitions: &ChunkTransitions,
+) -> MeshData {
+    if settings.seams != SeamMode::TransitionCells
+        || settings.algorithm != MeshingAlgorithm::MarchingCubes
+        || transitions.is_empty()
+    {
+        return mesh_chunk_density(grid, settings);
+    }
+
+    // No pre-pass, the finer samples may cross the iso level where the grid doesn't
+    let origin = settings.get_chunk_origin(coord);
+
+    transition::march_chunk_transitions(
+        grid,
+        TerrainSettings::GHOST,
+        transitions,
+        settings.iso_level,
+        settings.vertex_placement,
+        &|point| density.sample((origin + point) * settings.voxel_scale),
+    )
+}
+
+/// Marches every `cell_size`th sample of a grid returned by `sample_chunk_density`, positions
+/// are in cells of the full resolution grid
+#[cfg(feature = "cpu-mesher")]
+pub fn mesh_chunk_density_at(
+    grid: &DensityGrid,
+    settings: &TerrainSettings,
+    cell_size: u32,
+) -> MeshData {
+    if !grid.crosses(settings.iso_level) {
+        return MeshData::default();
+    }
+
+    let coarse = grid.downsample(TerrainSettings::GHOST, cell_size);
+    let (positions, indices) =
+        cpu::march_chunk(&coarse, settings.iso_level, settings.vertex_placement);
+    let mut mesh_data = MeshData::with_flat_normals(positions, indices);
+
+    mesh_data.transform(cell_size.max(1) as f32, Vec3::ZERO);
+
+    mesh_data
+}
+
+/// Meshes a grid returned by `sample_chunk_density` at full resolution and at
+/// `TerrainSettings::collision_cell_size`
+#[cfg(feature = "cpu-mesher")]
+fn mesh_chunk_resolutions(
+    grid: &DensityGrid,
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+    transitions: &ChunkTransitions,
+) -> (MeshData, Option<MeshData>) {
+    (
+        mesh_chunk_density_with_transitions(grid, density, coord, settings, transitions),
+        collision_mesh(grid, settings),
+    )
+}
+
+#[cfg(feature = "cpu-mesher")]
+fn collision_mesh(grid: &DensityGrid, settings: &TerrainSettings) -> Option<MeshData> {
+    settings
+        .collision_cell_size
+        .map(|cell_size| mesh_chunk_density_at(grid, settings, cell_size))
+}
+
+/// Like `generate_chunk_mesh_data`, loading the density grid from `provider` and saving it there
+/// when it had to be sampled, also returns the collision mesh
+#[cfg(feature = "cpu-mesher")]
+fn generate_provided_chunk_mesh_data(
+    provider: &dyn ChunkProvider,
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+) -> (MeshData, Option<MeshData>) {
+    // Provided chunks are always meshed at full detail, which has no finer neighbours
+    mesh_chunk_resolutions(
+        &provided_chunk_density(provider, density, coord, settings),
+        density,
+        coord,
+        settings,
+        &ChunkTransitions::default(),
+    )
+}
+
+/// Loads the density grid of a chunk from `provider`, sampling and saving it when it wasn't
+/// stored
+#[cfg(feature = "cpu-mesher")]
+fn provided_chunk_density(
+    provider: &dyn ChunkProvider,
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+) -> DensityGrid {
+    let dims = UVec3::splat(settings.padded_samples_per_axis());
+
+    match provider.load(coord) {
+        Some(data) if data.density.dims() == dims => data.density,
+        _ => {
+            let data = ChunkData {
+                density: sample_chunk_density(density, coord, settings),
+            };
+
+            provider.save(coord, &data);
+
+            data.density
         }
+    }
+}
 
-        mesh.set_indices(Some(Indices::U32(indices)));
+/// Like `generate_chunk_mesh_data`, reading the mesh or the density grid from `cache` when they
+/// were stored before and storing whatever had to be generated, also returns the collision mesh
+///
+/// Collision meshes aren't cached, chunks whose mesh was cached extract it from the cached
+/// density. Neither are the meshes of chunks with transition cells, which depend on the chunks
+/// around them.
+#[cfg(feature = "cpu-mesher")]
+fn generate_cached_chunk_mesh_data(
+    cache: &ChunkCache,
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+    transitions: &ChunkTransitions,
+) -> (MeshData, Option<MeshData>) {
+    if !transitions.is_empty() {
+        let grid = cached_chunk_density(cache, density, coord, settings);
+
+        return mesh_chunk_resolutions(&grid, density, coord, settings, transitions);
+    }
+
+    if let Some(mesh_data) = cache.load_mesh(settings, coord) {
+        let collision = settings.collision_cell_size.and_then(|_| {
+            collision_mesh(
+                &cached_chunk_density(cache, density, coord, settings),
+                settings,
+            )
+        });
 
-        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        return (mesh_data, collision);
+    }
+
+    let grid = cached_chunk_density(cache, density, coord, settings);
+    let (mesh_data, collision) =
+        mesh_chunk_resolutions(&grid, density, coord, settings, transitions);
 
-        mesh
+    if let Err(error) = cache.store_mesh(settings, coord, &mesh_data) {
+        warn!("Failed to cache the mesh of chunk {:?}: {}", coord, error);
     }
+
+    (mesh_data, collision)
+}
+
+/// Reads the density grid of a chunk from `cache`, sampling and storing it when it wasn't
+#[cfg(feature = "cpu-mesher")]
+fn cached_chunk_density(
+    cache: &ChunkCache,
+    density: &dyn DensityField,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+) -> DensityGrid {
+    cache
+        .load_density(settings, coord)
+        .filter(|grid| grid.dims() == UVec3::splat(settings.padded_samples_per_axis()))
+        .unwrap_or_else(|| {
+            let grid = sample_chunk_density(density, coord, settings);
+
+            if let Err(error) = cache.store_density(settings, coord, &grid) {
+                warn!(
+                    "Failed to cache the density of chunk {:?}: {}",
+                    coord, error
+                );
+            }
+
+            grid
+        })
 }
 
 fn update_chunks(
     mut commands: Commands,
-    mut terrain: ResMut<Terrain>,
-    render_device: Res<RenderDevice>,
-    render_queue: Res<RenderQueue>,
+    backend: Res<MeshingBackend>,
+    status: Res<TerrainStatus>,
+    render_device: Option<Res<RenderDevice>>,
+    #[cfg(feature = "gpu-compute")] gpu_jobs: Res<GpuChunkJobs>,
     task_pool: Res<AsyncComputeTaskPool>,
-    camera_query: Query<(&Camera, &Transform)>,
-    terrain_chunks_query: Query<(Entity, &TerrainChunk)>,
+    post_processors: Res<MeshPostProcessors>,
+    vertex_colors: Res<MeshVertexColors>,
+    cache: Option<Res<ChunkCache>>,
+    budget: Res<GenerationBudget>,
+    mut progress: ResMut<GenerationProgress>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    chunk_query: Query<&TerrainChunk>,
+    mut terrain_query: Query<
+        (
+            Entity,
+            &TerrainSettings,
+            &TerrainDensity,
+            Option<&TerrainChunkProvider>,
+            &mut ChunkMap,
+            &mut ChunkOctree,
+            &GlobalTransform,
+            ChangeTrackers<TerrainSettings>,
+            ChangeTrackers<TerrainDensity>,
+        ),
+        With<Terrain>,
+    >,
+    mut chunk_queued_events: EventWriter<ChunkQueued>,
+    mut chunk_despawned_events: EventWriter<ChunkDespawned>,
 ) {
-    let mut visible_chunk_coords: HashSet<(i32, i32, i32)> = HashSet::new();
+    let generator = ChunkGenerator::new(
+        *backend,
+        &status,
+        render_device.as_deref(),
+        #[cfg(feature = "gpu-compute")]
+        &gpu_jobs,
+        &task_pool,
+        &post_processors,
+        &vertex_colors,
+        cache.as_deref(),
+    );
+
+    let mut frame_budget = FrameBudget::new(budget.chunks_per_frame, budget.frame_time);
+
+    progress.queued = 0;
+
+    for (
+        terrain,
+        settings,
+        density,
+        provider,
+        mut chunk_map,
+        mut octree,
+        terrain_transform,
+        settings_tracker,
+        density_tracker,
+    ) in terrain_query.iter_mut()
+    {
+        let terrain_to_world = terrain_transform.compute_matrix();
+        let world_to_terrain = terrain_to_world.inverse();
+        let chunk_to_world =
+            terrain_to_world * Mat4::from_scale(Vec3::splat(settings.chunk_world_size()));
+
+        let mut centers = Vec::new();
+        let mut viewers = Vec::new();
+
+        for (camera, transform) in camera_query.iter() {
+            let translation = world_to_terrain.transform_point3(transform.translation);
+            let forward = world_to_terrain.transform_vector3(transform.rotation * -Vec3::Z);
+
+            let chunk_to_clip =
+                camera.projection_matrix * transform.compute_matrix().inverse() * chunk_to_world;
+
+            centers.push(settings.get_chunk_coord_at_translation(&translation));
+            viewers.push(ChunkViewer {
+                position: translation / settings.chunk_world_size(),
+                forward: forward.normalize(),
+                frustum: ChunkFrustum::from_matrix(chunk_to_clip),
+            });
+        }
 
-    for (_, transform) in camera_query.iter() {
-        let (center_x, center_y, center_z) =
-            terrain.get_chunk_coords_at_translation(&transform.translation);
+        let mut visible_chunk_coords: HashSet<ChunkCoord> = centers
+            .iter()
+            .flat_map(|center| center.within_radius(settings.world_extent))
+            .filter(|coord| settings.is_within_vertical_bounds(*coord))
+            .collect();
 
-        let chunk_view_distance = terrain.chunk_view_distance as i32;
+        let unload_extent = settings.unload_extent.max(settings.world_extent) as i32;
 
-        for y in -chunk_view_distance..chunk_view_distance + 1 {
-            let mut z = 0;
+        let regenerate = settings_tracker.is_changed() || density_tracker.is_changed();
 
-            {
-                let mut x = 0;
+        if regenerate {
+            octree.clear();
+        }
+
+        if settings.layout == ChunkLayout::Octree {
+            octree.retain_surface_chunks(&mut visible_chunk_coords, &*density.0, settings);
+        }
+
+        let distance_squared_to_camera = |coord: ChunkCoord| {
+            centers
+                .iter()
+                .map(|center| center.distance_squared(coord))
+                .min()
+                .unwrap_or(i32::MAX)
+        };
+        let lod_at =
+            |coord: ChunkCoord| settings.lod_at_distance_squared(distance_squared_to_camera(coord));
+        let transitions_at = |coord: ChunkCoord, lod: u32| {
+            if settings.seams == SeamMode::TransitionCells {
+                ChunkTransitions::new(coord, lod, lod_at)
+            } else {
+                ChunkTransitions::default()
+            }
+        };
+
+        for (coord, entity) in chunk_map.iter().collect::<Vec<_>>() {
+            let distance_squared = distance_squared_to_camera(coord);
+            let lod = settings.lod_at_distance_squared(distance_squared);
+            let chunk = chunk_query.get(entity).ok();
+
+            let in_range = distance_squared <= unload_extent * unload_extent;
+            let lod_changed = chunk.map_or(false, |chunk| chunk.lod != lod);
+
+            // Chunks changing detail level are generated again by the loop below
+            if regenerate || !in_range || lod_changed {
+                despawn_chunk(
+                    &mut commands,
+                    terrain,
+                    &mut chunk_map,
+                    entity,
+                    coord,
+                    &mut chunk_despawned_events,
+                );
+
+                continue;
+            }
 
-                visible_chunk_coords.insert((center_x, center_y + y, center_z));
+            visible_chunk_coords.remove(&coord);
 
-                x += 1;
+            let transitions = transitions_at(coord, lod);
 
-                loop {
-                    let cubic_distance_from_center = x * x + z * z + y * y;
-                    let squared_view_distance = chunk_view_distance * chunk_view_distance;
-                    if cubic_distance_from_center > squared_view_distance {
-                        break;
-                    }
+            if chunk.map_or(true, |chunk| chunk.transitions == transitions)
+                || frame_budget.is_exhausted()
+            {
+                continue;
+            }
 
-                    visible_chunk_coords.insert((center_x + x, center_y + y, center_z));
-                    visible_chunk_coords.insert((center_x - x, center_y + y, center_z));
+            // Chunks whose neighbours changed detail level are stitched to them again in place,
+            // keeping their mesh until the new one is ready
+            commands.entity(entity).insert(TerrainChunk {
+                terrain,
+                coord,
+                lod,
+                transitions,
+            });
 
-                    x += 1;
-                }
+            if provider.is_none() && settings.is_chunk_empty(&*density.0, coord) {
+                continue;
             }
 
-            z += 1;
+            frame_budget.spend();
 
-            loop {
-                let squared_distance_from_center = z * z + y * y;
-                let squared_view_distance = chunk_view_distance * chunk_view_distance;
-                if squared_distance_from_center > squared_view_distance {
-                    break;
-                }
+            let task = generator.generate(density, provider, settings, coord, lod, transitions);
 
-                {
-                    let mut x = 0;
-                    visible_chunk_coords.insert((center_x + x, center_y + y, center_z + z));
-                    x += 1;
-                    loop {
-                        let cubic_distance_from_center = x * x + z * z + y * y;
-                        let squared_view_distance = chunk_view_distance;
-                        if cubic_distance_from_center > squared_view_distance {
-                            break;
-                        }
+            commands.entity(entity).insert(task);
 
-                        visible_chunk_coords.insert((center_x + x, center_y + y, center_z + z));
-                        visible_chunk_coords.insert((center_x - x, center_y + y, center_z + z));
-                        x += 1;
-                    }
-                }
+            chunk_queued_events.send(ChunkQueued {
+                terrain,
+                entity,
+                coord,
+            });
+        }
 
-                {
-                    let mut x = 0;
-                    visible_chunk_coords.insert((center_x + x, center_y + y, center_z - z));
-                    x += 1;
-                    loop {
-                        let cubic_distance_from_center = x * x + z * z + y * y;
-                        let squared_view_distance = chunk_view_distance;
-                        if cubic_distance_from_center > squared_view_distance {
-                            break;
-                        }
-                        visible_chunk_coords.insert((center_x + x, center_y + y, center_z - z));
-                        visible_chunk_coords.insert((center_x - x, center_y + y, center_z - z));
-                        x += 1;
-                    }
-                }
+        // Chunks left over by the budget are still missing next frame and get queued again
+        let mut queue = ChunkQueue::from_coords(
+            visible_chunk_coords,
+            &viewers,
+            settings.order,
+            budget.frustum_margin,
+        );
+
+        while !frame_budget.is_exhausted() {
+            let coord = match queue.pop() {
+                Some(coord) => coord,
+                None => break,
+            };
 
-                z += 1;
+            let lod = lod_at(coord);
+            let transitions = transitions_at(coord, lod);
+
+            // Empty chunks are kept in the map without a mesh so they aren't queued again, the
+            // density bounds say nothing about provided chunks
+            if provider.is_none() && settings.is_chunk_empty(&*density.0, coord) {
+                let chunk_entity =
+                    spawn_chunk(&mut commands, terrain, settings, coord, lod, transitions);
+
+                chunk_map.insert(coord, chunk_entity);
+
+                continue;
             }
+
+            frame_budget.spend();
+
+            let task = generator.generate(density, provider, settings, coord, lod, transitions);
+
+            let chunk_entity =
+                spawn_chunk(&mut commands, terrain, settings, coord, lod, transitions);
+
+            commands.entity(chunk_entity).insert(task);
+
+            chunk_map.insert(coord, chunk_entity);
+
+            chunk_queued_events.send(ChunkQueued {
+                terrain,
+                entity: chunk_entity,
+                coord,
+            });
         }
-    }
 
-    for (entity, terrain_chunk) in terrain_chunks_query.iter() {
-        if !visible_chunk_coords.contains(&terrain_chunk.coords) {
-            terrain.remove_chunk(
-                terrain_chunk.coords.0,
-                terrain_chunk.coords.1,
-                terrain_chunk.coords.2,
-            );
+        progress.queued += queue.len();
+    }
+}
 
-            let mut entity = commands.entity(entity);
+/// Starts generation tasks with the resources borrowed by a system
+pub(crate) struct ChunkGenerator<'a> {
+    backend: MeshingBackend,
+    /// Whether the GPU was asked for explicitly instead of picked by `MeshingBackend::Auto`
+    forced_gpu: bool,
+    #[cfg(feature = "gpu-compute")]
+    gpu_jobs: &'a GpuChunkJobs,
+    task_pool: &'a AsyncComputeTaskPool,
+    post_processors: &'a MeshPostProcessors,
+    vertex_colors: &'a MeshVertexColors,
+    cache: Option<&'a ChunkCache>,
+}
 
-            entity.despawn();
+impl<'a> ChunkGenerator<'a> {
+    pub(crate) fn new(
+        backend: MeshingBackend,
+        status: &TerrainStatus,
+        render_device: Option<&RenderDevice>,
+        #[cfg(feature = "gpu-compute")] gpu_jobs: &'a GpuChunkJobs,
+        task_pool: &'a AsyncComputeTaskPool,
+        post_processors: &'a MeshPostProcessors,
+        vertex_colors: &'a MeshVertexColors,
+        cache: Option<&'a ChunkCache>,
+    ) -> Self {
+        let backend = if status.gpu_fallback {
+            MeshingBackend::Cpu
         } else {
-            visible_chunk_coords.remove(&terrain_chunk.coords);
+            backend
+        };
+        let forced_gpu = matches!(backend, MeshingBackend::Gpu | MeshingBackend::GpuResident);
+
+        Self {
+            backend: backend.resolve(render_device),
+            forced_gpu,
+            #[cfg(feature = "gpu-compute")]
+            gpu_jobs,
+            task_pool,
+            post_processors,
+            vertex_colors,
+            cache,
         }
     }
 
-    let chunk_size = terrain.chunk_size;
+    /// Spawns the task generating the mesh of a chunk at detail level `lod`, stitched to the
+    /// finer chunks around it by `transitions`
+    pub(crate) fn generate(
+        &self,
+        density: &TerrainDensity,
+        provider: Option<&TerrainChunkProvider>,
+        settings: &TerrainSettings,
+        coord: ChunkCoord,
+        lod: u32,
+        transitions: ChunkTransitions,
+    ) -> ChunkMeshTask {
+        let mut settings = settings.for_lod(lod);
+        let post_processors = self.post_processors.clone();
+        let vertex_colors = self.vertex_colors.clone();
+        let cache = self.cache.cloned();
+
+        #[cfg(feature = "gpu-compute")]
+        let gpu_density = terrain_gpu::GpuDensity::of(&*density.0);
+        #[cfg(feature = "gpu-compute")]
+        let density_runs_on_gpu = gpu_density.is_some();
+        #[cfg(not(feature = "gpu-compute"))]
+        let density_runs_on_gpu = false;
+
+        // Transition cells are only built by the marching cubes of the CPU mesher
+        if settings.seams == SeamMode::TransitionCells
+            && (settings.algorithm != MeshingAlgorithm::MarchingCubes
+                || !cfg!(feature = "cpu-mesher"))
+        {
+            settings.seams = SeamMode::Skirts;
+        }
 
-    for (x, y, z) in visible_chunk_coords {
-        let render_device = render_device.clone();
-        let render_queue = render_queue.clone();
+        let stitched = settings.seams == SeamMode::TransitionCells && !transitions.is_empty();
+
+        // Only the CPU mesher reads density grids and builds transition cells, so provided and
+        // stitched chunks never run on the GPU, and neither do algorithms the compute shader
+        // lacks. Density fields it can't express only reach it when the GPU was forced, and
+        // fail there.
+        let provider = provider.filter(|_| lod == 0).cloned();
+        let backend = if (provider.is_some()
+            || stitched
+            || !settings.algorithm.runs_on_gpu()
+            || (!density_runs_on_gpu && !self.forced_gpu))
+            && cfg!(feature = "cpu-mesher")
+        {
+            MeshingBackend::Cpu
+        } else {
+            self.backend
+        };
+
+        #[cfg(not(feature = "cpu-mesher"))]
+        let _ = (density, provider, transitions);
+
+        match backend {
+            #[cfg(feature = "gpu-compute")]
+            MeshingBackend::Gpu => {
+                let gpu_density = match gpu_density {
+                    Some(gpu_density) => gpu_density,
+                    None => {
+                        return ChunkMeshTask::failed(
+                            self.task_pool,
+                            TerrainError::UnsupportedGpuDensity,
+                        )
+                    }
+                };
+                let mesh_data = terrain_gpu::generate_mesh_data(
+                    self.gpu_jobs.clone(),
+                    coord,
+                    settings.clone(),
+                    gpu_density,
+                );
+                let density = density.clone();
+
+                // The compute shader only starts once the future is polled, so cache hits skip it
+                ChunkMeshTask::spawn(self.task_pool, async move {
+                    let mut gpu_time = Duration::ZERO;
+
+                    let mesh_data = match cache
+                        .as_ref()
+                        .and_then(|cache| cache.load_mesh(&settings, coord))
+                    {
+                        Some(mesh_data) => mesh_data,
+                        None => {
+                            let started = Instant::now();
+                            let mesh_data = mesh_data.await?;
+                            gpu_time = started.elapsed();
+
+                            if let Some(cache) = &cache {
+                                if let Err(error) = cache.store_mesh(&settings, coord, &mesh_data) {
+                                    warn!(
+                                        "Failed to cache the mesh of chunk {:?}: {}",
+                                        coord, error
+                                    );
+                                }
+                            }
+
+                            mesh_data
+                        }
+                    };
+
+                    let mesh = finish_mesh(
+                        mesh_data,
+                        None,
+                        &*density.0,
+                        &settings,
+                        coord,
+                        lod,
+                        &post_processors,
+                        &vertex_colors,
+                    );
 
-        let task = task_pool.spawn(async move {
-            let buffer_size =
-                (chunk_size * chunk_size * chunk_size * (Cube::std140_size_static() as u32))
-                    as BufferAddress;
-
-            let shader = Shader::from_wgsl(include_str!("../assets/chunk.wgsl"));
-            let shader_module = render_device.create_shader_module(&shader);
-
-            let input_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-                contents: bytes_of(
-                    &InputBuffer {
-                        chunk_size: chunk_size,
-                        position: Vec3::new(
-                            x as f32 * chunk_size as f32 - (chunk_size as f32 / 2.0),
-                            y as f32 * chunk_size as f32 - (chunk_size as f32 / 2.0),
-                            z as f32 * chunk_size as f32 - (chunk_size as f32 / 2.0),
-                        ),
+                    Ok((mesh, gpu_time))
+                })
+            }
+            #[cfg(feature = "gpu-compute")]
+            MeshingBackend::GpuResident => {
+                let gpu_density = match gpu_density {
+                    Some(gpu_density) => gpu_density,
+                    None => {
+                        return ChunkMeshTask::failed(
+                            self.task_pool,
+                            TerrainError::UnsupportedGpuDensity,
+                        )
+                    }
+                };
+                let mesh = terrain_gpu::generate_gpu_mesh(
+                    self.gpu_jobs.clone(),
+                    coord,
+                    settings,
+                    gpu_density,
+                );
+
+                // Only waits for the compute shader, which keeps the vertices
+                ChunkMeshTask::spawn(self.task_pool, async move {
+                    let started = Instant::now();
+                    let mesh = mesh.await?;
+
+                    Ok((ChunkMesh::Gpu(mesh), started.elapsed()))
+                })
+            }
+            #[cfg(feature = "cpu-mesher")]
+            MeshingBackend::Cpu => {
+                let density = density.clone();
+
+                // Only the plain polygonization can be patched cell by cell
+                let patchable = settings.incremental_edits
+                    && lod == 0
+                    && settings.algorithm == MeshingAlgorithm::MarchingCubes
+                    && settings.seams != SeamMode::Skirts
+                    && post_processors.is_empty();
+
+                ChunkMeshTask::spawn(self.task_pool, async move {
+                    if patchable {
+                        let grid = match (&provider, &cache) {
+                            (Some(provider), _) => {
+                                provided_chunk_density(&*provider.0, &*density.0, coord, &settings)
+                            }
+                            (None, Some(cache)) => {
+                                cached_chunk_density(cache, &*density.0, coord, &settings)
+                            }
+                            (None, None) => sample_chunk_density(&*density.0, coord, &settings),
+                        };
+
+                        let patch =
+                            ChunkPatch::new(grid, &*density.0, &settings, coord, &vertex_colors);
+                        let collision = collision_mesh(patch.grid(), &settings);
+                        let wireframe = wireframe_mesh(patch.mesh_data(), &settings);
+                        let (mesh, bounds) =
+                            build_chunk_mesh(patch.mesh_data().clone(), &settings, coord);
+
+                        let mesh = ChunkMesh::Mesh {
+                            mesh,
+                            bounds,
+                            collision,
+                            patch: Some(patch),
+                            wireframe,
+                        };
+
+                        return Ok((mesh, Duration::ZERO));
                     }
-                    .as_std140(),
-                ),
-                label: None,
-                usage: BufferUsages::STORAGE,
-            });
 
-            let output_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: None,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
-                mapped_at_creation: false,
-                size: buffer_size,
-            });
+                    let (mesh_data, collision) = match (&provider, &cache) {
+                        (Some(provider), _) => generate_provided_chunk_mesh_data(
+                            &*provider.0,
+                            &*density.0,
+                            coord,
+                            &settings,
+                        ),
+                        (None, Some(cache)) => generate_cached_chunk_mesh_data(
+                            cache,
+                            &*density.0,
+                            coord,
+                            &settings,
+                            &transitions,
+                        ),
+                        (None, None) => mesh_chunk_resolutions(
+                            &sample_chunk_density(&*density.0, coord, &settings),
+                            &*density.0,
+                            coord,
+                            &settings,
+                            &transitions,
+                        ),
+                    };
+
+                    let mesh = finish_mesh(
+                        mesh_data,
+                        collision,
+                        &*density.0,
+                        &settings,
+                        coord,
+                        lod,
+                        &post_processors,
+                        &vertex_colors,
+                    );
 
-            let bind_group_layout =
-                render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                    label: None,
-                    entries: &[
-                        BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: ShaderStages::COMPUTE,
-                            ty: BindingType::Buffer {
-                                ty: BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        },
-                        BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: ShaderStages::COMPUTE,
-                            ty: BindingType::Buffer {
-                                ty: BufferBindingType::Storage { read_only: false },
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        },
-                    ],
-                });
+                    Ok((mesh, Duration::ZERO))
+                })
+            }
+            _ => unreachable!("resolved meshing backend is not enabled"),
+        }
+    }
+}
 
-            let pipeline_layout = render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
-                label: None,
-                push_constant_ranges: &[],
-                bind_group_layouts: &[&bind_group_layout],
-            });
+/// Runs the post processors, decimates distant chunks, drops degenerate triangles, builds the
+/// seams, projects the UVs and bakes the ambient occlusion of a generated chunk
+fn finish_mesh(
+    mesh_data: MeshData,
+    collision: Option<MeshData>,
+    density: &dyn DensityField,
+    settings: &TerrainSettings,
+    coord: ChunkCoord,
+    lod: u32,
+    post_processors: &MeshPostProcessors,
+    vertex_colors: &MeshVertexColors,
+) -> ChunkMesh {
+    let mut mesh_data = post_processors.process(mesh_data);
+
+    if lod > 0 && settings.lod_decimation < 1.0 {
+        mesh_data = decimate::decimate(mesh_data, settings.lod_decimation);
+    }
 
-            let compute_pipeline =
-                render_device.create_compute_pipeline(&ComputePipelineDescriptor {
-                    label: None,
-                    layout: Some(&pipeline_layout),
-                    module: &shader_module,
-                    entry_point: "main",
-                });
+    mesh_data.remove_degenerate_triangles(MeshData::MIN_TRIANGLE_AREA);
 
-            let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
-                label: None,
-                layout: &bind_group_layout,
-                entries: &[
-                    BindGroupEntry {
-                        binding: 0,
-                        resource: input_buffer.as_entire_binding(),
-                    },
-                    BindGroupEntry {
-                        binding: 1,
-                        resource: output_buffer.as_entire_binding(),
-                    },
-                ],
-            });
+    if settings.seams == SeamMode::Skirts {
+        mesh_data.add_skirts(settings.chunk_size as f32, 1.0);
+    }
 
-            let mut command_encoder =
-                render_device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+    color_chunk_mesh(&mut mesh_data, density, settings, coord, vertex_colors);
 
-            {
-                let mut compute_pass =
-                    command_encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
-                compute_pass.set_pipeline(&compute_pipeline);
-                compute_pass.set_bind_group(0, &*bind_group, &[]);
+    let wireframe = wireframe_mesh(&mesh_data, settings);
+    let (mesh, bounds) = build_chunk_mesh(mesh_data, settings, coord);
 
-                compute_pass.dispatch(chunk_size / 8, chunk_size / 8, chunk_size / 8);
-            }
+    ChunkMesh::Mesh {
+        mesh,
+        bounds,
+        collision,
+        patch: None,
+        wireframe,
+    }
+}
 
-            let buffer = render_device.create_buffer(&BufferDescriptor {
-                label: None,
-                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
-                mapped_at_creation: false,
-                size: buffer_size,
-            });
+/// Line list of the edges of a chunk mesh when `TerrainSettings::wireframe` is enabled
+pub(crate) fn wireframe_mesh(mesh_data: &MeshData, settings: &TerrainSettings) -> Option<Mesh> {
+    if !settings.wireframe {
+        return None;
+    }
 
-            command_encoder.copy_buffer_to_buffer(&output_buffer, 0, &buffer, 0, buffer_size);
+    Some(mesh_data.clone().into_wireframe_mesh())
+}
 
-            let gpu_commands = command_encoder.finish();
+/// Fills the vertex colors of a chunk mesh from the `VertexColorSource` and multiplies the baked
+/// ambient occlusion into them
+pub(crate) fn color_chunk_mesh(
+    mesh_data: &mut MeshData,
+    density: &dyn DensityField,
+    settings: &TerrainSettings,
+    coord: ChunkCoord,
+    vertex_colors: &MeshVertexColors,
+) {
+    if let Some(colors) = vertex_colors.colors(
+        &mesh_data,
+        density,
+        settings.iso_level,
+        settings.get_chunk_origin(coord),
+        settings.voxel_scale,
+    ) {
+        mesh_data.colors = colors;
+    }
 
-            render_queue.submit([gpu_commands]);
+    if let Some(ambient_occlusion) = settings.ambient_occlusion {
+        let occlusion = ambient_occlusion.bake(
+            &mesh_data,
+            density,
+            settings.iso_level,
+            settings.get_chunk_origin(coord),
+            settings.voxel_scale,
+        );
+        let occlusion_colors = ambient_occlusion.colors(&occlusion);
+
+        if mesh_data.colors.is_empty() {
+            mesh_data.colors = occlusion_colors;
+        } else {
+            for (color, occlusion) in mesh_data.colors.iter_mut().zip(occlusion_colors) {
+                // Alpha is left alone, it may carry a material id
+                for (channel, light) in color.iter_mut().zip(occlusion.iter()).take(3) {
+                    *channel *= light;
+                }
+            }
+        }
+    }
+}
 
-            let buffer_slice = buffer.slice(..);
+/// Projects the UVs of a chunk mesh and builds its `Mesh` and bounds
+pub(crate) fn build_chunk_mesh(
+    mesh_data: MeshData,
+    settings: &TerrainSettings,
+    coord: ChunkCoord,
+) -> (Mesh, Option<ChunkBounds>) {
+    // Textures repeat, so only the fraction of the chunk offset matters, which keeps the UVs of
+    // far away chunks precise
+    let scale = settings.voxel_scale / settings.texture_scale;
+    let offset = settings.get_chunk_origin(coord) * scale;
+    let uvs = mesh_data.box_projected_uvs(scale, offset - offset.floor());
+
+    let bounds = ChunkBounds::from_mesh(&mesh_data);
+
+    (mesh_data.into_mesh_with_uvs(uvs), bounds)
+}
 
-            let buffer_future = buffer_slice.map_async(MapMode::Read);
+/// Spawns a chunk as a child of its terrain, already placed so it follows the terrain when it
+/// is moved or rotated before the mesh is ready
+fn spawn_chunk(
+    commands: &mut Commands,
+    terrain: Entity,
+    settings: &TerrainSettings,
+    coord: ChunkCoord,
+    lod: u32,
+    transitions: ChunkTransitions,
+) -> Entity {
+    let settings = settings.for_lod(lod);
+
+    let chunk_entity = commands
+        .spawn()
+        .insert(TerrainChunk {
+            terrain,
+            coord,
+            lod,
+            transitions,
+        })
+        .insert(Name::new(format!(
+            "Chunk ({}, {}, {})",
+            coord.0.x, coord.0.y, coord.0.z
+        )))
+        .insert(
+            Transform::from_translation(settings.get_chunk_translation(coord))
+                .with_scale(Vec3::splat(settings.voxel_scale)),
+        )
+        .insert(GlobalTransform::default())
+        .id();
 
-            let result = buffer_future.await;
+    commands.entity(terrain).push_children(&[chunk_entity]);
 
-            let mut triangles: Vec<Triangle> = Vec::new();
+    chunk_entity
+}
 
-            if let Ok(_) = result {
-                let buffer_data = buffer_slice.get_mapped_range();
+/// Despawning the entity drops its strong mesh and material handles, which frees both assets,
+/// and drops the generation task of chunks that were not meshed yet, cancelling it and
+/// releasing its GPU buffers
+fn despawn_chunk(
+    commands: &mut Commands,
+    terrain: Entity,
+    chunk_map: &mut ChunkMap,
+    entity: Entity,
+    coord: ChunkCoord,
+    chunk_despawned_events: &mut EventWriter<ChunkDespawned>,
+) {
+    chunk_map.remove(coord);
 
-                let cubes: &[Std140Cube] = bytemuck::cast_slice(&buffer_data);
+    commands.entity(entity).despawn_recursive();
 
-                for cube in cubes.iter() {
-                    let cube = Cube::from_std140(*cube);
+    chunk_despawned_events.send(ChunkDespawned {
+        terrain,
+        entity,
+        coord,
+    });
+}
 
-                    for i in 0..cube.triangle_count {
-                        triangles.push(cube.triangles[i as usize]);
-                    }
-                }
+/// Swaps the `SimplexDensity` of terrains whose `TerrainSettings::noise` changed for one summing
+/// the new octaves, changing the settings already regenerates their chunks
+///
+/// Only reacts to changes of the noise, so a `SimplexDensity` with its own octaves handed to
+/// `TerrainBundle::new` stays until the settings ask for different ones. Densities with
+/// modifiers are kept, swapping them would drop the modifiers.
+fn apply_noise_settings(
+    mut applied: Local<HashMap<Entity, SimplexNoise>>,
+    mut terrain_query: Query<
+        (Entity, &TerrainSettings, &mut TerrainDensity),
+        (With<Terrain>, Changed<TerrainSettings>),
+    >,
+) {
+    for (terrain, settings, mut density) in terrain_query.iter_mut() {
+        let previous = applied.insert(terrain, settings.noise);
 
-                drop(buffer_data);
-            }
+        if previous.map_or(true, |previous| previous == settings.noise) {
+            continue;
+        }
 
-            buffer.unmap();
-            buffer.destroy();
+        // Keeps the seed of the density, which may differ from the one of the settings
+        let seed = match density.0.simplex_noise() {
+            Some((_, seed)) if density.0.modifiers().is_empty() => seed,
+            _ => continue,
+        };
 
-            let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        density.0 = Arc::new(SimplexDensity::with_noise(seed, settings.noise));
+    }
+}
 
-            let vertices = triangles
-                .iter()
-                .map(|triangle| [triangle.a, triangle.b, triangle.c])
-                .flatten()
-                .map(|vector| [vector.x, vector.y, vector.z])
-                .collect::<Vec<_>>();
-            let indices = (0..vertices.len())
-                .map(|index| index as u32)
-                .collect::<Vec<u32>>();
-            let uvs = (0..vertices.len())
-                .map(|_| [0.0, 0.0])
-                .collect::<Vec<[f32; 2]>>();
-
-            let mut normals: Vec<[f32; 3]> = Vec::new();
-
-            for triangle in indices.chunks(3) {
-                let a = Vec3::from(vertices[(triangle)[0] as usize]);
-                let b = Vec3::from(vertices[(triangle)[1] as usize]);
-                let c = Vec3::from(vertices[(triangle)[2] as usize]);
-
-                let normal = (b - a).cross(c - a).normalize();
-
-                normals.push(normal.into());
-                normals.push(normal.into());
-                normals.push(normal.into());
+fn regenerate_terrain(
+    mut commands: Commands,
+    mut terrain_query: Query<
+        (
+            Entity,
+            &mut TerrainSettings,
+            &mut TerrainDensity,
+            &mut ChunkMap,
+        ),
+        With<Terrain>,
+    >,
+    mut regenerate_terrain_events: EventReader<RegenerateTerrain>,
+    mut chunk_despawned_events: EventWriter<ChunkDespawned>,
+) {
+    for event in regenerate_terrain_events.iter() {
+        for (terrain, mut settings, mut density, mut chunk_map) in terrain_query.iter_mut() {
+            if event.terrain.map_or(false, |target| target != terrain) {
+                continue;
             }
 
-            mesh.set_indices(Some(Indices::U32(indices)));
+            if let Some(seed) = event.seed {
+                // Changing the settings makes `update_chunks` regenerate every chunk
+                settings.seed = seed;
 
-            mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-            mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-            mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+                if let Some(reseeded) = density.0.with_seed(seed) {
+                    density.0 = reseeded;
+                }
 
-            // let mesh = TerrainChunk::generate_mesh((x, y, z), chunk_size);
+                continue;
+            }
 
-            let material = StandardMaterial {
-                base_color: Color::BLUE,
-                perceptual_roughness: 1.0,
-                ..Default::default()
+            let chunks = match &event.chunks {
+                Some(chunks) => chunks.clone(),
+                None => chunk_map.coords().collect(),
             };
 
-            (mesh, material)
-        });
-
-        let chunk_entity = commands
-            .spawn()
-            .insert(TerrainChunk { coords: (x, y, z) })
-            .insert(task)
-            .id();
-
-        terrain.set_chunk(x, y, z, chunk_entity);
+            for coord in chunks {
+                if let Some(entity) = chunk_map.get(coord) {
+                    despawn_chunk(
+                        &mut commands,
+                        terrain,
+                        &mut chunk_map,
+                        entity,
+                        coord,
+                        &mut chunk_despawned_events,
+                    );
+                }
+            }
+        }
     }
 }
 
@@ -562,29 +1942,310 @@ fn handle_terrain_chunk_tasks(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    terrain: Res<Terrain>,
-    mut terrain_chunk_tasks: Query<(Entity, &TerrainChunk, &mut Task<(Mesh, StandardMaterial)>)>,
+    mut status: ResMut<TerrainStatus>,
+    mut terrain_query: Query<(&TerrainSettings, &mut ChunkMap), With<Terrain>>,
+    terrain_chunk_tasks: Query<(Entity, &TerrainChunk, &ChunkMeshTask)>,
+    wireframe_query: Query<&ChunkWireframe>,
+    mut chunk_density_ready_events: EventWriter<ChunkDensityReady>,
+    mut chunk_meshed_events: EventWriter<ChunkMeshed>,
+    mut chunk_despawned_events: EventWriter<ChunkDespawned>,
+    budget: Res<GenerationBudget>,
 ) {
-    for (entity, chunk, mut task) in terrain_chunk_tasks.iter_mut() {
-        if let Some((mesh, material)) = future::block_on(future::poll_once(&mut *task)) {
-            let material = materials.add(material);
+    let mut frame_budget = FrameBudget::new(budget.uploads_per_frame, budget.frame_time);
+
+    for (entity, chunk, task) in terrain_chunk_tasks.iter() {
+        if frame_budget.is_exhausted() {
+            break;
+        }
+
+        let (settings, mut chunk_map) = match terrain_query.get_mut(chunk.terrain) {
+            Ok(terrain) => terrain,
+            Err(_) => {
+                // The terrain was despawned while the chunk was generating, dropping the task
+                // with the chunk cancels it
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+
+        // Chunks unloaded this frame are despawned together with their task, whatever it
+        // finished is thrown away
+        if chunk_map.get(chunk.coord) != Some(entity) {
+            continue;
+        }
+
+        let result = match task.take_result() {
+            Some(result) => result,
+            None => continue,
+        };
+
+        let GeneratedChunk {
+            mesh,
+            cpu_time,
+            gpu_time,
+        } = match result {
+            Ok(generated) => generated,
+            Err(error) => {
+                warn!("Failed to generate chunk {:?}: {}", chunk.coord, error);
+
+                // Forcing the GPU for a density it can't express is no failure of the device
+                let gpu_failed = error != TerrainError::UnsupportedGpuDensity;
+
+                status.last_error = Some(error);
+
+                if cfg!(feature = "cpu-mesher") && gpu_failed {
+                    // Despawning lets `update_chunks` queue the chunk again on the CPU
+                    status.gpu_fallback = true;
+
+                    despawn_chunk(
+                        &mut commands,
+                        chunk.terrain,
+                        &mut chunk_map,
+                        entity,
+                        chunk.coord,
+                        &mut chunk_despawned_events,
+                    );
+                } else {
+                    commands.entity(entity).remove::<ChunkMeshTask>();
+                }
+
+                continue;
+            }
+        };
+
+        let settings = settings.for_lod(chunk.lod);
+
+        chunk_density_ready_events.send(ChunkDensityReady {
+            terrain: chunk.terrain,
+            entity,
+            coord: chunk.coord,
+        });
+
+        frame_budget.spend();
+
+        if let Ok(previous) = wireframe_query.get(entity) {
+            commands.entity(previous.entity).despawn();
+            commands.entity(entity).remove::<ChunkWireframe>();
+        }
+
+        let mesh = match mesh {
+            ChunkMesh::Mesh {
+                mesh,
+                bounds,
+                collision,
+                patch,
+                wireframe,
+            } => {
+                commands
+                    .entity(entity)
+                    .insert(ChunkMeshStats::from_mesh(&mesh, cpu_time, gpu_time));
+
+                let mesh = meshes.add(mesh);
+
+                match bounds {
+                    Some(bounds) => commands.entity(entity).insert(bounds),
+                    None => commands.entity(entity).remove::<ChunkBounds>(),
+                };
+
+                match collision {
+                    Some(collision) => commands
+                        .entity(entity)
+                        .insert(ChunkCollisionMesh(collision)),
+                    None => commands.entity(entity).remove::<ChunkCollisionMesh>(),
+                };
+
+                match patch {
+                    Some(patch) => commands.entity(entity).insert(patch),
+                    None => commands.entity(entity).remove::<ChunkPatch>(),
+                };
+
+                if let Some(wireframe) = wireframe {
+                    let wireframe = meshes.add(wireframe);
+
+                    // Placed by the transform of the chunk
+                    let wireframe_entity = commands
+                        .spawn_bundle(PbrBundle {
+                            mesh: wireframe.clone(),
+                            material: materials.add(StandardMaterial {
+                                base_color: Color::WHITE,
+                                unlit: true,
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        })
+                        .id();
+
+                    commands
+                        .entity(entity)
+                        .push_children(&[wireframe_entity])
+                        .insert(ChunkWireframe {
+                            entity: wireframe_entity,
+                            mesh: wireframe,
+                        });
+                }
 
-            if terrain.has_chunk(chunk.coords.0, chunk.coords.1, chunk.coords.2) {
                 commands.entity(entity).insert_bundle(PbrBundle {
-                    mesh: meshes.add(mesh),
-                    material: material.clone(),
-                    transform: Transform::from_xyz(
-                        chunk.coords.0 as f32 * terrain.chunk_size as f32,
-                        chunk.coords.1 as f32 * terrain.chunk_size as f32,
-                        chunk.coords.2 as f32 * terrain.chunk_size as f32,
-                    ),
+                    mesh: mesh.clone(),
+                    material: materials.add(StandardMaterial {
+                        base_color: Color::BLUE,
+                        perceptual_roughness: 1.0,
+                        ..Default::default()
+                    }),
+                    transform: Transform::from_translation(
+                        settings.get_chunk_translation(chunk.coord),
+                    )
+                    .with_scale(Vec3::splat(settings.voxel_scale)),
                     ..Default::default()
                 });
 
-                commands
-                    .entity(entity)
-                    .remove::<Task<(Mesh, StandardMaterial)>>();
+                // Chunks meshed again after falling back to the CPU drop their GPU vertices
+                #[cfg(feature = "gpu-compute")]
+                commands.entity(entity).remove::<GpuChunkMesh>();
+
+                Some(mesh)
+            }
+            #[cfg(feature = "gpu-compute")]
+            ChunkMesh::Gpu(gpu_mesh) => {
+                let vertices = gpu_mesh
+                    .as_ref()
+                    .map_or(0, |gpu_mesh| gpu_mesh.vertex_count());
+
+                // Drawn without an index buffer, every three vertices make a triangle
+                commands.entity(entity).insert(ChunkMeshStats {
+                    triangles: vertices / 3,
+                    vertices,
+                    cpu_ms: cpu_time.as_secs_f32() * 1000.0,
+                    gpu_ms: gpu_time.as_secs_f32() * 1000.0,
+                    buffer_bytes: gpu_mesh.as_ref().map_or(0, |gpu_mesh| gpu_mesh.bytes()),
+                });
+
+                // Placed by `spawn_chunk` already, the render world reads its global transform
+                match gpu_mesh {
+                    Some(gpu_mesh) => {
+                        commands
+                            .entity(entity)
+                            .insert(gpu_mesh)
+                            .insert(ChunkBounds {
+                                min: Vec3::ZERO,
+                                max: Vec3::splat(settings.chunk_size as f32),
+                            })
+                    }
+                    None => commands
+                        .entity(entity)
+                        .remove::<GpuChunkMesh>()
+                        .remove::<ChunkBounds>(),
+                };
+
+                // Chunks meshed on the CPU before keep nothing to patch
+                commands.entity(entity).remove::<ChunkPatch>();
+
+                None
             }
+        };
+
+        commands.entity(entity).remove::<ChunkMeshTask>();
+
+        chunk_meshed_events.send(ChunkMeshed {
+            terrain: chunk.terrain,
+            entity,
+            coord: chunk.coord,
+            mesh,
+        });
+    }
+}
+
+#[cfg(all(test, feature = "cpu-mesher"))]
+mod tests {
+    use super::*;
+    use crate::mesh_validate::MeshValidator;
+
+    /// Meshes a chunk and its neighbours on the positive side of every axis and compares the
+    /// faces they share, like `--check-seams` of the headless runner
+    fn assert_seamless(settings: &TerrainSettings, coord: ChunkCoord) {
+        let density = SimplexDensity::with_noise(settings.seed, settings.noise);
+        let validator = MeshValidator {
+            chunk_size: Some(settings.chunk_size as f32),
+            ..Default::default()
+        };
+        let mesh_data = generate_chunk_mesh_data(&density, coord, settings);
+
+        assert!(!mesh_data.is_empty(), "{:?} has no surface", coord);
+
+        for axis in [IVec3::X, IVec3::Y, IVec3::Z].iter() {
+            let neighbor = ChunkCoord(coord.0 + *axis);
+            let neighbor_mesh_data = generate_chunk_mesh_data(&density, neighbor, settings);
+            let offset = axis.as_vec3() * settings.chunk_size as f32;
+
+            assert_eq!(
+                validator.seam_gaps(&mesh_data, &neighbor_mesh_data, offset),
+                0,
+                "crack between {:?} and {:?}",
+                coord,
+                neighbor
+            );
         }
     }
+
+    #[test]
+    fn neighbouring_chunks_share_their_faces() {
+        assert_seamless(&TerrainSettings::default(), ChunkCoord::default());
+    }
+
+    #[test]
+    fn neighbouring_chunks_of_scaled_voxels_share_their_faces() {
+        let settings = TerrainSettings {
+            chunk_size: 16,
+            voxel_scale: 0.5,
+            seed: 1337,
+            ..TerrainSettings::default()
+        };
+
+        assert_seamless(&settings, ChunkCoord(IVec3::new(-1, 0, 2)));
+    }
+
+    #[test]
+    fn transition_cells_share_their_faces_with_finer_chunks() {
+        let settings = TerrainSettings {
+            seams: SeamMode::TransitionCells,
+            ..TerrainSettings::default()
+        };
+        let coarse_settings = settings.for_lod(1);
+        let density = SimplexDensity::with_noise(settings.seed, settings.noise);
+        let validator = MeshValidator {
+            chunk_size: Some(coarse_settings.chunk_size as f32),
+            ..Default::default()
+        };
+
+        let coord = ChunkCoord::default();
+        let neighbor = ChunkCoord(IVec3::X);
+        let transitions = ChunkTransitions::new(coord, 1, |chunk| (chunk != neighbor) as u32);
+
+        let mesh_data = mesh_chunk_density_with_transitions(
+            &sample_chunk_density(&density, coord, &coarse_settings),
+            &density,
+            coord,
+            &coarse_settings,
+            &transitions,
+        );
+        let mut neighbor_mesh_data = generate_chunk_mesh_data(&density, neighbor, &settings);
+
+        assert!(
+            !neighbor_mesh_data.is_empty(),
+            "{:?} has no surface",
+            neighbor
+        );
+
+        // In cells of the coarser chunk
+        neighbor_mesh_data.transform(0.5, Vec3::ZERO);
+
+        let offset = Vec3::X * coarse_settings.chunk_size as f32;
+
+        assert_eq!(
+            validator.seam_gaps(&mesh_data, &neighbor_mesh_data, offset),
+            0,
+            "crack between {:?} and the finer {:?}",
+            coord,
+            neighbor
+        );
+    }
 }
@@ -0,0 +1,422 @@
+use crate::gpu::{PooledBuffer, TrackedAllocation, VertexPrecision};
+use bevy::{
+    app::{App, Plugin},
+    core_pipeline::Transparent3d,
+    ecs::{
+        entity::Entity,
+        system::{Commands, Local, Query, Res, ResMut},
+        world::World,
+    },
+    math::{Mat4, Vec3},
+    render2::{
+        render_phase::{Draw, DrawFunctions, RenderPhase, TrackedRenderPass},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, Buffer,
+            BufferBindingType, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
+            DepthStencilState, DynamicUniformVec, Face, FragmentState, FrontFace, MultisampleState,
+            PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology,
+            RenderPipeline, RenderPipelineDescriptor, ShaderStages, StencilFaceState, StencilState,
+            TextureFormat, VertexAttribute, VertexBufferLayout, VertexFormat, VertexState,
+            VertexStepMode,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        shader::Shader,
+        texture::BevyDefault,
+        view::{ExtractedView, Msaa, ViewUniformOffset, ViewUniforms},
+        RenderApp, RenderStage,
+    },
+    transform::components::GlobalTransform,
+};
+use crevice::std140::AsStd140;
+
+/// Layout of the vertices written for a chunk, picked from the `VertexPrecision` for its size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChunkVertexFormat {
+    /// `Vertex` of `terrain/mesher.wgsl`, a position and a normal padded to four floats
+    Full,
+    /// `QuantizedVertex`, the position relative to the chunk size as unorm16 and the normal as
+    /// snorm16, both padded to four components
+    Quantized,
+}
+
+impl ChunkVertexFormat {
+    pub fn new(precision: VertexPrecision, chunk_size: u32) -> Self {
+        if precision.quantizes(chunk_size) {
+            ChunkVertexFormat::Quantized
+        } else {
+            ChunkVertexFormat::Full
+        }
+    }
+
+    /// Size of a vertex in bytes
+    pub fn size(self) -> u64 {
+        match self {
+            ChunkVertexFormat::Full => 32,
+            ChunkVertexFormat::Quantized => 16,
+        }
+    }
+
+    /// Words of a vertex, a readback of the vertex buffer is decoded in steps of these
+    pub fn words(self) -> usize {
+        self.size() as usize / 4
+    }
+
+    /// Position and normal of a vertex read back from a chunk of the given size
+    pub fn decode(self, vertex: &[u32], chunk_size: u32) -> ([f32; 3], [f32; 3]) {
+        match self {
+            ChunkVertexFormat::Full => (
+                [
+                    f32::from_bits(vertex[0]),
+                    f32::from_bits(vertex[1]),
+                    f32::from_bits(vertex[2]),
+                ],
+                [
+                    f32::from_bits(vertex[4]),
+                    f32::from_bits(vertex[5]),
+                    f32::from_bits(vertex[6]),
+                ],
+            ),
+            ChunkVertexFormat::Quantized => {
+                // `pack2x16unorm` and `pack2x16snorm` put the first component in the low bits
+                let unorm = |bits: u32| (bits & 0xffff) as f32 / 65535.0 * chunk_size as f32;
+                let snorm = |bits: u32| ((bits & 0xffff) as u16 as i16 as f32 / 32767.0).max(-1.0);
+
+                (
+                    [unorm(vertex[0]), unorm(vertex[0] >> 16), unorm(vertex[1])],
+                    [snorm(vertex[2]), snorm(vertex[2] >> 16), snorm(vertex[3])],
+                )
+            }
+        }
+    }
+
+    /// Scale taking the positions of a chunk of the given size back to voxels when drawing
+    fn position_scale(self, chunk_size: u32) -> f32 {
+        match self {
+            ChunkVertexFormat::Full => 1.0,
+            ChunkVertexFormat::Quantized => chunk_size as f32,
+        }
+    }
+}
+
+/// Vertices of a chunk kept in GPU memory, drawn with `draw_indirect` instead of a `Mesh`
+pub(crate) struct GpuChunkMesh {
+    pub vertex_buffer: Buffer,
+    pub draw_args: PooledBuffer,
+    pub format: ChunkVertexFormat,
+    pub chunk_size: u32,
+    /// Counts the vertex buffer in `TerrainGpuMemory` for as long as the chunk is drawn
+    pub memory: TrackedAllocation,
+}
+
+impl GpuChunkMesh {
+    /// Size of the vertex buffer
+    pub fn bytes(&self) -> usize {
+        self.memory.bytes() as usize
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.bytes() / self.format.size() as usize
+    }
+}
+
+/// Copy of a `GpuChunkMesh` in the render world
+struct ExtractedGpuChunk {
+    vertex_buffer: Buffer,
+    draw_args: Buffer,
+    format: ChunkVertexFormat,
+    transform: Mat4,
+}
+
+#[derive(AsStd140)]
+struct ChunkUniform {
+    transform: Mat4,
+}
+
+#[derive(Default)]
+struct ChunkUniforms(DynamicUniformVec<ChunkUniform>);
+
+struct ChunkUniformOffset(u32);
+
+struct ChunkDrawPipeline {
+    pipeline: RenderPipeline,
+    /// Reads quantized vertices, which the vertex fetch turns back into floats
+    quantized_pipeline: RenderPipeline,
+    view_layout: BindGroupLayout,
+    chunk_layout: BindGroupLayout,
+}
+
+#[derive(Default)]
+struct ChunkDrawBindGroups(Option<(BindGroup, BindGroup)>);
+
+/// Draws the chunks generated by `MeshingBackend::GpuResident` in the main pass
+pub(crate) struct GpuChunkDrawPlugin;
+
+impl Plugin for GpuChunkDrawPlugin {
+    fn build(&self, app: &mut App) {
+        let samples = app
+            .world
+            .get_resource::<Msaa>()
+            .map_or(1, |msaa| msaa.samples);
+
+        let render_app = app.sub_app(RenderApp);
+
+        let pipeline = ChunkDrawPipeline::new(
+            render_app.world.get_resource::<RenderDevice>().unwrap(),
+            samples,
+        );
+
+        render_app.insert_resource(pipeline);
+        render_app.init_resource::<ChunkUniforms>();
+        render_app.init_resource::<ChunkDrawBindGroups>();
+        render_app.add_system_to_stage(RenderStage::Extract, extract_gpu_chunks);
+        render_app.add_system_to_stage(RenderStage::Prepare, prepare_gpu_chunks);
+        render_app.add_system_to_stage(RenderStage::Queue, queue_gpu_chunks);
+
+        render_app
+            .world
+            .get_resource::<DrawFunctions<Transparent3d>>()
+            .unwrap()
+            .write()
+            .add(DrawGpuChunk);
+    }
+}
+
+impl ChunkDrawPipeline {
+    fn new(render_device: &RenderDevice, samples: u32) -> Self {
+        let shader = Shader::from_wgsl(include_str!("../../assets/chunk_draw.wgsl"));
+        let shader_module = render_device.create_shader_module(&shader);
+
+        let uniform_layout = |visibility| {
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            })
+        };
+
+        let view_layout = uniform_layout(ShaderStages::VERTEX | ShaderStages::FRAGMENT);
+        let chunk_layout = uniform_layout(ShaderStages::VERTEX);
+
+        let pipeline_layout = render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            push_constant_ranges: &[],
+            bind_group_layouts: &[&view_layout, &chunk_layout],
+        });
+
+        let create_pipeline = |vertex_format: ChunkVertexFormat| {
+            let (position_format, normal_format) = match vertex_format {
+                ChunkVertexFormat::Full => (VertexFormat::Float32x4, VertexFormat::Float32x4),
+                ChunkVertexFormat::Quantized => (VertexFormat::Unorm16x4, VertexFormat::Snorm16x4),
+            };
+
+            render_device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader_module,
+                    entry_point: "vertex",
+                    buffers: &[VertexBufferLayout {
+                        array_stride: vertex_format.size(),
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: &[
+                            VertexAttribute {
+                                format: position_format,
+                                offset: 0,
+                                shader_location: 0,
+                            },
+                            VertexAttribute {
+                                format: normal_format,
+                                offset: vertex_format.size() / 2,
+                                shader_location: 1,
+                            },
+                        ],
+                    }],
+                },
+                fragment: Some(FragmentState {
+                    module: &shader_module,
+                    entry_point: "fragment",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::bevy_default(),
+                        blend: Some(BlendState::REPLACE),
+                        write_mask: ColorWrites::ALL,
+                    }],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    cull_mode: Some(Face::Back),
+                    polygon_mode: PolygonMode::Fill,
+                    clamp_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: CompareFunction::Greater,
+                    stencil: StencilState {
+                        front: StencilFaceState::IGNORE,
+                        back: StencilFaceState::IGNORE,
+                        read_mask: 0,
+                        write_mask: 0,
+                    },
+                    bias: DepthBiasState {
+                        constant: 0,
+                        slope_scale: 0.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: MultisampleState {
+                    count: samples,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+            })
+        };
+
+        Self {
+            pipeline: create_pipeline(ChunkVertexFormat::Full),
+            quantized_pipeline: create_pipeline(ChunkVertexFormat::Quantized),
+            view_layout,
+            chunk_layout,
+        }
+    }
+}
+
+fn extract_gpu_chunks(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    chunk_query: Query<(Entity, &GpuChunkMesh, &GlobalTransform)>,
+) {
+    let mut chunks = Vec::with_capacity(*previous_len);
+
+    for (entity, mesh, transform) in chunk_query.iter() {
+        chunks.push((
+            entity,
+            (ExtractedGpuChunk {
+                vertex_buffer: mesh.vertex_buffer.clone(),
+                draw_args: (*mesh.draw_args).clone(),
+                format: mesh.format,
+                transform: transform.compute_matrix()
+                    * Mat4::from_scale(Vec3::splat(mesh.format.position_scale(mesh.chunk_size))),
+            },),
+        ));
+    }
+
+    *previous_len = chunks.len();
+
+    commands.insert_or_spawn_batch(chunks);
+}
+
+fn prepare_gpu_chunks(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut chunk_uniforms: ResMut<ChunkUniforms>,
+    chunk_query: Query<(Entity, &ExtractedGpuChunk)>,
+) {
+    let chunks = chunk_query.iter().collect::<Vec<_>>();
+
+    chunk_uniforms
+        .0
+        .reserve_and_clear(chunks.len(), &render_device);
+
+    for (entity, chunk) in chunks {
+        let offset = chunk_uniforms.0.push(ChunkUniform {
+            transform: chunk.transform,
+        });
+
+        commands.entity(entity).insert(ChunkUniformOffset(offset));
+    }
+
+    chunk_uniforms.0.write_buffer(&render_queue);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_gpu_chunks(
+    draw_functions: Res<DrawFunctions<Transparent3d>>,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<ChunkDrawPipeline>,
+    view_uniforms: Res<ViewUniforms>,
+    chunk_uniforms: Res<ChunkUniforms>,
+    mut bind_groups: ResMut<ChunkDrawBindGroups>,
+    chunk_query: Query<(Entity, &ExtractedGpuChunk)>,
+    mut view_query: Query<(&ExtractedView, &mut RenderPhase<Transparent3d>)>,
+) {
+    let (view_binding, chunk_binding) =
+        match (view_uniforms.uniforms.binding(), chunk_uniforms.0.binding()) {
+            (Some(view_binding), Some(chunk_binding)) => (view_binding, chunk_binding),
+            _ => return,
+        };
+
+    let bind_group = |layout, binding| {
+        render_device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: binding,
+            }],
+        })
+    };
+
+    bind_groups.0 = Some((
+        bind_group(&pipeline.view_layout, view_binding),
+        bind_group(&pipeline.chunk_layout, chunk_binding),
+    ));
+
+    let draw_gpu_chunk = draw_functions.read().get_id::<DrawGpuChunk>().unwrap();
+
+    for (view, mut transparent_phase) in view_query.iter_mut() {
+        let inverse_view_row_2 = view.transform.compute_matrix().inverse().row(2);
+
+        for (entity, chunk) in chunk_query.iter() {
+            transparent_phase.add(Transparent3d {
+                distance: inverse_view_row_2.dot(chunk.transform.col(3)),
+                entity,
+                draw_function: draw_gpu_chunk,
+            });
+        }
+    }
+}
+
+struct DrawGpuChunk;
+
+impl Draw<Transparent3d> for DrawGpuChunk {
+    fn draw<'w>(
+        &mut self,
+        world: &'w World,
+        pass: &mut TrackedRenderPass<'w>,
+        view: Entity,
+        item: &Transparent3d,
+    ) {
+        let pipeline = world.get_resource::<ChunkDrawPipeline>().unwrap();
+        let (view_bind_group, chunk_bind_group) =
+            match &world.get_resource::<ChunkDrawBindGroups>().unwrap().0 {
+                Some(bind_groups) => bind_groups,
+                None => return,
+            };
+
+        let view_offset = world.get::<ViewUniformOffset>(view).unwrap();
+        let chunk = world.get::<ExtractedGpuChunk>(item.entity).unwrap();
+        let chunk_offset = world.get::<ChunkUniformOffset>(item.entity).unwrap();
+
+        pass.set_render_pipeline(match chunk.format {
+            ChunkVertexFormat::Full => &pipeline.pipeline,
+            ChunkVertexFormat::Quantized => &pipeline.quantized_pipeline,
+        });
+        pass.set_bind_group(0, view_bind_group, &[view_offset.offset]);
+        pass.set_bind_group(1, chunk_bind_group, &[chunk_offset.0]);
+        pass.set_vertex_buffer(0, chunk.vertex_buffer.slice(..));
+        pass.draw_indirect(&chunk.draw_args, 0);
+    }
+}
@@ -0,0 +1,190 @@
+use super::{draw::ChunkVertexFormat, CopiedChunk, DispatchedChunk, GpuChunkMesh, GpuDensity};
+use crate::{chunk::ChunkCoord, error::TerrainError, gpu::PooledBuffer, terrain::TerrainSettings};
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Chunks waiting for the render world to dispatch them, shared between both worlds
+#[derive(Clone, Default)]
+pub(crate) struct GpuChunkJobs {
+    jobs: Arc<Mutex<Vec<GpuChunkJob>>>,
+}
+
+impl GpuChunkJobs {
+    /// Queues a chunk for the next frame of the render world, the receiver resolves to its slabs
+    /// once their commands have been submitted
+    pub fn dispatch(
+        &self,
+        coord: ChunkCoord,
+        settings: TerrainSettings,
+        density: GpuDensity,
+    ) -> JobReceiver<DispatchResult> {
+        let (sender, receiver) = channel();
+
+        self.jobs.lock().unwrap().push(GpuChunkJob::Dispatch {
+            coord,
+            settings,
+            density,
+            sender,
+        });
+
+        receiver
+    }
+
+    /// Queues a copy of the first `vertex_count` vertices written by a dispatch, so only the
+    /// generated vertices are read back
+    pub fn copy_vertices(
+        &self,
+        vertex_buffer: PooledBuffer,
+        vertex_count: u32,
+        format: ChunkVertexFormat,
+    ) -> JobReceiver<CopiedChunk> {
+        let (sender, receiver) = channel();
+
+        self.jobs.lock().unwrap().push(GpuChunkJob::CopyVertices {
+            vertex_buffer,
+            vertex_count,
+            format,
+            sender,
+        });
+
+        receiver
+    }
+
+    /// Queues a copy of the vertices written by the slabs of a dispatch, along with their vertex
+    /// counts, into a vertex buffer of their own, which is drawn with the draw args buffer of
+    /// the first slab
+    pub fn retain_vertices(
+        &self,
+        vertex_buffers: Vec<(PooledBuffer, u32)>,
+        draw_args_buffer: PooledBuffer,
+        format: ChunkVertexFormat,
+        chunk_size: u32,
+    ) -> JobReceiver<GpuChunkMesh> {
+        let (sender, receiver) = channel();
+
+        self.jobs.lock().unwrap().push(GpuChunkJob::RetainVertices {
+            vertex_buffers,
+            draw_args_buffer,
+            format,
+            chunk_size,
+            sender,
+        });
+
+        receiver
+    }
+
+    pub fn take(&self) -> Vec<GpuChunkJob> {
+        mem::take(&mut *self.jobs.lock().unwrap())
+    }
+
+    /// Puts jobs the render world couldn't run this frame back in front of the queue
+    pub fn defer(&self, mut deferred: Vec<GpuChunkJob>) {
+        let mut jobs = self.jobs.lock().unwrap();
+
+        deferred.append(&mut jobs);
+        *jobs = deferred;
+    }
+}
+
+pub(crate) type DispatchResult = Result<DispatchedChunk, TerrainError>;
+
+/// Work waiting for the render world
+pub(crate) enum GpuChunkJob {
+    /// Runs the compute shader of a chunk
+    Dispatch {
+        coord: ChunkCoord,
+        settings: TerrainSettings,
+        density: GpuDensity,
+        sender: JobSender<DispatchResult>,
+    },
+    /// Copies the vertices of a dispatched chunk into a staging buffer
+    CopyVertices {
+        vertex_buffer: PooledBuffer,
+        vertex_count: u32,
+        format: ChunkVertexFormat,
+        sender: JobSender<CopiedChunk>,
+    },
+    /// Copies the vertices of the slabs of a dispatched chunk into a vertex buffer that stays on
+    /// the GPU
+    RetainVertices {
+        vertex_buffers: Vec<(PooledBuffer, u32)>,
+        draw_args_buffer: PooledBuffer,
+        format: ChunkVertexFormat,
+        chunk_size: u32,
+        sender: JobSender<GpuChunkMesh>,
+    },
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// Sending half of a single value channel between the render world and a chunk task
+pub(crate) struct JobSender<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+/// Resolves to the sent value, or `None` when the sender was dropped without sending
+pub(crate) struct JobReceiver<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+fn channel<T>() -> (JobSender<T>, JobReceiver<T>) {
+    let slot = Arc::new(Mutex::new(Slot {
+        value: None,
+        waker: None,
+        closed: false,
+    }));
+
+    (JobSender { slot: slot.clone() }, JobReceiver { slot })
+}
+
+impl<T> JobSender<T> {
+    /// Whether the receiving task was dropped, which happens when its chunk got unloaded
+    pub fn is_cancelled(&self) -> bool {
+        Arc::strong_count(&self.slot) == 1
+    }
+
+    pub fn send(self, value: T) {
+        self.slot.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<T> Drop for JobSender<T> {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock().unwrap();
+
+        slot.closed = true;
+
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for JobReceiver<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut slot = self.slot.lock().unwrap();
+
+        if let Some(value) = slot.value.take() {
+            return Poll::Ready(Some(value));
+        }
+
+        if slot.closed {
+            return Poll::Ready(None);
+        }
+
+        slot.waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+}
@@ -1,332 +1,1360 @@
+mod draw;
+mod job;
+mod node;
+mod pipeline;
+mod reload;
+mod shader;
+mod teardown;
+mod timer;
+
+pub(crate) use draw::GpuChunkMesh;
+pub(crate) use job::GpuChunkJobs;
+
+use self::{
+    draw::{ChunkVertexFormat, GpuChunkDrawPlugin},
+    job::{DispatchResult, GpuChunkJob, JobSender},
+    node::{submit_chunk_jobs, TerrainComputeNode},
+    pipeline::{TerrainComputeResources, DENSITY_FORMAT},
+    reload::{load_compute_shader, reload_compute_pipelines, watch_compute_shader, ShaderReload},
+    teardown::{request_gpu_teardown, teardown_gpu_work, GpuTeardown},
+    timer::{update_gpu_terrain_stats, FrameTimestamps, GpuTimer, TimestampQueries},
+};
+use crate::{
+    chunk::ChunkCoord,
+    density::{DensityField, NoiseBasis, SimplexNoise},
+    error::TerrainError,
+    gpu::{
+        BufferPool, GpuMemoryLimits, GpuReadback, GpuSubmission, GpuTerrainStats, MemoryKind,
+        PooledBuffer, TerrainGpuMemory, TrackedAllocation, VertexPrecision, WorkgroupSize,
+    },
+    mesh::MeshData,
+    modifiers::{self, DensityModifier},
+    simplex,
+    terrain::{GenerationBudget, TerrainSettings, VertexPlacement},
+};
 use bevy::{
-    app::{App, Plugin},
-    asset::Assets,
-    core::bytes_of,
+    app::{App, CoreStage, Plugin},
+    core::{bytes_of, cast_slice},
+    core_pipeline::node::MAIN_PASS_DEPENDENCIES,
     ecs::{
-        entity::Entity,
-        system::{Commands, Query, Res, ResMut},
-        world::{FromWorld, World},
+        schedule::SystemLabel,
+        system::{Local, Res, ResMut},
+    },
+    log::warn,
+    math::Vec3,
+    prelude::{
+        ExclusiveSystemDescriptorCoercion, IntoExclusiveSystem, ParallelSystemDescriptorCoercion,
     },
-    math::{IVec3, Vec3},
-    pbr2::{PbrBundle, StandardMaterial},
     render2::{
-        color::Color,
-        mesh::{Indices, Mesh},
-        render_resource::{MapMode, PrimitiveTopology, *},
+        render_graph::RenderGraph,
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, BufferDescriptor,
+            BufferUsages, Extent3d, Maintain, Texture, TextureDescriptor, TextureDimension,
+            TextureUsages, TextureViewDescriptor,
+        },
         renderer::{RenderDevice, RenderQueue},
-        shader::Shader,
+        RenderApp, RenderStage,
     },
-    tasks::{AsyncComputeTaskPool, Task},
-    transform::components::Transform,
 };
 use bytemuck::{Pod, Zeroable};
+use std::{
+    mem,
+    sync::{Arc, Weak},
+};
 
 use crevice::std140::AsStd140;
 
-use futures_lite::future;
-
-const CHUNK_SIZE: u32 = 64;
+/// Every cell emits at most five triangles
+const MAX_VERTICES_PER_CELL: u64 = 15;
 
-#[repr(C)]
-#[derive(Debug, AsStd140, Copy, Clone, Zeroable, Pod)]
-struct Triangle {
-    pub a: Vec3,
-    pub b: Vec3,
-    pub c: Vec3,
-}
+/// Words a permutation table takes up in the storage buffer of `GpuDensity::perlin_tables`
+const PERLIN_TABLE_WORDS: usize = 65;
 
-#[repr(C)]
-#[derive(Debug, AsStd140, Copy, Clone, Zeroable, Pod)]
-struct Cube {
-    pub triangle_count: u32,
-    pub triangles: [Triangle; 5],
-}
+/// Frames whose chunks may be dispatched before the first of them has been read back, so one
+/// set of buffers is computed while the other is mapped
+const FRAMES_IN_FLIGHT: usize = 2;
 
 #[repr(C)]
 #[derive(Debug, AsStd140, Copy, Clone, Zeroable, Pod)]
 struct InputBuffer {
     pub chunk_size: u32,
+    pub voxel_scale: f32,
+    pub iso_level: f32,
+    pub seed: u32,
     pub position: Vec3,
+    pub frequency: f32,
+    pub octaves: u32,
+    pub amplitude: f32,
+    pub lacunarity: f32,
+    pub persistence: f32,
+    pub octave_seed_offset: u32,
+    pub ridged_octaves: u32,
+    pub billow_octaves: u32,
+    pub warp_layers: u32,
+    pub warp_strength: f32,
+    pub warp_frequency: f32,
+    pub basis: u32,
+    pub modifier_count: u32,
+    pub perlin_table_count: u32,
+    pub slab_start: u32,
+    pub slab_cells: u32,
+    pub interpolate: u32,
 }
 
-pub struct TerrainPlugin;
-impl Plugin for TerrainPlugin {
-    fn build(&self, app: &mut App) {
-        app.init_resource::<TerrainShaders>();
-        app.add_startup_system(setup_terrain);
-        app.add_system(update_terrain);
+/// What `density` of `terrain/density.wgsl` evaluates for a chunk
+#[derive(Debug, Clone)]
+pub(crate) struct GpuDensity {
+    pub noise: SimplexNoise,
+    /// Seed the field samples its noise with, which may differ from `TerrainSettings::seed`
+    pub seed: u32,
+    /// Modifiers with the seeds of the stacks they belong to
+    pub modifiers: Vec<(DensityModifier, u32)>,
+}
+
+impl GpuDensity {
+    /// The noise and the modifiers of a density field with their seeds, `None` for fields the
+    /// compute shader can't express
+    pub fn of(density: &dyn DensityField) -> Option<Self> {
+        let (noise, seed) = density.simplex_noise()?;
+
+        Some(Self {
+            noise,
+            seed,
+            modifiers: density.modifiers(),
+        })
+    }
+
+    /// Every seed the compute shader samples `NoiseBasis::Perlin` with, in the order of the
+    /// tables of `perlin_tables`
+    pub fn perlin_seeds(&self) -> Vec<u32> {
+        let mut seeds = Vec::new();
+
+        if self.noise.basis == NoiseBasis::Perlin {
+            let warp = &self.noise.warp;
+
+            seeds.extend(
+                (0..self.noise.octaves).map(|octave| self.noise.octave_seed(self.seed, octave)),
+            );
+            seeds.extend(
+                (0..warp.layer_count())
+                    .flat_map(|layer| (0..3).map(move |axis| warp.seed(self.seed, layer, axis))),
+            );
+        }
+
+        for (modifier, seed) in self.modifiers.iter() {
+            seeds.extend(modifiers::perlin_seeds(modifier, *seed));
+        }
+
+        seeds.sort_unstable();
+        seeds.dedup();
+        seeds
+    }
+
+    /// Contents of the storage buffer the compute shader reads the permutation tables of
+    /// `noise::Perlin` from, laid out like the `PerlinTable` struct of `terrain/noise.wgsl`
+    ///
+    /// Every table is its seed followed by the 256 bytes of `simplex::perlin_table` packed into
+    /// 64 words, lowest byte first. Storage buffers can't be empty, so a density without Perlin
+    /// noise still takes up one table the shader never reads.
+    pub fn perlin_tables(&self) -> Vec<u32> {
+        let seeds = self.perlin_seeds();
+        let mut words = Vec::with_capacity(seeds.len().max(1) * PERLIN_TABLE_WORDS);
+
+        for seed in seeds {
+            words.push(seed);
+            words.extend(
+                simplex::perlin_table(seed)
+                    .chunks(4)
+                    .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            );
+        }
+
+        if words.is_empty() {
+            words.resize(PERLIN_TABLE_WORDS, 0);
+        }
+
+        words
     }
 }
 
-pub struct TerrainShaders {
-    bind_group_layout: BindGroupLayout,
-    compute_pipeline: ComputePipeline,
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
+enum ComputeSystemLabels {
+    ReloadPipelines,
+    PrepareJobs,
 }
 
-impl FromWorld for TerrainShaders {
-    fn from_world(world: &mut World) -> Self {
-        let render_device = world.get_resource::<RenderDevice>().unwrap();
+/// Returns whether the device can run the compute shader at all
+pub(crate) fn is_supported(render_device: &RenderDevice) -> bool {
+    let limits = render_device.limits();
 
-        let shader = Shader::from_wgsl(include_str!("../../assets/chunk.wgsl"));
-        let shader_module = render_device.create_shader_module(&shader);
+    limits.max_storage_buffers_per_shader_stage >= 6
+        && limits.max_storage_textures_per_shader_stage >= 1
+}
 
-        let bind_group_layout =
-            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                label: None,
-                entries: &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::COMPUTE,
-                        ty: BindingType::Buffer {
-                            ty: BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: ShaderStages::COMPUTE,
-                        ty: BindingType::Buffer {
-                            ty: BufferBindingType::Storage { read_only: false },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                ],
-            });
+/// Registers the render world side of the GPU mesher, chunks are queued from the main world
+/// and dispatched by a node of the render graph
+pub(crate) struct TerrainComputePlugin;
 
-        let pipeline_layout = render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: None,
-            push_constant_ranges: &[],
-            bind_group_layouts: &[&bind_group_layout],
-        });
+impl Plugin for TerrainComputePlugin {
+    fn build(&self, app: &mut App) {
+        let buffer_pool = BufferPool::default();
+        let jobs = GpuChunkJobs::default();
+        let reload = ShaderReload::default();
 
-        let compute_pipeline = render_device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            module: &shader_module,
-            entry_point: "main",
-        });
+        let workgroup_size = app
+            .world
+            .get_resource::<WorkgroupSize>()
+            .copied()
+            .unwrap_or_default();
+        let vertex_precision = app
+            .world
+            .get_resource::<VertexPrecision>()
+            .copied()
+            .unwrap_or_default();
+        let submission = app
+            .world
+            .get_resource::<GpuSubmission>()
+            .copied()
+            .unwrap_or_default();
+
+        let timer = GpuTimer::new(
+            app.sub_app(RenderApp)
+                .world
+                .get_resource::<RenderQueue>()
+                .unwrap()
+                .get_timestamp_period(),
+        );
+
+        app.insert_resource(buffer_pool.clone());
+        app.insert_resource(jobs.clone());
+        app.insert_resource(timer.clone());
+        app.insert_resource(reload.clone());
+        app.init_resource::<GpuTerrainStats>();
+        app.init_resource::<TerrainGpuMemory>();
+        app.init_resource::<GpuMemoryLimits>();
+        app.add_startup_system(load_compute_shader);
+        app.add_system(watch_compute_shader);
+        app.add_system(size_buffer_pool);
+        app.add_system(update_gpu_memory);
+        app.add_system(update_gpu_terrain_stats);
+        app.add_system_to_stage(CoreStage::PreUpdate, poll_render_device);
+        app.init_resource::<GpuTeardown>();
+        app.add_system_to_stage(CoreStage::Last, request_gpu_teardown);
+        app.add_system_to_stage(
+            CoreStage::Last,
+            teardown_gpu_work.exclusive_system().at_end(),
+        );
+
+        let render_app = app.sub_app(RenderApp);
+
+        render_app.insert_resource(buffer_pool);
+        render_app.insert_resource(jobs);
+        render_app.insert_resource(timer);
+        render_app.insert_resource(reload);
+        render_app.insert_resource(vertex_precision);
+        render_app.insert_resource(submission);
+        render_app.init_resource::<PreparedChunks>();
+        render_app.init_resource::<FramesInFlight>();
+
+        // Devices that can't run the shader never get dispatches queued, see `is_supported`
+        let render_device = render_app.world.get_resource::<RenderDevice>().unwrap();
 
-        Self {
-            bind_group_layout,
-            compute_pipeline,
+        let queries = TimestampQueries::new(render_device);
+
+        if is_supported(render_device) {
+            let resources = TerrainComputeResources::new(render_device, workgroup_size);
+
+            render_app.insert_resource(resources);
         }
+
+        render_app.insert_resource(queries);
+
+        render_app.add_system_to_stage(
+            RenderStage::Prepare,
+            reload_compute_pipelines.label(ComputeSystemLabels::ReloadPipelines),
+        );
+        render_app.add_system_to_stage(
+            RenderStage::Prepare,
+            prepare_chunk_jobs
+                .label(ComputeSystemLabels::PrepareJobs)
+                .after(ComputeSystemLabels::ReloadPipelines),
+        );
+        render_app.add_system_to_stage(
+            RenderStage::Prepare,
+            submit_chunk_jobs.after(ComputeSystemLabels::PrepareJobs),
+        );
+        render_app.add_system_to_stage(RenderStage::Cleanup, finish_chunk_jobs);
+
+        let mut render_graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+
+        render_graph.add_node(TerrainComputeNode::NAME, TerrainComputeNode);
+        render_graph
+            .add_node_edge(TerrainComputeNode::NAME, MAIN_PASS_DEPENDENCIES)
+            .unwrap();
+
+        app.add_plugin(GpuChunkDrawPlugin);
     }
 }
 
-struct Chunk {
-    buffer: Buffer,
-    buffer_size: BufferAddress,
-    position: IVec3,
+/// Bind groups and resources of the passes of a slab of a chunk, see `prepare_slab`
+pub(crate) struct SlabDispatch {
+    density_bind_group: BindGroup,
+    density_workgroups: [u32; 3],
+    bind_group: BindGroup,
+    workgroups: [u32; 3],
+    scan_workgroups: u32,
+    cells: u64,
+    format: ChunkVertexFormat,
+    input_buffer: PooledBuffer,
+    /// Only read by the density pass, like the permutation tables
+    modifiers_buffer: PooledBuffer,
+    perlin_tables_buffer: PooledBuffer,
+    density_texture: Texture,
+    density_memory: TrackedAllocation,
+    offsets_buffer: PooledBuffer,
+    block_sums_buffer: PooledBuffer,
+    /// Also holds the arguments of the indirect dispatch of the compact pass
+    active_cells_buffer: PooledBuffer,
+    draw_args_buffer: PooledBuffer,
+    vertex_buffer: PooledBuffer,
+    draw_args: GpuReadback<u32>,
 }
 
-impl Chunk {
-    fn new(position: IVec3, render_device: &RenderDevice) -> Self {
-        let buffer_size =
-            CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * (Cube::std140_size_static() as u32);
+/// Chunk whose buffers and bind groups are ready for `TerrainComputeNode`, split into slabs
+/// when its vertices don't fit into a single storage buffer binding
+pub(crate) struct PreparedDispatch {
+    slabs: Vec<SlabDispatch>,
+    buffer_set: BufferSet,
+    sender: JobSender<DispatchResult>,
+}
 
-        let buffer = render_device.create_buffer(&BufferDescriptor {
-            label: None,
-            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-            size: buffer_size as BufferAddress,
-        });
+/// Vertices of a dispatched chunk waiting for `TerrainComputeNode` to copy them
+pub(crate) struct PreparedCopy {
+    vertex_buffer: PooledBuffer,
+    /// Read as words since the layout depends on the format of the chunk
+    vertices: GpuReadback<u32>,
+    sender: JobSender<CopiedChunk>,
+}
 
-        Self {
-            buffer: buffer,
-            buffer_size: buffer_size as BufferAddress,
-            position,
-        }
+/// Vertices of the slabs of a dispatched chunk waiting for `TerrainComputeNode` to move them
+/// into a buffer of their own, along with their size in bytes
+pub(crate) struct PreparedRetain {
+    vertex_buffers: Vec<(PooledBuffer, u64)>,
+    mesh: GpuChunkMesh,
+    sender: JobSender<GpuChunkMesh>,
+}
+
+#[derive(Default)]
+pub(crate) struct PreparedChunks {
+    dispatches: Vec<PreparedDispatch>,
+    copies: Vec<PreparedCopy>,
+    retains: Vec<PreparedRetain>,
+    /// Set in the frames measured by `GpuTimer`
+    timestamps: Option<FrameTimestamps>,
+}
+
+impl PreparedChunks {
+    fn is_empty(&self) -> bool {
+        self.dispatches.is_empty() && self.copies.is_empty() && self.retains.is_empty()
     }
 
-    fn create_compute_task(
-        &self,
-        render_device: &RenderDevice,
-        render_queue: &RenderQueue,
-        terrain_shaders: &TerrainShaders,
-        task_pool: &AsyncComputeTaskPool,
-    ) -> Task<Result<(), BufferAsyncError>> {
-        let input_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            contents: bytes_of(
-                &InputBuffer {
-                    chunk_size: CHUNK_SIZE,
-                    position: Vec3::new(
-                        self.position.x as f32 * CHUNK_SIZE as f32 - (CHUNK_SIZE as f32 / 2.0),
-                        self.position.y as f32 * CHUNK_SIZE as f32 - (CHUNK_SIZE as f32 / 2.0),
-                        self.position.z as f32 * CHUNK_SIZE as f32 - (CHUNK_SIZE as f32 / 2.0),
-                    ),
-                }
-                .as_std140(),
-            ),
-            label: None,
-            usage: BufferUsages::STORAGE,
-        });
+    fn slabs(&self) -> impl Iterator<Item = &SlabDispatch> {
+        self.dispatches.iter().flat_map(|chunk| chunk.slabs.iter())
+    }
+}
 
-        let output_buffer = render_device.create_buffer(&BufferDescriptor {
-            label: None,
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
-            mapped_at_creation: false,
-            size: self.buffer_size,
-        });
+/// Marks the chunks dispatched in the same frame, the frame counts as in flight until the tasks
+/// of all of them are done with their buffers
+#[derive(Clone)]
+pub(crate) struct BufferSet(Arc<()>);
 
-        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: &terrain_shaders.bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: input_buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: output_buffer.as_entire_binding(),
-                },
-            ],
-        });
+/// Buffer sets of the frames in flight, see `FRAMES_IN_FLIGHT`
+#[derive(Default)]
+struct FramesInFlight(Vec<Weak<()>>);
 
-        let mut command_encoder =
-            render_device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+/// Chunk whose dispatch was submitted, handed back to its task to read the vertex counts
+pub(crate) struct DispatchedChunk {
+    slabs: Vec<DispatchedSlab>,
+    format: ChunkVertexFormat,
+    buffer_set: BufferSet,
+}
 
-        {
-            let mut compute_pass =
-                command_encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
-            compute_pass.set_pipeline(&terrain_shaders.compute_pipeline);
-            compute_pass.set_bind_group(0, &*bind_group, &[]);
+/// Slab whose dispatch was submitted
+pub(crate) struct DispatchedSlab {
+    draw_args_buffer: PooledBuffer,
+    vertex_buffer: PooledBuffer,
+    draw_args: GpuReadback<u32>,
+}
 
-            compute_pass.dispatch(CHUNK_SIZE / 8, CHUNK_SIZE / 8, CHUNK_SIZE / 8);
-        }
+/// Chunk whose vertex copy was submitted, handed back to its task for the readback
+pub(crate) struct CopiedChunk {
+    vertex_buffer: PooledBuffer,
+    vertices: GpuReadback<u32>,
+}
+
+/// Generates the mesh of a chunk with the passes of `terrain/density.wgsl` and
+/// `terrain/mesher.wgsl`
+///
+/// The dispatch happens in the next frame of the render world. Once the vertex count is read
+/// back, only that many vertices are copied out in the frame after. The vertices of every slab
+/// are positioned relative to the chunk, so stitching them is appending them.
+pub(crate) async fn generate_mesh_data(
+    jobs: GpuChunkJobs,
+    coord: ChunkCoord,
+    settings: TerrainSettings,
+    density: GpuDensity,
+) -> Result<MeshData, TerrainError> {
+    let chunk_size = settings.chunk_size;
 
-        command_encoder.copy_buffer_to_buffer(&output_buffer, 0, &self.buffer, 0, self.buffer_size);
+    // The frame of the chunk stays in flight until its vertices are read back
+    let (slabs, format, _buffer_set) = dispatch_chunk(&jobs, coord, settings, density).await?;
 
-        let gpu_commands = command_encoder.finish();
+    // Queued together so every slab is copied in the same frame
+    let copies = slabs
+        .into_iter()
+        .filter(|(_, _, vertex_count)| *vertex_count > 0)
+        .map(|(_, vertex_buffer, vertex_count)| {
+            jobs.copy_vertices(vertex_buffer, vertex_count, format)
+        })
+        .collect::<Vec<_>>();
 
-        render_queue.submit([gpu_commands]);
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
 
-        let buffer_slice = self.buffer.slice(..);
+    for copy in copies {
+        let copied = copy.await.ok_or(TerrainError::GpuJobDropped)?;
 
-        let buffer_future = buffer_slice.map_async(MapMode::Read);
+        let read = copied
+            .vertices
+            .read(|words| {
+                for vertex in words.chunks_exact(format.words()) {
+                    let (position, normal) = format.decode(vertex, chunk_size);
 
-        let task_future = async move { buffer_future.await };
+                    positions.push(position);
+                    normals.push(normal);
+                }
+            })
+            .await;
 
-        let task = task_pool.spawn(task_future);
+        // The copy out of the vertex buffer is done once the readback is mapped
+        drop(copied.vertex_buffer);
 
-        task
+        read.map_err(|_| TerrainError::BufferMapFailed)?;
     }
 
-    fn create_mesh(&self) -> Mesh {
-        let buffer_slice = self.buffer.slice(..);
+    let indices = (0..positions.len() as u32).collect();
 
-        let data = buffer_slice.get_mapped_range();
+    Ok(MeshData {
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    })
+}
 
-        let cubes: &[Std140Cube] = bytemuck::cast_slice(&data);
+/// Generates the vertices of a chunk like `generate_mesh_data`, but moves them into a vertex
+/// buffer on the GPU instead of reading them back, `None` for chunks without a surface
+pub(crate) async fn generate_gpu_mesh(
+    jobs: GpuChunkJobs,
+    coord: ChunkCoord,
+    settings: TerrainSettings,
+    density: GpuDensity,
+) -> Result<Option<GpuChunkMesh>, TerrainError> {
+    let chunk_size = settings.chunk_size;
+    let (slabs, format, _buffer_set) = dispatch_chunk(&jobs, coord, settings, density).await?;
 
-        let mut triangles: Vec<Triangle> = Vec::new();
+    let mut draw_args_buffer = None;
+    let mut vertex_buffers = Vec::new();
 
-        for cube in cubes.iter() {
-            let cube = Cube::from_std140(*cube);
+    for (slab_draw_args_buffer, vertex_buffer, vertex_count) in slabs {
+        // The draw args of the first slab are reused for the whole chunk
+        draw_args_buffer.get_or_insert(slab_draw_args_buffer);
 
-            for i in 0..cube.triangle_count {
-                triangles.push(cube.triangles[i as usize]);
-            }
+        if vertex_count > 0 {
+            vertex_buffers.push((vertex_buffer, vertex_count));
         }
+    }
 
-        drop(data);
-        self.buffer.unmap();
-        self.buffer.destroy();
-
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-
-        let vertices = triangles
-            .iter()
-            .map(|triangle| [triangle.a, triangle.b, triangle.c])
-            .flatten()
-            .map(|vector| [vector.x, vector.y, vector.z])
-            .collect::<Vec<_>>();
-        let indices = (0..vertices.len())
-            .map(|index| index as u32)
-            .collect::<Vec<u32>>();
-        let uvs = (0..vertices.len())
-            .map(|_| [0.0, 0.0])
-            .collect::<Vec<[f32; 2]>>();
-
-        let mut normals: Vec<[f32; 3]> = Vec::new();
-
-        for triangle in indices.chunks(3) {
-            let a = Vec3::from(vertices[(triangle)[0] as usize]);
-            let b = Vec3::from(vertices[(triangle)[1] as usize]);
-            let c = Vec3::from(vertices[(triangle)[2] as usize]);
-
-            let normal = (b - a).cross(c - a).normalize();
-
-            normals.push(normal.into());
-            normals.push(normal.into());
-            normals.push(normal.into());
-        }
+    let draw_args_buffer = match draw_args_buffer {
+        Some(draw_args_buffer) if !vertex_buffers.is_empty() => draw_args_buffer,
+        _ => return Ok(None),
+    };
 
-        mesh.set_indices(Some(Indices::U32(indices)));
+    let mesh = jobs
+        .retain_vertices(vertex_buffers, draw_args_buffer, format, chunk_size)
+        .await
+        .ok_or(TerrainError::GpuJobDropped)?;
 
-        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    Ok(Some(mesh))
+}
+
+/// Runs the compute shader of a chunk and reads back how many vertices it wrote, returns the
+/// draw args and vertex buffers together with the vertex count of every slab and the format of
+/// the vertices
+///
+/// The buffer set is returned as well, the caller keeps it until it is done with the buffers.
+#[allow(clippy::type_complexity)]
+async fn dispatch_chunk(
+    jobs: &GpuChunkJobs,
+    coord: ChunkCoord,
+    settings: TerrainSettings,
+    density: GpuDensity,
+) -> Result<
+    (
+        Vec<(PooledBuffer, PooledBuffer, u32)>,
+        ChunkVertexFormat,
+        BufferSet,
+    ),
+    TerrainError,
+> {
+    let DispatchedChunk {
+        slabs,
+        format,
+        buffer_set,
+    } = jobs
+        .dispatch(coord, settings, density)
+        .await
+        .ok_or(TerrainError::GpuJobDropped)??;
+
+    let mut dispatched = Vec::with_capacity(slabs.len());
+
+    for slab in slabs {
+        let vertex_count = slab
+            .draw_args
+            .read(|draw_args| draw_args[0])
+            .await
+            .map_err(|_| TerrainError::BufferMapFailed)?;
+
+        dispatched.push((slab.draw_args_buffer, slab.vertex_buffer, vertex_count));
+    }
+
+    Ok((dispatched, format, buffer_set))
+}
+
+/// Completes the mappings of finished readbacks without waiting for the device, which wakes
+/// the chunk tasks awaiting them
+fn poll_render_device(render_device: Res<RenderDevice>) {
+    render_device.wgpu_device().poll(Maintain::Poll);
+}
+
+/// Keeps a pooled buffer of every kind per chunk scheduled in each frame in flight
+fn size_buffer_pool(budget: Res<GenerationBudget>, buffer_pool: Res<BufferPool>) {
+    if budget.is_changed() {
+        buffer_pool.set_capacity(budget.chunks_per_frame * FRAMES_IN_FLIGHT);
+    }
+}
+
+/// Copies the counted allocations into `TerrainGpuMemory`, trims the pool when the limits ask for
+/// it and warns once whenever the usage crosses the warning threshold
+fn update_gpu_memory(
+    buffer_pool: Res<BufferPool>,
+    limits: Res<GpuMemoryLimits>,
+    mut memory: ResMut<TerrainGpuMemory>,
+    mut warned: Local<bool>,
+) {
+    *memory = buffer_pool.tracker().snapshot();
 
-        mesh
+    if limits.should_trim(&memory) {
+        buffer_pool.clear();
+        *memory = buffer_pool.tracker().snapshot();
     }
+
+    let threshold = match limits.warning_threshold {
+        Some(threshold) => threshold,
+        None => return,
+    };
+
+    let exceeded = memory.total() > threshold;
+
+    if exceeded && !*warned {
+        warn!(
+            "Terrain GPU memory of {} MiB exceeds the threshold of {} MiB, {} MiB of it are pooled buffers",
+            memory.total() >> 20,
+            threshold >> 20,
+            memory.pooled >> 20
+        );
+    }
+
+    *warned = exceeded;
 }
 
-fn setup_terrain(
-    mut commands: Commands,
+#[allow(clippy::too_many_arguments)]
+fn prepare_chunk_jobs(
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
-    terrain_shaders: Res<TerrainShaders>,
-    task_pool: Res<AsyncComputeTaskPool>,
+    resources: Option<Res<TerrainComputeResources>>,
+    vertex_precision: Res<VertexPrecision>,
+    buffer_pool: Res<BufferPool>,
+    jobs: Res<GpuChunkJobs>,
+    queries: Res<TimestampQueries>,
+    timer: Res<GpuTimer>,
+    mut frames_in_flight: ResMut<FramesInFlight>,
+    mut prepared: ResMut<PreparedChunks>,
 ) {
-    for x in 0..1 {
-        for y in 0..1 {
-            for z in 0..1 {
-                let chunk = Chunk::new(IVec3::new(x, y, z), &*render_device);
-
-                let task = chunk.create_compute_task(
-                    &*render_device,
-                    &*render_queue,
-                    &*terrain_shaders,
-                    &*task_pool,
+    frames_in_flight
+        .0
+        .retain(|buffer_set| buffer_set.strong_count() > 0);
+
+    // Once every buffer set is in use, new chunks wait for the oldest frame to be read back
+    // while copies and retains of dispatched chunks still run
+    let can_dispatch = frames_in_flight.0.len() < FRAMES_IN_FLIGHT;
+    let buffer_set = BufferSet(Arc::new(()));
+    let mut deferred = Vec::new();
+
+    for job in jobs.take() {
+        if !can_dispatch && matches!(job, GpuChunkJob::Dispatch { .. }) {
+            deferred.push(job);
+            continue;
+        }
+
+        match job {
+            GpuChunkJob::Dispatch {
+                coord,
+                settings,
+                density,
+                sender,
+            } => {
+                // Chunks unloaded before their dispatch never reach the GPU
+                if sender.is_cancelled() {
+                    continue;
+                }
+
+                let resources = match &resources {
+                    Some(resources) => resources,
+                    None => continue,
+                };
+
+                match prepare_chunk(
+                    &render_device,
+                    &render_queue,
+                    resources,
+                    &buffer_pool,
+                    ChunkVertexFormat::new(*vertex_precision, settings.chunk_size),
+                    coord,
+                    &settings,
+                    &density,
+                ) {
+                    Ok(slabs) => prepared.dispatches.push(PreparedDispatch {
+                        slabs,
+                        buffer_set: buffer_set.clone(),
+                        sender,
+                    }),
+                    Err(error) => sender.send(Err(error)),
+                }
+            }
+            GpuChunkJob::CopyVertices {
+                vertex_buffer,
+                vertex_count,
+                format,
+                sender,
+            } => {
+                if sender.is_cancelled() {
+                    continue;
+                }
+
+                prepared.copies.push(PreparedCopy {
+                    vertex_buffer,
+                    vertices: GpuReadback::from_pool(
+                        &buffer_pool,
+                        &render_device,
+                        vertex_count as usize * format.words(),
+                    ),
+                    sender,
+                });
+            }
+            GpuChunkJob::RetainVertices {
+                vertex_buffers,
+                draw_args_buffer,
+                format,
+                chunk_size,
+                sender,
+            } => {
+                if sender.is_cancelled() {
+                    continue;
+                }
+
+                let vertex_count = vertex_buffers.iter().map(|(_, count)| *count).sum::<u32>();
+
+                // Slabs only count their own vertices, the draw args cover all of them. The
+                // scan that wrote them finished in an earlier frame.
+                render_queue.write_buffer(
+                    &draw_args_buffer,
+                    0,
+                    cast_slice(&[vertex_count, 1u32, 0, 0]),
                 );
-                commands.spawn().insert(task).insert(chunk);
+
+                prepared.retains.push(PreparedRetain {
+                    vertex_buffers: vertex_buffers
+                        .into_iter()
+                        .map(|(buffer, count)| (buffer, count as u64 * format.size()))
+                        .collect(),
+                    mesh: GpuChunkMesh {
+                        vertex_buffer: render_device.create_buffer(&BufferDescriptor {
+                            label: None,
+                            size: vertex_count as u64 * format.size(),
+                            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        }),
+                        draw_args: draw_args_buffer,
+                        format,
+                        chunk_size,
+                        memory: buffer_pool
+                            .tracker()
+                            .allocate(MemoryKind::Meshes, vertex_count as u64 * format.size()),
+                    },
+                    sender,
+                });
             }
         }
     }
+
+    if !deferred.is_empty() {
+        jobs.defer(deferred);
+    }
+
+    if !prepared.dispatches.is_empty() {
+        frames_in_flight.0.push(Arc::downgrade(&buffer_set.0));
+    }
+
+    if queries.0.is_some() && !prepared.dispatches.is_empty() {
+        let slabs = prepared.slabs().count();
+        let cells = prepared.slabs().map(|slab| slab.cells).sum();
+
+        prepared.timestamps = timer.begin_frame(&buffer_pool, &render_device, slabs, cells);
+    }
 }
 
-fn update_terrain(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut chunks: Query<(Entity, &Chunk, &mut Task<Result<(), BufferAsyncError>>)>,
-) {
-    for (entity, chunk, mut task) in chunks.iter_mut() {
-        if let Some(_) = future::block_on(future::poll_once(&mut *task)) {
-            let mesh = chunk.create_mesh();
-
-            commands.entity(entity).insert_bundle(PbrBundle {
-                mesh: meshes.add(mesh),
-                material: materials.add(StandardMaterial {
-                    base_color: Color::BLUE,
-                    ..Default::default()
+/// Runs after the render graph was submitted, so the readbacks only map finished copies
+fn finish_chunk_jobs(timer: Res<GpuTimer>, mut prepared: ResMut<PreparedChunks>) {
+    if let Some(timestamps) = prepared.timestamps.take() {
+        timer.submit(timestamps);
+    }
+
+    for chunk in prepared.dispatches.drain(..) {
+        // Every slab of a chunk is written in the same format
+        let format = chunk
+            .slabs
+            .first()
+            .map_or(ChunkVertexFormat::Full, |slab| slab.format);
+
+        let slabs = chunk
+            .slabs
+            .into_iter()
+            .map(|slab| {
+                let SlabDispatch {
+                    input_buffer,
+                    modifiers_buffer,
+                    perlin_tables_buffer,
+                    density_texture,
+                    density_memory,
+                    offsets_buffer,
+                    block_sums_buffer,
+                    active_cells_buffer,
+                    draw_args_buffer,
+                    vertex_buffer,
+                    draw_args,
+                    ..
+                } = slab;
+
+                // Later submits are ordered after this one, so the input buffer can be reused
+                // right away. The density volume and the offsets are only read by the marching
+                // stage.
+                drop(input_buffer);
+                drop(modifiers_buffer);
+                drop(perlin_tables_buffer);
+                drop(density_texture);
+                drop(density_memory);
+                drop(offsets_buffer);
+                drop(block_sums_buffer);
+                drop(active_cells_buffer);
+
+                DispatchedSlab {
+                    draw_args_buffer,
+                    vertex_buffer,
+                    draw_args,
+                }
+            })
+            .collect();
+
+        chunk.sender.send(Ok(DispatchedChunk {
+            slabs,
+            format,
+            buffer_set: chunk.buffer_set,
+        }));
+    }
+
+    for chunk in prepared.copies.drain(..) {
+        chunk.sender.send(CopiedChunk {
+            vertex_buffer: chunk.vertex_buffer,
+            vertices: chunk.vertices,
+        });
+    }
+
+    // The vertex buffers are returned to the pool the same way
+    for chunk in prepared.retains.drain(..) {
+        chunk.sender.send(chunk.mesh);
+    }
+}
+
+/// Splits a chunk into slabs of cells along z whose vertices fit into a storage buffer binding
+/// and prepares the dispatches of all of them
+fn prepare_chunk(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    resources: &TerrainComputeResources,
+    buffer_pool: &BufferPool,
+    format: ChunkVertexFormat,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+    density: &GpuDensity,
+) -> Result<Vec<SlabDispatch>, TerrainError> {
+    let chunk_size = settings.chunk_size;
+
+    // One sample per corner plus the ghost samples the shader reads for the normals
+    let samples = settings.padded_samples_per_axis();
+    let max_texture_size = render_device.limits().max_texture_dimension_3d;
+
+    if samples > max_texture_size {
+        return Err(TerrainError::TextureTooLarge {
+            size: samples,
+            max: max_texture_size,
+        });
+    }
+
+    // The vertices are packed by the shader, but a single slab can still fill every cell
+    let layer_size = (chunk_size as u64).pow(2) * MAX_VERTICES_PER_CELL * format.size();
+    let max_binding_size = render_device.limits().max_storage_buffer_binding_size as u64;
+
+    if layer_size > max_binding_size {
+        return Err(TerrainError::BufferTooLarge {
+            size: layer_size,
+            max: max_binding_size,
+        });
+    }
+
+    let slab_cells = (max_binding_size / layer_size).min(chunk_size as u64) as u32;
+
+    (0..chunk_size)
+        .step_by(slab_cells as usize)
+        .map(|slab_start| {
+            prepare_slab(
+                render_device,
+                render_queue,
+                resources,
+                buffer_pool,
+                format,
+                coord,
+                settings,
+                density,
+                slab_start,
+                slab_cells.min(chunk_size - slab_start),
+            )
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_slab(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    resources: &TerrainComputeResources,
+    buffer_pool: &BufferPool,
+    format: ChunkVertexFormat,
+    coord: ChunkCoord,
+    settings: &TerrainSettings,
+    density: &GpuDensity,
+    slab_start: u32,
+    slab_cells: u32,
+) -> Result<SlabDispatch, TerrainError> {
+    let chunk_size = settings.chunk_size;
+    let samples = settings.padded_samples_per_axis();
+    let slab_samples = samples - chunk_size + slab_cells;
+
+    let vertex_buffer_size =
+        (chunk_size as u64).pow(2) * slab_cells as u64 * MAX_VERTICES_PER_CELL * format.size();
+
+    let noise = &density.noise;
+    let input = InputBuffer {
+        chunk_size,
+        voxel_scale: settings.voxel_scale,
+        iso_level: settings.iso_level,
+        seed: density.seed,
+        position: settings.get_chunk_origin(coord),
+        frequency: noise.frequency,
+        octaves: noise.octaves,
+        amplitude: noise.amplitude,
+        lacunarity: noise.lacunarity,
+        persistence: noise.persistence,
+        octave_seed_offset: noise.octave_seed_offset,
+        ridged_octaves: noise.ridged_octaves,
+        billow_octaves: noise.billow_octaves,
+        warp_layers: noise.warp.layer_count(),
+        warp_strength: noise.warp.strength,
+        warp_frequency: noise.warp.frequency,
+        basis: noise.basis as u32,
+        modifier_count: density.modifiers.len() as u32,
+        perlin_table_count: density.perlin_seeds().len() as u32,
+        slab_start,
+        slab_cells,
+        interpolate: (settings.vertex_placement == VertexPlacement::Interpolated) as u32,
+    }
+    .as_std140();
+
+    // Pooled buffers are filled by the queue, those writes land before the frame is submitted.
+    // The input buffer is only returned to the pool after the submit, so no other chunk of the
+    // frame overwrites it.
+    let input_buffer = buffer_pool.take(
+        render_device,
+        bytes_of(&input).len() as u64,
+        BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    );
+    render_queue.write_buffer(&input_buffer, 0, bytes_of(&input));
+
+    let modifiers = modifiers::to_storage_buffer(&density.modifiers);
+    let modifiers_buffer = buffer_pool.take(
+        render_device,
+        mem::size_of_val(&modifiers[..]) as u64,
+        BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    );
+    render_queue.write_buffer(&modifiers_buffer, 0, cast_slice(&modifiers));
+
+    let perlin_tables = density.perlin_tables();
+    let perlin_tables_buffer = buffer_pool.take(
+        render_device,
+        mem::size_of_val(&perlin_tables[..]) as u64,
+        BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    );
+    render_queue.write_buffer(&perlin_tables_buffer, 0, cast_slice(&perlin_tables));
+
+    // Starts at zero vertices and a single instance, the scan writes the vertex count
+    let draw_args = [0u32, 1, 0, 0];
+    let draw_args_buffer = buffer_pool.take(
+        render_device,
+        mem::size_of_val(&draw_args) as u64,
+        BufferUsages::STORAGE
+            | BufferUsages::INDIRECT
+            | BufferUsages::COPY_SRC
+            | BufferUsages::COPY_DST,
+    );
+    render_queue.write_buffer(&draw_args_buffer, 0, cast_slice(&draw_args));
+
+    // The draw args and vertex buffers are handed to the chunk task after the submit and released
+    // when it is dropped, which happens as soon as the chunk is unloaded
+    let vertex_buffer = buffer_pool.take(
+        render_device,
+        vertex_buffer_size,
+        BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+    );
+
+    // The offsets are padded to whole blocks, so the scan never reads past the buffer
+    let scan_block_size = resources.scan_block_size();
+    let block_count = (chunk_size.pow(2) * slab_cells + scan_block_size - 1) / scan_block_size;
+    let offsets_buffer = buffer_pool.take(
+        render_device,
+        (block_count * scan_block_size) as u64 * 4,
+        BufferUsages::STORAGE,
+    );
+    let block_sums_buffer =
+        buffer_pool.take(render_device, block_count as u64 * 4, BufferUsages::STORAGE);
+
+    // A header of a single workgroup row and no active cells, followed by room for every cell
+    let active_cells_header = [0u32, 1, 1, 0];
+    let active_cells_buffer = buffer_pool.take(
+        render_device,
+        mem::size_of_val(&active_cells_header) as u64
+            + chunk_size.pow(2) as u64 * slab_cells as u64 * 4,
+        BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+    );
+    render_queue.write_buffer(&active_cells_buffer, 0, cast_slice(&active_cells_header));
+
+    // Written and read within the frame, so it isn't pooled like the buffers
+    let density_texture = render_device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width: samples,
+            height: samples,
+            depth_or_array_layers: slab_samples,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D3,
+        format: DENSITY_FORMAT,
+        // Copied out by the tests comparing the volume with the samples of the CPU
+        usage: TextureUsages::STORAGE_BINDING
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC,
+    });
+    let density_view = density_texture.create_view(&TextureViewDescriptor::default());
+    let density_memory = buffer_pool.tracker().allocate(
+        MemoryKind::Textures,
+        samples as u64 * samples as u64 * slab_samples as u64 * 4,
+    );
+
+    let density_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout: &resources.density_bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: input_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(&density_view),
+            },
+            BindGroupEntry {
+                binding: 9,
+                resource: modifiers_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 10,
+                resource: perlin_tables_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
+    let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout: &resources.bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: input_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: draw_args_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: vertex_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: BindingResource::TextureView(&density_view),
+            },
+            BindGroupEntry {
+                binding: 5,
+                resource: offsets_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 6,
+                resource: block_sums_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 7,
+                resource: resources.tables_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 8,
+                resource: active_cells_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
+    // Chunk sizes don't have to be multiples of the workgroup size, the shader skips the
+    // invocations past the end
+    let workgroup_size = resources.workgroup_size;
+    let workgroups = |len: u32| (len + workgroup_size - 1) / workgroup_size;
+
+    Ok(SlabDispatch {
+        density_bind_group,
+        density_workgroups: [
+            workgroups(samples),
+            workgroups(samples),
+            workgroups(slab_samples),
+        ],
+        bind_group,
+        workgroups: [
+            workgroups(chunk_size),
+            workgroups(chunk_size),
+            workgroups(slab_cells),
+        ],
+        scan_workgroups: block_count,
+        cells: (chunk_size as u64).pow(2) * slab_cells as u64,
+        format,
+        input_buffer,
+        modifiers_buffer,
+        perlin_tables_buffer,
+        density_texture,
+        density_memory,
+        offsets_buffer,
+        block_sums_buffer,
+        active_cells_buffer,
+        draw_args_buffer,
+        vertex_buffer,
+        draw_args: GpuReadback::from_pool(buffer_pool, render_device, 4),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cpu,
+        density::{DomainWarp, SimplexDensity},
+        terrain,
+    };
+    use bevy::{
+        math::IVec3,
+        render2::render_resource::{Buffer, CommandEncoderDescriptor, MapMode},
+    };
+    use futures_lite::future;
+    use std::{future::Future, iter, num::NonZeroU32};
+
+    /// Device of the first adapter wgpu finds, `None` on machines without one
+    fn test_device() -> Option<(RenderDevice, RenderQueue)> {
+        let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
+        let adapter =
+            future::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = future::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: adapter.limits(),
+            },
+            None,
+        ))
+        .ok()?;
+
+        Some((RenderDevice::from(Arc::new(device)), Arc::new(queue)))
+    }
+
+    /// Waits for a mapping, polling the device until it completes
+    fn wait<T>(render_device: &RenderDevice, mapping: impl Future<Output = T>) -> T {
+        future::block_on(future::zip(mapping, async {
+            render_device.wgpu_device().poll(Maintain::Wait)
+        }))
+        .0
+    }
+
+    /// Words of a buffer mapped for reading
+    fn read_words(render_device: &RenderDevice, buffer: &Buffer) -> Vec<u32> {
+        let slice = buffer.slice(..);
+
+        wait(render_device, slice.map_async(MapMode::Read)).unwrap();
+
+        let words = cast_slice::<u8, u32>(&slice.get_mapped_range()).to_vec();
+        buffer.unmap();
+
+        words
+    }
+
+    /// Reads back the density volume of a chunk dispatched in a single slab, laid out like the
+    /// grid of `terrain::sample_chunk_density`
+    fn read_density(
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        slab: &SlabDispatch,
+        samples: u32,
+    ) -> Vec<u32> {
+        let size = Extent3d {
+            width: samples,
+            height: samples,
+            depth_or_array_layers: samples,
+        };
+        // Rows of a copy out of a texture are aligned to 256 bytes
+        let row_words = (size.width + 63) / 64 * 64;
+
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (row_words * size.height * size.depth_or_array_layers) as u64 * 4,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut command_encoder =
+            render_device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        command_encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &slab.density_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(row_words * 4),
+                    rows_per_image: NonZeroU32::new(size.height),
+                },
+            },
+            size,
+        );
+        render_queue.submit(iter::once(command_encoder.finish()));
+
+        read_words(render_device, &buffer)
+            .chunks_exact(row_words as usize)
+            .flat_map(|row| row[..size.width as usize].iter().copied())
+            .collect()
+    }
+
+    /// Runs the passes of a chunk and reads back its density volume and its vertices
+    fn dispatch(
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        resources: &TerrainComputeResources,
+        coord: ChunkCoord,
+        settings: &TerrainSettings,
+        density: &GpuDensity,
+    ) -> (Vec<u32>, MeshData) {
+        let buffer_pool = BufferPool::default();
+        let format = ChunkVertexFormat::Full;
+
+        let slabs = prepare_chunk(
+            render_device,
+            render_queue,
+            resources,
+            &buffer_pool,
+            format,
+            coord,
+            settings,
+            density,
+        )
+        .unwrap();
+        assert_eq!(slabs.len(), 1, "the chunk has to fit into a single slab");
+
+        let jobs = GpuChunkJobs::default();
+        let _receiver = jobs.dispatch(coord, settings.clone(), density.clone());
+        let sender = match jobs.take().pop() {
+            Some(GpuChunkJob::Dispatch { sender, .. }) => sender,
+            _ => unreachable!(),
+        };
+
+        let prepared = PreparedChunks {
+            dispatches: vec![PreparedDispatch {
+                slabs,
+                buffer_set: BufferSet(Arc::new(())),
+                sender,
+            }],
+            ..PreparedChunks::default()
+        };
+
+        let mut command_encoder =
+            render_device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        node::record_chunk_jobs(
+            &prepared,
+            Some(resources),
+            &TimestampQueries(None),
+            &mut command_encoder,
+        );
+        render_queue.submit(iter::once(command_encoder.finish()));
+
+        let slab = prepared.slabs().next().unwrap();
+        let densities = read_density(
+            render_device,
+            render_queue,
+            slab,
+            settings.padded_samples_per_axis(),
+        );
+
+        let PreparedChunks { mut dispatches, .. } = prepared;
+        let slab = dispatches.pop().unwrap().slabs.pop().unwrap();
+        let vertex_count =
+            wait(render_device, slab.draw_args.read(|draw_args| draw_args[0])).unwrap() as usize;
+
+        let mut mesh_data = MeshData::default();
+
+        if vertex_count > 0 {
+            let vertices = GpuReadback::<u32>::new(render_device, vertex_count * format.words());
+            let mut command_encoder =
+                render_device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+            vertices.copy_from(&mut command_encoder, &slab.vertex_buffer);
+            render_queue.submit(iter::once(command_encoder.finish()));
+
+            wait(
+                render_device,
+                vertices.read(|words| {
+                    for vertex in words.chunks_exact(format.words()) {
+                        let (position, normal) = format.decode(vertex, settings.chunk_size);
+
+                        mesh_data.positions.push(position);
+                        mesh_data.normals.push(normal);
+                    }
                 }),
-                transform: Transform::from_xyz(
-                    chunk.position.x as f32 * CHUNK_SIZE as f32 - (CHUNK_SIZE as f32 / 2.0),
-                    chunk.position.y as f32 * CHUNK_SIZE as f32 - (CHUNK_SIZE as f32 / 2.0),
-                    chunk.position.z as f32 * CHUNK_SIZE as f32 - (CHUNK_SIZE as f32 / 2.0),
-                ),
-                ..Default::default()
-            });
-
-            commands
-                .entity(entity)
-                .remove::<Task<Result<(), BufferAsyncError>>>();
+            )
+            .unwrap();
+        }
+
+        (densities, mesh_data)
+    }
+
+    fn bits(values: &[[f32; 3]]) -> Vec<[u32; 3]> {
+        values.iter().map(|value| value.map(f32::to_bits)).collect()
+    }
+
+    /// Skipped on machines without a GPU, which can't run the compute shader
+    #[test]
+    fn fixed_point_chunks_match_cpu_mesher_exactly() {
+        let (render_device, render_queue) = match test_device() {
+            Some(device) if is_supported(&device.0) => device,
+            _ => {
+                eprintln!("no adapter able to run the compute shader, skipping");
+                return;
+            }
+        };
+
+        let resources = TerrainComputeResources::new(&render_device, WorkgroupSize::default());
+        let settings = TerrainSettings {
+            chunk_size: 16,
+            iso_level: 0.1,
+            ..TerrainSettings::default()
+        };
+        let density = SimplexDensity::with_noise(
+            1337,
+            SimplexNoise {
+                frequency: 1.0 / 24.0,
+                octaves: 4,
+                octave_seed_offset: 101,
+                ridged_octaves: 0b0010,
+                warp: DomainWarp {
+                    layers: 1,
+                    ..DomainWarp::default()
+                },
+                basis: NoiseBasis::FixedPoint,
+                ..SimplexNoise::default()
+            },
+        );
+        let gpu_density = GpuDensity::of(&density).unwrap();
+
+        let mut vertex_count = 0;
+
+        for x in -1..=1 {
+            for y in -1..=1 {
+                let coord = ChunkCoord(IVec3::new(x, y, 0));
+
+                let (densities, gpu_mesh) = dispatch(
+                    &render_device,
+                    &render_queue,
+                    &resources,
+                    coord,
+                    &settings,
+                    &gpu_density,
+                );
+
+                let grid = terrain::sample_chunk_density(&density, coord, &settings);
+                let cpu_mesh = cpu::march_chunk_smooth(
+                    &grid,
+                    TerrainSettings::GHOST,
+                    settings.iso_level,
+                    settings.vertex_placement,
+                );
+
+                let cpu_densities = grid.values().iter().map(|value| value.to_bits());
+
+                assert!(
+                    cpu_densities.eq(densities.iter().copied()),
+                    "densities of {:?} differ",
+                    coord
+                );
+                assert_eq!(bits(&gpu_mesh.positions), bits(&cpu_mesh.positions));
+                assert_eq!(bits(&gpu_mesh.normals), bits(&cpu_mesh.normals));
+
+                vertex_count += cpu_mesh.positions.len();
+            }
         }
+
+        assert!(vertex_count > 0, "none of the chunks crosses the surface");
     }
 }
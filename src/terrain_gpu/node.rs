@@ -0,0 +1,193 @@
+use super::{
+    timer::{TimestampQueries, TIMESTAMP_COUNT},
+    PreparedChunks, TerrainComputeResources,
+};
+use crate::gpu::GpuSubmission;
+use bevy::{
+    ecs::{system::Res, world::World},
+    render2::{
+        render_graph::{Node, NodeRunError, RenderGraphContext},
+        render_resource::{CommandEncoder, CommandEncoderDescriptor, ComputePassDescriptor},
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+    },
+};
+use std::iter;
+
+/// Render graph node recording the dispatches and vertex copies prepared this frame into the
+/// encoder of the graph, so they are submitted together with the rest of the frame, unless
+/// `GpuSubmission::Separate` submits them on their own
+pub(crate) struct TerrainComputeNode;
+
+impl TerrainComputeNode {
+    pub const NAME: &'static str = "terrain_compute";
+}
+
+impl Node for TerrainComputeNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        // Submitted on their own by `submit_chunk_jobs` otherwise
+        if *world.get_resource::<GpuSubmission>().unwrap() != GpuSubmission::RenderGraph {
+            return Ok(());
+        }
+
+        record_chunk_jobs(
+            world.get_resource::<PreparedChunks>().unwrap(),
+            world.get_resource::<TerrainComputeResources>(),
+            world.get_resource::<TimestampQueries>().unwrap(),
+            &mut render_context.command_encoder,
+        );
+
+        Ok(())
+    }
+}
+
+/// Submits the jobs prepared this frame in a command buffer of their own with
+/// `GpuSubmission::Separate`, right after they were prepared and before the render graph runs
+pub(crate) fn submit_chunk_jobs(
+    submission: Res<GpuSubmission>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    prepared: Res<PreparedChunks>,
+    resources: Option<Res<TerrainComputeResources>>,
+    queries: Res<TimestampQueries>,
+) {
+    if *submission != GpuSubmission::Separate || prepared.is_empty() {
+        return;
+    }
+
+    let mut command_encoder =
+        render_device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+
+    record_chunk_jobs(
+        &prepared,
+        resources.as_deref(),
+        &queries,
+        &mut command_encoder,
+    );
+
+    render_queue.submit(iter::once(command_encoder.finish()));
+}
+
+/// Records the dispatches and copies prepared this frame into `command_encoder`
+///
+/// `resources` are only missing on devices that can't run the shader, which never prepare
+/// dispatches.
+pub(super) fn record_chunk_jobs(
+    prepared: &PreparedChunks,
+    resources: Option<&TerrainComputeResources>,
+    queries: &TimestampQueries,
+    command_encoder: &mut CommandEncoder,
+) {
+    // Only written in the frames measured for `GpuTerrainStats`
+    let query_set = match (&queries.0, &prepared.timestamps) {
+        (Some(query_set), Some(_)) => Some(query_set),
+        _ => None,
+    };
+
+    // Every chunk of the frame is dispatched from a single compute pass, every pass runs for
+    // all of them before the next one starts. The copies are recorded after it into the
+    // same encoder.
+    if let (Some(resources), false) = (resources, prepared.dispatches.is_empty()) {
+        let mut compute_pass =
+            command_encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
+
+        if let Some(query_set) = query_set {
+            compute_pass.write_timestamp(query_set, 0);
+        }
+
+        compute_pass.set_pipeline(&resources.density_pipeline);
+
+        for slab in prepared.slabs() {
+            let [x, y, z] = slab.density_workgroups;
+
+            compute_pass.set_bind_group(0, &*slab.density_bind_group, &[]);
+            compute_pass.dispatch(x, y, z);
+        }
+
+        if let Some(query_set) = query_set {
+            compute_pass.write_timestamp(query_set, 1);
+        }
+
+        compute_pass.set_pipeline(&resources.count_pipeline);
+
+        for slab in prepared.slabs() {
+            let [x, y, z] = slab.workgroups;
+
+            compute_pass.set_bind_group(0, &*slab.bind_group, &[]);
+            compute_pass.dispatch(x, y, z);
+        }
+
+        compute_pass.set_pipeline(&resources.scan_blocks_pipeline);
+
+        for slab in prepared.slabs() {
+            compute_pass.set_bind_group(0, &*slab.bind_group, &[]);
+            compute_pass.dispatch(slab.scan_workgroups, 1, 1);
+        }
+
+        // The block sums of a slab are scanned by a single workgroup
+        compute_pass.set_pipeline(&resources.scan_block_sums_pipeline);
+
+        for slab in prepared.slabs() {
+            compute_pass.set_bind_group(0, &*slab.bind_group, &[]);
+            compute_pass.dispatch(1, 1, 1);
+        }
+
+        // Chunks pick the format of their vertices by their size. The scan wrote how many
+        // workgroups cover the active cells, so empty and solid cells cost nothing here.
+        for slab in prepared.slabs() {
+            compute_pass.set_pipeline(resources.compact_pipeline_for(slab.format));
+            compute_pass.set_bind_group(0, &*slab.bind_group, &[]);
+            compute_pass.dispatch_indirect(&slab.active_cells_buffer, 0);
+        }
+
+        if let Some(query_set) = query_set {
+            compute_pass.write_timestamp(query_set, 2);
+        }
+
+        drop(compute_pass);
+
+        if let (Some(query_set), Some(timestamps)) = (query_set, &prepared.timestamps) {
+            command_encoder.resolve_query_set(
+                query_set,
+                0..TIMESTAMP_COUNT,
+                &timestamps.resolve_buffer,
+                0,
+            );
+            timestamps
+                .readback
+                .copy_from(command_encoder, &timestamps.resolve_buffer);
+        }
+    }
+
+    for slab in prepared.slabs() {
+        slab.draw_args
+            .copy_from(command_encoder, &slab.draw_args_buffer);
+    }
+
+    for chunk in prepared.copies.iter() {
+        chunk
+            .vertices
+            .copy_from(command_encoder, &chunk.vertex_buffer);
+    }
+
+    // The slabs of a chunk are copied one after the other into its vertex buffer
+    for chunk in prepared.retains.iter() {
+        let mut offset = 0;
+
+        for (vertex_buffer, size) in chunk.vertex_buffers.iter() {
+            command_encoder.copy_buffer_to_buffer(
+                vertex_buffer,
+                0,
+                &chunk.mesh.vertex_buffer,
+                offset,
+                *size,
+            );
+
+            offset += size;
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use super::{draw::ChunkVertexFormat, shader};
+use crate::{gpu::WorkgroupSize, tables};
+use bevy::{
+    core::cast_slice,
+    render2::{
+        render_resource::{
+            BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferUsages, ComputePipeline,
+            ComputePipelineDescriptor, PipelineLayoutDescriptor, ShaderStages,
+            StorageTextureAccess, TextureFormat, TextureSampleType, TextureViewDimension,
+        },
+        renderer::RenderDevice,
+        shader::Shader,
+    },
+};
+
+/// Format of the density volume written by `density_main` of `terrain/density.wgsl`
+pub(crate) const DENSITY_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+/// Bind group layouts and pipelines of the passes of `terrain/density.wgsl` and
+/// `terrain/mesher.wgsl`, created with the shader module when the plugin is built and again
+/// whenever the shader is reloaded, shared by every dispatch
+///
+/// The density pass fills the density volume. The marching stage counts the triangles of every
+/// cell, scans the counts into offsets and writes the triangles of each cell at its offset.
+pub(crate) struct TerrainComputeResources {
+    /// Edge length of the workgroups of the density and count passes
+    pub workgroup_size: u32,
+    pub density_bind_group_layout: BindGroupLayout,
+    pub density_pipeline: ComputePipeline,
+    pub bind_group_layout: BindGroupLayout,
+    /// Lookup tables read by the marching stage, see `tables::to_storage_buffer`
+    pub tables_buffer: Buffer,
+    pub count_pipeline: ComputePipeline,
+    pub scan_blocks_pipeline: ComputePipeline,
+    pub scan_block_sums_pipeline: ComputePipeline,
+    pub compact_pipeline: ComputePipeline,
+    pub compact_quantized_pipeline: ComputePipeline,
+}
+
+impl TerrainComputeResources {
+    pub fn new(render_device: &RenderDevice, workgroup_size: WorkgroupSize) -> Self {
+        Self::from_source(render_device, workgroup_size, &shader::builtin_source())
+    }
+
+    /// Builds the pipelines from `chunk.wgsl` with its modules included, see `shader::compose`
+    pub fn from_source(
+        render_device: &RenderDevice,
+        workgroup_size: WorkgroupSize,
+        source: &str,
+    ) -> Self {
+        let workgroup_size = workgroup_size.resolve(render_device);
+        let scan_block_size = workgroup_size.pow(3);
+
+        // Workgroup sizes have to be literals, so they are replaced in the source
+        let source = source
+            .replace(
+                "let SCAN_BLOCK_SIZE: u32 = 256u;",
+                &format!("let SCAN_BLOCK_SIZE: u32 = {}u;", scan_block_size),
+            )
+            .replace(
+                "workgroup_size(8, 8, 8)",
+                &format!("workgroup_size({0}, {0}, {0})", workgroup_size),
+            )
+            .replace(
+                "workgroup_size(256)",
+                &format!("workgroup_size({})", scan_block_size),
+            );
+
+        let shader = Shader::from_wgsl(source);
+        let shader_module = render_device.create_shader_module(&shader);
+
+        let buffer_entry = |binding, ty| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let texture_entry = |binding, ty| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+
+        // The input, the density volume written as a storage texture, the modifiers and the
+        // permutation tables of Perlin noise
+        let density_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    buffer_entry(0, BufferBindingType::Uniform),
+                    texture_entry(
+                        3,
+                        BindingType::StorageTexture {
+                            access: StorageTextureAccess::WriteOnly,
+                            format: DENSITY_FORMAT,
+                            view_dimension: TextureViewDimension::D3,
+                        },
+                    ),
+                    buffer_entry(9, BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(10, BufferBindingType::Storage { read_only: true }),
+                ],
+            });
+
+        // The input, the draw args, the vertices, the density volume, the offsets of the cells
+        // and blocks, the lookup tables and the active cells. The volume is only loaded from so
+        // it doesn't need a filterable format.
+        let bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    buffer_entry(0, BufferBindingType::Uniform),
+                    buffer_entry(1, BufferBindingType::Storage { read_only: false }),
+                    buffer_entry(2, BufferBindingType::Storage { read_only: false }),
+                    texture_entry(
+                        4,
+                        BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: false },
+                            view_dimension: TextureViewDimension::D3,
+                            multisampled: false,
+                        },
+                    ),
+                    buffer_entry(5, BufferBindingType::Storage { read_only: false }),
+                    buffer_entry(6, BufferBindingType::Storage { read_only: false }),
+                    buffer_entry(7, BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(8, BufferBindingType::Storage { read_only: false }),
+                ],
+            });
+
+        let create_pipeline = |bind_group_layout, entry_point| {
+            let pipeline_layout = render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: None,
+                push_constant_ranges: &[],
+                bind_group_layouts: &[bind_group_layout],
+            });
+
+            render_device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                module: &shader_module,
+                entry_point,
+            })
+        };
+
+        let density_pipeline = create_pipeline(&density_bind_group_layout, "density_main");
+        let count_pipeline = create_pipeline(&bind_group_layout, "count_main");
+        let scan_blocks_pipeline = create_pipeline(&bind_group_layout, "scan_blocks_main");
+        let scan_block_sums_pipeline = create_pipeline(&bind_group_layout, "scan_block_sums_main");
+        let compact_pipeline = create_pipeline(&bind_group_layout, "compact_main");
+        let compact_quantized_pipeline =
+            create_pipeline(&bind_group_layout, "compact_quantized_main");
+
+        let tables_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: None,
+            contents: cast_slice(&tables::to_storage_buffer()),
+            usage: BufferUsages::STORAGE,
+        });
+
+        Self {
+            workgroup_size,
+            density_bind_group_layout,
+            density_pipeline,
+            bind_group_layout,
+            tables_buffer,
+            count_pipeline,
+            scan_blocks_pipeline,
+            scan_block_sums_pipeline,
+            compact_pipeline,
+            compact_quantized_pipeline,
+        }
+    }
+
+    /// Pipeline of the last pass writing vertices in the given format
+    pub fn compact_pipeline_for(&self, format: ChunkVertexFormat) -> &ComputePipeline {
+        match format {
+            ChunkVertexFormat::Full => &self.compact_pipeline,
+            ChunkVertexFormat::Quantized => &self.compact_quantized_pipeline,
+        }
+    }
+
+    /// Number of cells whose triangle counts are summed by one workgroup of the scan, also the
+    /// number of active cells polygonized by one workgroup of the compact pass
+    pub fn scan_block_size(&self) -> u32 {
+        self.workgroup_size.pow(3)
+    }
+}
@@ -0,0 +1,157 @@
+use super::{
+    pipeline::TerrainComputeResources,
+    shader::{self, MODULE_FOLDER, SHADER_PATH},
+};
+use crate::{
+    dirty::ChunkDirty,
+    gpu::WorkgroupSize,
+    terrain::{MeshingBackend, TerrainChunk},
+};
+use bevy::{
+    app::EventReader,
+    asset::{AssetEvent, AssetServer, AssetServerSettings, FileAssetIo, Handle, HandleUntyped},
+    ecs::{
+        entity::Entity,
+        query::With,
+        system::{Commands, Query, Res, ResMut},
+    },
+    log::{info, warn},
+    render2::{render_resource::ErrorFilter, renderer::RenderDevice, shader::Shader},
+};
+use futures_lite::future;
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+/// Composed source of a changed compute shader waiting for the render world to rebuild the pipelines,
+/// shared between both worlds like `GpuChunkJobs`
+#[derive(Clone, Default)]
+pub(crate) struct ShaderReload(Arc<Mutex<Option<String>>>);
+
+/// Keeps the watched shader and the modules in `MODULE_FOLDER` loaded, the assets are only used
+/// to get notified of changes because the pipelines are built from the composed source
+struct ComputeShaderHandles {
+    shader: Handle<Shader>,
+    modules: Vec<HandleUntyped>,
+}
+
+pub(crate) fn load_compute_shader(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let modules = asset_server
+        .load_folder(MODULE_FOLDER)
+        .unwrap_or_else(|error| {
+            warn!("Failed to watch the shader modules: {:?}", error);
+            Vec::new()
+        });
+
+    commands.insert_resource(ComputeShaderHandles {
+        shader: asset_server.load(SHADER_PATH),
+        modules,
+    });
+}
+
+/// Reads and composes the compute shader again when the asset server reports a change to it or
+/// one of its modules and marks every chunk dirty, so they are remeshed once the render world
+/// rebuilt the pipelines
+///
+/// Includes are read from the assets folder and fall back to the modules built into the crate.
+/// Changes are only reported with `watch_for_changes` in the `AssetServerSettings`.
+pub(crate) fn watch_compute_shader(
+    mut commands: Commands,
+    mut shader_events: EventReader<AssetEvent<Shader>>,
+    handles: Option<Res<ComputeShaderHandles>>,
+    asset_server_settings: Option<Res<AssetServerSettings>>,
+    backend: Res<MeshingBackend>,
+    reload: Res<ShaderReload>,
+    chunk_query: Query<Entity, With<TerrainChunk>>,
+) {
+    let handles = match handles {
+        Some(handles) => handles,
+        None => return,
+    };
+
+    // The first load is the source the pipelines were built from
+    let modified = shader_events.iter().any(|event| match event {
+        AssetEvent::Modified { handle } => {
+            handle.id == handles.shader.id
+                || handles.modules.iter().any(|module| module.id == handle.id)
+        }
+        _ => false,
+    });
+
+    if !modified {
+        return;
+    }
+
+    let asset_folder = asset_server_settings
+        .as_ref()
+        .map_or("assets", |settings| settings.asset_folder.as_str());
+    let root = FileAssetIo::get_root_path().join(asset_folder);
+
+    let source = match fs::read_to_string(root.join(SHADER_PATH)) {
+        Ok(source) => source,
+        Err(error) => {
+            warn!("Failed to read {}: {}", SHADER_PATH, error);
+            return;
+        }
+    };
+
+    let mut load = |path: &str| {
+        fs::read_to_string(root.join(path))
+            .ok()
+            .or_else(|| shader::builtin_module(path))
+    };
+
+    match shader::compose(&source, &mut load) {
+        Ok(source) => *reload.0.lock().unwrap() = Some(source),
+        Err(error) => {
+            warn!("Failed to compose {}: {}", SHADER_PATH, error);
+            return;
+        }
+    }
+
+    // Chunks meshed on the CPU don't depend on the shader
+    if *backend == MeshingBackend::Cpu {
+        return;
+    }
+
+    for entity in chunk_query.iter() {
+        commands.entity(entity).insert(ChunkDirty::default());
+    }
+}
+
+/// Rebuilds the pipelines from a reloaded shader before the chunks of the frame are prepared,
+/// a shader that doesn't validate keeps the previous pipelines
+pub(crate) fn reload_compute_pipelines(
+    reload: Res<ShaderReload>,
+    render_device: Res<RenderDevice>,
+    resources: Option<ResMut<TerrainComputeResources>>,
+) {
+    let mut resources = match resources {
+        Some(resources) => resources,
+        None => return,
+    };
+
+    let source = match reload.0.lock().unwrap().take() {
+        Some(source) => source,
+        None => return,
+    };
+
+    let device = render_device.wgpu_device();
+
+    device.push_error_scope(ErrorFilter::Validation);
+
+    let reloaded = TerrainComputeResources::from_source(
+        &render_device,
+        WorkgroupSize::Fixed(resources.workgroup_size),
+        &source,
+    );
+
+    match future::block_on(device.pop_error_scope()) {
+        Some(error) => warn!("Keeping the previous compute shader: {}", error),
+        None => {
+            *resources = reloaded;
+            info!("Reloaded the compute shader");
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::tables;
+use std::{collections::HashSet, fmt};
+
+/// Path of the compute shader in the assets folder, a stub including the modules of
+/// `MODULE_FOLDER`
+pub(crate) const SHADER_PATH: &str = "chunk.wgsl";
+
+/// Folder of the modules included by the compute shader, relative to the assets folder
+pub(crate) const MODULE_FOLDER: &str = "terrain";
+
+/// Modules built into the crate, used until the shader is reloaded from the assets folder
+const BUILTIN_MODULES: [(&str, &str); 3] = [
+    (
+        "terrain/mesher.wgsl",
+        include_str!("../../assets/terrain/mesher.wgsl"),
+    ),
+    (
+        "terrain/noise.wgsl",
+        include_str!("../../assets/terrain/noise.wgsl"),
+    ),
+    (
+        "terrain/density.wgsl",
+        include_str!("../../assets/terrain/density.wgsl"),
+    ),
+];
+
+/// Returned by `compose` for an include that couldn't be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MissingInclude(pub String);
+
+impl fmt::Display for MissingInclude {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no shader module named \"{}\"", self.0)
+    }
+}
+
+/// Replaces every `#include "path"` line of a WGSL source with the module loaded for the path
+///
+/// Includes are resolved recursively and every module is included once, where it is first
+/// included, so modules can include what they depend on. `tables` is generated by
+/// `tables::to_wgsl`, every other path goes through `load`.
+pub(crate) fn compose(
+    source: &str,
+    load: &mut dyn FnMut(&str) -> Option<String>,
+) -> Result<String, MissingInclude> {
+    let mut composed = String::new();
+
+    compose_into(&mut composed, source, load, &mut HashSet::new())?;
+
+    Ok(composed)
+}
+
+fn compose_into(
+    composed: &mut String,
+    source: &str,
+    load: &mut dyn FnMut(&str) -> Option<String>,
+    included: &mut HashSet<String>,
+) -> Result<(), MissingInclude> {
+    for line in source.lines() {
+        let path = match include_path(line) {
+            Some(path) => path,
+            None => {
+                composed.push_str(line);
+                composed.push('\n');
+                continue;
+            }
+        };
+
+        if !included.insert(path.to_string()) {
+            continue;
+        }
+
+        let module = if path == "tables" {
+            tables::to_wgsl()
+        } else {
+            load(path).ok_or_else(|| MissingInclude(path.to_string()))?
+        };
+
+        compose_into(composed, &module, load, included)?;
+    }
+
+    Ok(())
+}
+
+fn include_path(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("#include")?
+        .trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')
+}
+
+/// Module of the given path built into the crate
+pub(crate) fn builtin_module(path: &str) -> Option<String> {
+    BUILTIN_MODULES
+        .iter()
+        .find(|(module_path, _)| *module_path == path)
+        .map(|(_, module)| module.to_string())
+}
+
+/// The compute shader built into the crate with its includes resolved
+pub(crate) fn builtin_source() -> String {
+    compose(include_str!("../../assets/chunk.wgsl"), &mut builtin_module)
+        .expect("built in shader modules are missing")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules(path: &str) -> Option<String> {
+        match path {
+            "a.wgsl" => Some("#include \"b.wgsl\"\nfn a() {}".to_string()),
+            "b.wgsl" => Some("fn b() {}".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn includes_are_resolved_recursively() {
+        let composed = compose("#include \"a.wgsl\"\nfn main() {}", &mut modules).unwrap();
+
+        assert_eq!(composed, "fn b() {}\nfn a() {}\nfn main() {}\n");
+    }
+
+    #[test]
+    fn modules_are_included_once() {
+        let source = "  #include \"b.wgsl\"\n#include \"a.wgsl\"\n#include \"b.wgsl\"";
+        let composed = compose(source, &mut modules).unwrap();
+
+        assert_eq!(composed, "fn b() {}\nfn a() {}\n");
+    }
+
+    #[test]
+    fn tables_are_generated() {
+        let composed = compose("#include \"tables\"", &mut |path| {
+            panic!("\"{}\" was loaded", path)
+        })
+        .unwrap();
+
+        assert!(composed.lines().eq(tables::to_wgsl().lines()));
+    }
+
+    #[test]
+    fn missing_include_is_an_error() {
+        assert_eq!(
+            compose("#include \"a.wgsl\"\n#include \"c.wgsl\"", &mut modules),
+            Err(MissingInclude("c.wgsl".to_string()))
+        );
+    }
+
+    #[test]
+    fn builtin_modules_compose() {
+        let source = builtin_source();
+
+        for entry_point in ["density_main", "count_main", "compact_main"].iter() {
+            assert!(source.contains(&format!("fn {}(", entry_point)));
+        }
+        assert!(!source.lines().any(|line| include_path(line).is_some()));
+    }
+}
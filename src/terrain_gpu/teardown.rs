@@ -0,0 +1,60 @@
+use super::GpuChunkJobs;
+use crate::{gpu::BufferPool, terrain::ChunkMeshTask};
+use bevy::{
+    app::{AppExit, EventReader},
+    ecs::{entity::Entity, query::With, system::ResMut, world::World},
+    render2::{render_resource::Maintain, renderer::RenderDevice},
+};
+use futures_lite::future;
+
+/// Set once an `AppExit` was sent, see `teardown_gpu_work`
+#[derive(Default)]
+pub(crate) struct GpuTeardown {
+    requested: bool,
+}
+
+pub(crate) fn request_gpu_teardown(
+    mut exit_events: EventReader<AppExit>,
+    mut teardown: ResMut<GpuTeardown>,
+) {
+    if exit_events.iter().next().is_some() {
+        teardown.requested = true;
+    }
+}
+
+/// Winds down the GPU work of the terrain in the frame the app exits, before the device is
+/// destroyed with the render world
+///
+/// The chunk tasks are cancelled first, so none of them maps a buffer afterwards, then the device
+/// finishes its work, which completes or fails every pending mapping. Without it a mapping still
+/// pending when the device goes away raises validation errors or blocks the shutdown.
+pub(crate) fn teardown_gpu_work(world: &mut World) {
+    if !world
+        .get_resource::<GpuTeardown>()
+        .map_or(false, |teardown| teardown.requested)
+    {
+        return;
+    }
+
+    let entities = world
+        .query_filtered::<Entity, With<ChunkMeshTask>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+
+    for entity in entities {
+        if let Some(task) = world.entity_mut(entity).remove::<ChunkMeshTask>() {
+            future::block_on(task.cancel());
+        }
+    }
+
+    // Dropping the queued jobs closes their channels, the render world has nothing left to
+    // dispatch in this frame
+    drop(world.get_resource::<GpuChunkJobs>().unwrap().take());
+
+    if let Some(render_device) = world.get_resource::<RenderDevice>() {
+        render_device.wgpu_device().poll(Maintain::Wait);
+    }
+
+    world.get_resource::<BufferPool>().unwrap().clear();
+    world.get_resource_mut::<GpuTeardown>().unwrap().requested = false;
+}
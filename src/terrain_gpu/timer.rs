@@ -0,0 +1,172 @@
+use crate::gpu::{BufferPool, GpuReadback, GpuTerrainStats, PooledBuffer};
+use bevy::{
+    ecs::system::{Res, ResMut},
+    render2::{
+        options::WgpuFeatures,
+        render_resource::{BufferUsages, QuerySet, QuerySetDescriptor, QueryType},
+        renderer::RenderDevice,
+    },
+    tasks::AsyncComputeTaskPool,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Timestamps written per measured frame, before the density passes, between them and the
+/// marching passes and after those
+pub(crate) const TIMESTAMP_COUNT: u32 = 3;
+
+/// Query set the compute node writes its timestamps into, `None` on devices without
+/// `WgpuFeatures::TIMESTAMP_QUERY`
+pub(crate) struct TimestampQueries(pub Option<QuerySet>);
+
+impl TimestampQueries {
+    pub fn new(render_device: &RenderDevice) -> Self {
+        if !render_device
+            .features()
+            .contains(WgpuFeatures::TIMESTAMP_QUERY)
+        {
+            return Self(None);
+        }
+
+        Self(Some(render_device.wgpu_device().create_query_set(
+            &QuerySetDescriptor {
+                label: None,
+                ty: QueryType::Timestamp,
+                count: TIMESTAMP_COUNT,
+            },
+        )))
+    }
+}
+
+/// Timestamps resolved by the compute node in a measured frame, see `GpuTimer::begin_frame`
+pub(crate) struct FrameTimestamps {
+    pub resolve_buffer: PooledBuffer,
+    pub readback: GpuReadback<u64>,
+    pub slabs: usize,
+    pub cells: u64,
+}
+
+/// Hands the timestamps of measured frames from the render world to the main world, shared
+/// between both worlds like `GpuChunkJobs`
+#[derive(Clone)]
+pub(crate) struct GpuTimer {
+    inner: Arc<Mutex<TimerInner>>,
+}
+
+struct TimerInner {
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    in_flight: bool,
+    submitted: Option<FrameTimestamps>,
+    measured: Option<(Duration, Duration)>,
+    slabs: usize,
+    cells: u64,
+}
+
+impl GpuTimer {
+    pub fn new(period: f32) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(TimerInner {
+                period,
+                in_flight: false,
+                submitted: None,
+                measured: None,
+                slabs: 0,
+                cells: 0,
+            })),
+        }
+    }
+
+    /// Starts measuring a frame with `slabs` slabs covering `cells` cells, unless the previous
+    /// measurement hasn't been read back yet
+    pub fn begin_frame(
+        &self,
+        buffer_pool: &BufferPool,
+        render_device: &RenderDevice,
+        slabs: usize,
+        cells: u64,
+    ) -> Option<FrameTimestamps> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.in_flight {
+            return None;
+        }
+
+        inner.in_flight = true;
+
+        let size = TIMESTAMP_COUNT as u64 * 8;
+
+        Some(FrameTimestamps {
+            resolve_buffer: buffer_pool.take(
+                render_device,
+                size,
+                BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            ),
+            readback: GpuReadback::from_pool(buffer_pool, render_device, TIMESTAMP_COUNT as usize),
+            slabs,
+            cells,
+        })
+    }
+
+    /// Called once the frame has been submitted
+    pub fn submit(&self, timestamps: FrameTimestamps) {
+        self.inner.lock().unwrap().submitted = Some(timestamps);
+    }
+}
+
+/// Reads back the timestamps of the last measured frame on the task pool and copies finished
+/// measurements into `GpuTerrainStats`
+pub(crate) fn update_gpu_terrain_stats(
+    task_pool: Res<AsyncComputeTaskPool>,
+    timer: Res<GpuTimer>,
+    mut stats: ResMut<GpuTerrainStats>,
+) {
+    let mut inner = timer.inner.lock().unwrap();
+
+    if let Some((density, marching)) = inner.measured.take() {
+        stats.density = density;
+        stats.marching = marching;
+        stats.slabs = inner.slabs;
+        stats.cells = inner.cells;
+        stats.frames += 1;
+    }
+
+    let timestamps = match inner.submitted.take() {
+        Some(timestamps) => timestamps,
+        None => return,
+    };
+
+    let timer = timer.clone();
+
+    task_pool
+        .spawn(async move {
+            let FrameTimestamps {
+                resolve_buffer,
+                readback,
+                slabs,
+                cells,
+            } = timestamps;
+
+            // The resolve is done once the copy out of it is
+            let ticks = readback.read(|ticks| ticks.to_vec()).await;
+            drop(resolve_buffer);
+
+            let mut inner = timer.inner.lock().unwrap();
+
+            inner.in_flight = false;
+
+            if let Ok(ticks) = ticks {
+                let period = inner.period as f64;
+                let duration = |from: u64, to: u64| {
+                    Duration::from_nanos((to.saturating_sub(from) as f64 * period) as u64)
+                };
+
+                inner.measured = Some((duration(ticks[0], ticks[1]), duration(ticks[1], ticks[2])));
+                inner.slabs = slabs;
+                inner.cells = cells;
+            }
+        })
+        .detach();
+}
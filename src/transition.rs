@@ -0,0 +1,539 @@
+use crate::{
+    chunk::ChunkCoord, cpu, density::DensityGrid, mesh::MeshData, terrain::VertexPlacement,
+};
+use bevy::math::{IVec3, UVec3, Vec3};
+use std::collections::{HashMap, HashSet};
+
+/// Detail levels by which each of the 26 chunks around a chunk is finer than it, see
+/// `SeamMode::TransitionCells`
+///
+/// Chunks meshed at the same or a coarser level count as 0, they stitch themselves to this one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkTransitions([u8; 27]);
+
+impl ChunkTransitions {
+    /// Transitions of the chunk at `coord` meshed at detail level `lod`, `lod_of` returns the
+    /// detail level of the chunks around it
+    pub fn new(coord: ChunkCoord, lod: u32, lod_of: impl Fn(ChunkCoord) -> u32) -> Self {
+        let mut levels = [0; 27];
+
+        for (index, level) in levels.iter_mut().enumerate() {
+            let index = index as i32;
+            let offset = IVec3::new(index % 3, index / 3 % 3, index / 9) - IVec3::ONE;
+
+            if offset != IVec3::ZERO {
+                *level = lod.saturating_sub(lod_of(coord.offset(offset))) as u8;
+            }
+        }
+
+        Self(levels)
+    }
+
+    /// Levels by which the chunk at `offset` is finer, the components of `offset` range from -1
+    /// to 1
+    pub fn finer_levels(&self, offset: IVec3) -> u32 {
+        let index = offset + IVec3::ONE;
+
+        self.0[(index.z * 9 + index.y * 3 + index.x) as usize] as u32
+    }
+
+    /// Levels by which the finest chunk around is finer
+    pub fn max_finer_levels(&self) -> u32 {
+        self.0.iter().copied().max().unwrap_or(0) as u32
+    }
+
+    /// Whether no chunk around is finer, so the chunk is meshed without transition cells
+    pub fn is_empty(&self) -> bool {
+        self.max_finer_levels() == 0
+    }
+}
+
+/// Polygonizes a grid returned by `terrain::sample_chunk_density` like
+/// `cpu::march_chunk_smooth`, replacing the cells touching finer chunks by transition cells
+///
+/// The faces and edges a transition cell shares with finer chunks are sampled at their
+/// resolution, `sample` returns the density at a position in cells relative to the chunk. Every
+/// face of the cell is contoured like marching squares, which keeps the corners below the iso
+/// level apart on ambiguous faces the way `TRI_TABLE` does, so the contours match the cells on
+/// the other side whichever chunk they belong to. The loops the contours form around the cell
+/// are fanned out from their centre.
+pub fn march_chunk_transitions(
+    grid: &DensityGrid,
+    border: u32,
+    transitions: &ChunkTransitions,
+    iso_level: f32,
+    placement: VertexPlacement,
+    sample: &dyn Fn(Vec3) -> f32,
+) -> MeshData {
+    let dims = grid.dims();
+    let end = |len: u32| len.saturating_sub(border + 1);
+
+    let (regular, cells) = cpu::march_cells_smooth(
+        grid,
+        border,
+        UVec3::splat(border),
+        UVec3::new(end(dims.x), end(dims.y), end(dims.z)),
+        iso_level,
+        placement,
+    );
+
+    let mut mesher = TransitionMesher::new(grid, border, transitions, iso_level, placement, sample);
+    let transition_cells = mesher.transition_cells();
+    let replaced = transition_cells.iter().copied().collect::<HashSet<_>>();
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+
+    for (triangle, cell) in cells.iter().enumerate() {
+        if replaced.contains(&(cell.as_ivec3() - IVec3::splat(border as i32))) {
+            continue;
+        }
+
+        positions.extend_from_slice(&regular.positions[triangle * 3..triangle * 3 + 3]);
+        normals.extend_from_slice(&regular.normals[triangle * 3..triangle * 3 + 3]);
+    }
+
+    for cell in transition_cells.iter() {
+        mesher.march_cell(*cell, &mut positions, &mut normals);
+    }
+
+    let indices = (0..positions.len() as u32).collect();
+
+    MeshData {
+        positions,
+        normals,
+        indices,
+        colors: Vec::new(),
+    }
+}
+
+/// Edge crossing the surface, as its ends ordered like `cpu::interpolate_vertices` orders them
+type Crossing = (IVec3, IVec3);
+
+/// Samples and contours the transition cells of a chunk
+///
+/// Points are given in steps of `1 / scale` cells relative to the chunk, `scale` being the
+/// ratio between the cells of the chunk and those of its finest neighbour.
+struct TransitionMesher<'a> {
+    grid: &'a DensityGrid,
+    border: u32,
+    transitions: &'a ChunkTransitions,
+    iso_level: f32,
+    placement: VertexPlacement,
+    sample: &'a dyn Fn(Vec3) -> f32,
+    scale: i32,
+    /// Far corner of the chunk
+    size: IVec3,
+    /// Samples between the grid points
+    values: HashMap<IVec3, f32>,
+}
+
+impl<'a> TransitionMesher<'a> {
+    fn new(
+        grid: &'a DensityGrid,
+        border: u32,
+        transitions: &'a ChunkTransitions,
+        iso_level: f32,
+        placement: VertexPlacement,
+        sample: &'a dyn Fn(Vec3) -> f32,
+    ) -> Self {
+        let scale = 1 << transitions.max_finer_levels();
+        let cells = grid.dims().as_ivec3() - IVec3::splat(2 * border as i32 + 1);
+
+        Self {
+            grid,
+            border,
+            transitions,
+            iso_level,
+            placement,
+            sample,
+            scale,
+            size: cells * scale,
+            values: HashMap::new(),
+        }
+    }
+
+    /// Cells with an edge on a face of the chunk shared with a finer chunk, in the order they
+    /// are marched
+    fn transition_cells(&self) -> Vec<IVec3> {
+        let cells = self.size / self.scale;
+        let last = cells - IVec3::ONE;
+        let mut transition_cells = Vec::new();
+
+        for z in 0..cells.z {
+            for y in 0..cells.y {
+                for x in 0..cells.x {
+                    let cell = IVec3::new(x, y, z);
+
+                    let on_border = (0..3).any(|axis| cell[axis] == 0 || cell[axis] == last[axis]);
+
+                    if on_border && self.has_fine_edge(cell) {
+                        transition_cells.push(cell);
+                    }
+                }
+            }
+        }
+
+        transition_cells
+    }
+
+    /// Whether an edge of a cell is sampled finer than the cell, the faces of the cell sampled
+    /// finer have such edges as well
+    fn has_fine_edge(&self, cell: IVec3) -> bool {
+        let min = cell * self.scale;
+
+        (0..3).any(|axis| {
+            let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            (0..4).any(|corner| {
+                let mut start = min;
+                start[b] += (corner & 1) * self.scale;
+                start[c] += (corner >> 1) * self.scale;
+
+                let mut end = start;
+                end[axis] += self.scale;
+
+                self.step(start, end) < self.scale
+            })
+        })
+    }
+
+    /// Spacing of the samples along an edge or over a face from `min` to `max`, set by the
+    /// finest chunk sharing it
+    fn step(&self, min: IVec3, max: IVec3) -> i32 {
+        let range = |axis: usize| {
+            if min[axis] != max[axis] {
+                0..=0
+            } else if min[axis] == 0 {
+                -1..=0
+            } else if min[axis] == self.size[axis] {
+                0..=1
+            } else {
+                0..=0
+            }
+        };
+
+        let mut levels = 0;
+
+        for z in range(2) {
+            for y in range(1) {
+                for x in range(0) {
+                    levels = levels.max(self.transitions.finer_levels(IVec3::new(x, y, z)));
+                }
+            }
+        }
+
+        self.scale >> levels
+    }
+
+    /// Density at a point, read from the grid when it has the point
+    fn value(&mut self, point: IVec3) -> f32 {
+        let grid_point = point / self.scale + IVec3::splat(self.border as i32);
+        let dims = self.grid.dims().as_ivec3();
+
+        let on_grid = point % self.scale == IVec3::ZERO
+            && (0..3).all(|axis| grid_point[axis] >= 0 && grid_point[axis] < dims[axis]);
+
+        if on_grid {
+            let grid_point = grid_point.as_uvec3();
+
+            return self.grid.get(grid_point.x, grid_point.y, grid_point.z);
+        }
+
+        let (sample, scale) = (self.sample, self.scale);
+
+        *self
+            .values
+            .entry(point)
+            .or_insert_with(|| sample(point.as_vec3() * (1.0 / scale as f32)))
+    }
+
+    fn is_below(&mut self, point: IVec3) -> bool {
+        self.value(point) < self.iso_level
+    }
+
+    /// Central difference of the density over `step`, like `cpu::gradient` at the resolution of
+    /// the edge
+    fn gradient(&mut self, point: IVec3, step: i32) -> Vec3 {
+        let mut difference =
+            |axis: IVec3| self.value(point + axis * step) - self.value(point - axis * step);
+
+        Vec3::new(
+            difference(IVec3::X),
+            difference(IVec3::Y),
+            difference(IVec3::Z),
+        ) / 2.0
+    }
+
+    /// Position, in cells relative to the chunk, and normal of the vertex on a crossing edge
+    fn vertex(&mut self, (a, b): Crossing) -> (Vec3, Vec3) {
+        let step = (b - a).abs().max_element();
+        let (a_value, b_value) = (self.value(a), self.value(b));
+
+        let position = cpu::interpolate_vertices(
+            (a.as_uvec3(), a_value),
+            (b.as_uvec3(), b_value),
+            self.iso_level,
+            self.placement,
+        ) * (1.0 / self.scale as f32);
+
+        let normal = cpu::normalize_exactly(self.gradient(a, step) + self.gradient(b, step));
+
+        (position, normal)
+    }
+
+    /// Contours every face of a cell and triangulates the loops of the contours
+    fn march_cell(
+        &mut self,
+        cell: IVec3,
+        positions: &mut Vec<[f32; 3]>,
+        normals: &mut Vec<[f32; 3]>,
+    ) {
+        let min = cell * self.scale;
+
+        // Each crossing starts exactly one segment, walking them from crossing to crossing
+        // closes the loops
+        let mut segments = HashMap::new();
+        let mut starts = Vec::new();
+
+        for axis in 0..3 {
+            let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            for side in 0..2 {
+                let mut face_min = min;
+                face_min[axis] += side * self.scale;
+
+                let mut face_max = face_min;
+                face_max[b] += self.scale;
+                face_max[c] += self.scale;
+
+                let step = self.step(face_min, face_max);
+
+                for j in 0..self.scale / step {
+                    for i in 0..self.scale / step {
+                        let mut origin = face_min;
+                        origin[b] += i * step;
+                        origin[c] += j * step;
+
+                        let mut corners = [origin; 4];
+                        corners[1][b] += step;
+                        corners[2][b] += step;
+                        corners[2][c] += step;
+                        corners[3][c] += step;
+
+                        // Counterclockwise seen from outside the cell
+                        if side == 0 {
+                            corners.swap(1, 3);
+                        }
+
+                        for (start, end) in self.contour_square(&corners) {
+                            segments.insert(start, end);
+                            starts.push(start);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut visited = HashSet::new();
+
+        for first in starts {
+            if visited.contains(&first) {
+                continue;
+            }
+
+            let mut ring = Vec::new();
+            let mut crossing = first;
+
+            while visited.insert(crossing) {
+                ring.push(self.vertex(crossing));
+
+                crossing = match segments.get(&crossing) {
+                    Some(next) => *next,
+                    None => break,
+                };
+            }
+
+            self.triangulate(cell, &ring, positions, normals);
+        }
+    }
+
+    /// Segments of the contour through a square of a cell face, whose corners are given
+    /// counterclockwise seen from outside the cell, directed so the surface they bound faces
+    /// towards the higher density
+    ///
+    /// The corners are joined by the samples of the edges between them, a segment cuts off
+    /// every run of samples below the iso level.
+    fn contour_square(&mut self, corners: &[IVec3; 4]) -> Vec<(Crossing, Crossing)> {
+        let mut points = Vec::new();
+
+        for (index, start) in corners.iter().enumerate() {
+            let end = corners[(index + 1) % 4];
+            let step = self.step(start.min(end), start.max(end));
+            let direction = (end - *start) / (end - *start).abs().max_element();
+            let samples = (end - *start).abs().max_element() / step;
+
+            points.extend((0..samples).map(|sample| *start + direction * sample * step));
+        }
+
+        let below = points
+            .iter()
+            .map(|point| self.is_below(*point))
+            .collect::<Vec<_>>();
+
+        let len = points.len();
+        let crossing = |index: usize| {
+            let (a, b) = (points[index], points[(index + 1) % len]);
+
+            if (a.z, a.y, a.x) <= (b.z, b.y, b.x) {
+                (a, b)
+            } else {
+                (b, a)
+            }
+        };
+
+        let mut segments = Vec::new();
+
+        for enter in 0..len {
+            if below[enter] || !below[(enter + 1) % len] {
+                continue;
+            }
+
+            let mut leave = (enter + 1) % len;
+
+            while below[(leave + 1) % len] {
+                leave = (leave + 1) % len;
+            }
+
+            segments.push((crossing(enter), crossing(leave)));
+        }
+
+        segments
+    }
+
+    /// Fans a loop of vertices out from its centre, a centre lying on a face of the cell is
+    /// pulled towards the middle of the cell so the fan doesn't lie flat on the face
+    fn triangulate(
+        &self,
+        cell: IVec3,
+        ring: &[(Vec3, Vec3)],
+        positions: &mut Vec<[f32; 3]>,
+        normals: &mut Vec<[f32; 3]>,
+    ) {
+        let mut push = |vertices: [(Vec3, Vec3); 3]| {
+            for (position, normal) in vertices.iter() {
+                positions.push((*position).into());
+                normals.push((*normal).into());
+            }
+        };
+
+        if ring.len() < 3 {
+            return;
+        }
+
+        if ring.len() == 3 {
+            push([ring[0], ring[1], ring[2]]);
+
+            return;
+        }
+
+        let mut center = ring
+            .iter()
+            .fold(Vec3::ZERO, |sum, (position, _)| sum + *position)
+            / ring.len() as f32;
+        let normal = cpu::normalize_exactly(
+            ring.iter()
+                .fold(Vec3::ZERO, |sum, (_, normal)| sum + *normal),
+        );
+
+        let cell_min = cell.as_vec3();
+        let flat = (0..3).any(|axis| {
+            [cell_min[axis], cell_min[axis] + 1.0]
+                .iter()
+                .any(|face| ring.iter().all(|(position, _)| position[axis] == *face))
+        });
+
+        if flat {
+            center += (cell_min + Vec3::splat(0.5) - center) * 0.25;
+        }
+
+        for (index, vertex) in ring.iter().enumerate() {
+            push([(center, normal), *vertex, ring[(index + 1) % ring.len()]]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Edges of the triangles running along a face of the cell at the origin, in the direction
+    /// the triangles wind
+    fn face_edges(positions: &[[f32; 3]]) -> HashSet<([u32; 3], [u32; 3])> {
+        let bits = |position: [f32; 3]| {
+            [
+                position[0].to_bits(),
+                position[1].to_bits(),
+                position[2].to_bits(),
+            ]
+        };
+        let on_face = |a: [f32; 3], b: [f32; 3]| {
+            (0..3).any(|axis| {
+                (a[axis] == 0.0 && b[axis] == 0.0) || (a[axis] == 1.0 && b[axis] == 1.0)
+            })
+        };
+
+        positions
+            .chunks_exact(3)
+            .flat_map(|triangle| {
+                (0..3).map(move |corner| (triangle[corner], triangle[(corner + 1) % 3]))
+            })
+            .filter(|(a, b)| on_face(*a, *b))
+            .map(|(a, b)| (bits(a), bits(b)))
+            .collect()
+    }
+
+    #[test]
+    fn plain_transition_cells_match_marching_cubes() {
+        let transitions = ChunkTransitions::default();
+        let sample = |_: Vec3| 1.0;
+
+        for configuration in 0..256 {
+            // One cell inside a layer of ghost samples
+            let grid = DensityGrid::from_fn(UVec3::splat(4), |point| {
+                let corner = cpu::CORNER_OFFSETS
+                    .iter()
+                    .position(|offset| *offset + UVec3::ONE == point);
+
+                match corner {
+                    Some(corner) if configuration & (1 << corner) != 0 => {
+                        -0.5 - corner as f32 * 0.1
+                    }
+                    Some(corner) => 0.5 + corner as f32 * 0.1,
+                    None => 1.0,
+                }
+            });
+
+            let regular = cpu::march_chunk_smooth(&grid, 1, 0.0, VertexPlacement::Interpolated);
+
+            let mut mesher = TransitionMesher::new(
+                &grid,
+                1,
+                &transitions,
+                0.0,
+                VertexPlacement::Interpolated,
+                &sample,
+            );
+            let (mut positions, mut normals) = (Vec::new(), Vec::new());
+
+            mesher.march_cell(IVec3::ZERO, &mut positions, &mut normals);
+
+            assert_eq!(
+                face_edges(&positions),
+                face_edges(&regular.positions),
+                "configuration {} contours the faces differently",
+                configuration
+            );
+        }
+    }
+}
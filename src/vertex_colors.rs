@@ -0,0 +1,114 @@
+use crate::{density::DensityField, mesh::MeshData};
+use bevy::math::Vec3;
+use std::sync::Arc;
+
+/// Colors the vertices of every chunk mesh, so materials reading `Mesh::ATTRIBUTE_COLOR` can
+/// blend between ground types without textures
+///
+/// The color channels are free to carry anything, a material id, weights of several materials
+/// or the slope. Runs on the async compute task pool after the post processors.
+pub trait VertexColorSource: Send + Sync {
+    fn color(&self, vertex: &SurfaceVertex) -> [f32; 4];
+}
+
+impl<F> VertexColorSource for F
+where
+    F: Fn(&SurfaceVertex) -> [f32; 4] + Send + Sync,
+{
+    fn color(&self, vertex: &SurfaceVertex) -> [f32; 4] {
+        self(vertex)
+    }
+}
+
+/// Vertex color source of the chunk meshes, inserted by `TerrainPlugin`, vertices are left
+/// uncolored when `None`
+#[derive(Clone, Default)]
+pub struct MeshVertexColors(pub Option<Arc<dyn VertexColorSource>>);
+
+impl MeshVertexColors {
+    pub fn new(source: impl VertexColorSource + 'static) -> Self {
+        Self(Some(Arc::new(source)))
+    }
+
+    /// Colors every vertex of `mesh`, placed in the density field at `(position + origin) *
+    /// scale` like the density samples of a chunk
+    pub fn colors(
+        &self,
+        mesh: &MeshData,
+        density: &dyn DensityField,
+        iso_level: f32,
+        origin: Vec3,
+        scale: f32,
+    ) -> Option<Vec<[f32; 4]>> {
+        let source = self.0.as_ref()?;
+
+        let colors = mesh
+            .positions
+            .iter()
+            .zip(mesh.normals.iter())
+            .map(|(position, normal)| {
+                source.color(&SurfaceVertex {
+                    position: (Vec3::from(*position) + origin) * scale,
+                    normal: Vec3::from(*normal),
+                    density,
+                    iso_level,
+                })
+            })
+            .collect();
+
+        Some(colors)
+    }
+}
+
+/// Vertex of a chunk mesh handed to a `VertexColorSource`
+pub struct SurfaceVertex<'a> {
+    /// Position in terrain space, where the density field is sampled
+    pub position: Vec3,
+    /// Normal pointing out of the ground, zero for degenerate vertices
+    pub normal: Vec3,
+    density: &'a dyn DensityField,
+    iso_level: f32,
+}
+
+impl<'a> SurfaceVertex<'a> {
+    /// Density field of the terrain, for sources deriving materials from their own noise
+    pub fn density(&self) -> &dyn DensityField {
+        self.density
+    }
+
+    pub fn iso_level(&self) -> f32 {
+        self.iso_level
+    }
+
+    /// Angle between the normal and the up axis in radians, 0 on flat ground, `PI / 2` on
+    /// vertical walls and `PI` on cave ceilings
+    pub fn slope(&self) -> f32 {
+        if self.normal == Vec3::ZERO {
+            return 0.0;
+        }
+
+        self.normal.normalize().y.max(-1.0).min(1.0).acos()
+    }
+
+    /// Distance straight up through the ground to open air, sampled every `step` world units
+    ///
+    /// Vertices under open sky are at depth 0, vertices on cave ceilings at the thickness of the
+    /// rock above them. Depths beyond `max_depth` return `max_depth`.
+    pub fn depth(&self, step: f32, max_depth: f32) -> f32 {
+        if step <= 0.0 {
+            return 0.0;
+        }
+
+        let mut depth = step;
+
+        while depth < max_depth {
+            if self.density.sample(self.position + Vec3::Y * depth) >= self.iso_level {
+                return depth - step;
+            }
+
+            depth += step;
+        }
+
+        max_depth
+    }
+}